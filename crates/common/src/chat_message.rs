@@ -1,14 +1,19 @@
 //! ChatMessage v1 wire format — shared between daemon and CLI.
 //!
-//! Wire protocol: `{ v, id, ts, type, payload }`
+//! Wire protocol: `{ v, id, ts, type, payload }`. These are the chat
+//! messages of [`CloudMessage`](crate::protocol::CloudMessage) in the
+//! untyped form the daemon relays; `session_init` offers the protocol
+//! versions this build speaks.
 
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::protocol::{PROTOCOL_VERSION, SUPPORTED_PROTOCOL_VERSIONS};
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ChatMessage {
-    /// Protocol version (always 1).
+    /// Protocol version.
     pub v: u32,
     /// Unique message id.
     pub id: String,
@@ -38,6 +43,15 @@ pub enum ChatMessageType {
     SessionInitAck,
     /// Error notification (either direction).
     Error,
+    /// Plan for the session's task, awaiting approval (cloud -> app).
+    /// `metadata` holds the plan: `plan_id`, `summary`, `risk_tier`,
+    /// `steps` and `requires_approval`.
+    PlanProposal,
+    /// The user's answer to a plan proposal (app -> cloud). `content` is
+    /// the action; `metadata` holds `plan_id` and step `modifications`.
+    PlanDecision,
+    /// Stop work on the session's task (app -> cloud).
+    TaskCancel,
     /// Catch-all for unrecognised message types (e.g. HEARTBEAT_ACK).
     #[serde(other)]
     Unknown,
@@ -54,7 +68,7 @@ pub struct ChatPayload {
 impl ChatMessage {
     pub fn new(msg_type: ChatMessageType, payload: ChatPayload) -> Self {
         Self {
-            v: 1,
+            v: PROTOCOL_VERSION,
             id: Uuid::new_v4().to_string(),
             ts: Utc::now().timestamp_millis(),
             msg_type,
@@ -79,7 +93,10 @@ impl ChatMessage {
             ChatPayload {
                 session_id,
                 content: String::new(),
-                metadata: Some(serde_json::json!({ "locale": locale })),
+                metadata: Some(serde_json::json!({
+                    "locale": locale,
+                    "protocol_versions": SUPPORTED_PROTOCOL_VERSIONS,
+                })),
             },
         )
     }
@@ -36,6 +36,10 @@ pub struct Config {
     /// Redaction rules for cloud-bound messages
     #[serde(default)]
     pub redaction: RedactionConfig,
+
+    /// Local memory maintenance
+    #[serde(default)]
+    pub memory: MemoryConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -53,6 +57,31 @@ pub struct DatabaseConfig {
     /// Enable WAL mode
     #[serde(default = "default_true")]
     pub wal_enabled: bool,
+
+    /// At-rest encryption
+    #[serde(default)]
+    pub encryption: EncryptionConfig,
+}
+
+/// At-rest encryption of the local database (SQLCipher).
+///
+/// The key is a passphrase read from the `passphrase_env` environment
+/// variable when set, otherwise a random 256-bit key stored hex-encoded in
+/// `key_file`.  The key file must only be readable by its owner; it is
+/// generated on first use.  Passphrases are never read from this file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptionConfig {
+    /// Encrypt the database; an existing plaintext database is migrated
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// File holding the hex-encoded database key
+    #[serde(default = "default_key_file")]
+    pub key_file: PathBuf,
+
+    /// Environment variable that supplies a passphrase instead of the key file
+    #[serde(default = "default_passphrase_env")]
+    pub passphrase_env: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -124,9 +153,141 @@ pub struct RedactionConfig {
     #[serde(default = "default_true")]
     pub redact_private_keys: bool,
 
+    /// Redact JWTs and high-entropy strings that match no known pattern.
+    #[serde(default = "default_true")]
+    pub detect_high_entropy: bool,
+
+    /// Normalized Shannon entropy (0–1) a candidate string needs to be
+    /// treated as a secret. Lower catches more, with more false positives.
+    #[serde(default = "default_entropy_threshold")]
+    pub entropy_threshold: f64,
+
     /// Additional custom regex patterns to redact (user-supplied).
     #[serde(default)]
     pub custom_patterns: Vec<String>,
+
+    /// JSON keys whose string values are always redacted, matched as whole
+    /// words in any case style (`api_key` also covers `apiKey`, `X-Api-Key`).
+    #[serde(default = "default_sensitive_keys")]
+    pub sensitive_keys: Vec<String>,
+
+    /// Replace session values with stable tokens such as `«API_KEY_3»` that
+    /// the daemon restores locally in agent replies and command arguments.
+    #[serde(default)]
+    pub reversible: bool,
+
+    /// How files known or found to hold secrets are treated when an agent
+    /// tool reads them.
+    #[serde(default)]
+    pub files: FilePolicyConfig,
+}
+
+/// What happens when an agent tool reads a sensitive file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileAction {
+    /// Return the content as is (generic redaction still applies).
+    Allow,
+    /// Return the content with secret values masked.
+    Mask,
+    /// Ask the user before returning the content.
+    Approve,
+    /// Refuse to read the file.
+    Deny,
+}
+
+/// Sensitive-file policy for agent file reads.
+///
+/// Patterns containing a `/` match the whole path (`~/` is the home
+/// directory); patterns without one match the file name only.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilePolicyConfig {
+    /// Master switch — when false, files are read without any checks.
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+
+    /// Glob patterns of files known to hold secrets.
+    #[serde(default = "default_secret_paths")]
+    pub secret_paths: Vec<String>,
+
+    /// Glob patterns exempt from `secret_paths` (templates, public keys).
+    #[serde(default = "default_exempt_paths")]
+    pub exempt_paths: Vec<String>,
+
+    /// Action for files matching `secret_paths`.
+    #[serde(default = "default_file_action")]
+    pub secret_path_action: FileAction,
+
+    /// Scan the content of other files for secrets.
+    #[serde(default = "default_true")]
+    pub sniff_content: bool,
+
+    /// Action for files whose content is found to hold secrets.
+    #[serde(default = "default_file_action")]
+    pub secret_content_action: FileAction,
+}
+
+impl Default for FilePolicyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            secret_paths: default_secret_paths(),
+            exempt_paths: default_exempt_paths(),
+            secret_path_action: default_file_action(),
+            sniff_content: true,
+            secret_content_action: default_file_action(),
+        }
+    }
+}
+
+/// Which summarizer memory compression uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SummarizerKind {
+    /// Structured extraction on the local machine.
+    #[default]
+    Local,
+    /// LLM summary via the cloud connection, falling back to local.
+    Cloud,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MemoryConfig {
+    /// Summarizer used when compressing old memories
+    #[serde(default)]
+    pub summarizer: SummarizerKind,
+
+    /// Background retention and database upkeep
+    #[serde(default)]
+    pub maintenance: MaintenanceConfig,
+}
+
+/// Schedule and thresholds for background memory maintenance.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintenanceConfig {
+    /// Run the maintenance scheduler
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+
+    /// Minutes between maintenance runs
+    #[serde(default = "default_maintenance_interval")]
+    pub interval_minutes: u64,
+
+    /// Compress task and agent memories older than this
+    #[serde(default = "default_compress_after_days")]
+    pub compress_after_days: i64,
+
+    /// Archive session events older than this
+    #[serde(default = "default_archive_sessions_after_hours")]
+    pub archive_sessions_after_hours: i64,
+
+    /// Hours between VACUUMs (0 disables VACUUM)
+    #[serde(default = "default_vacuum_interval")]
+    pub vacuum_interval_hours: u64,
+
+    /// Pre-migration database backups to keep
+    #[serde(default = "default_keep_backups")]
+    pub keep_backups: usize,
 }
 
 impl Default for RedactionConfig {
@@ -141,7 +302,12 @@ impl Default for RedactionConfig {
             limit_system_info: true,
             redact_connection_strings: true,
             redact_private_keys: true,
+            detect_high_entropy: true,
+            entropy_threshold: default_entropy_threshold(),
             custom_patterns: Vec::new(),
+            sensitive_keys: default_sensitive_keys(),
+            reversible: false,
+            files: FilePolicyConfig::default(),
         }
     }
 }
@@ -159,6 +325,97 @@ fn default_db_path() -> PathBuf {
     crate::config_dir().join("d1doctor.db")
 }
 
+fn default_key_file() -> PathBuf {
+    crate::config_dir().join("db.key")
+}
+
+fn default_passphrase_env() -> String {
+    "D1_DB_PASSPHRASE".to_string()
+}
+
+fn default_maintenance_interval() -> u64 {
+    360 // 6 hours
+}
+
+fn default_compress_after_days() -> i64 {
+    30
+}
+
+fn default_archive_sessions_after_hours() -> i64 {
+    168 // 1 week
+}
+
+fn default_vacuum_interval() -> u64 {
+    168 // 1 week
+}
+
+fn default_keep_backups() -> usize {
+    3
+}
+
+fn default_entropy_threshold() -> f64 {
+    0.8
+}
+
+fn default_sensitive_keys() -> Vec<String> {
+    [
+        "password",
+        "passwd",
+        "pwd",
+        "passphrase",
+        "secret",
+        "token",
+        "api_key",
+        "apikey",
+        "access_key",
+        "private_key",
+        "auth",
+        "authorization",
+        "credential",
+        "credentials",
+        "cookie",
+    ]
+    .iter()
+    .map(|k| k.to_string())
+    .collect()
+}
+
+fn default_secret_paths() -> Vec<String> {
+    [
+        "~/.aws/credentials",
+        "~/.ssh/id_*",
+        "~/.netrc",
+        "~/.pgpass",
+        "~/.git-credentials",
+        "~/.docker/config.json",
+        "~/.kube/config",
+        "~/.npmrc",
+        "~/.pypirc",
+        "~/.config/gh/hosts.yml",
+        "~/.gnupg/**",
+        "~/.d1doctor/api_token",
+        ".env",
+        ".env.*",
+        "*.pem",
+        "*.key",
+        "credentials.json",
+    ]
+    .iter()
+    .map(|p| p.to_string())
+    .collect()
+}
+
+fn default_exempt_paths() -> Vec<String> {
+    [".env.example", ".env.sample", ".env.template", "*.pub"]
+        .iter()
+        .map(|p| p.to_string())
+        .collect()
+}
+
+fn default_file_action() -> FileAction {
+    FileAction::Mask
+}
+
 fn default_true() -> bool {
     true
 }
@@ -185,6 +442,7 @@ impl Default for Config {
             logging: LoggingConfig::default(),
             permissions: PermissionsConfig::default(),
             redaction: RedactionConfig::default(),
+            memory: MemoryConfig::default(),
         }
     }
 }
@@ -194,6 +452,30 @@ impl Default for DatabaseConfig {
         Self {
             path: default_db_path(),
             wal_enabled: true,
+            encryption: EncryptionConfig::default(),
+        }
+    }
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_minutes: default_maintenance_interval(),
+            compress_after_days: default_compress_after_days(),
+            archive_sessions_after_hours: default_archive_sessions_after_hours(),
+            vacuum_interval_hours: default_vacuum_interval(),
+            keep_backups: default_keep_backups(),
+        }
+    }
+}
+
+impl Default for EncryptionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            key_file: default_key_file(),
+            passphrase_env: default_passphrase_env(),
         }
     }
 }
@@ -267,6 +549,7 @@ impl Config {
         self.logging = other.logging;
         self.permissions = other.permissions;
         self.redaction = other.redaction;
+        self.memory = other.memory;
     }
 }
 
@@ -287,4 +570,97 @@ mod tests {
         let toml = toml::to_string_pretty(&config).unwrap();
         assert!(toml.contains("orchestrator_url"));
     }
+
+    #[test]
+    fn test_memory_summarizer_config() {
+        let config: Config = toml::from_str("").unwrap();
+        assert_eq!(config.memory.summarizer, SummarizerKind::Local);
+
+        let config: Config = toml::from_str("[memory]\nsummarizer = \"cloud\"").unwrap();
+        assert_eq!(config.memory.summarizer, SummarizerKind::Cloud);
+    }
+
+    #[test]
+    fn test_memory_maintenance_config() {
+        let config: Config = toml::from_str("").unwrap();
+        assert!(config.memory.maintenance.enabled);
+        assert_eq!(config.memory.maintenance.interval_minutes, 360);
+
+        let config: Config =
+            toml::from_str("[memory.maintenance]\ninterval_minutes = 30\nkeep_backups = 1")
+                .unwrap();
+        assert_eq!(config.memory.maintenance.interval_minutes, 30);
+        assert_eq!(config.memory.maintenance.keep_backups, 1);
+        assert_eq!(config.memory.maintenance.compress_after_days, 30);
+    }
+
+    #[test]
+    fn test_database_encryption_config() {
+        let config: Config = toml::from_str("").unwrap();
+        assert!(!config.database.encryption.enabled);
+        assert_eq!(
+            config.database.encryption.passphrase_env,
+            "D1_DB_PASSPHRASE"
+        );
+        assert!(config.database.encryption.key_file.ends_with("db.key"));
+
+        let config: Config =
+            toml::from_str("[database.encryption]\nenabled = true\nkey_file = \"/tmp/k\"").unwrap();
+        assert!(config.database.encryption.enabled);
+        assert_eq!(config.database.encryption.key_file, PathBuf::from("/tmp/k"));
+    }
+
+    #[test]
+    fn test_reversible_redaction_config() {
+        let config: Config = toml::from_str("").unwrap();
+        assert!(!config.redaction.reversible);
+
+        let config: Config = toml::from_str("[redaction]\nreversible = true").unwrap();
+        assert!(config.redaction.reversible);
+        assert!(config.redaction.redact_api_keys);
+    }
+
+    #[test]
+    fn test_entropy_detection_config() {
+        let config: Config = toml::from_str("").unwrap();
+        assert!(config.redaction.detect_high_entropy);
+        assert_eq!(config.redaction.entropy_threshold, 0.8);
+
+        let config: Config = toml::from_str("[redaction]\nentropy_threshold = 0.9").unwrap();
+        assert_eq!(config.redaction.entropy_threshold, 0.9);
+    }
+
+    #[test]
+    fn test_sensitive_keys_config() {
+        let config: Config = toml::from_str("").unwrap();
+        assert!(config
+            .redaction
+            .sensitive_keys
+            .contains(&"password".to_string()));
+
+        let config: Config = toml::from_str("[redaction]\nsensitive_keys = [\"pin\"]").unwrap();
+        assert_eq!(config.redaction.sensitive_keys, vec!["pin".to_string()]);
+    }
+
+    #[test]
+    fn test_file_policy_config() {
+        let config: Config = toml::from_str("").unwrap();
+        let files = &config.redaction.files;
+        assert!(files.enabled && files.sniff_content);
+        assert_eq!(files.secret_path_action, FileAction::Mask);
+        assert!(files
+            .secret_paths
+            .contains(&"~/.aws/credentials".to_string()));
+
+        let config: Config = toml::from_str(
+            "[redaction.files]\nsecret_path_action = \"deny\"\nsecret_content_action = \"approve\"",
+        )
+        .unwrap();
+        assert_eq!(config.redaction.files.secret_path_action, FileAction::Deny);
+        assert_eq!(
+            config.redaction.files.secret_content_action,
+            FileAction::Approve
+        );
+        assert!(!config.redaction.files.exempt_paths.is_empty());
+    }
 }
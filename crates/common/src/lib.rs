@@ -6,17 +6,16 @@
 pub mod chat_message;
 pub mod config;
 pub mod errors;
-pub mod proto;
+pub mod protocol;
 
 pub use chat_message::{ChatMessage, ChatMessageType, ChatPayload};
-pub use config::{Config, RedactionConfig};
+pub use config::{
+    Config, EncryptionConfig, MaintenanceConfig, MemoryConfig, RedactionConfig, SummarizerKind,
+};
 pub use errors::{D1Error, Result};
-pub use proto::*;
+pub use protocol::{Envelope, PROTOCOL_VERSION};
 
-/// Current version of the d1-doctor protocol
-pub const PROTOCOL_VERSION: u32 = 1;
-
-/// Maximum size of a protobuf message (100 MB)
+/// Maximum size of a protocol message (100 MB)
 pub const MAX_MESSAGE_SIZE: usize = 100 * 1024 * 1024;
 
 /// Default port for the local daemon
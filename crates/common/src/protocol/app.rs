@@ -0,0 +1,689 @@
+//! Desktop app ↔ daemon messages (`/ws`).
+//!
+//! Kept in step with `crates/desktop/src/types/daemon.ts`; payload types
+//! carry the same names as their TypeScript counterparts.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::HeartbeatPayload;
+
+// ---------------------------------------------------------------------------
+// Shared enums
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RiskTier {
+    #[default]
+    Low,
+    Medium,
+    High,
+    /// A tier this build doesn't know.
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AgentName {
+    Planner,
+    Executor,
+    Diagnostician,
+    Reviewer,
+    Coder,
+    Researcher,
+    /// An agent this build doesn't know.
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ActionType {
+    Shell,
+    FileRead,
+    FileWrite,
+    FileMove,
+    FileDelete,
+    Process,
+    SystemInfo,
+    WebSearch,
+    /// An action this build doesn't know.
+    #[serde(other)]
+    Unknown,
+}
+
+/// The user's answer to a permission request. Unlike the informational
+/// enums, an unknown answer is a protocol error rather than a guess.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PermissionAction {
+    Grant,
+    Deny,
+    TimeoutApproved,
+    TimeoutDenied,
+}
+
+impl PermissionAction {
+    pub fn is_granted(self) -> bool {
+        matches!(self, Self::Grant | Self::TimeoutApproved)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AgentMessageType {
+    Info,
+    Warning,
+    Thinking,
+    #[serde(other)]
+    Unknown,
+}
+
+/// The user's decision on a proposed plan; strict like [`PermissionAction`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PlanApproveAction {
+    Approve,
+    Modify,
+    Reject,
+}
+
+impl PlanApproveAction {
+    /// The action's wire name.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Approve => "APPROVE",
+            Self::Modify => "MODIFY",
+            Self::Reject => "REJECT",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TaskFailCode {
+    Unrecoverable,
+    ConnectionLost,
+    UserDenied,
+    PermissionTimeout,
+    Cancelled,
+    InternalError,
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    ProtocolError,
+    AuthError,
+    RateLimited,
+    InternalError,
+    ProtocolVersionMismatch,
+    #[serde(other)]
+    Unknown,
+}
+
+// ---------------------------------------------------------------------------
+// Client -> daemon payloads
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskSubmitPayload {
+    pub task_id: String,
+    pub input: String,
+    #[serde(default)]
+    pub context: TaskContext,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TaskContext {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env: Option<BTreeMap<String, String>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskCancelPayload {
+    pub task_id: String,
+}
+
+/// A change the user made to one step of a proposed plan.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanStepModification {
+    pub step_id: String,
+    /// Replacement step description.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Leave the step out.
+    #[serde(default)]
+    pub skip: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanApprovePayload {
+    pub task_id: String,
+    pub plan_id: String,
+    pub action: PlanApproveAction,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modifications: Option<Vec<PlanStepModification>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PermissionResponsePayload {
+    pub task_id: String,
+    pub permission_id: String,
+    pub action: PermissionAction,
+    #[serde(default)]
+    pub remember: bool,
+}
+
+// ---------------------------------------------------------------------------
+// Daemon -> client payloads
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanStep {
+    pub step_id: String,
+    pub order: u32,
+    pub description: String,
+    pub agent: AgentName,
+    pub risk_tier: RiskTier,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimated_seconds: Option<u64>,
+    /// Fields from newer cloud versions, relayed as-is.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// A plan for the user to approve. The cloud proposes it in a
+/// `plan_proposal` message's metadata, so fields this build doesn't know
+/// are kept in `extra` and relayed to the app.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanProposedPayload {
+    pub task_id: String,
+    pub plan_id: String,
+    #[serde(default)]
+    pub summary: String,
+    #[serde(default)]
+    pub risk_tier: RiskTier,
+    #[serde(default)]
+    pub steps: Vec<PlanStep>,
+    #[serde(default = "requires_approval")]
+    pub requires_approval: bool,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+fn requires_approval() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StepStartedPayload {
+    pub task_id: String,
+    pub step_id: String,
+    pub order: u32,
+    pub description: String,
+    pub agent: AgentName,
+    /// Unix millis.
+    pub started_at: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StepResult {
+    pub summary: String,
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StepCompletedPayload {
+    pub task_id: String,
+    pub step_id: String,
+    pub order: u32,
+    pub result: StepResult,
+    pub duration_ms: u64,
+    pub credits_used: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StepError {
+    pub code: String,
+    pub message: String,
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StepFailedPayload {
+    pub task_id: String,
+    pub step_id: String,
+    pub order: u32,
+    pub error: StepError,
+    pub duration_ms: u64,
+    pub diagnostician_triggered: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentMessagePayload {
+    pub task_id: String,
+    pub step_id: String,
+    pub agent: AgentName,
+    pub message: String,
+    pub message_type: AgentMessageType,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PermissionRequestedPayload {
+    pub task_id: String,
+    pub step_id: String,
+    pub permission_id: String,
+    pub risk_tier: RiskTier,
+    pub action_type: ActionType,
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command_preview: Option<String>,
+    pub remember: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArtifactKind {
+    File,
+    Url,
+    Text,
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskArtifact {
+    #[serde(rename = "type")]
+    pub kind: ArtifactKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskCompletedPayload {
+    pub task_id: String,
+    pub summary: String,
+    pub steps_completed: u32,
+    pub steps_total: u32,
+    pub duration_ms: u64,
+    pub credits_used: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artifacts: Option<Vec<TaskArtifact>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskError {
+    pub code: TaskFailCode,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failed_step: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diagnosis: Option<String>,
+    pub steps_completed: u32,
+    pub steps_total: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskFailedPayload {
+    pub task_id: String,
+    pub error: TaskError,
+    pub credits_used: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CreditsUpdatedPayload {
+    pub daily_balance: u64,
+    pub bonus_balance: u64,
+    pub used_this_task: u64,
+    /// ISO 8601.
+    pub reset_at: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DaemonStatusPayload {
+    pub daemon_version: String,
+    pub protocol_version: u32,
+    pub orchestrator_connected: bool,
+    pub orchestrator_url: String,
+    pub active_tasks: usize,
+    pub device_id: String,
+    /// State of the cloud link (`online`, `degraded`, ...).
+    #[serde(default)]
+    pub connection_state: String,
+    /// Chat messages waiting for the cloud link.
+    #[serde(default)]
+    pub queued_messages: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErrorPayload {
+    pub code: ErrorCode,
+    pub message: String,
+    /// `id` of the message being rejected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// Only on `PROTOCOL_VERSION_MISMATCH`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub daemon_version: Option<String>,
+    /// Only on `PROTOCOL_VERSION_MISMATCH`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub supported_protocol_versions: Option<Vec<u32>>,
+}
+
+wire_messages! {
+    /// A message on the desktop app link.
+    pub enum AppMessage {
+        // Client -> daemon
+        TaskSubmit(TaskSubmitPayload) = "task.submit",
+        TaskCancel(TaskCancelPayload) = "task.cancel",
+        PlanApprove(PlanApprovePayload) = "plan.approve",
+        PermissionResponse(PermissionResponsePayload) = "permission.response",
+        /// `ping` from the client, `pong` from the daemon.
+        Heartbeat(HeartbeatPayload) = "heartbeat",
+        // Daemon -> client
+        PlanProposed(PlanProposedPayload) = "plan.proposed",
+        StepStarted(StepStartedPayload) = "step.started",
+        StepCompleted(StepCompletedPayload) = "step.completed",
+        StepFailed(StepFailedPayload) = "step.failed",
+        AgentMessage(AgentMessagePayload) = "agent.message",
+        PermissionRequested(PermissionRequestedPayload) = "permission.requested",
+        TaskCompleted(TaskCompletedPayload) = "task.completed",
+        TaskFailed(TaskFailedPayload) = "task.failed",
+        CreditsUpdated(CreditsUpdatedPayload) = "credits.updated",
+        DaemonStatus(DaemonStatusPayload) = "daemon.status",
+        Error(ErrorPayload) = "error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::golden::{check, envelope};
+    use crate::protocol::{Envelope, WireMessage};
+
+    fn plan_step(step_id: &str, order: u32, agent: AgentName, risk_tier: RiskTier) -> PlanStep {
+        PlanStep {
+            step_id: step_id.into(),
+            order,
+            description: format!("step {order}"),
+            agent,
+            risk_tier,
+            estimated_seconds: None,
+            extra: Map::new(),
+        }
+    }
+
+    /// One golden case per message type.
+    fn cases() -> Vec<(&'static str, AppMessage)> {
+        let mut env = BTreeMap::new();
+        env.insert("NODE_ENV".to_string(), "development".to_string());
+        let mut extra = Map::new();
+        extra.insert("rollback".into(), serde_json::json!({ "step_id": "st-9" }));
+        let mut step = plan_step("st-1", 1, AgentName::Executor, RiskTier::Medium);
+        step.estimated_seconds = Some(30);
+
+        vec![
+            (
+                "task_submit",
+                AppMessage::TaskSubmit(TaskSubmitPayload {
+                    task_id: "task-1".into(),
+                    input: "Install Node".into(),
+                    context: TaskContext {
+                        cwd: Some("/Users/dev/app".into()),
+                        env: Some(env),
+                    },
+                }),
+            ),
+            (
+                "task_cancel",
+                AppMessage::TaskCancel(TaskCancelPayload {
+                    task_id: "task-1".into(),
+                }),
+            ),
+            (
+                "plan_approve",
+                AppMessage::PlanApprove(PlanApprovePayload {
+                    task_id: "task-1".into(),
+                    plan_id: "plan-1".into(),
+                    action: PlanApproveAction::Modify,
+                    modifications: Some(vec![
+                        PlanStepModification {
+                            step_id: "st-1".into(),
+                            description: Some("brew install node@20".into()),
+                            skip: false,
+                        },
+                        PlanStepModification {
+                            step_id: "st-2".into(),
+                            description: None,
+                            skip: true,
+                        },
+                    ]),
+                }),
+            ),
+            (
+                "permission_response",
+                AppMessage::PermissionResponse(PermissionResponsePayload {
+                    task_id: "task-1".into(),
+                    permission_id: "perm-1".into(),
+                    action: PermissionAction::Grant,
+                    remember: true,
+                }),
+            ),
+            (
+                "heartbeat",
+                AppMessage::Heartbeat(HeartbeatPayload {
+                    ping: true,
+                    pong: false,
+                }),
+            ),
+            (
+                "plan_proposed",
+                AppMessage::PlanProposed(PlanProposedPayload {
+                    task_id: "task-1".into(),
+                    plan_id: "plan-1".into(),
+                    summary: "Install Node".into(),
+                    risk_tier: RiskTier::Medium,
+                    steps: vec![
+                        step,
+                        plan_step("st-2", 2, AgentName::Reviewer, RiskTier::Low),
+                    ],
+                    requires_approval: true,
+                    extra,
+                }),
+            ),
+            (
+                "step_started",
+                AppMessage::StepStarted(StepStartedPayload {
+                    task_id: "task-1".into(),
+                    step_id: "st-1".into(),
+                    order: 1,
+                    description: "brew install node".into(),
+                    agent: AgentName::Executor,
+                    started_at: 1_760_000_000_500,
+                }),
+            ),
+            (
+                "step_completed",
+                AppMessage::StepCompleted(StepCompletedPayload {
+                    task_id: "task-1".into(),
+                    step_id: "st-1".into(),
+                    order: 1,
+                    result: StepResult {
+                        summary: "Node installed".into(),
+                        stdout: "node 20.11.0".into(),
+                        stderr: String::new(),
+                        exit_code: 0,
+                    },
+                    duration_ms: 4200,
+                    credits_used: 2,
+                }),
+            ),
+            (
+                "step_failed",
+                AppMessage::StepFailed(StepFailedPayload {
+                    task_id: "task-1".into(),
+                    step_id: "st-1".into(),
+                    order: 1,
+                    error: StepError {
+                        code: "EXIT_NONZERO".into(),
+                        message: "brew failed".into(),
+                        stdout: String::new(),
+                        stderr: "Error: No such keg".into(),
+                        exit_code: 1,
+                    },
+                    duration_ms: 900,
+                    diagnostician_triggered: true,
+                }),
+            ),
+            (
+                "agent_message",
+                AppMessage::AgentMessage(AgentMessagePayload {
+                    task_id: "task-1".into(),
+                    step_id: "chat".into(),
+                    agent: AgentName::Planner,
+                    message: "Looking at your setup".into(),
+                    message_type: AgentMessageType::Thinking,
+                }),
+            ),
+            (
+                "permission_requested",
+                AppMessage::PermissionRequested(PermissionRequestedPayload {
+                    task_id: "task-1".into(),
+                    step_id: "st-1".into(),
+                    permission_id: "perm-1".into(),
+                    risk_tier: RiskTier::High,
+                    action_type: ActionType::Shell,
+                    description: "High-risk command".into(),
+                    command_preview: Some("sudo rm -rf /opt/old".into()),
+                    remember: true,
+                }),
+            ),
+            (
+                "task_completed",
+                AppMessage::TaskCompleted(TaskCompletedPayload {
+                    task_id: "task-1".into(),
+                    summary: "Node 20 installed".into(),
+                    steps_completed: 2,
+                    steps_total: 2,
+                    duration_ms: 5100,
+                    credits_used: 3,
+                    artifacts: Some(vec![TaskArtifact {
+                        kind: ArtifactKind::File,
+                        path: Some("/Users/dev/.nvmrc".into()),
+                        url: None,
+                        description: "Pinned Node version".into(),
+                    }]),
+                }),
+            ),
+            (
+                "task_failed",
+                AppMessage::TaskFailed(TaskFailedPayload {
+                    task_id: "task-1".into(),
+                    error: TaskError {
+                        code: TaskFailCode::Cancelled,
+                        message: "Task cancelled".into(),
+                        failed_step: Some("st-2".into()),
+                        diagnosis: None,
+                        steps_completed: 1,
+                        steps_total: 2,
+                    },
+                    credits_used: 1,
+                }),
+            ),
+            (
+                "credits_updated",
+                AppMessage::CreditsUpdated(CreditsUpdatedPayload {
+                    daily_balance: 40,
+                    bonus_balance: 10,
+                    used_this_task: 3,
+                    reset_at: "2026-10-19T00:00:00Z".into(),
+                }),
+            ),
+            (
+                "daemon_status",
+                AppMessage::DaemonStatus(DaemonStatusPayload {
+                    daemon_version: "0.1.0".into(),
+                    protocol_version: 1,
+                    orchestrator_connected: true,
+                    orchestrator_url: "wss://example.test/ws".into(),
+                    active_tasks: 1,
+                    device_id: "device-1".into(),
+                    connection_state: "online".into(),
+                    queued_messages: 0,
+                }),
+            ),
+            (
+                "error",
+                AppMessage::Error(ErrorPayload {
+                    code: ErrorCode::ProtocolVersionMismatch,
+                    message: "protocol version 2 is not supported".into(),
+                    request_id: Some("req-1".into()),
+                    daemon_version: Some("0.1.0".into()),
+                    supported_protocol_versions: Some(vec![1]),
+                }),
+            ),
+        ]
+    }
+
+    #[test]
+    fn messages_match_golden_files() {
+        let cases = cases();
+        for (name, message) in &cases {
+            check(&format!("app/{name}"), &envelope(message.clone()));
+        }
+        for msg_type in AppMessage::MESSAGE_TYPES {
+            assert!(
+                cases.iter().any(|(_, m)| m.msg_type() == *msg_type),
+                "no golden case for {msg_type}"
+            );
+        }
+    }
+
+    #[test]
+    fn plans_from_newer_clouds_keep_unknown_fields() {
+        let text = r#"{"type":"plan.proposed","payload":{
+            "task_id":"task-1","plan_id":"plan-1",
+            "steps":[{"step_id":"st-1","order":1,"description":"d","agent":"auditor",
+                      "risk_tier":"EXTREME","sandbox":"strict"}],
+            "budget":{"credits":5}}}"#;
+        let envelope: Envelope<AppMessage> = Envelope::parse(text).unwrap();
+        let AppMessage::PlanProposed(plan) = &envelope.message else {
+            panic!("expected plan.proposed");
+        };
+        // Missing fields take their defaults
+        assert_eq!(plan.summary, "");
+        assert!(plan.requires_approval);
+        assert_eq!(plan.steps[0].agent, AgentName::Unknown);
+        assert_eq!(plan.steps[0].risk_tier, RiskTier::Unknown);
+
+        let relayed = serde_json::to_value(&envelope).unwrap();
+        assert_eq!(relayed["payload"]["budget"]["credits"], 5);
+        assert_eq!(relayed["payload"]["steps"][0]["sandbox"], "strict");
+    }
+
+    #[test]
+    fn decisions_must_be_known_actions() {
+        let text = r#"{"type":"permission.response","payload":{
+            "task_id":"task-1","permission_id":"perm-1","action":"MAYBE"}}"#;
+        assert!(Envelope::<AppMessage>::parse(text).is_err());
+        assert!(PermissionAction::TimeoutApproved.is_granted());
+        assert!(!PermissionAction::TimeoutDenied.is_granted());
+        assert_eq!(PlanApproveAction::Reject.as_str(), "REJECT");
+    }
+}
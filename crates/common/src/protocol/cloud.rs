@@ -0,0 +1,520 @@
+//! Daemon ↔ cloud messages.
+//!
+//! The chat messages share their payload shape with [`ChatMessage`], which
+//! the daemon relays and the CLI speaks on `/chat`; [`Envelope::into_chat`]
+//! and [`Envelope::from_chat`] convert between the two forms.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{legacy_version, legacy_versions, Envelope, HeartbeatPayload, WireMessage};
+use crate::chat_message::{ChatMessage, ChatMessageType, ChatPayload};
+
+// ---------------------------------------------------------------------------
+// Auth
+// ---------------------------------------------------------------------------
+
+/// What the daemon proves itself with.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuthCredentials {
+    pub jwt: String,
+    pub device_fingerprint: String,
+}
+
+/// `AUTH` payload: chat-shaped, with the [`AuthCredentials`] as JSON text
+/// in `content`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuthPayload {
+    #[serde(default)]
+    pub session_id: String,
+    pub content: String,
+}
+
+impl AuthPayload {
+    pub fn new(credentials: &AuthCredentials) -> Self {
+        Self {
+            session_id: String::new(),
+            content: serde_json::to_string(credentials).expect("credentials always serialize"),
+        }
+    }
+
+    pub fn credentials(&self) -> serde_json::Result<AuthCredentials> {
+        serde_json::from_str(&self.content)
+    }
+}
+
+/// `AUTH_OK` / `AUTH_FAIL` payload; a failure's reason is in `content`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AuthResultPayload {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub content: String,
+}
+
+// ---------------------------------------------------------------------------
+// Session init (version negotiation)
+// ---------------------------------------------------------------------------
+
+/// `session_init` payload: opens a chat session and offers the protocol
+/// versions the sender speaks.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionInitPayload {
+    pub session_id: String,
+    #[serde(default)]
+    pub content: String,
+    #[serde(default)]
+    pub metadata: SessionInitMetadata,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionInitMetadata {
+    #[serde(default)]
+    pub locale: String,
+    /// Versions the sender speaks. Senders that predate negotiation leave
+    /// it out and speak version 1 only.
+    #[serde(default = "legacy_versions")]
+    pub protocol_versions: Vec<u32>,
+}
+
+impl Default for SessionInitMetadata {
+    fn default() -> Self {
+        Self {
+            locale: String::new(),
+            protocol_versions: legacy_versions(),
+        }
+    }
+}
+
+impl SessionInitPayload {
+    /// The version to answer this init with, if the sender speaks one we do.
+    pub fn negotiate(&self) -> Option<u32> {
+        super::negotiate_version(&self.metadata.protocol_versions)
+    }
+}
+
+/// `session_init_ack` payload: names the version chosen for the session.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionInitAckPayload {
+    pub session_id: String,
+    #[serde(default)]
+    pub content: String,
+    #[serde(default)]
+    pub metadata: SessionInitAckMetadata,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionInitAckMetadata {
+    /// Version chosen for the session. Peers that predate negotiation
+    /// leave it out and speak version 1.
+    #[serde(default = "legacy_version")]
+    pub protocol_version: u32,
+}
+
+impl Default for SessionInitAckMetadata {
+    fn default() -> Self {
+        Self {
+            protocol_version: legacy_version(),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Commands
+// ---------------------------------------------------------------------------
+
+/// `command.request`: a command the cloud wants run on this machine.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommandRequest {
+    /// Unique identifier for this command invocation.
+    pub id: String,
+    /// The command type: `shell_exec`, `file_read`, `file_write`, `system_info`.
+    pub command_type: String,
+    /// Shell command string (for `shell_exec`) or structured payload.
+    pub payload: Value,
+    /// Optional working directory override.
+    #[serde(default)]
+    pub cwd: Option<String>,
+    /// Optional timeout in milliseconds.
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    /// Chat session the command belongs to, used to restore redaction
+    /// tokens in its arguments.
+    #[serde(default)]
+    pub session_id: Option<String>,
+}
+
+/// `command.accepted`: the daemon will run the command.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommandAccepted {
+    pub command_id: String,
+}
+
+/// `command.rejected`: the command was blocked or the user denied it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommandRejected {
+    pub command_id: String,
+    pub reason: String,
+}
+
+/// `command.stdout`: a chunk of output while the command runs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommandStdout {
+    pub command_id: String,
+    pub data: String,
+}
+
+/// `command.completed`: the command finished.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommandCompleted {
+    pub command_id: String,
+    pub success: bool,
+    pub exit_code: i32,
+    pub stdout: String,
+    pub stderr: String,
+    pub duration_ms: u64,
+}
+
+// ---------------------------------------------------------------------------
+// Summaries
+// ---------------------------------------------------------------------------
+
+/// `memory_summary`: the cloud's answer to a `memory_summarize` request,
+/// whose envelope `id` it echoes in `request_id`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemorySummaryPayload {
+    pub request_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+wire_messages! {
+    /// A message on the daemon's cloud link.
+    pub enum CloudMessage {
+        Auth(AuthPayload) = "AUTH",
+        AuthOk(AuthResultPayload) = "AUTH_OK",
+        AuthFail(AuthResultPayload) = "AUTH_FAIL",
+        Heartbeat(HeartbeatPayload) = "HEARTBEAT",
+        HeartbeatAck(HeartbeatPayload) = "HEARTBEAT_ACK",
+        SessionInit(SessionInitPayload) = "session_init",
+        SessionInitAck(SessionInitAckPayload) = "session_init_ack",
+        UserMessage(ChatPayload) = "user_message",
+        AgentResponse(ChatPayload) = "agent_response",
+        StreamChunk(ChatPayload) = "stream_chunk",
+        StreamEnd(ChatPayload) = "stream_end",
+        Error(ChatPayload) = "error",
+        /// `metadata` holds the plan: `plan_id`, `summary`, `risk_tier`,
+        /// `steps` and `requires_approval`.
+        PlanProposal(ChatPayload) = "plan_proposal",
+        /// `content` is the action; `metadata` holds `plan_id` and step
+        /// `modifications`.
+        PlanDecision(ChatPayload) = "plan_decision",
+        TaskCancel(ChatPayload) = "task_cancel",
+        CommandRequest(CommandRequest) = "command.request",
+        CommandAccepted(CommandAccepted) = "command.accepted",
+        CommandRejected(CommandRejected) = "command.rejected",
+        CommandStdout(CommandStdout) = "command.stdout",
+        CommandCompleted(CommandCompleted) = "command.completed",
+        /// The input to summarize, as the daemon's summarizer describes it.
+        MemorySummarize(Value) = "memory_summarize",
+        MemorySummary(MemorySummaryPayload) = "memory_summary",
+    }
+}
+
+impl CloudMessage {
+    /// The [`ChatMessageType`] of a chat message, `None` for the rest.
+    pub fn chat_type(&self) -> Option<ChatMessageType> {
+        Some(match self {
+            Self::SessionInit(_) => ChatMessageType::SessionInit,
+            Self::SessionInitAck(_) => ChatMessageType::SessionInitAck,
+            Self::UserMessage(_) => ChatMessageType::UserMessage,
+            Self::AgentResponse(_) => ChatMessageType::AgentResponse,
+            Self::StreamChunk(_) => ChatMessageType::StreamChunk,
+            Self::StreamEnd(_) => ChatMessageType::StreamEnd,
+            Self::Error(_) => ChatMessageType::Error,
+            Self::PlanProposal(_) => ChatMessageType::PlanProposal,
+            Self::PlanDecision(_) => ChatMessageType::PlanDecision,
+            Self::TaskCancel(_) => ChatMessageType::TaskCancel,
+            _ => return None,
+        })
+    }
+}
+
+impl Envelope<CloudMessage> {
+    /// The [`ChatMessage`] form of a chat message, `None` for the rest.
+    pub fn into_chat(self) -> Option<ChatMessage> {
+        let msg_type = self.message.chat_type()?;
+        let payload = serde_json::from_value(self.message.to_payload().ok()?).ok()?;
+        Some(ChatMessage {
+            v: self.v,
+            id: self.id,
+            ts: self.ts,
+            msg_type,
+            payload,
+        })
+    }
+
+    /// The typed form of a [`ChatMessage`].
+    pub fn from_chat(msg: &ChatMessage) -> serde_json::Result<Self> {
+        serde_json::from_value(serde_json::to_value(msg)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::golden::{check, envelope};
+
+    fn chat(session_id: &str, content: &str, metadata: Option<Value>) -> ChatPayload {
+        ChatPayload {
+            session_id: session_id.into(),
+            content: content.into(),
+            metadata,
+        }
+    }
+
+    /// One golden case per message type.
+    fn cases() -> Vec<(&'static str, CloudMessage)> {
+        vec![
+            (
+                "auth",
+                CloudMessage::Auth(AuthPayload::new(&AuthCredentials {
+                    jwt: "eyJhbGciOi.test.jwt".into(),
+                    device_fingerprint: "fp-1".into(),
+                })),
+            ),
+            (
+                "auth_ok",
+                CloudMessage::AuthOk(AuthResultPayload::default()),
+            ),
+            (
+                "auth_fail",
+                CloudMessage::AuthFail(AuthResultPayload {
+                    content: "token expired".into(),
+                }),
+            ),
+            (
+                "heartbeat",
+                CloudMessage::Heartbeat(HeartbeatPayload::default()),
+            ),
+            (
+                "heartbeat_ack",
+                CloudMessage::HeartbeatAck(HeartbeatPayload::default()),
+            ),
+            (
+                "session_init",
+                CloudMessage::SessionInit(SessionInitPayload {
+                    session_id: "s1".into(),
+                    content: String::new(),
+                    metadata: SessionInitMetadata {
+                        locale: "en-US".into(),
+                        protocol_versions: vec![1],
+                    },
+                }),
+            ),
+            (
+                "session_init_ack",
+                CloudMessage::SessionInitAck(SessionInitAckPayload {
+                    session_id: "s1".into(),
+                    content: String::new(),
+                    metadata: SessionInitAckMetadata {
+                        protocol_version: 1,
+                    },
+                }),
+            ),
+            (
+                "user_message",
+                CloudMessage::UserMessage(chat("s1", "Install Node", None)),
+            ),
+            (
+                "agent_response",
+                CloudMessage::AgentResponse(chat("s1", "Node 20 is installed.", None)),
+            ),
+            (
+                "stream_chunk",
+                CloudMessage::StreamChunk(chat("s1", "Installing", None)),
+            ),
+            ("stream_end", CloudMessage::StreamEnd(chat("s1", "", None))),
+            (
+                "error",
+                CloudMessage::Error(chat("s1", "model unavailable", None)),
+            ),
+            (
+                "plan_proposal",
+                CloudMessage::PlanProposal(chat(
+                    "s1",
+                    "Install Node",
+                    Some(serde_json::json!({
+                        "plan_id": "plan-1",
+                        "risk_tier": "MEDIUM",
+                        "requires_approval": true,
+                        "steps": [{
+                            "step_id": "st-1", "order": 1, "description": "brew install node",
+                            "agent": "executor", "risk_tier": "MEDIUM"
+                        }]
+                    })),
+                )),
+            ),
+            (
+                "plan_decision",
+                CloudMessage::PlanDecision(chat(
+                    "s1",
+                    "MODIFY",
+                    Some(serde_json::json!({
+                        "plan_id": "plan-1",
+                        "action": "MODIFY",
+                        "modifications": [{ "step_id": "st-1", "skip": true }]
+                    })),
+                )),
+            ),
+            (
+                "task_cancel",
+                CloudMessage::TaskCancel(chat("s1", "", None)),
+            ),
+            (
+                "command_request",
+                CloudMessage::CommandRequest(CommandRequest {
+                    id: "cmd-1".into(),
+                    command_type: "shell_exec".into(),
+                    payload: Value::String("node --version".into()),
+                    cwd: Some("/tmp".into()),
+                    timeout_ms: Some(30_000),
+                    session_id: Some("s1".into()),
+                }),
+            ),
+            (
+                "command_accepted",
+                CloudMessage::CommandAccepted(CommandAccepted {
+                    command_id: "cmd-1".into(),
+                }),
+            ),
+            (
+                "command_rejected",
+                CloudMessage::CommandRejected(CommandRejected {
+                    command_id: "cmd-1".into(),
+                    reason: "User denied approval".into(),
+                }),
+            ),
+            (
+                "command_stdout",
+                CloudMessage::CommandStdout(CommandStdout {
+                    command_id: "cmd-1".into(),
+                    data: "v20.11.0\n".into(),
+                }),
+            ),
+            (
+                "command_completed",
+                CloudMessage::CommandCompleted(CommandCompleted {
+                    command_id: "cmd-1".into(),
+                    success: true,
+                    exit_code: 0,
+                    stdout: "v20.11.0\n".into(),
+                    stderr: String::new(),
+                    duration_ms: 42,
+                }),
+            ),
+            (
+                "memory_summarize",
+                CloudMessage::MemorySummarize(serde_json::json!({
+                    "kind": "agent_memory",
+                    "memory_type": "fact",
+                    "content": "The user keeps their dotfiles in git."
+                })),
+            ),
+            (
+                "memory_summary",
+                CloudMessage::MemorySummary(MemorySummaryPayload {
+                    request_id: "00000000-0000-4000-8000-000000000000".into(),
+                    summary: Some("dotfiles in git".into()),
+                    error: None,
+                }),
+            ),
+        ]
+    }
+
+    #[test]
+    fn messages_match_golden_files() {
+        let cases = cases();
+        for (name, message) in &cases {
+            check(&format!("cloud/{name}"), &envelope(message.clone()));
+        }
+        for msg_type in CloudMessage::MESSAGE_TYPES {
+            assert!(
+                cases.iter().any(|(_, m)| m.msg_type() == *msg_type),
+                "no golden case for {msg_type}"
+            );
+        }
+    }
+
+    #[test]
+    fn chat_messages_convert_both_ways() {
+        let msg = ChatMessage::new(
+            ChatMessageType::PlanProposal,
+            chat(
+                "s1",
+                "Install Node",
+                Some(serde_json::json!({ "plan_id": "p" })),
+            ),
+        );
+        let typed = Envelope::from_chat(&msg).unwrap();
+        assert!(matches!(typed.message, CloudMessage::PlanProposal(_)));
+        assert_eq!(typed.id, msg.id);
+        assert_eq!(typed.into_chat().unwrap(), msg);
+
+        let init = ChatMessage::session_init("s1".into(), "en-US".into());
+        let typed = Envelope::from_chat(&init).unwrap();
+        let CloudMessage::SessionInit(payload) = &typed.message else {
+            panic!("expected session_init");
+        };
+        assert_eq!(payload.metadata.locale, "en-US");
+        assert_eq!(payload.metadata.protocol_versions, vec![1]);
+        assert_eq!(typed.into_chat().unwrap(), init);
+
+        let heartbeat = envelope(CloudMessage::Heartbeat(HeartbeatPayload::default()));
+        assert!(heartbeat.into_chat().is_none());
+    }
+
+    #[test]
+    fn session_init_negotiates_a_version() {
+        // A sender that predates negotiation speaks version 1
+        let legacy: SessionInitPayload =
+            serde_json::from_str(r#"{"session_id":"s1","content":"","metadata":{"locale":"en"}}"#)
+                .unwrap();
+        assert_eq!(legacy.metadata.protocol_versions, vec![1]);
+        assert_eq!(legacy.negotiate(), Some(1));
+
+        let newer = SessionInitPayload {
+            metadata: SessionInitMetadata {
+                protocol_versions: vec![1, 2],
+                ..legacy.metadata.clone()
+            },
+            ..legacy.clone()
+        };
+        assert_eq!(newer.negotiate(), Some(1));
+
+        let future = SessionInitPayload {
+            metadata: SessionInitMetadata {
+                protocol_versions: vec![3],
+                ..legacy.metadata.clone()
+            },
+            ..legacy
+        };
+        assert_eq!(future.negotiate(), None);
+
+        // An ack without metadata comes from a version 1 peer
+        let ack: SessionInitAckPayload =
+            serde_json::from_str(r#"{"session_id":"s1","content":""}"#).unwrap();
+        assert_eq!(ack.metadata.protocol_version, 1);
+    }
+
+    #[test]
+    fn auth_carries_credentials_as_text() {
+        let credentials = AuthCredentials {
+            jwt: "tok".into(),
+            device_fingerprint: "fp".into(),
+        };
+        let auth = AuthPayload::new(&credentials);
+        assert_eq!(auth.session_id, "");
+        assert_eq!(auth.credentials().unwrap(), credentials);
+    }
+}
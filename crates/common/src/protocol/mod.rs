@@ -0,0 +1,389 @@
+//! Typed wire protocol shared by the CLI, daemon, desktop app and cloud.
+//!
+//! Every message travels in the same JSON envelope,
+//! `{ v, id, ts, type, payload }`: `type` names the message and `payload`
+//! carries its body. Two message sets ride in it:
+//!
+//! - [`AppMessage`] — desktop app ↔ daemon on `/ws`: tasks, plans,
+//!   approvals and status. Mirrors `crates/desktop/src/types/daemon.ts`.
+//! - [`CloudMessage`] — daemon ↔ cloud: auth, chat, plans, commands,
+//!   summaries and heartbeats. The CLI's `/chat` link carries the chat
+//!   messages in their [`ChatMessage`](crate::ChatMessage) form.
+//!
+//! # Versions
+//!
+//! `v` is the protocol version of the envelope. On the cloud link the
+//! `session_init` message offers every version the sender speaks and the
+//! `session_init_ack` names the one chosen (see [`negotiate_version`]).
+//! App clients stamp each envelope with their version; the daemon answers
+//! one it doesn't speak with a `PROTOCOL_VERSION_MISMATCH` error.
+//!
+//! # Forward compatibility
+//!
+//! Newer peers may add fields and message types. Unknown payload fields are
+//! ignored, and a message type this build doesn't know decodes to the
+//! `Unknown` variant with its payload intact instead of failing. Messages
+//! the daemon relays between peers ([`PlanProposedPayload`]) keep unknown
+//! fields so they reach the other side.
+//!
+//! The golden files in `src/protocol/golden/` pin the JSON of every message;
+//! run the tests with `UPDATE_GOLDEN=1` to rewrite them after a deliberate
+//! change.
+
+use chrono::Utc;
+use serde::de::Error as _;
+use serde::ser::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use uuid::Uuid;
+
+/// Defines a message set: the enum, its `Unknown` fallback and its
+/// [`WireMessage`] impl, from `Variant(Payload) = "wire type"` entries.
+macro_rules! wire_messages {
+    (
+        $(#[$meta:meta])*
+        pub enum $name:ident {
+            $(
+                $(#[$variant_meta:meta])*
+                $variant:ident($payload:ty) = $wire:literal,
+            )*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, PartialEq)]
+        pub enum $name {
+            $(
+                $(#[$variant_meta])*
+                $variant($payload),
+            )*
+            /// A message type this build doesn't know, kept as received.
+            Unknown {
+                msg_type: String,
+                payload: serde_json::Value,
+            },
+        }
+
+        impl $name {
+            /// Wire `type` of every known message.
+            pub const MESSAGE_TYPES: &'static [&'static str] = &[$($wire),*];
+        }
+
+        impl $crate::protocol::WireMessage for $name {
+            fn msg_type(&self) -> &str {
+                match self {
+                    $(Self::$variant(_) => $wire,)*
+                    Self::Unknown { msg_type, .. } => msg_type,
+                }
+            }
+
+            fn to_payload(&self) -> serde_json::Result<serde_json::Value> {
+                match self {
+                    $(Self::$variant(payload) => serde_json::to_value(payload),)*
+                    Self::Unknown { payload, .. } => Ok(payload.clone()),
+                }
+            }
+
+            fn from_parts(
+                msg_type: String,
+                payload: serde_json::Value,
+            ) -> serde_json::Result<Self> {
+                // A message without a body decodes like an empty one
+                let body = match &payload {
+                    serde_json::Value::Null => serde_json::Value::Object(Default::default()),
+                    _ => payload.clone(),
+                };
+                match msg_type.as_str() {
+                    $($wire => serde_json::from_value(body).map(Self::$variant),)*
+                    _ => Ok(Self::Unknown { msg_type, payload }),
+                }
+            }
+        }
+    };
+}
+
+pub mod app;
+pub mod cloud;
+
+pub use app::*;
+pub use cloud::*;
+
+/// Protocol version this build writes.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Every protocol version this build reads, oldest first.
+pub const SUPPORTED_PROTOCOL_VERSIONS: &[u32] = &[1];
+
+/// Version spoken by peers that predate version negotiation.
+const LEGACY_PROTOCOL_VERSION: u32 = 1;
+
+/// Whether this build speaks protocol version `v`.
+pub fn is_supported_version(v: u32) -> bool {
+    SUPPORTED_PROTOCOL_VERSIONS.contains(&v)
+}
+
+/// The newest version both this build and a peer offering `offered` speak.
+pub fn negotiate_version(offered: &[u32]) -> Option<u32> {
+    offered
+        .iter()
+        .copied()
+        .filter(|v| is_supported_version(*v))
+        .max()
+}
+
+fn legacy_version() -> u32 {
+    LEGACY_PROTOCOL_VERSION
+}
+
+fn legacy_versions() -> Vec<u32> {
+    vec![LEGACY_PROTOCOL_VERSION]
+}
+
+fn is_false(b: &bool) -> bool {
+    !*b
+}
+
+// ---------------------------------------------------------------------------
+// Envelope
+// ---------------------------------------------------------------------------
+
+/// A message set that travels in an [`Envelope`].
+pub trait WireMessage: Sized {
+    /// The envelope's `type`.
+    fn msg_type(&self) -> &str;
+    /// The envelope's `payload`.
+    fn to_payload(&self) -> serde_json::Result<Value>;
+    /// Decode a message from its envelope `type` and `payload`. Unknown
+    /// types decode to the `Unknown` variant; a known type with a malformed
+    /// payload is an error.
+    fn from_parts(msg_type: String, payload: Value) -> serde_json::Result<Self>;
+}
+
+/// A typed message in its wire envelope.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Envelope<M> {
+    /// Protocol version.
+    pub v: u32,
+    /// Unique message id.
+    pub id: String,
+    /// Unix-millis timestamp.
+    pub ts: i64,
+    pub message: M,
+}
+
+impl<M: WireMessage> Envelope<M> {
+    /// Wrap `message` in a fresh envelope of the current version.
+    pub fn new(message: M) -> Self {
+        Self {
+            v: PROTOCOL_VERSION,
+            id: Uuid::new_v4().to_string(),
+            ts: Utc::now().timestamp_millis(),
+            message,
+        }
+    }
+
+    pub fn msg_type(&self) -> &str {
+        self.message.msg_type()
+    }
+
+    /// Decode an envelope from its JSON text.
+    pub fn parse(text: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(text)
+    }
+
+    /// The envelope's JSON text.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("protocol messages always serialize")
+    }
+}
+
+impl<M: WireMessage> Serialize for Envelope<M> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let payload = self.message.to_payload().map_err(S::Error::custom)?;
+        RawEnvelope {
+            v: self.v,
+            id: self.id.clone(),
+            ts: self.ts,
+            msg_type: self.msg_type().to_string(),
+            payload,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, M: WireMessage> Deserialize<'de> for Envelope<M> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        RawEnvelope::deserialize(deserializer)?
+            .decode()
+            .map_err(D::Error::custom)
+    }
+}
+
+/// An envelope whose payload hasn't been decoded yet.
+///
+/// Lets a receiver read the `id` and `v` of a message it can't decode, to
+/// reject it properly.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RawEnvelope {
+    #[serde(default = "legacy_version")]
+    pub v: u32,
+    #[serde(default)]
+    pub id: String,
+    #[serde(default)]
+    pub ts: i64,
+    #[serde(rename = "type")]
+    pub msg_type: String,
+    #[serde(default)]
+    pub payload: Value,
+}
+
+impl RawEnvelope {
+    /// Decode the payload into a typed message.
+    pub fn decode<M: WireMessage>(self) -> serde_json::Result<Envelope<M>> {
+        let message = M::from_parts(self.msg_type, self.payload)?;
+        Ok(Envelope {
+            v: self.v,
+            id: self.id,
+            ts: self.ts,
+            message,
+        })
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Shared payloads
+// ---------------------------------------------------------------------------
+
+/// Keep-alive on either link. App clients send `ping` and the daemon
+/// answers with `pong`; the cloud link sends neither.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HeartbeatPayload {
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub ping: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub pong: bool,
+}
+
+#[cfg(test)]
+pub(crate) mod golden {
+    //! Golden-file checks shared by the message set tests.
+
+    use std::path::PathBuf;
+
+    use super::*;
+
+    /// A fixed envelope, so its JSON is stable.
+    pub fn envelope<M: WireMessage>(message: M) -> Envelope<M> {
+        Envelope {
+            v: PROTOCOL_VERSION,
+            id: "00000000-0000-4000-8000-000000000001".to_string(),
+            ts: 1_760_000_000_000,
+            message,
+        }
+    }
+
+    /// Check `envelope` against `golden/{name}.json` both ways: it
+    /// serializes to the file's JSON and the file decodes back to it.
+    pub fn check<M>(name: &str, envelope: &Envelope<M>)
+    where
+        M: WireMessage + PartialEq + std::fmt::Debug,
+    {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("src/protocol/golden")
+            .join(format!("{name}.json"));
+        let actual = serde_json::to_value(envelope).unwrap();
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            let text = serde_json::to_string_pretty(&actual).unwrap();
+            std::fs::write(&path, text + "\n").unwrap();
+        }
+
+        let text = std::fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("{}: {e} (run with UPDATE_GOLDEN=1)", path.display()));
+        let expected: Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(actual, expected, "{name}: JSON differs from golden file");
+        let decoded: Envelope<M> = serde_json::from_value(expected).unwrap();
+        assert_eq!(
+            &decoded, envelope,
+            "{name}: golden file decodes differently"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negotiates_the_newest_common_version() {
+        assert_eq!(negotiate_version(&[1]), Some(1));
+        assert_eq!(negotiate_version(&[1, 2, 7]), Some(1));
+        assert_eq!(negotiate_version(&[7]), None);
+        assert_eq!(negotiate_version(&[]), None);
+        assert!(is_supported_version(PROTOCOL_VERSION));
+    }
+
+    #[test]
+    fn unknown_types_keep_their_payload() {
+        let text = r#"{"v":1,"id":"m1","ts":5,"type":"step.paused","payload":{"step_id":"st-1"}}"#;
+        let envelope: Envelope<AppMessage> = Envelope::parse(text).unwrap();
+        assert_eq!(
+            envelope.message,
+            AppMessage::Unknown {
+                msg_type: "step.paused".into(),
+                payload: serde_json::json!({ "step_id": "st-1" }),
+            }
+        );
+        // ...and relay unchanged
+        let again: Value = serde_json::to_value(&envelope).unwrap();
+        assert_eq!(again, serde_json::from_str::<Value>(text).unwrap());
+    }
+
+    #[test]
+    fn unknown_fields_are_ignored() {
+        let text = r#"{"v":1,"id":"m1","ts":5,"trace":"t","type":"task.cancel",
+                       "payload":{"task_id":"task-1","reason":"later"}}"#;
+        let envelope: Envelope<AppMessage> = Envelope::parse(text).unwrap();
+        assert_eq!(
+            envelope.message,
+            AppMessage::TaskCancel(TaskCancelPayload {
+                task_id: "task-1".into()
+            })
+        );
+    }
+
+    #[test]
+    fn known_types_with_bad_payloads_are_errors() {
+        let text = r#"{"v":1,"id":"m1","ts":5,"type":"task.cancel","payload":{"task":1}}"#;
+        assert!(Envelope::<AppMessage>::parse(text).is_err());
+
+        // The raw envelope still names the message to reject
+        let raw: RawEnvelope = serde_json::from_str(text).unwrap();
+        assert_eq!(raw.id, "m1");
+        assert!(raw.decode::<AppMessage>().is_err());
+    }
+
+    #[test]
+    fn bare_envelopes_default_to_the_legacy_version() {
+        let envelope: Envelope<AppMessage> = Envelope::parse(r#"{"type":"heartbeat"}"#).unwrap();
+        assert_eq!(envelope.v, 1);
+        assert_eq!(
+            envelope.message,
+            AppMessage::Heartbeat(HeartbeatPayload::default())
+        );
+    }
+
+    #[test]
+    fn new_envelopes_are_current_and_unique() {
+        let a = Envelope::new(CloudMessage::Heartbeat(HeartbeatPayload::default()));
+        let b = Envelope::new(CloudMessage::Heartbeat(HeartbeatPayload::default()));
+        assert_eq!(a.v, PROTOCOL_VERSION);
+        assert_ne!(a.id, b.id);
+        assert_eq!(a.msg_type(), "HEARTBEAT");
+        assert_eq!(
+            a.to_json(),
+            format!(
+                r#"{{"v":1,"id":"{}","ts":{},"type":"HEARTBEAT","payload":{{}}}}"#,
+                a.id, a.ts
+            )
+        );
+    }
+}
@@ -0,0 +1,106 @@
+//! Bearer token for sensitive local REST endpoints.
+//!
+//! The REST API listens on 127.0.0.1, which every local user and process
+//! can reach.  Endpoints that re-key the database, export memory or read
+//! local files additionally require `Authorization: Bearer <token>`.  The
+//! token is generated at daemon start and written to a file only its owner
+//! can read (`~/.d1doctor/api_token` by default); clients read it from
+//! there.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use subtle::ConstantTimeEq;
+
+/// Length in bytes of a generated token.
+const TOKEN_LEN: usize = 32;
+
+/// The daemon's local API token.
+pub struct ApiToken(String);
+
+impl std::fmt::Debug for ApiToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ApiToken(..)")
+    }
+}
+
+impl ApiToken {
+    /// A fresh random token.
+    pub fn generate() -> Result<Self> {
+        let mut bytes = [0u8; TOKEN_LEN];
+        getrandom::getrandom(&mut bytes).context("generating API token")?;
+        Ok(Self(hex::encode(bytes)))
+    }
+
+    /// A fixed token, for tests.
+    #[cfg(test)]
+    pub fn new(token: &str) -> Self {
+        Self(token.to_string())
+    }
+
+    /// Default token file, `~/.d1doctor/api_token`.
+    pub fn default_path() -> PathBuf {
+        d1_common::config_dir().join("api_token")
+    }
+
+    /// Writes the token to `path`, readable by the owner only.  Replaces
+    /// the token of a previous run.
+    pub fn write_file(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options
+            .open(path)
+            .with_context(|| format!("creating API token file {}", path.display()))?;
+        writeln!(file, "{}", self.0)?;
+        file.sync_all()?;
+        Ok(())
+    }
+
+    /// Whether `presented` is this token, compared in constant time.
+    pub fn verify(&self, presented: &str) -> bool {
+        self.0.as_bytes().ct_eq(presented.as_bytes()).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_tokens_verify_and_differ() {
+        let token = ApiToken::generate().unwrap();
+        assert!(token.verify(&token.0.clone()));
+        assert!(!token.verify(""));
+        assert!(!token.verify(&token.0[1..]));
+        assert_ne!(token.0, ApiToken::generate().unwrap().0);
+        assert_eq!(format!("{token:?}"), "ApiToken(..)");
+    }
+
+    #[test]
+    fn test_token_file_is_owner_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("api_token");
+        ApiToken::new("first").write_file(&path).unwrap();
+        ApiToken::new("second").write_file(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap().trim(), "second");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+}
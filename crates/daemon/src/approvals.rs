@@ -0,0 +1,250 @@
+//! Routes command approval prompts to connected app clients.
+//!
+//! [`CommandRelay`](crate::command_relay::CommandRelay) asks its
+//! [`ApprovalHandler`] before running risky commands. The [`ApprovalBroker`]
+//! publishes each prompt to its subscribers (the `/ws` app connections),
+//! which show it as `permission.requested` and answer with
+//! `permission.response`. Prompts nobody can see are denied immediately;
+//! prompts nobody answers are denied after a timeout.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+use tokio::sync::{broadcast, oneshot};
+use tracing::{debug, info, warn};
+
+use crate::command_relay::{ApprovalHandler, ApprovalKind, ApprovalPrompt};
+
+/// How long a prompt waits for an answer before it is denied.
+pub const DEFAULT_APPROVAL_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// A prompt waiting for its answer.
+struct Pending {
+    session_id: Option<String>,
+    command: String,
+    reply: oneshot::Sender<bool>,
+}
+
+/// [`ApprovalHandler`] that asks connected app clients.
+pub struct ApprovalBroker {
+    timeout: Duration,
+    prompts_tx: broadcast::Sender<ApprovalPrompt>,
+    /// Unanswered prompts, by command id.
+    pending: Mutex<HashMap<String, Pending>>,
+    /// `(session_id, command)` pairs the user approved with "remember".
+    remembered: Mutex<HashSet<(String, String)>>,
+}
+
+impl ApprovalBroker {
+    pub fn new(timeout: Duration) -> Self {
+        let (prompts_tx, _) = broadcast::channel(64);
+        Self {
+            timeout,
+            prompts_tx,
+            pending: Mutex::new(HashMap::new()),
+            remembered: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Receive every prompt raised from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<ApprovalPrompt> {
+        self.prompts_tx.subscribe()
+    }
+
+    /// Answer the prompt for `command_id`. With `remember`, a granted
+    /// command is approved without asking for the rest of its session.
+    /// Returns `false` if no such prompt is waiting.
+    pub fn respond(&self, command_id: &str, granted: bool, remember: bool) -> bool {
+        let Some(pending) = self.pending().remove(command_id) else {
+            return false;
+        };
+        if granted && remember {
+            if let Some(session_id) = pending.session_id {
+                self.remembered().insert((session_id, pending.command));
+            }
+        }
+        info!(command_id, granted, "Approval answered");
+        let _ = pending.reply.send(granted);
+        true
+    }
+
+    /// Deny every prompt of `session_id` and forget its remembered
+    /// approvals. Returns how many prompts were denied.
+    pub fn cancel_session(&self, session_id: &str) -> usize {
+        self.remembered()
+            .retain(|(session, _)| session != session_id);
+        let mut pending = self.pending();
+        let ids: Vec<String> = pending
+            .iter()
+            .filter(|(_, p)| p.session_id.as_deref() == Some(session_id))
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in &ids {
+            if let Some(p) = pending.remove(id) {
+                let _ = p.reply.send(false);
+            }
+        }
+        ids.len()
+    }
+
+    /// Number of prompts waiting for an answer.
+    pub fn pending_count(&self) -> usize {
+        self.pending().len()
+    }
+
+    fn is_remembered(&self, prompt: &ApprovalPrompt) -> bool {
+        prompt.session_id.as_ref().is_some_and(|session_id| {
+            self.remembered()
+                .contains(&(session_id.clone(), prompt.command.clone()))
+        })
+    }
+
+    fn pending(&self) -> MutexGuard<'_, HashMap<String, Pending>> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn remembered(&self) -> MutexGuard<'_, HashSet<(String, String)>> {
+        self.remembered.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for ApprovalBroker {
+    fn default() -> Self {
+        Self::new(DEFAULT_APPROVAL_TIMEOUT)
+    }
+}
+
+#[async_trait::async_trait]
+impl ApprovalHandler for ApprovalBroker {
+    async fn request_approval(&self, command_id: &str, command: &str, reason: &str) -> bool {
+        self.approve(&ApprovalPrompt {
+            command_id: command_id.to_string(),
+            session_id: None,
+            kind: ApprovalKind::Shell,
+            command: command.to_string(),
+            reason: reason.to_string(),
+        })
+        .await
+    }
+
+    async fn approve(&self, prompt: &ApprovalPrompt) -> bool {
+        if self.is_remembered(prompt) {
+            debug!(command_id = %prompt.command_id, "Approval remembered for session");
+            return true;
+        }
+
+        let (reply, answer) = oneshot::channel();
+        self.pending().insert(
+            prompt.command_id.clone(),
+            Pending {
+                session_id: prompt.session_id.clone(),
+                command: prompt.command.clone(),
+                reply,
+            },
+        );
+        if self.prompts_tx.send(prompt.clone()).is_err() {
+            self.pending().remove(&prompt.command_id);
+            warn!(command_id = %prompt.command_id, "No client to ask for approval — denying");
+            return false;
+        }
+
+        match tokio::time::timeout(self.timeout, answer).await {
+            Ok(Ok(granted)) => granted,
+            Ok(Err(_)) => false,
+            Err(_) => {
+                self.pending().remove(&prompt.command_id);
+                warn!(command_id = %prompt.command_id, "Approval timed out — denying");
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn prompt(id: &str, session: Option<&str>, command: &str) -> ApprovalPrompt {
+        ApprovalPrompt {
+            command_id: id.to_string(),
+            session_id: session.map(str::to_string),
+            kind: ApprovalKind::Shell,
+            command: command.to_string(),
+            reason: "High-risk command".to_string(),
+        }
+    }
+
+    /// Raise `prompt` from a background task.
+    fn ask(broker: &Arc<ApprovalBroker>, prompt: ApprovalPrompt) -> tokio::task::JoinHandle<bool> {
+        let broker = Arc::clone(broker);
+        tokio::spawn(async move { broker.approve(&prompt).await })
+    }
+
+    #[tokio::test]
+    async fn denies_without_subscribers() {
+        let broker = ApprovalBroker::default();
+        assert!(!broker.approve(&prompt("c1", None, "sudo ls")).await);
+        assert_eq!(broker.pending_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn answers_reach_the_waiting_command() {
+        let broker = Arc::new(ApprovalBroker::default());
+        let mut prompts = broker.subscribe();
+
+        let granted = ask(&broker, prompt("c1", Some("s1"), "sudo ls"));
+        let seen = prompts.recv().await.unwrap();
+        assert_eq!(seen.command_id, "c1");
+        assert!(broker.respond("c1", true, false));
+        assert!(granted.await.unwrap());
+
+        let denied = ask(&broker, prompt("c2", Some("s1"), "sudo ls"));
+        prompts.recv().await.unwrap();
+        assert!(broker.respond("c2", false, false));
+        assert!(!denied.await.unwrap());
+        assert!(!broker.respond("c2", true, false));
+    }
+
+    #[tokio::test]
+    async fn remembered_grants_skip_the_prompt_for_the_session() {
+        let broker = Arc::new(ApprovalBroker::default());
+        let mut prompts = broker.subscribe();
+
+        let granted = ask(&broker, prompt("c1", Some("s1"), "sudo ls"));
+        prompts.recv().await.unwrap();
+        broker.respond("c1", true, true);
+        assert!(granted.await.unwrap());
+
+        assert!(broker.approve(&prompt("c2", Some("s1"), "sudo ls")).await);
+        assert!(prompts.try_recv().is_err());
+
+        // Other sessions and commands still ask
+        let other = ask(&broker, prompt("c3", Some("s2"), "sudo ls"));
+        assert_eq!(prompts.recv().await.unwrap().command_id, "c3");
+        broker.respond("c3", false, false);
+        assert!(!other.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn cancel_session_denies_its_prompts() {
+        let broker = Arc::new(ApprovalBroker::default());
+        let mut prompts = broker.subscribe();
+
+        let waiting = ask(&broker, prompt("c1", Some("s1"), "sudo ls"));
+        prompts.recv().await.unwrap();
+        assert_eq!(broker.cancel_session("s2"), 0);
+        assert_eq!(broker.cancel_session("s1"), 1);
+        assert!(!waiting.await.unwrap());
+        assert_eq!(broker.pending_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn unanswered_prompts_time_out() {
+        let broker = ApprovalBroker::new(Duration::from_millis(20));
+        let _prompts = broker.subscribe();
+        assert!(!broker.approve(&prompt("c1", None, "sudo ls")).await);
+        assert_eq!(broker.pending_count(), 0);
+    }
+}
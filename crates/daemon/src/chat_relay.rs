@@ -2,11 +2,21 @@
 //!
 //! The relay bridges local WebSocket clients (on localhost:9876) with the cloud
 //! agent WebSocket (wss://api.day1.doctor), forwarding user messages upstream
-//! and streaming agent responses back to all connected local clients.
-
-use std::collections::VecDeque;
-use std::sync::Arc;
+//! and streaming agent responses back to local clients.
+//!
+//! Responses are routed by `session_id`: a [`LocalSubscriber`] only receives
+//! messages for the sessions it has joined, and a session belongs to the
+//! subscriber that joined it first until that subscriber leaves it or goes
+//! away. Observers (dashboards) see every message. Messages with an empty
+//! `session_id` are daemon-wide notices and reach everyone.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::error::TrySendError;
 use tokio::sync::{broadcast, mpsc, RwLock};
+use tracing::{debug, warn};
+
+use crate::outbound_queue::{EnqueueOutcome, OutboundQueue};
 
 // Re-export wire types from d1_common so the rest of the daemon keeps working.
 pub use d1_common::chat_message::{ChatMessage, ChatMessageType, ChatPayload};
@@ -14,9 +24,12 @@ pub use d1_common::chat_message::{ChatMessage, ChatMessageType, ChatPayload};
 /// Maximum number of messages to queue when cloud is disconnected.
 const MAX_QUEUE_SIZE: usize = 256;
 
-/// Broadcast channel capacity for local client fan-out.
+/// Broadcast channel capacity for observer fan-out.
 const BROADCAST_CAPACITY: usize = 512;
 
+/// Messages buffered per session subscriber before new ones are dropped.
+const SUBSCRIBER_CAPACITY: usize = 512;
+
 // ---------------------------------------------------------------------------
 // Cloud connection state
 // ---------------------------------------------------------------------------
@@ -27,6 +40,85 @@ pub enum CloudConnectionState {
     Disconnected,
 }
 
+// ---------------------------------------------------------------------------
+// Session subscriptions
+// ---------------------------------------------------------------------------
+
+struct Subscriber {
+    tx: mpsc::Sender<ChatMessage>,
+    sessions: HashSet<String>,
+}
+
+#[derive(Default)]
+struct Subscribers {
+    next_id: u64,
+    by_id: HashMap<u64, Subscriber>,
+    /// Subscriber that opened each joined session.
+    owners: HashMap<String, u64>,
+}
+
+/// A local client's registration for the sessions it takes part in.
+///
+/// Messages for joined sessions arrive on the receiver returned with it by
+/// [`ChatRelay::subscribe_sessions`]. Dropping the subscriber unregisters it
+/// and closes that receiver.
+pub struct LocalSubscriber {
+    id: u64,
+    subscribers: Arc<Mutex<Subscribers>>,
+}
+
+impl LocalSubscriber {
+    /// Start receiving messages for `session_id`. Returns `false`, without
+    /// joining, when another subscriber opened the session. The empty
+    /// session (daemon-wide notices) reaches everyone and is never owned.
+    pub fn join(&self, session_id: &str) -> bool {
+        if session_id.is_empty() {
+            return true;
+        }
+        let mut subscribers = self.subscribers.lock().unwrap();
+        let owner = *subscribers
+            .owners
+            .entry(session_id.to_string())
+            .or_insert(self.id);
+        if owner != self.id {
+            return false;
+        }
+        if let Some(sub) = subscribers.by_id.get_mut(&self.id) {
+            sub.sessions.insert(session_id.to_string());
+        }
+        true
+    }
+
+    /// Stop receiving messages for `session_id` and give up the session.
+    pub fn leave(&self, session_id: &str) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        if let Some(sub) = subscribers.by_id.get_mut(&self.id) {
+            sub.sessions.remove(session_id);
+        }
+        if subscribers.owners.get(session_id) == Some(&self.id) {
+            subscribers.owners.remove(session_id);
+        }
+    }
+
+    /// Whether messages for `session_id` reach this subscriber.
+    pub fn is_joined(&self, session_id: &str) -> bool {
+        self.subscribers
+            .lock()
+            .unwrap()
+            .by_id
+            .get(&self.id)
+            .is_some_and(|sub| sub.sessions.contains(session_id))
+    }
+}
+
+impl Drop for LocalSubscriber {
+    fn drop(&mut self) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.by_id.remove(&self.id);
+        subscribers.owners.retain(|_, owner| *owner != self.id);
+    }
+}
+
 // ---------------------------------------------------------------------------
 // ChatRelay — the core relay engine
 // ---------------------------------------------------------------------------
@@ -34,10 +126,15 @@ pub enum CloudConnectionState {
 pub struct ChatRelay {
     /// Sender half: local clients push user messages here.
     to_cloud_tx: mpsc::Sender<ChatMessage>,
-    /// Broadcast sender: cloud responses fan out to all local clients.
-    to_local_tx: broadcast::Sender<ChatMessage>,
+    /// Broadcast sender: every cloud message fans out to observers.
+    to_observers_tx: broadcast::Sender<ChatMessage>,
+    /// Session subscribers, by subscriber id.
+    subscribers: Arc<Mutex<Subscribers>>,
     /// Pending messages queued while cloud is disconnected.
     queue: Arc<RwLock<VecDeque<ChatMessage>>>,
+    /// Persistent record of every cloud-bound message, replacing `queue`
+    /// when configured.
+    outbound: Option<Arc<OutboundQueue>>,
     /// Current cloud connection state.
     cloud_state: Arc<RwLock<CloudConnectionState>>,
 }
@@ -49,29 +146,77 @@ impl ChatRelay {
     /// the cloud WebSocket writer task should consume.
     pub fn new() -> (Self, mpsc::Receiver<ChatMessage>) {
         let (to_cloud_tx, to_cloud_rx) = mpsc::channel(256);
-        let (to_local_tx, _) = broadcast::channel(BROADCAST_CAPACITY);
+        let (to_observers_tx, _) = broadcast::channel(BROADCAST_CAPACITY);
 
         let relay = Self {
             to_cloud_tx,
-            to_local_tx,
+            to_observers_tx,
+            subscribers: Arc::new(Mutex::new(Subscribers::default())),
             queue: Arc::new(RwLock::new(VecDeque::new())),
+            outbound: None,
             cloud_state: Arc::new(RwLock::new(CloudConnectionState::Disconnected)),
         };
 
         (relay, to_cloud_rx)
     }
 
-    /// Subscribe a local client to receive cloud responses (broadcast).
-    pub fn subscribe_local(&self) -> broadcast::Receiver<ChatMessage> {
-        self.to_local_tx.subscribe()
+    /// Persist every cloud-bound message in `outbound` before sending it, so
+    /// it survives a dropped link or a daemon restart until the cloud socket
+    /// writer marks it delivered.
+    pub fn with_outbound_queue(mut self, outbound: Arc<OutboundQueue>) -> Self {
+        self.outbound = Some(outbound);
+        self
+    }
+
+    /// Register a local client that receives messages for the sessions it
+    /// joins (plus daemon-wide notices).
+    pub fn subscribe_sessions(&self) -> (LocalSubscriber, mpsc::Receiver<ChatMessage>) {
+        let (tx, rx) = mpsc::channel(SUBSCRIBER_CAPACITY);
+        let mut subscribers = self.subscribers.lock().unwrap();
+        let id = subscribers.next_id;
+        subscribers.next_id += 1;
+        subscribers.by_id.insert(
+            id,
+            Subscriber {
+                tx,
+                sessions: HashSet::new(),
+            },
+        );
+        let subscriber = LocalSubscriber {
+            id,
+            subscribers: Arc::clone(&self.subscribers),
+        };
+        (subscriber, rx)
+    }
+
+    /// Subscribe an observer that receives every cloud message, whatever
+    /// its session.
+    pub fn subscribe_observer(&self) -> broadcast::Receiver<ChatMessage> {
+        self.to_observers_tx.subscribe()
     }
 
     /// Send a user message towards the cloud.
     ///
-    /// If the cloud connection is down the message is queued (up to
-    /// [`MAX_QUEUE_SIZE`]). Returns the number of currently queued messages
-    /// when queuing occurs.
+    /// With a persistent outbound queue the message is written to it first,
+    /// whatever the connection state, and only sent while connected; a
+    /// message id that is already queued or delivered is ignored. Without
+    /// one, messages are held in memory (up to [`MAX_QUEUE_SIZE`]) while the
+    /// cloud connection is down.
     pub async fn send_to_cloud(&self, msg: ChatMessage) -> Result<(), RelayError> {
+        if let Some(outbound) = &self.outbound {
+            let payload =
+                serde_json::to_vec(&msg).map_err(|e| RelayError::Storage(e.to_string()))?;
+            match outbound.enqueue(&msg.id, &payload) {
+                Ok(EnqueueOutcome::Queued) => {}
+                Ok(EnqueueOutcome::Duplicate) => {
+                    debug!(id = %msg.id, "Message already queued");
+                    return Ok(());
+                }
+                Ok(EnqueueOutcome::Full) => return Err(RelayError::QueueFull),
+                Err(e) => return Err(RelayError::Storage(e.to_string())),
+            }
+        }
+
         let state = *self.cloud_state.read().await;
         match state {
             CloudConnectionState::Connected => {
@@ -80,6 +225,8 @@ impl ChatRelay {
                     .await
                     .map_err(|_| RelayError::ChannelClosed)?;
             }
+            // Replayed from the persistent queue on reconnect.
+            CloudConnectionState::Disconnected if self.outbound.is_some() => {}
             CloudConnectionState::Disconnected => {
                 let mut q = self.queue.write().await;
                 if q.len() >= MAX_QUEUE_SIZE {
@@ -91,13 +238,42 @@ impl ChatRelay {
         Ok(())
     }
 
-    /// Deliver a message from the cloud to all local clients.
+    /// Deliver a message from the cloud to the subscribers of its session
+    /// and to every observer. A message without a session reaches every
+    /// subscriber.
     ///
     /// Returns the number of receivers that got the message.
     pub fn send_to_local(&self, msg: ChatMessage) -> Result<usize, RelayError> {
-        self.to_local_tx
-            .send(msg)
-            .map_err(|_| RelayError::NoLocalClients)
+        let session_id = msg.payload.session_id.as_str();
+        let mut delivered = 0;
+        {
+            let mut subscribers = self.subscribers.lock().unwrap();
+            subscribers.by_id.retain(|id, sub| {
+                if !session_id.is_empty() && !sub.sessions.contains(session_id) {
+                    return true;
+                }
+                match sub.tx.try_send(msg.clone()) {
+                    Ok(()) => {
+                        delivered += 1;
+                        true
+                    }
+                    Err(TrySendError::Full(_)) => {
+                        warn!(
+                            subscriber = id,
+                            session_id, "Local client lagging; dropped message"
+                        );
+                        true
+                    }
+                    Err(TrySendError::Closed(_)) => false,
+                }
+            });
+        }
+        delivered += self.to_observers_tx.send(msg).unwrap_or(0);
+
+        if delivered == 0 {
+            return Err(RelayError::NoLocalClients);
+        }
+        Ok(delivered)
     }
 
     /// Mark the cloud connection as up and flush any queued messages.
@@ -106,7 +282,8 @@ impl ChatRelay {
         self.flush_queue().await
     }
 
-    /// Mark the cloud connection as down and notify local clients.
+    /// Mark the cloud connection as down and notify the clients of
+    /// `session_id` (every client when it is empty).
     pub async fn set_disconnected(&self, session_id: &str) {
         *self.cloud_state.write().await = CloudConnectionState::Disconnected;
         let _ = self.send_to_local(ChatMessage::error(
@@ -119,8 +296,12 @@ impl ChatRelay {
     ///
     /// Returns the messages that were flushed (for testing / logging).
     async fn flush_queue(&self) -> Result<Vec<ChatMessage>, RelayError> {
+        let mut flushed = match &self.outbound {
+            Some(outbound) => self.replay_outbound(outbound).await?,
+            None => Vec::new(),
+        };
+
         let mut q = self.queue.write().await;
-        let mut flushed = Vec::with_capacity(q.len());
         while let Some(msg) = q.pop_front() {
             self.to_cloud_tx
                 .send(msg.clone())
@@ -131,9 +312,48 @@ impl ChatRelay {
         Ok(flushed)
     }
 
+    /// Send the persistent queue's undelivered messages in order, each
+    /// counted as a delivery attempt. Stops at the first failure to keep
+    /// the order. The messages stay queued until the cloud socket writer
+    /// marks them delivered.
+    async fn replay_outbound(
+        &self,
+        outbound: &OutboundQueue,
+    ) -> Result<Vec<ChatMessage>, RelayError> {
+        let storage = |e: anyhow::Error| RelayError::Storage(e.to_string());
+        let mut flushed = Vec::new();
+        for pending in outbound.pending().map_err(storage)? {
+            if !outbound.begin_attempt(&pending.id).map_err(storage)? {
+                continue;
+            }
+            let msg: ChatMessage = match serde_json::from_slice(&pending.payload) {
+                Ok(msg) => msg,
+                Err(e) => {
+                    outbound
+                        .dead_letter(&pending.id, &format!("undecodable message: {e}"))
+                        .map_err(storage)?;
+                    continue;
+                }
+            };
+            if self.to_cloud_tx.send(msg.clone()).await.is_err() {
+                outbound
+                    .mark_failed(&pending.id, "cloud channel closed")
+                    .map_err(storage)?;
+                return Err(RelayError::ChannelClosed);
+            }
+            flushed.push(msg);
+        }
+        Ok(flushed)
+    }
+
     /// Number of messages currently queued.
     pub async fn queue_len(&self) -> usize {
-        self.queue.read().await.len()
+        let persisted = self
+            .outbound
+            .as_ref()
+            .and_then(|outbound| outbound.len().ok())
+            .unwrap_or(0);
+        self.queue.read().await.len() + persisted
     }
 
     /// Current cloud connection state.
@@ -154,6 +374,8 @@ pub enum RelayError {
     QueueFull,
     #[error("no local clients connected")]
     NoLocalClients,
+    #[error("outbound queue storage failed: {0}")]
+    Storage(String),
 }
 
 // ---------------------------------------------------------------------------
@@ -253,11 +475,11 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_send_to_local_broadcast() {
+    async fn test_send_to_local_reaches_observers() {
         let (relay, _cloud_rx) = ChatRelay::new();
 
-        let mut rx1 = relay.subscribe_local();
-        let mut rx2 = relay.subscribe_local();
+        let mut rx1 = relay.subscribe_observer();
+        let mut rx2 = relay.subscribe_observer();
 
         let msg = agent_msg("s1", "answer");
         let count = relay.send_to_local(msg).unwrap();
@@ -323,12 +545,112 @@ mod tests {
         assert_eq!(result, Err(RelayError::QueueFull));
     }
 
+    // -- relay: persistent queue ---------------------------------------------
+
+    fn outbound_queue(
+        db: &Arc<crate::local_db::LocalDb>,
+        config: crate::outbound_queue::OutboundQueueConfig,
+    ) -> Arc<OutboundQueue> {
+        Arc::new(OutboundQueue::new(Arc::clone(db), config))
+    }
+
+    #[tokio::test]
+    async fn test_persistent_queue_survives_restart() {
+        let db = Arc::new(crate::local_db::LocalDb::open_in_memory().unwrap());
+
+        // First daemon run: queue while offline, then "crash"
+        {
+            let (relay, _cloud_rx) = ChatRelay::new();
+            let relay = relay.with_outbound_queue(outbound_queue(&db, Default::default()));
+            let first = user_msg("s1", "first");
+            relay.send_to_cloud(first.clone()).await.unwrap();
+            relay.send_to_cloud(user_msg("s1", "second")).await.unwrap();
+            // A client retry of the same message is not queued twice
+            relay.send_to_cloud(first).await.unwrap();
+            assert_eq!(relay.queue_len().await, 2);
+        }
+
+        // Second run: replayed in order on connect
+        let outbound = outbound_queue(&db, Default::default());
+        let (relay, mut cloud_rx) = ChatRelay::new();
+        let relay = relay.with_outbound_queue(Arc::clone(&outbound));
+        assert_eq!(relay.queue_len().await, 2);
+        let flushed = relay.set_connected().await.unwrap();
+        assert_eq!(flushed.len(), 2);
+        let first = cloud_rx.recv().await.unwrap();
+        let second = cloud_rx.recv().await.unwrap();
+        assert_eq!(first.payload.content, "first");
+        assert_eq!(second.payload.content, "second");
+        // Still queued until the socket writer has sent them
+        assert_eq!(relay.queue_len().await, 2);
+        outbound.mark_delivered(&first.id).unwrap();
+        outbound.mark_delivered(&second.id).unwrap();
+        assert_eq!(relay.queue_len().await, 0);
+
+        // Nothing is replayed twice
+        relay.set_disconnected("").await;
+        assert!(relay.set_connected().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_persistent_queue_keeps_unsent_messages_while_connected() {
+        let db = Arc::new(crate::local_db::LocalDb::open_in_memory().unwrap());
+        let outbound = outbound_queue(&db, Default::default());
+        let (relay, mut cloud_rx) = ChatRelay::new();
+        let relay = relay.with_outbound_queue(Arc::clone(&outbound));
+        relay.set_connected().await.unwrap();
+
+        // Sent while connected, but the link drops before the socket
+        // writer gets to it
+        relay
+            .send_to_cloud(user_msg("s1", "in flight"))
+            .await
+            .unwrap();
+        assert_eq!(cloud_rx.recv().await.unwrap().payload.content, "in flight");
+        assert_eq!(relay.queue_len().await, 1);
+        relay.set_disconnected("").await;
+
+        let flushed = relay.set_connected().await.unwrap();
+        assert_eq!(flushed.len(), 1);
+        let replayed = cloud_rx.recv().await.unwrap();
+        assert_eq!(replayed.payload.content, "in flight");
+        outbound.mark_delivered(&replayed.id).unwrap();
+        assert_eq!(relay.queue_len().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_persistent_queue_dead_letters_after_retries() {
+        let db = Arc::new(crate::local_db::LocalDb::open_in_memory().unwrap());
+        let config = crate::outbound_queue::OutboundQueueConfig {
+            max_attempts: 1,
+            ..Default::default()
+        };
+        let outbound = outbound_queue(&db, config);
+
+        let (relay, cloud_rx) = ChatRelay::new();
+        let relay = relay.with_outbound_queue(Arc::clone(&outbound));
+        relay.send_to_cloud(user_msg("s1", "doomed")).await.unwrap();
+
+        // The cloud writer is gone: the replay attempt fails
+        drop(cloud_rx);
+        assert_eq!(relay.set_connected().await, Err(RelayError::ChannelClosed));
+        assert_eq!(relay.queue_len().await, 1);
+
+        // The next replay exceeds the attempt limit
+        relay.set_disconnected("").await;
+        assert!(relay.set_connected().await.unwrap().is_empty());
+        assert_eq!(relay.queue_len().await, 0);
+        let dead = outbound.dead_letters().unwrap();
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].attempts, 2);
+    }
+
     // -- relay: disconnect notification -------------------------------------
 
     #[tokio::test]
     async fn test_disconnect_notifies_local_clients() {
         let (relay, _cloud_rx) = ChatRelay::new();
-        let mut local_rx = relay.subscribe_local();
+        let mut local_rx = relay.subscribe_observer();
 
         relay.set_disconnected("s1").await;
 
@@ -342,7 +664,7 @@ mod tests {
     #[tokio::test]
     async fn test_streaming_token_relay() {
         let (relay, _cloud_rx) = ChatRelay::new();
-        let mut local_rx = relay.subscribe_local();
+        let mut local_rx = relay.subscribe_observer();
 
         // Simulate cloud sending streaming tokens.
         relay.send_to_local(stream_chunk("s1", "Hello")).unwrap();
@@ -380,13 +702,110 @@ mod tests {
         );
     }
 
+    // -- session routing ----------------------------------------------------
+
+    #[tokio::test]
+    async fn test_messages_routed_by_session() {
+        let (relay, _cloud_rx) = ChatRelay::new();
+        let (cli_a, mut rx_a) = relay.subscribe_sessions();
+        let (cli_b, mut rx_b) = relay.subscribe_sessions();
+        cli_a.join("s1");
+        cli_b.join("s2");
+
+        assert_eq!(relay.send_to_local(agent_msg("s1", "for a")).unwrap(), 1);
+        assert_eq!(relay.send_to_local(agent_msg("s2", "for b")).unwrap(), 1);
+        assert_eq!(
+            relay.send_to_local(agent_msg("s3", "nobody")),
+            Err(RelayError::NoLocalClients)
+        );
+
+        assert_eq!(rx_a.recv().await.unwrap().payload.content, "for a");
+        assert_eq!(rx_b.recv().await.unwrap().payload.content, "for b");
+        assert!(rx_a.try_recv().is_err());
+        assert!(rx_b.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_session_belongs_to_the_subscriber_that_opened_it() {
+        let (relay, _cloud_rx) = ChatRelay::new();
+        let (owner, mut owner_rx) = relay.subscribe_sessions();
+        let (other, mut other_rx) = relay.subscribe_sessions();
+        assert!(owner.join("s1"));
+        assert!(owner.join("s1"));
+
+        // Naming someone else's session does not subscribe to it
+        assert!(!other.join("s1"));
+        assert!(!other.is_joined("s1"));
+        assert_eq!(relay.send_to_local(agent_msg("s1", "private")).unwrap(), 1);
+        assert_eq!(owner_rx.recv().await.unwrap().payload.content, "private");
+        assert!(other_rx.try_recv().is_err());
+
+        // Free again once the owner leaves it or goes away
+        owner.leave("s1");
+        assert!(other.join("s1"));
+        assert!(!owner.join("s1"));
+        drop(other);
+        assert!(owner.join("s1"));
+
+        assert!(owner.join(""));
+        let (third, _third_rx) = relay.subscribe_sessions();
+        assert!(third.join(""));
+    }
+
+    #[tokio::test]
+    async fn test_observer_sees_every_session() {
+        let (relay, _cloud_rx) = ChatRelay::new();
+        let (cli, mut cli_rx) = relay.subscribe_sessions();
+        cli.join("s1");
+        let mut observer = relay.subscribe_observer();
+
+        assert_eq!(relay.send_to_local(agent_msg("s1", "one")).unwrap(), 2);
+        assert_eq!(relay.send_to_local(agent_msg("s2", "two")).unwrap(), 1);
+
+        assert_eq!(observer.recv().await.unwrap().payload.content, "one");
+        assert_eq!(observer.recv().await.unwrap().payload.content, "two");
+        assert_eq!(cli_rx.recv().await.unwrap().payload.content, "one");
+        assert!(cli_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sessionless_notice_reaches_every_subscriber() {
+        let (relay, _cloud_rx) = ChatRelay::new();
+        let (_a, mut rx_a) = relay.subscribe_sessions();
+        let (_b, mut rx_b) = relay.subscribe_sessions();
+
+        relay.set_disconnected("").await;
+
+        assert_eq!(rx_a.recv().await.unwrap().msg_type, ChatMessageType::Error);
+        assert_eq!(rx_b.recv().await.unwrap().msg_type, ChatMessageType::Error);
+    }
+
+    #[tokio::test]
+    async fn test_leave_and_drop_stop_delivery() {
+        let (relay, _cloud_rx) = ChatRelay::new();
+        let (cli, mut rx) = relay.subscribe_sessions();
+        cli.join("s1");
+        cli.join("s2");
+        assert!(cli.is_joined("s1"));
+
+        cli.leave("s1");
+        assert!(!cli.is_joined("s1"));
+        assert!(relay.send_to_local(agent_msg("s1", "gone")).is_err());
+        relay.send_to_local(agent_msg("s2", "still here")).unwrap();
+        assert_eq!(rx.recv().await.unwrap().payload.content, "still here");
+
+        drop(cli);
+        assert!(relay.send_to_local(agent_msg("s2", "gone")).is_err());
+        assert!(rx.recv().await.is_none());
+    }
+
     // -- multi-client broadcast ---------------------------------------------
 
     #[tokio::test]
-    async fn test_multiple_local_clients_receive_same_events() {
+    async fn test_multiple_observers_receive_same_events() {
         let (relay, _cloud_rx) = ChatRelay::new();
 
-        let mut clients: Vec<_> = (0..5).map(|_| relay.subscribe_local()).collect();
+        let mut clients: Vec<_> = (0..5).map(|_| relay.subscribe_observer()).collect();
 
         let msg = agent_msg("s1", "broadcast test");
         let count = relay.send_to_local(msg).unwrap();
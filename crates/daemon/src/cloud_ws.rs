@@ -2,103 +2,79 @@
 //!
 //! Connects to `wss://api.day1.doctor/ws/daemon`, authenticates with a JWT token,
 //! maintains a heartbeat, and auto-reconnects with exponential backoff.
+//!
+//! Connectivity is reported through a shared [`ConnectionStateMachine`]:
+//! the client moves it to `Reconnecting` while (re)connecting, `Online` once
+//! authenticated, `Degraded` when a health-check ping goes unanswered, and
+//! `Offline` once reconnect attempts are exhausted or on shutdown.
+//!
+//! An outbound frame is only dropped once the socket has accepted it: a
+//! frame whose send fails is sent first on the next connection, and a
+//! frame from the persistent [`OutboundQueue`] is marked delivered only
+//! after it was sent.
 
 use std::sync::Arc;
 use std::time::Duration;
 
 use futures::stream::{SplitSink, SplitStream};
 use futures::{SinkExt, StreamExt};
-use serde::{Deserialize, Serialize};
 use tokio::net::TcpStream;
-use tokio::sync::{mpsc, watch, RwLock};
+use tokio::sync::{broadcast, mpsc, watch};
 use tokio::time;
 use tokio_tungstenite::tungstenite::Message;
 use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
 use tracing::{debug, error, info, warn};
-use uuid::Uuid;
+
+use d1_common::protocol::{
+    AuthCredentials, AuthPayload, CloudMessage, Envelope, HeartbeatPayload, WireMessage,
+};
+
+use crate::connection_state::{ConnectionState, ConnectionStateEvent, ConnectionStateMachine};
+use crate::outbound_queue::OutboundQueue;
 
 /// Default cloud WebSocket endpoint.
 pub const DEFAULT_CLOUD_WS_URL: &str = "wss://api.day1.doctor/ws/daemon";
 
-/// Heartbeat interval.
-const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
-
 /// Auth handshake timeout.
 const AUTH_TIMEOUT: Duration = Duration::from_secs(10);
 
-/// Backoff parameters.
-const BACKOFF_INITIAL: Duration = Duration::from_secs(1);
-const BACKOFF_MAX: Duration = Duration::from_secs(30);
-const BACKOFF_MULTIPLIER: u32 = 2;
-
 // ---------------------------------------------------------------------------
-// Connection state
+// CloudWsClient
 // ---------------------------------------------------------------------------
 
-/// State of the cloud WebSocket connection.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum ConnectionState {
-    Disconnected,
-    Connecting,
-    Authenticating,
-    Connected,
-}
-
-impl std::fmt::Display for ConnectionState {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::Disconnected => write!(f, "disconnected"),
-            Self::Connecting => write!(f, "connecting"),
-            Self::Authenticating => write!(f, "authenticating"),
-            Self::Connected => write!(f, "connected"),
-        }
-    }
+/// Configuration for the cloud WebSocket client.
+#[derive(Debug, Clone)]
+pub struct CloudWsConfig {
+    pub url: String,
+    pub jwt: String,
+    pub device_fingerprint: String,
 }
 
-// ---------------------------------------------------------------------------
-// Protocol messages  (v1 wire format: { v, id, ts, type, payload })
-// ---------------------------------------------------------------------------
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct WsMessage {
-    pub v: u32,
-    pub id: String,
-    pub ts: i64,
-    #[serde(rename = "type")]
-    pub msg_type: String,
-    #[serde(default)]
-    pub payload: serde_json::Value,
+/// A text frame from the daemon to the cloud.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutboundFrame {
+    pub text: String,
+    /// Id of the message in the persistent [`OutboundQueue`], if it is one.
+    pub queue_id: Option<String>,
 }
 
-impl WsMessage {
-    pub fn new(msg_type: &str, payload: serde_json::Value) -> Self {
+impl OutboundFrame {
+    /// A frame for a message held in the persistent queue under `id`.
+    pub fn queued(text: String, id: impl Into<String>) -> Self {
         Self {
-            v: 1,
-            id: Uuid::new_v4().to_string(),
-            ts: chrono::Utc::now().timestamp_millis(),
-            msg_type: msg_type.to_string(),
-            payload,
+            text,
+            queue_id: Some(id.into()),
         }
     }
 }
 
-/// Payload sent with the AUTH message.
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct AuthPayload {
-    pub jwt: String,
-    pub device_fingerprint: String,
-}
-
-// ---------------------------------------------------------------------------
-// CloudWsClient
-// ---------------------------------------------------------------------------
-
-/// Configuration for the cloud WebSocket client.
-#[derive(Debug, Clone)]
-pub struct CloudWsConfig {
-    pub url: String,
-    pub jwt: String,
-    pub device_fingerprint: String,
+impl From<String> for OutboundFrame {
+    fn from(text: String) -> Self {
+        Self {
+            text,
+            queue_id: None,
+        }
+    }
 }
 
 /// Cloud WebSocket client that manages the connection lifecycle.
@@ -107,112 +83,145 @@ pub struct CloudWsConfig {
 /// Use [`CloudWsClient::state`] to query the current connection state and
 /// [`CloudWsClient::subscribe_state`] to watch for state changes.
 pub struct CloudWsClient {
-    state: Arc<RwLock<ConnectionState>>,
-    state_tx: watch::Sender<ConnectionState>,
-    state_rx: watch::Receiver<ConnectionState>,
+    connection: Arc<ConnectionStateMachine>,
+    outbound_queue: Option<Arc<OutboundQueue>>,
     shutdown_tx: watch::Sender<bool>,
 }
 
 impl CloudWsClient {
-    /// Create a new client (does **not** connect yet).
-    pub fn new() -> Self {
-        let (state_tx, state_rx) = watch::channel(ConnectionState::Disconnected);
+    /// Create a new client (does **not** connect yet) that reports into
+    /// `connection`. Heartbeat and backoff timing come from its config.
+    pub fn new(connection: Arc<ConnectionStateMachine>) -> Self {
         let (shutdown_tx, _) = watch::channel(false);
         Self {
-            state: Arc::new(RwLock::new(ConnectionState::Disconnected)),
-            state_tx,
-            state_rx,
+            connection,
+            outbound_queue: None,
             shutdown_tx,
         }
     }
 
+    /// Mark queued frames delivered in `queue` once they are sent, and skip
+    /// replayed copies of frames that already were.
+    pub fn with_outbound_queue(mut self, queue: Arc<OutboundQueue>) -> Self {
+        self.outbound_queue = Some(queue);
+        self
+    }
+
+    /// The state machine this client drives.
+    pub fn connection(&self) -> &Arc<ConnectionStateMachine> {
+        &self.connection
+    }
+
     /// Current connection state.
     pub async fn state(&self) -> ConnectionState {
-        *self.state.read().await
+        self.connection.state().await
     }
 
     /// Subscribe to state changes.
-    pub fn subscribe_state(&self) -> watch::Receiver<ConnectionState> {
-        self.state_rx.clone()
+    pub fn subscribe_state(&self) -> broadcast::Receiver<ConnectionStateEvent> {
+        self.connection.subscribe()
     }
 
     /// Spawn the background connection loop.
     ///
     /// Returns a `JoinHandle` for the task. The loop will keep reconnecting
     /// with exponential backoff until [`CloudWsClient::shutdown`] is called.
+    /// Once the state machine's reconnect attempts are exhausted it goes
+    /// `Offline` but keeps retrying at the maximum delay.
     ///
     /// - `outbound_rx`: messages from the daemon to send to the cloud WS.
     /// - `inbound_tx`: messages received from the cloud WS to forward into the daemon.
     pub fn spawn(
         &self,
         config: CloudWsConfig,
-        outbound_rx: mpsc::Receiver<String>,
+        outbound_rx: mpsc::Receiver<OutboundFrame>,
         inbound_tx: mpsc::Sender<String>,
     ) -> tokio::task::JoinHandle<()> {
-        let state = Arc::clone(&self.state);
-        let state_tx = self.state_tx.clone();
+        let connection = Arc::clone(&self.connection);
         let mut shutdown_rx = self.shutdown_tx.subscribe();
         // mpsc::Receiver is not Clone — move it into the spawned task.
-        let mut outbound_rx = outbound_rx;
+        let mut outbound = Outbound {
+            queue: self.outbound_queue.clone(),
+            rx: outbound_rx,
+            unsent: None,
+        };
 
         tokio::spawn(async move {
-            let mut backoff = BACKOFF_INITIAL;
-
             loop {
                 // Check shutdown before attempting connection.
                 if *shutdown_rx.borrow() {
-                    set_state(&state, &state_tx, ConnectionState::Disconnected).await;
+                    let _ = connection
+                        .transition(ConnectionState::Offline, "shutdown")
+                        .await;
                     info!("cloud_ws: shutdown requested, exiting connection loop");
                     return;
                 }
 
-                set_state(&state, &state_tx, ConnectionState::Connecting).await;
+                let _ = connection
+                    .transition(ConnectionState::Reconnecting, "connecting to cloud")
+                    .await;
                 info!(url = %config.url, "cloud_ws: connecting");
 
-                match connect_and_auth(&config, &state, &state_tx).await {
+                match connect_and_auth(&config).await {
                     Ok((mut sink, mut stream)) => {
                         info!("cloud_ws: authenticated, entering message loop");
-                        backoff = BACKOFF_INITIAL; // reset on success
+                        let _ = connection
+                            .transition(ConnectionState::Online, "authenticated")
+                            .await;
 
                         // Run the message loop (heartbeat + read + send).
                         let reason = message_loop(
+                            &connection,
                             &mut sink,
                             &mut stream,
                             &mut shutdown_rx,
-                            &mut outbound_rx,
+                            &mut outbound,
                             &inbound_tx,
                         )
                         .await;
 
                         info!(?reason, "cloud_ws: message loop ended");
+                        let _ = connection
+                            .transition(ConnectionState::Reconnecting, reason.to_string())
+                            .await;
                     }
                     Err(e) => {
                         warn!(%e, "cloud_ws: connection/auth failed");
                     }
                 }
 
-                set_state(&state, &state_tx, ConnectionState::Disconnected).await;
-
                 // Check shutdown before sleeping.
                 if *shutdown_rx.borrow() {
+                    let _ = connection
+                        .transition(ConnectionState::Offline, "shutdown")
+                        .await;
                     info!("cloud_ws: shutdown requested, exiting connection loop");
                     return;
                 }
 
+                let backoff = match connection.next_reconnect_delay().await {
+                    Some(delay) => delay,
+                    None => {
+                        let _ = connection
+                            .transition(ConnectionState::Offline, "reconnect attempts exhausted")
+                            .await;
+                        connection.config().max_reconnect_delay
+                    }
+                };
                 info!(
-                    backoff_secs = backoff.as_secs(),
+                    backoff_ms = backoff.as_millis() as u64,
                     "cloud_ws: reconnecting after backoff"
                 );
                 tokio::select! {
                     _ = time::sleep(backoff) => {}
                     _ = shutdown_rx.changed() => {
+                        let _ = connection
+                            .transition(ConnectionState::Offline, "shutdown")
+                            .await;
                         info!("cloud_ws: shutdown during backoff");
                         return;
                     }
                 }
-
-                // Exponential backoff.
-                backoff = std::cmp::min(backoff * BACKOFF_MULTIPLIER, BACKOFF_MAX);
             }
         })
     }
@@ -230,41 +239,17 @@ impl CloudWsClient {
 type WsSink = SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>;
 type WsStream = SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>;
 
-async fn set_state(
-    state: &Arc<RwLock<ConnectionState>>,
-    tx: &watch::Sender<ConnectionState>,
-    new: ConnectionState,
-) {
-    *state.write().await = new;
-    let _ = tx.send(new);
-    debug!(%new, "cloud_ws: state changed");
-}
-
 /// Connect to the server and perform the AUTH handshake.
-async fn connect_and_auth(
-    config: &CloudWsConfig,
-    state: &Arc<RwLock<ConnectionState>>,
-    state_tx: &watch::Sender<ConnectionState>,
-) -> anyhow::Result<(WsSink, WsStream)> {
+async fn connect_and_auth(config: &CloudWsConfig) -> anyhow::Result<(WsSink, WsStream)> {
     let (ws, _resp) = connect_async(&config.url).await?;
     let (mut sink, mut stream) = ws.split();
 
-    // Transition to Authenticating.
-    set_state(state, state_tx, ConnectionState::Authenticating).await;
-
-    // Build and send AUTH message (ChatMessage v1 format: payload.content is JSON string).
-    let auth_payload = AuthPayload {
+    // Build and send AUTH message (chat-shaped: payload.content is JSON text).
+    let auth_msg = Envelope::new(CloudMessage::Auth(AuthPayload::new(&AuthCredentials {
         jwt: config.jwt.clone(),
         device_fingerprint: config.device_fingerprint.clone(),
-    };
-    let content_json = serde_json::to_string(&auth_payload)?;
-    let v1_payload = serde_json::json!({
-        "session_id": "",
-        "content": content_json,
-    });
-    let auth_msg = WsMessage::new("AUTH", v1_payload);
-    let text = serde_json::to_string(&auth_msg)?;
-    sink.send(Message::Text(text)).await?;
+    })));
+    sink.send(Message::Text(auth_msg.to_json())).await?;
     debug!("cloud_ws: AUTH sent, waiting for response");
 
     // Wait for AUTH_OK or AUTH_FAIL.
@@ -272,24 +257,23 @@ async fn connect_and_auth(
 
     match response {
         Ok(Some(Ok(Message::Text(text)))) => {
-            let msg: WsMessage = serde_json::from_str(&text)?;
-            match msg.msg_type.as_str() {
-                "AUTH_OK" => {
+            let msg: Envelope<CloudMessage> = Envelope::parse(&text)?;
+            match msg.message {
+                CloudMessage::AuthOk(_) => {
                     info!("cloud_ws: AUTH_OK received");
-                    set_state(state, state_tx, ConnectionState::Connected).await;
                     Ok((sink, stream))
                 }
-                "AUTH_FAIL" => {
-                    let reason = msg
-                        .payload
-                        .get("content")
-                        .and_then(|v| v.as_str())
-                        .unwrap_or("unknown");
+                CloudMessage::AuthFail(result) => {
+                    let reason = if result.content.is_empty() {
+                        "unknown"
+                    } else {
+                        &result.content
+                    };
                     Err(anyhow::anyhow!("AUTH_FAIL: {}", reason))
                 }
                 other => Err(anyhow::anyhow!(
                     "unexpected message type during auth: {}",
-                    other
+                    other.msg_type()
                 )),
             }
         }
@@ -301,41 +285,119 @@ async fn connect_and_auth(
     }
 }
 
+/// The outbound side of the connection, kept across reconnects.
+struct Outbound {
+    queue: Option<Arc<OutboundQueue>>,
+    rx: mpsc::Receiver<OutboundFrame>,
+    /// A frame whose send failed, sent first on the next connection.
+    unsent: Option<OutboundFrame>,
+}
+
+impl Outbound {
+    /// Send `frame`, then mark it delivered if it came from the persistent
+    /// queue. A queued frame that was already delivered (a replayed copy)
+    /// is skipped. On failure the frame is kept for the next connection.
+    async fn send(&mut self, sink: &mut WsSink, frame: OutboundFrame) -> Result<(), String> {
+        let queued = self.queue.as_deref().zip(frame.queue_id.as_deref());
+        if let Some((queue, id)) = queued {
+            if queue.is_delivered(id).unwrap_or(false) {
+                debug!(%id, "cloud_ws: skipping already delivered message");
+                return Ok(());
+            }
+        }
+        match sink.send(Message::Text(frame.text.clone())).await {
+            Ok(()) => {
+                if let Some((queue, id)) = queued {
+                    if let Err(e) = queue.mark_delivered(id) {
+                        warn!(%id, %e, "cloud_ws: failed to mark message delivered");
+                    }
+                }
+                Ok(())
+            }
+            Err(e) => {
+                if let Some((queue, id)) = queued {
+                    if let Err(e) = queue.mark_failed(id, &e.to_string()) {
+                        warn!(%id, %e, "cloud_ws: failed to record send failure");
+                    }
+                }
+                self.unsent = Some(frame);
+                Err(e.to_string())
+            }
+        }
+    }
+}
+
 /// Reason the message loop exited.
 #[derive(Debug)]
 enum LoopExit {
     ServerClosed,
     Error(String),
+    HealthCheckFailed,
     Shutdown,
 }
 
+impl std::fmt::Display for LoopExit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ServerClosed => write!(f, "server closed the connection"),
+            Self::Error(e) => write!(f, "connection error: {e}"),
+            Self::HealthCheckFailed => write!(f, "health check: consecutive failures"),
+            Self::Shutdown => write!(f, "shutdown"),
+        }
+    }
+}
+
 /// Main message loop: sends heartbeats, reads incoming messages, and sends
 /// outbound messages from the daemon to the cloud.
+///
+/// Every heartbeat also sends a WebSocket ping; a pong that does not arrive
+/// within `ping_timeout` is reported to the state machine as a failed health
+/// check, and the loop gives up on the link once that moves it to
+/// `Reconnecting`.
 async fn message_loop(
+    connection: &ConnectionStateMachine,
     sink: &mut WsSink,
     stream: &mut WsStream,
     shutdown_rx: &mut watch::Receiver<bool>,
-    outbound_rx: &mut mpsc::Receiver<String>,
+    outbound: &mut Outbound,
     inbound_tx: &mpsc::Sender<String>,
 ) -> LoopExit {
-    let mut heartbeat_interval = time::interval(HEARTBEAT_INTERVAL);
+    if let Some(frame) = outbound.unsent.take() {
+        if let Err(e) = outbound.send(sink, frame).await {
+            return LoopExit::Error(format!("outbound send failed: {e}"));
+        }
+        debug!("cloud_ws: sent outbound message held from the last connection");
+    }
+
+    let ping_timeout = connection.config().ping_timeout;
+    let mut heartbeat_interval = time::interval(connection.config().ping_interval);
     // Skip the first immediate tick.
     heartbeat_interval.tick().await;
+    // When the outstanding ping's pong is due, if one is in flight.
+    let mut pong_deadline: Option<time::Instant> = None;
 
     loop {
         tokio::select! {
             _ = heartbeat_interval.tick() => {
-                let ping = WsMessage::new("HEARTBEAT", serde_json::json!({}));
-                match serde_json::to_string(&ping) {
-                    Ok(text) => {
-                        if let Err(e) = sink.send(Message::Text(text)).await {
-                            return LoopExit::Error(format!("heartbeat send failed: {e}"));
-                        }
-                        debug!("cloud_ws: heartbeat sent");
-                    }
-                    Err(e) => {
-                        return LoopExit::Error(format!("heartbeat serialize failed: {e}"));
+                let ping = Envelope::new(CloudMessage::Heartbeat(HeartbeatPayload::default()));
+                if let Err(e) = sink.send(Message::Text(ping.to_json())).await {
+                    return LoopExit::Error(format!("heartbeat send failed: {e}"));
+                }
+                debug!("cloud_ws: heartbeat sent");
+                if pong_deadline.is_none() {
+                    if let Err(e) = sink.send(Message::Ping(Vec::new())).await {
+                        return LoopExit::Error(format!("ping send failed: {e}"));
                     }
+                    pong_deadline = Some(time::Instant::now() + ping_timeout);
+                }
+            }
+            _ = time::sleep_until(pong_deadline.unwrap_or_else(time::Instant::now)),
+                if pong_deadline.is_some() =>
+            {
+                pong_deadline = None;
+                warn!("cloud_ws: ping timed out");
+                if connection.record_ping(false).await == ConnectionState::Reconnecting {
+                    return LoopExit::HealthCheckFailed;
                 }
             }
             msg = stream.next() => {
@@ -352,6 +414,11 @@ async fn message_loop(
                             return LoopExit::Error(format!("pong send failed: {e}"));
                         }
                     }
+                    Some(Ok(Message::Pong(_))) => {
+                        if pong_deadline.take().is_some() {
+                            connection.record_ping(true).await;
+                        }
+                    }
                     Some(Ok(Message::Close(_))) => {
                         info!("cloud_ws: server sent close frame");
                         return LoopExit::ServerClosed;
@@ -364,12 +431,12 @@ async fn message_loop(
                         info!("cloud_ws: stream ended");
                         return LoopExit::ServerClosed;
                     }
-                    _ => {} // Binary, Pong, Frame — ignore.
+                    _ => {} // Binary, Frame — ignore.
                 }
             }
             // Send outbound messages from the daemon to the cloud WS.
-            Some(text) = outbound_rx.recv() => {
-                if let Err(e) = sink.send(Message::Text(text)).await {
+            Some(frame) = outbound.rx.recv() => {
+                if let Err(e) = outbound.send(sink, frame).await {
                     return LoopExit::Error(format!("outbound send failed: {e}"));
                 }
                 debug!("cloud_ws: sent outbound message");
@@ -390,115 +457,83 @@ async fn message_loop(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::connection_state::ConnectionConfig;
 
-    #[test]
-    fn test_connection_state_display() {
-        assert_eq!(ConnectionState::Disconnected.to_string(), "disconnected");
-        assert_eq!(ConnectionState::Connecting.to_string(), "connecting");
-        assert_eq!(
-            ConnectionState::Authenticating.to_string(),
-            "authenticating"
-        );
-        assert_eq!(ConnectionState::Connected.to_string(), "connected");
+    fn client() -> CloudWsClient {
+        let (connection, _) = ConnectionStateMachine::new(ConnectionConfig::default());
+        CloudWsClient::new(connection)
     }
 
     #[test]
-    fn test_ws_message_new() {
-        let msg = WsMessage::new("AUTH", serde_json::json!({"jwt": "tok"}));
-        assert_eq!(msg.v, 1);
-        assert_eq!(msg.msg_type, "AUTH");
-        assert!(!msg.id.is_empty());
-        assert!(msg.ts > 0);
-    }
-
-    #[test]
-    fn test_ws_message_roundtrip() {
-        let msg = WsMessage::new("HEARTBEAT", serde_json::json!({}));
-        let json = serde_json::to_string(&msg).unwrap();
-        let parsed: WsMessage = serde_json::from_str(&json).unwrap();
-        assert_eq!(parsed.v, msg.v);
-        assert_eq!(parsed.msg_type, "HEARTBEAT");
-        assert_eq!(parsed.id, msg.id);
-    }
-
-    #[test]
-    fn test_auth_payload_serialization() {
-        let payload = AuthPayload {
+    fn test_auth_message_wire_format() {
+        let msg = Envelope::new(CloudMessage::Auth(AuthPayload::new(&AuthCredentials {
             jwt: "my.jwt.token".to_string(),
             device_fingerprint: "fp-123".to_string(),
-        };
-        let json = serde_json::to_string(&payload).unwrap();
-        assert!(json.contains("my.jwt.token"));
-        assert!(json.contains("fp-123"));
+        })));
+        let wire: serde_json::Value = serde_json::from_str(&msg.to_json()).unwrap();
 
-        let parsed: AuthPayload = serde_json::from_str(&json).unwrap();
-        assert_eq!(parsed.jwt, "my.jwt.token");
-        assert_eq!(parsed.device_fingerprint, "fp-123");
+        assert_eq!(wire["v"], 1);
+        assert_eq!(wire["type"], "AUTH");
+        assert_eq!(wire["payload"]["session_id"], "");
+        let credentials: serde_json::Value =
+            serde_json::from_str(wire["payload"]["content"].as_str().unwrap()).unwrap();
+        assert_eq!(credentials["jwt"], "my.jwt.token");
+        assert_eq!(credentials["device_fingerprint"], "fp-123");
     }
 
     #[test]
-    fn test_auth_message_wire_format() {
-        let auth_payload = AuthPayload {
-            jwt: "tok".to_string(),
-            device_fingerprint: "dev1".to_string(),
-        };
-        let msg = WsMessage::new("AUTH", serde_json::to_value(&auth_payload).unwrap());
-        let wire: serde_json::Value = serde_json::to_value(&msg).unwrap();
-
-        assert_eq!(wire["v"], 1);
-        assert_eq!(wire["type"], "AUTH");
-        assert_eq!(wire["payload"]["jwt"], "tok");
-        assert_eq!(wire["payload"]["device_fingerprint"], "dev1");
+    fn test_heartbeat_wire_format() {
+        let msg = Envelope::new(CloudMessage::Heartbeat(HeartbeatPayload::default()));
+        let parsed: Envelope<CloudMessage> = Envelope::parse(&msg.to_json()).unwrap();
+        assert_eq!(parsed.msg_type(), "HEARTBEAT");
+        assert_eq!(parsed.id, msg.id);
+        assert_eq!(
+            serde_json::to_value(&parsed).unwrap()["payload"],
+            serde_json::json!({})
+        );
     }
 
     #[tokio::test]
     async fn test_client_initial_state() {
-        let client = CloudWsClient::new();
-        assert_eq!(client.state().await, ConnectionState::Disconnected);
+        let client = client();
+        assert_eq!(client.state().await, ConnectionState::Offline);
     }
 
     #[tokio::test]
     async fn test_client_state_subscribe() {
-        let client = CloudWsClient::new();
-        let rx = client.subscribe_state();
-        assert_eq!(*rx.borrow(), ConnectionState::Disconnected);
+        let client = client();
+        let mut rx = client.subscribe_state();
+        client
+            .connection()
+            .transition(ConnectionState::Reconnecting, "connecting")
+            .await
+            .unwrap();
+        let event = rx.recv().await.unwrap();
+        assert_eq!(event.previous, ConnectionState::Offline);
+        assert_eq!(event.current, ConnectionState::Reconnecting);
     }
 
     #[tokio::test]
     async fn test_client_shutdown_idempotent() {
-        let client = CloudWsClient::new();
+        let client = client();
         client.shutdown();
         client.shutdown(); // should not panic
-        assert_eq!(client.state().await, ConnectionState::Disconnected);
+        assert_eq!(client.state().await, ConnectionState::Offline);
     }
 
     #[tokio::test]
-    async fn test_set_state_updates_both() {
-        let state = Arc::new(RwLock::new(ConnectionState::Disconnected));
-        let (tx, rx) = watch::channel(ConnectionState::Disconnected);
-
-        set_state(&state, &tx, ConnectionState::Connecting).await;
-        assert_eq!(*state.read().await, ConnectionState::Connecting);
-        assert_eq!(*rx.borrow(), ConnectionState::Connecting);
-
-        set_state(&state, &tx, ConnectionState::Connected).await;
-        assert_eq!(*state.read().await, ConnectionState::Connected);
-        assert_eq!(*rx.borrow(), ConnectionState::Connected);
-    }
-
-    #[test]
-    fn test_backoff_progression() {
-        let mut backoff = BACKOFF_INITIAL;
+    async fn test_backoff_progression() {
+        let client = client();
         let expected = [1, 2, 4, 8, 16, 30, 30];
         for &exp in &expected {
+            let backoff = client.connection().next_reconnect_delay().await.unwrap();
             assert_eq!(backoff.as_secs(), exp);
-            backoff = std::cmp::min(backoff * BACKOFF_MULTIPLIER, BACKOFF_MAX);
         }
     }
 
     #[tokio::test]
     async fn test_spawn_shutdown_immediate() {
-        let client = CloudWsClient::new();
+        let client = client();
         let config = CloudWsConfig {
             url: "ws://127.0.0.1:1".to_string(), // will fail to connect
             jwt: "test".to_string(),
@@ -514,12 +549,129 @@ mod tests {
         // Should exit gracefully.
         let result = tokio::time::timeout(Duration::from_secs(5), handle).await;
         assert!(result.is_ok(), "spawn task should exit after shutdown");
+        assert_eq!(client.state().await, ConnectionState::Offline);
     }
 
-    #[test]
-    fn test_connection_state_equality() {
-        assert_eq!(ConnectionState::Disconnected, ConnectionState::Disconnected);
-        assert_ne!(ConnectionState::Disconnected, ConnectionState::Connected);
+    /// Next state the client reports, failing the test after a few seconds.
+    async fn next_state(rx: &mut broadcast::Receiver<ConnectionStateEvent>) -> ConnectionState {
+        time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("state change")
+            .unwrap()
+            .current
+    }
+
+    #[tokio::test]
+    async fn test_missed_pongs_degrade_then_reconnect() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        // Accept and authenticate, then stop reading so pings go unanswered.
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((tcp, _)) = listener.accept().await {
+                let mut ws = tokio_tungstenite::accept_async(tcp).await.unwrap();
+                let Some(Ok(Message::Text(auth))) = ws.next().await else {
+                    continue;
+                };
+                let auth: Envelope<CloudMessage> = Envelope::parse(&auth).unwrap();
+                assert_eq!(auth.msg_type(), "AUTH");
+                let ok = Envelope::new(CloudMessage::AuthOk(Default::default()));
+                ws.send(Message::Text(ok.to_json())).await.unwrap();
+                held.push(ws);
+            }
+        });
+
+        let (connection, mut rx) = ConnectionStateMachine::new(ConnectionConfig {
+            ping_interval: Duration::from_millis(50),
+            ping_timeout: Duration::from_millis(20),
+            ..Default::default()
+        });
+        let client = CloudWsClient::new(connection);
+        let (_outbound_tx, outbound_rx) = mpsc::channel(1);
+        let (inbound_tx, _inbound_rx) = mpsc::channel(1);
+        let handle = client.spawn(
+            CloudWsConfig {
+                url: format!("ws://{addr}"),
+                jwt: "test".to_string(),
+                device_fingerprint: "fp".to_string(),
+            },
+            outbound_rx,
+            inbound_tx,
+        );
+
+        assert_eq!(next_state(&mut rx).await, ConnectionState::Reconnecting);
+        assert_eq!(next_state(&mut rx).await, ConnectionState::Online);
+        assert_eq!(next_state(&mut rx).await, ConnectionState::Degraded);
+        assert_eq!(next_state(&mut rx).await, ConnectionState::Reconnecting);
+        assert_eq!(next_state(&mut rx).await, ConnectionState::Online);
+
+        client.shutdown();
+        handle.await.unwrap();
+        assert_eq!(client.state().await, ConnectionState::Offline);
+    }
+
+    #[tokio::test]
+    async fn test_queued_frames_marked_delivered_once_sent() {
+        use crate::local_db::LocalDb;
+        use crate::outbound_queue::OutboundQueueConfig;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (received_tx, mut received_rx) = mpsc::channel(8);
+        tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(tcp).await.unwrap();
+            ws.next().await.unwrap().unwrap();
+            let ok = Envelope::new(CloudMessage::AuthOk(Default::default()));
+            ws.send(Message::Text(ok.to_json())).await.unwrap();
+            while let Some(Ok(Message::Text(text))) = ws.next().await {
+                received_tx.send(text).await.unwrap();
+            }
+        });
+
+        let queue = Arc::new(OutboundQueue::new(
+            Arc::new(LocalDb::open_in_memory().unwrap()),
+            OutboundQueueConfig::default(),
+        ));
+        queue.enqueue("m1", b"first").unwrap();
+        let client = client().with_outbound_queue(Arc::clone(&queue));
+        let (outbound_tx, outbound_rx) = mpsc::channel(4);
+        let (inbound_tx, _inbound_rx) = mpsc::channel(1);
+        let handle = client.spawn(
+            CloudWsConfig {
+                url: format!("ws://{addr}"),
+                jwt: "test".to_string(),
+                device_fingerprint: "fp".to_string(),
+            },
+            outbound_rx,
+            inbound_tx,
+        );
+
+        outbound_tx
+            .send(OutboundFrame::queued("first".into(), "m1"))
+            .await
+            .unwrap();
+        // A replayed copy of a delivered message is not sent again
+        outbound_tx
+            .send(OutboundFrame::queued("first".into(), "m1"))
+            .await
+            .unwrap();
+        outbound_tx.send("plain".to_string().into()).await.unwrap();
+
+        let mut received = Vec::new();
+        while received.len() < 2 {
+            let text = time::timeout(Duration::from_secs(5), received_rx.recv())
+                .await
+                .expect("frame from client")
+                .unwrap();
+            received.push(text);
+        }
+        assert_eq!(received, ["first", "plain"]);
+        assert!(queue.is_delivered("m1").unwrap());
+        assert!(queue.is_empty().unwrap());
+
+        client.shutdown();
+        handle.await.unwrap();
     }
 
     #[test]
@@ -537,9 +689,10 @@ mod tests {
 
     #[test]
     fn test_default_constants() {
-        assert_eq!(HEARTBEAT_INTERVAL, Duration::from_secs(30));
+        let config = ConnectionConfig::default();
+        assert_eq!(config.ping_interval, Duration::from_secs(30));
         assert_eq!(AUTH_TIMEOUT, Duration::from_secs(10));
-        assert_eq!(BACKOFF_INITIAL, Duration::from_secs(1));
-        assert_eq!(BACKOFF_MAX, Duration::from_secs(30));
+        assert_eq!(config.reconnect_base_delay, Duration::from_secs(1));
+        assert_eq!(config.max_reconnect_delay, Duration::from_secs(30));
     }
 }
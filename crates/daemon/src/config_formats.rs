@@ -0,0 +1,2744 @@
+//! Format-preserving readers and editors for configuration files.
+//!
+//! Backs [`SystemOps::config_read`](crate::system_ops::SystemOps::config_read)
+//! and [`SystemOps::config_edit`](crate::system_ops::SystemOps::config_edit).
+//! Every editor works on the original text and only touches the lines it
+//! has to, so comments, ordering and indentation survive an edit.
+//!
+//! Supported formats:
+//! - TOML (via `toml_edit`)
+//! - JSON
+//! - YAML (block mappings/sequences, scalars, single-line flow collections)
+//! - INI / gitconfig (`[section]`, `[section "subsection"]`)
+//! - dotenv (`KEY=value`, `export KEY=value`)
+//! - ssh_config (`Host` / `Match` blocks)
+//!
+//! Keys are addressed with a dotted path: `server.port`, `servers[0].host`,
+//! `remote.origin.url`, `Host."github.com".User`. Quote a segment that
+//! itself contains dots.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde_json::{Map, Value};
+
+// ---------------------------------------------------------------------------
+// Public types
+// ---------------------------------------------------------------------------
+
+/// A configuration file format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Toml,
+    Json,
+    Yaml,
+    Ini,
+    Dotenv,
+    SshConfig,
+}
+
+impl ConfigFormat {
+    /// Parse a format name as accepted by the MCP tools.
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "toml" => Ok(ConfigFormat::Toml),
+            "json" => Ok(ConfigFormat::Json),
+            "yaml" | "yml" => Ok(ConfigFormat::Yaml),
+            "ini" | "gitconfig" => Ok(ConfigFormat::Ini),
+            "dotenv" | "env" => Ok(ConfigFormat::Dotenv),
+            "ssh_config" | "ssh" => Ok(ConfigFormat::SshConfig),
+            other => anyhow::bail!("unsupported config format: {other}"),
+        }
+    }
+
+    /// Guess the format from a file path. Falls back to TOML.
+    pub fn detect(path: &str) -> Self {
+        let p = Path::new(path);
+        let name = p.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        let parent = p
+            .parent()
+            .and_then(|d| d.file_name())
+            .and_then(|n| n.to_str())
+            .unwrap_or("");
+
+        if name == ".env" || name.starts_with(".env.") || name.ends_with(".env") {
+            return ConfigFormat::Dotenv;
+        }
+        if name == "ssh_config" || (parent == ".ssh" && name == "config") {
+            return ConfigFormat::SshConfig;
+        }
+        if matches!(
+            name,
+            ".gitconfig" | "gitconfig" | ".gitmodules" | ".editorconfig"
+        ) || (parent == ".git" && name == "config")
+        {
+            return ConfigFormat::Ini;
+        }
+
+        match p.extension().and_then(|e| e.to_str()).unwrap_or("") {
+            "json" => ConfigFormat::Json,
+            "yaml" | "yml" => ConfigFormat::Yaml,
+            "ini" | "cfg" => ConfigFormat::Ini,
+            _ => ConfigFormat::Toml,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConfigFormat::Toml => "toml",
+            ConfigFormat::Json => "json",
+            ConfigFormat::Yaml => "yaml",
+            ConfigFormat::Ini => "ini",
+            ConfigFormat::Dotenv => "dotenv",
+            ConfigFormat::SshConfig => "ssh_config",
+        }
+    }
+}
+
+/// One segment of a key path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// An edit to apply at a key path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EditOp {
+    /// Create or replace the value at the path.
+    Set(String),
+    /// Remove the key (all occurrences for multi-valued keys).
+    Delete,
+    /// Push onto the array (or add another value to a multi-valued key).
+    Append(String),
+}
+
+impl EditOp {
+    /// Build an op from its MCP name and optional value.
+    pub fn parse(op: &str, value: Option<&str>) -> Result<Self> {
+        let need_value = || {
+            value
+                .map(|v| v.to_string())
+                .with_context(|| format!("operation {op} requires a value"))
+        };
+        match op {
+            "set" => Ok(EditOp::Set(need_value()?)),
+            "delete" => Ok(EditOp::Delete),
+            "append" => Ok(EditOp::Append(need_value()?)),
+            other => anyhow::bail!("unsupported config operation: {other}"),
+        }
+    }
+}
+
+/// Parse a dotted key path such as `a.b[0]."c.d"`.
+pub fn parse_key_path(key: &str) -> Result<Vec<PathSegment>> {
+    let mut segments = Vec::new();
+    let mut current = String::new();
+    let mut chars = key.chars().peekable();
+    // Whether the segment being built has been started (allows `""` keys).
+    let mut started = false;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                loop {
+                    match chars.next() {
+                        Some('\\') => {
+                            if let Some(esc) = chars.next() {
+                                current.push(esc);
+                            }
+                        }
+                        Some('"') => break,
+                        Some(ch) => current.push(ch),
+                        None => anyhow::bail!("unterminated quote in key path: {key}"),
+                    }
+                }
+                started = true;
+            }
+            '.' => {
+                if started {
+                    segments.push(PathSegment::Key(std::mem::take(&mut current)));
+                } else if !matches!(segments.last(), Some(PathSegment::Index(_))) {
+                    anyhow::bail!("empty segment in key path: {key}");
+                }
+                started = false;
+            }
+            '[' => {
+                if started {
+                    segments.push(PathSegment::Key(std::mem::take(&mut current)));
+                    started = false;
+                }
+                let mut digits = String::new();
+                loop {
+                    match chars.next() {
+                        Some(']') => break,
+                        Some(d) => digits.push(d),
+                        None => anyhow::bail!("unterminated index in key path: {key}"),
+                    }
+                }
+                let index = digits
+                    .trim()
+                    .parse::<usize>()
+                    .with_context(|| format!("invalid array index in key path: {key}"))?;
+                segments.push(PathSegment::Index(index));
+            }
+            other => {
+                current.push(other);
+                started = true;
+            }
+        }
+    }
+
+    if started {
+        segments.push(PathSegment::Key(current));
+    } else if key.ends_with('.') || segments.is_empty() {
+        anyhow::bail!("empty key path");
+    }
+    Ok(segments)
+}
+
+/// Parse `content` into a JSON value.
+pub fn read(format: ConfigFormat, content: &str) -> Result<Value> {
+    match format {
+        ConfigFormat::Toml => {
+            let table: toml::Value = toml::from_str(content).context("failed to parse TOML")?;
+            serde_json::to_value(table).context("failed to convert TOML to JSON")
+        }
+        ConfigFormat::Json => serde_json::from_str(content).context("failed to parse JSON"),
+        ConfigFormat::Yaml => {
+            let doc = YamlDoc::parse(content)?;
+            Ok(doc
+                .root
+                .as_ref()
+                .map(YamlDoc::to_json)
+                .unwrap_or(Value::Null))
+        }
+        ConfigFormat::Ini => Ok(SectionedDoc::parse(content, Dialect::Ini).to_json()),
+        ConfigFormat::SshConfig => Ok(SectionedDoc::parse(content, Dialect::Ssh).to_json()),
+        ConfigFormat::Dotenv => Ok(dotenv_read(content)),
+    }
+}
+
+/// Apply `op` at `path` to `content` and return the new text.
+///
+/// Only the affected lines change; everything else is returned verbatim.
+pub fn edit(
+    format: ConfigFormat,
+    content: &str,
+    path: &[PathSegment],
+    op: &EditOp,
+) -> Result<String> {
+    if path.is_empty() {
+        anyhow::bail!("empty key path");
+    }
+    match format {
+        ConfigFormat::Toml => toml_edit_doc(content, path, op),
+        ConfigFormat::Json => json_edit(content, path, op),
+        ConfigFormat::Yaml => YamlDoc::parse(content)?.edit(path, op),
+        ConfigFormat::Ini => SectionedDoc::parse(content, Dialect::Ini).edit(path, op),
+        ConfigFormat::SshConfig => SectionedDoc::parse(content, Dialect::Ssh).edit(path, op),
+        ConfigFormat::Dotenv => dotenv_edit(content, path, op),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Shared line helpers
+// ---------------------------------------------------------------------------
+
+/// Split text into lines, remembering whether it ended with a newline.
+fn split_lines(content: &str) -> (Vec<String>, bool) {
+    let trailing = content.ends_with('\n') || content.is_empty();
+    (content.lines().map(|l| l.to_string()).collect(), trailing)
+}
+
+fn join_lines(lines: &[String], trailing_newline: bool) -> String {
+    let mut out = lines.join("\n");
+    if trailing_newline && !out.is_empty() {
+        out.push('\n');
+    }
+    out
+}
+
+fn leading_ws(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+fn key_segment<'a>(path: &'a [PathSegment], what: &str) -> Result<&'a str> {
+    match path {
+        [PathSegment::Key(k)] => Ok(k),
+        _ => anyhow::bail!("{what} keys must be a single name"),
+    }
+}
+
+/// Split `path` into its key segments and an optional trailing index.
+fn keys_and_index(path: &[PathSegment]) -> Result<(Vec<&str>, Option<usize>)> {
+    let (body, index) = match path.last() {
+        Some(PathSegment::Index(i)) => (&path[..path.len() - 1], Some(*i)),
+        _ => (path, None),
+    };
+    let keys = body
+        .iter()
+        .map(|s| match s {
+            PathSegment::Key(k) => Ok(k.as_str()),
+            PathSegment::Index(_) => {
+                anyhow::bail!("array indices are only allowed at the end of the key path")
+            }
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok((keys, index))
+}
+
+// ---------------------------------------------------------------------------
+// TOML
+// ---------------------------------------------------------------------------
+
+fn parse_toml_value(s: &str) -> toml_edit::Value {
+    if let Ok(i) = s.parse::<i64>() {
+        return toml_edit::Value::from(i);
+    }
+    if let Ok(f) = s.parse::<f64>() {
+        return toml_edit::Value::from(f);
+    }
+    match s {
+        "true" => toml_edit::Value::from(true),
+        "false" => toml_edit::Value::from(false),
+        _ => toml_edit::Value::from(s),
+    }
+}
+
+fn toml_edit_doc(content: &str, path: &[PathSegment], op: &EditOp) -> Result<String> {
+    let mut doc: toml_edit::DocumentMut = content.parse().context("failed to parse TOML")?;
+    let (parents, last) = path.split_at(path.len() - 1);
+
+    let mut item = doc.as_item_mut();
+    for seg in parents {
+        item = match seg {
+            PathSegment::Key(k) => {
+                if item.get(k).is_none() {
+                    if matches!(op, EditOp::Delete) {
+                        return Ok(content.to_string());
+                    }
+                    if !item.is_table_like() {
+                        anyhow::bail!("cannot descend into non-table TOML value at {k}");
+                    }
+                    item[k.as_str()] = toml_edit::table();
+                    if let Some(t) = item[k.as_str()].as_table_mut() {
+                        t.set_implicit(true);
+                    }
+                }
+                &mut item[k.as_str()]
+            }
+            PathSegment::Index(i) => match item {
+                toml_edit::Item::ArrayOfTables(arr) => {
+                    let table = arr
+                        .get_mut(*i)
+                        .with_context(|| format!("array index {i} out of range"))?;
+                    return toml_edit_table(table, last, op).map(|_| doc.to_string());
+                }
+                toml_edit::Item::Value(toml_edit::Value::Array(_)) => {
+                    anyhow::bail!("editing inside inline arrays is not supported")
+                }
+                _ => anyhow::bail!("cannot index into non-array TOML value"),
+            },
+        };
+    }
+
+    toml_edit_item(item, &last[0], op)?;
+    Ok(doc.to_string())
+}
+
+fn toml_edit_table(table: &mut toml_edit::Table, last: &[PathSegment], op: &EditOp) -> Result<()> {
+    match last {
+        [PathSegment::Key(k)] => {
+            let mut item = toml_edit::Item::Table(std::mem::take(table));
+            let result = toml_edit_item(&mut item, &PathSegment::Key(k.clone()), op);
+            if let toml_edit::Item::Table(t) = item {
+                *table = t;
+            }
+            result
+        }
+        _ => anyhow::bail!("unsupported TOML key path"),
+    }
+}
+
+fn toml_edit_item(parent: &mut toml_edit::Item, seg: &PathSegment, op: &EditOp) -> Result<()> {
+    match (seg, op) {
+        (PathSegment::Key(k), EditOp::Set(v)) => {
+            let mut new_value = parse_toml_value(v);
+            if let Some(old) = parent.get(k).and_then(|i| i.as_value()) {
+                // Keep the spacing and trailing comment of the old value.
+                *new_value.decor_mut() = old.decor().clone();
+            }
+            parent[k.as_str()] = toml_edit::Item::Value(new_value);
+        }
+        (PathSegment::Key(k), EditOp::Delete) => {
+            if let Some(t) = parent.as_table_like_mut() {
+                t.remove(k);
+            }
+        }
+        (PathSegment::Key(k), EditOp::Append(v)) => match parent.get_mut(k) {
+            Some(toml_edit::Item::Value(toml_edit::Value::Array(arr))) => {
+                arr.push(parse_toml_value(v));
+            }
+            Some(toml_edit::Item::ArrayOfTables(_)) => {
+                anyhow::bail!("appending to an array of tables is not supported")
+            }
+            Some(_) => anyhow::bail!("cannot append to non-array TOML value at {k}"),
+            None => {
+                let mut arr = toml_edit::Array::new();
+                arr.push(parse_toml_value(v));
+                parent[k.as_str()] = toml_edit::value(arr);
+            }
+        },
+        (PathSegment::Index(i), _) => {
+            let arr = parent
+                .as_array_mut()
+                .context("cannot index into non-array TOML value")?;
+            if *i >= arr.len() {
+                anyhow::bail!("array index {i} out of range");
+            }
+            match op {
+                EditOp::Set(v) => {
+                    let mut new_value = parse_toml_value(v);
+                    if let Some(old) = arr.get(*i) {
+                        *new_value.decor_mut() = old.decor().clone();
+                    }
+                    arr.replace(*i, new_value);
+                }
+                EditOp::Delete => {
+                    arr.remove(*i);
+                }
+                EditOp::Append(_) => anyhow::bail!("append targets an array, not an element"),
+            }
+        }
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// JSON
+// ---------------------------------------------------------------------------
+
+/// A JSON value and the byte span it occupies in the source text.
+struct JNode {
+    start: usize,
+    end: usize,
+    kind: JKind,
+}
+
+enum JKind {
+    Scalar,
+    Object(Vec<JMember>),
+    Array(Vec<JNode>),
+}
+
+struct JMember {
+    key: String,
+    /// Byte span of the key, including its quotes.
+    key_start: usize,
+    key_end: usize,
+    value: JNode,
+}
+
+impl JNode {
+    /// Spans of the entries of an object or array, from the start of a key
+    /// (or item) to the end of its value.
+    fn entries(&self) -> Vec<(usize, usize)> {
+        match &self.kind {
+            JKind::Object(members) => members.iter().map(|m| (m.key_start, m.value.end)).collect(),
+            JKind::Array(items) => items.iter().map(|i| (i.start, i.end)).collect(),
+            JKind::Scalar => Vec::new(),
+        }
+    }
+}
+
+/// Records value spans in JSON text already validated by `serde_json`.
+struct JScanner<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl JScanner<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.src.as_bytes().get(self.pos).copied()
+    }
+
+    fn skip_ws(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn node(&mut self) -> Result<JNode> {
+        self.skip_ws();
+        let start = self.pos;
+        let kind = match self.peek() {
+            Some(b'{') => {
+                self.pos += 1;
+                let mut members = Vec::new();
+                loop {
+                    self.skip_ws();
+                    match self.peek() {
+                        Some(b'}') => break,
+                        Some(b',') => self.pos += 1,
+                        _ => {
+                            let key_start = self.pos;
+                            self.string()?;
+                            let key_end = self.pos;
+                            let key = serde_json::from_str(&self.src[key_start..key_end])
+                                .context("invalid JSON key")?;
+                            self.skip_ws();
+                            self.pos += 1; // ':'
+                            let value = self.node()?;
+                            members.push(JMember {
+                                key,
+                                key_start,
+                                key_end,
+                                value,
+                            });
+                        }
+                    }
+                }
+                self.pos += 1;
+                JKind::Object(members)
+            }
+            Some(b'[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                loop {
+                    self.skip_ws();
+                    match self.peek() {
+                        Some(b']') => break,
+                        Some(b',') => self.pos += 1,
+                        _ => items.push(self.node()?),
+                    }
+                }
+                self.pos += 1;
+                JKind::Array(items)
+            }
+            Some(b'"') => {
+                self.string()?;
+                JKind::Scalar
+            }
+            Some(_) => {
+                while !matches!(
+                    self.peek(),
+                    None | Some(b',' | b'}' | b']' | b' ' | b'\t' | b'\n' | b'\r')
+                ) {
+                    self.pos += 1;
+                }
+                JKind::Scalar
+            }
+            None => anyhow::bail!("unexpected end of JSON"),
+        };
+        Ok(JNode {
+            start,
+            end: self.pos,
+            kind,
+        })
+    }
+
+    /// Skip a string literal starting at the opening quote.
+    fn string(&mut self) -> Result<()> {
+        self.pos += 1;
+        loop {
+            match self.peek() {
+                Some(b'\\') => self.pos += 2,
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                Some(_) => self.pos += 1,
+                None => anyhow::bail!("unterminated JSON string"),
+            }
+        }
+    }
+}
+
+/// Layout of the document being edited, used to render new values.
+struct JStyle<'a> {
+    src: &'a str,
+    /// Indentation unit of the file.
+    unit: &'a str,
+    /// Whether the file is pretty-printed over several lines.
+    pretty: bool,
+}
+
+impl JStyle<'_> {
+    /// Leading whitespace of the line containing `pos`.
+    fn indent_at(&self, pos: usize) -> &str {
+        let line_start = self.src[..pos].rfind('\n').map_or(0, |i| i + 1);
+        leading_ws(&self.src[line_start..])
+    }
+
+    /// Render `value` to sit on a line indented by `indent`.
+    fn render(&self, value: &Value, indent: &str) -> String {
+        if !self.pretty {
+            return value.to_string();
+        }
+        let mut buf = Vec::new();
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(self.unit.as_bytes());
+        let mut ser = serde_json::Serializer::with_formatter(&mut buf, formatter);
+        serde::Serialize::serialize(value, &mut ser).expect("serializing a JSON value");
+        String::from_utf8_lossy(&buf).replace('\n', &format!("\n{indent}"))
+    }
+
+    fn replace(&self, range: (usize, usize), with: &str) -> String {
+        format!("{}{}{}", &self.src[..range.0], with, &self.src[range.1..])
+    }
+
+    /// Replace the value at `node` with `value`.
+    fn set(&self, node: &JNode, value: &Value) -> String {
+        let text = self.render(value, self.indent_at(node.start));
+        self.replace((node.start, node.end), &text)
+    }
+
+    /// Add an entry after the last one in `container`, copying the
+    /// separator used between its existing entries.  `entry` renders the
+    /// new entry for a given indentation.
+    fn add(&self, container: &JNode, entry: impl Fn(&str) -> String) -> String {
+        let entries = container.entries();
+        match entries.as_slice() {
+            [] => {
+                let inner = (container.start + 1, container.end - 1);
+                if self.pretty {
+                    let base = self.indent_at(container.start);
+                    let indent = format!("{base}{}", self.unit);
+                    self.replace(inner, &format!("\n{indent}{}\n{base}", entry(&indent)))
+                } else {
+                    self.replace(inner, &entry(""))
+                }
+            }
+            [.., (_, prev_end), (last_start, last_end)] => {
+                let sep = &self.src[*prev_end..*last_start];
+                let text = format!("{sep}{}", entry(self.indent_at(*last_start)));
+                self.replace((*last_end, *last_end), &text)
+            }
+            [(first_start, first_end)] => {
+                let ws = &self.src[container.start + 1..*first_start];
+                let sep = match (ws.contains('\n'), self.pretty) {
+                    (true, _) => format!(",{ws}"),
+                    (false, true) => ", ".to_string(),
+                    (false, false) => ",".to_string(),
+                };
+                let text = format!("{sep}{}", entry(self.indent_at(*first_start)));
+                self.replace((*first_end, *first_end), &text)
+            }
+        }
+    }
+
+    /// Add member `key` to `object`.
+    fn add_member(&self, object: &JNode, key: &str, value: &Value) -> String {
+        let colon = match &object.kind {
+            JKind::Object(members) => members.last().map(|m| &self.src[m.key_end..m.value.start]),
+            _ => None,
+        }
+        .unwrap_or(if self.pretty { ": " } else { ":" });
+        let key = Value::String(key.to_string()).to_string();
+        self.add(object, |indent| {
+            format!("{key}{colon}{}", self.render(value, indent))
+        })
+    }
+
+    /// Remove entry `index` of `container` together with one separator.
+    fn remove(&self, container: &JNode, index: usize) -> String {
+        let entries = container.entries();
+        let range = if entries.len() == 1 {
+            (container.start + 1, container.end - 1)
+        } else if index + 1 < entries.len() {
+            (entries[index].0, entries[index + 1].0)
+        } else {
+            (entries[index - 1].1, entries[index].1)
+        };
+        self.replace(range, "")
+    }
+}
+
+/// Build the value created by `op` at the missing path `rest`.
+fn json_nest(rest: &[PathSegment], value: Value) -> Result<Value> {
+    rest.iter().rev().try_fold(value, |value, seg| match seg {
+        PathSegment::Key(k) => Ok(Value::Object(Map::from_iter([(k.clone(), value)]))),
+        PathSegment::Index(i) => anyhow::bail!("array index {i} out of range"),
+    })
+}
+
+/// Edit a JSON document.
+///
+/// Only the span of the affected value changes; new entries copy the
+/// separators and indentation of their siblings.
+fn json_edit(content: &str, path: &[PathSegment], op: &EditOp) -> Result<String> {
+    let parse = |v: &str| serde_json::from_str(v).unwrap_or(Value::String(v.to_string()));
+    let new_value = match op {
+        EditOp::Set(v) => parse(v),
+        EditOp::Append(v) => Value::Array(vec![parse(v)]),
+        EditOp::Delete => Value::Null,
+    };
+
+    if content.trim().is_empty() {
+        if matches!(op, EditOp::Delete) {
+            return Ok(content.to_string());
+        }
+        let root = json_nest(path, new_value)?;
+        return serde_json::to_string_pretty(&root).context("failed to serialize JSON");
+    }
+
+    serde_json::from_str::<Value>(content).context("failed to parse existing JSON")?;
+    let root = JScanner {
+        src: content,
+        pos: 0,
+    }
+    .node()?;
+    let style = JStyle {
+        src: content,
+        unit: content
+            .lines()
+            .map(leading_ws)
+            .find(|ws| !ws.is_empty())
+            .unwrap_or("  "),
+        pretty: content.trim().contains('\n'),
+    };
+
+    let mut node = &root;
+    for (depth, seg) in path.iter().enumerate() {
+        let is_last = depth + 1 == path.len();
+        match (seg, &node.kind) {
+            (PathSegment::Key(k), JKind::Object(members)) => {
+                let Some(member) = members.iter().rposition(|m| m.key == *k) else {
+                    if matches!(op, EditOp::Delete) {
+                        return Ok(content.to_string());
+                    }
+                    let value = json_nest(&path[depth + 1..], new_value)?;
+                    return Ok(style.add_member(node, k, &value));
+                };
+                if is_last {
+                    let value = &members[member].value;
+                    return match op {
+                        EditOp::Set(_) => Ok(style.set(value, &new_value)),
+                        EditOp::Delete => Ok(style.remove(node, member)),
+                        EditOp::Append(v) => match value.kind {
+                            JKind::Array(_) => {
+                                Ok(style.add(value, |indent| style.render(&parse(v), indent)))
+                            }
+                            _ => anyhow::bail!("cannot append to non-array JSON value at {k}"),
+                        },
+                    };
+                }
+                node = &members[member].value;
+            }
+            (PathSegment::Key(_), _) if is_last && matches!(op, EditOp::Delete) => {
+                return Ok(content.to_string());
+            }
+            (PathSegment::Key(_), _) if is_last => {
+                anyhow::bail!("cannot set key on non-object JSON value")
+            }
+            (PathSegment::Key(_), _) => anyhow::bail!("cannot descend into non-object JSON value"),
+            (PathSegment::Index(i), JKind::Array(items)) => {
+                let item = items
+                    .get(*i)
+                    .with_context(|| format!("array index {i} out of range"))?;
+                if is_last {
+                    return match op {
+                        EditOp::Set(_) => Ok(style.set(item, &new_value)),
+                        EditOp::Delete => Ok(style.remove(node, *i)),
+                        EditOp::Append(_) => {
+                            anyhow::bail!("append targets an array, not an element")
+                        }
+                    };
+                }
+                node = item;
+            }
+            (PathSegment::Index(_), _) => anyhow::bail!("cannot index into non-array JSON value"),
+        }
+    }
+    unreachable!("edit paths are never empty")
+}
+
+// ---------------------------------------------------------------------------
+// dotenv
+// ---------------------------------------------------------------------------
+
+/// A parsed `KEY=value` line.
+struct DotenvEntry {
+    key: String,
+    value: String,
+    /// Byte range of the raw value (including quotes).
+    range: (usize, usize),
+    quote: Option<char>,
+}
+
+fn dotenv_parse_line(line: &str) -> Option<DotenvEntry> {
+    let trimmed = line.trim_start();
+    if trimmed.is_empty() || trimmed.starts_with('#') {
+        return None;
+    }
+    let offset = line.len() - trimmed.len();
+    let (rest, offset) = match trimmed.strip_prefix("export ") {
+        Some(r) => {
+            let r2 = r.trim_start();
+            (r2, offset + (trimmed.len() - r2.len()))
+        }
+        None => (trimmed, offset),
+    };
+    let eq = rest.find('=')?;
+    let key = rest[..eq].trim();
+    if key.is_empty()
+        || !key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'))
+    {
+        return None;
+    }
+
+    let after = &rest[eq + 1..];
+    let lead = after.len() - after.trim_start().len();
+    let start = offset + eq + 1 + lead;
+    let raw = &line[start..];
+
+    let (value, len, quote) = match raw.chars().next() {
+        Some(q @ ('"' | '\'')) => {
+            let mut value = String::new();
+            let mut end = None;
+            let mut chars = raw.char_indices().skip(1);
+            while let Some((i, c)) = chars.next() {
+                if c == q {
+                    end = Some(i + 1);
+                    break;
+                }
+                if c == '\\' && q == '"' {
+                    match chars.next() {
+                        Some((_, 'n')) => value.push('\n'),
+                        Some((_, 't')) => value.push('\t'),
+                        Some((_, other)) => value.push(other),
+                        None => {}
+                    }
+                } else {
+                    value.push(c);
+                }
+            }
+            (value, end.unwrap_or(raw.len()), Some(q))
+        }
+        _ => {
+            let end = raw.find(" #").unwrap_or(raw.len());
+            let v = raw[..end].trim_end();
+            (v.to_string(), v.len(), None)
+        }
+    };
+
+    Some(DotenvEntry {
+        key: key.to_string(),
+        value,
+        range: (start, start + len),
+        quote,
+    })
+}
+
+fn dotenv_read(content: &str) -> Value {
+    let mut map = Map::new();
+    for line in content.lines() {
+        if let Some(entry) = dotenv_parse_line(line) {
+            map.insert(entry.key, Value::String(entry.value));
+        }
+    }
+    Value::Object(map)
+}
+
+fn dotenv_render(value: &str, quote: Option<char>) -> String {
+    let needs_quotes = value.is_empty()
+        || value
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '#' | '"' | '\'' | '\\' | '$' | '`'));
+    match quote {
+        Some('\'') if !value.contains('\'') && !value.contains('\n') => format!("'{value}'"),
+        Some(_) => format!("\"{}\"", escape_double(value)),
+        None if needs_quotes => format!("\"{}\"", escape_double(value)),
+        None => value.to_string(),
+    }
+}
+
+fn escape_double(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn dotenv_edit(content: &str, path: &[PathSegment], op: &EditOp) -> Result<String> {
+    let key = key_segment(path, "dotenv")?;
+    let (mut lines, trailing) = split_lines(content);
+    let matches: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, l)| dotenv_parse_line(l).is_some_and(|e| e.key == key))
+        .map(|(i, _)| i)
+        .collect();
+
+    match op {
+        EditOp::Set(value) => match matches.last() {
+            // The last assignment wins, so that is the one to change.
+            Some(&i) => {
+                let entry = dotenv_parse_line(&lines[i]).expect("matched above");
+                let line = &lines[i];
+                let new_line = format!(
+                    "{}{}{}",
+                    &line[..entry.range.0],
+                    dotenv_render(value, entry.quote),
+                    &line[entry.range.1..]
+                );
+                lines[i] = new_line;
+            }
+            None => lines.push(format!("{key}={}", dotenv_render(value, None))),
+        },
+        EditOp::Delete => {
+            for &i in matches.iter().rev() {
+                lines.remove(i);
+            }
+        }
+        EditOp::Append(_) => anyhow::bail!("append is not supported for dotenv files"),
+    }
+    Ok(join_lines(&lines, trailing))
+}
+
+// ---------------------------------------------------------------------------
+// INI / gitconfig and ssh_config
+// ---------------------------------------------------------------------------
+
+/// Line-oriented formats made of section headers and key/value entries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dialect {
+    Ini,
+    Ssh,
+}
+
+#[derive(Debug, Clone)]
+enum LineKind {
+    /// Blank, comment, or unrecognised line.
+    Other,
+    Section(Vec<String>),
+    Entry {
+        key: String,
+        value: String,
+        /// Byte range of the raw value; `None` for git's bare boolean keys.
+        range: Option<(usize, usize)>,
+    },
+}
+
+struct SectionedDoc {
+    dialect: Dialect,
+    lines: Vec<String>,
+    kinds: Vec<LineKind>,
+    trailing_newline: bool,
+}
+
+impl SectionedDoc {
+    fn parse(content: &str, dialect: Dialect) -> Self {
+        let (lines, trailing_newline) = split_lines(content);
+        let kinds = lines
+            .iter()
+            .map(|l| match dialect {
+                Dialect::Ini => classify_ini(l),
+                Dialect::Ssh => classify_ssh(l),
+            })
+            .collect();
+        SectionedDoc {
+            dialect,
+            lines,
+            kinds,
+            trailing_newline,
+        }
+    }
+
+    /// Section path in effect for each line.
+    fn sections(&self) -> Vec<Vec<String>> {
+        let mut current = Vec::new();
+        self.kinds
+            .iter()
+            .map(|k| {
+                if let LineKind::Section(s) = k {
+                    current = s.clone();
+                }
+                current.clone()
+            })
+            .collect()
+    }
+
+    fn section_eq(&self, a: &[String], b: &[&str]) -> bool {
+        a.len() == b.len()
+            && a.iter().zip(b).enumerate().all(|(i, (x, y))| {
+                // Section names and keywords are case-insensitive; git
+                // subsections and ssh host patterns are not.
+                if i == 0 {
+                    x.eq_ignore_ascii_case(y)
+                } else {
+                    x == y
+                }
+            })
+    }
+
+    fn to_json(&self) -> Value {
+        let mut root = Value::Object(Map::new());
+        let sections = self.sections();
+        for (kind, section) in self.kinds.iter().zip(&sections) {
+            let LineKind::Entry { key, value, .. } = kind else {
+                continue;
+            };
+            let mut node = &mut root;
+            for seg in section {
+                node = node
+                    .as_object_mut()
+                    .expect("sections are objects")
+                    .entry(seg.clone())
+                    .or_insert_with(|| Value::Object(Map::new()));
+                if !node.is_object() {
+                    // A key and a section share a name; keep the section.
+                    *node = Value::Object(Map::new());
+                }
+            }
+            let map = node.as_object_mut().expect("sections are objects");
+            let value = Value::String(value.clone());
+            match map.get_mut(key) {
+                Some(Value::Array(arr)) => arr.push(value),
+                Some(existing) => {
+                    let first = existing.take();
+                    *existing = Value::Array(vec![first, value]);
+                }
+                None => {
+                    map.insert(key.clone(), value);
+                }
+            }
+        }
+        root
+    }
+
+    /// Map a key path onto (section, key, index) for this dialect.
+    fn resolve<'a>(
+        &self,
+        path: &'a [PathSegment],
+    ) -> Result<(Vec<&'a str>, &'a str, Option<usize>)> {
+        let (keys, index) = keys_and_index(path)?;
+        let Some((&key, section)) = keys.split_last() else {
+            anyhow::bail!("empty key path");
+        };
+        match self.dialect {
+            Dialect::Ini if section.len() > 2 => {
+                anyhow::bail!("INI key paths are section[.subsection].key")
+            }
+            Dialect::Ssh if !(section.is_empty() || section.len() == 2) => {
+                anyhow::bail!("ssh_config key paths are Keyword or Host.<pattern>.Keyword")
+            }
+            Dialect::Ssh
+                if section.len() == 2
+                    && !["host", "match"].contains(&section[0].to_lowercase().as_str()) =>
+            {
+                anyhow::bail!("ssh_config blocks must be Host or Match")
+            }
+            _ => {}
+        }
+        Ok((section.to_vec(), key, index))
+    }
+
+    fn edit(mut self, path: &[PathSegment], op: &EditOp) -> Result<String> {
+        let (section, key, index) = self.resolve(path)?;
+        let sections = self.sections();
+        let matches: Vec<usize> = self
+            .kinds
+            .iter()
+            .enumerate()
+            .filter(|(i, k)| {
+                matches!(k, LineKind::Entry { key: k2, .. } if k2.eq_ignore_ascii_case(key))
+                    && self.section_eq(&sections[*i], &section)
+            })
+            .map(|(i, _)| i)
+            .collect();
+
+        let target = match index {
+            Some(i) => Some(
+                *matches
+                    .get(i)
+                    .with_context(|| format!("{key} has no value at index {i}"))?,
+            ),
+            None => None,
+        };
+
+        match op {
+            EditOp::Set(value) => {
+                let line = match (target, matches.as_slice()) {
+                    (Some(t), _) => t,
+                    (None, [only]) => *only,
+                    (None, []) => {
+                        self.insert_entry(&section, key, value, None);
+                        return Ok(self.finish());
+                    }
+                    (None, many) => anyhow::bail!(
+                        "{key} has {} values; use an index or the append operation",
+                        many.len()
+                    ),
+                };
+                self.replace_value(line, value);
+            }
+            EditOp::Delete => {
+                let doomed: Vec<usize> = match target {
+                    Some(t) => vec![t],
+                    None => matches,
+                };
+                for &i in doomed.iter().rev() {
+                    self.lines.remove(i);
+                    self.kinds.remove(i);
+                }
+            }
+            EditOp::Append(value) => {
+                if target.is_some() {
+                    anyhow::bail!("append targets a key, not one of its values");
+                }
+                self.insert_entry(&section, key, value, matches.last().copied());
+            }
+        }
+        Ok(self.finish())
+    }
+
+    fn finish(&self) -> String {
+        join_lines(&self.lines, self.trailing_newline)
+    }
+
+    fn render_value(&self, value: &str) -> String {
+        match self.dialect {
+            Dialect::Ini => {
+                let needs_quotes = value != value.trim()
+                    || value.contains(['#', ';', '"'])
+                    || value.contains('\\');
+                if needs_quotes {
+                    format!("\"{}\"", escape_double(value))
+                } else {
+                    value.to_string()
+                }
+            }
+            Dialect::Ssh => {
+                if value.contains(char::is_whitespace) {
+                    format!("\"{value}\"")
+                } else {
+                    value.to_string()
+                }
+            }
+        }
+    }
+
+    fn render_entry(&self, indent: &str, key: &str, value: &str) -> String {
+        let value = self.render_value(value);
+        match self.dialect {
+            Dialect::Ini => format!("{indent}{key} = {value}"),
+            Dialect::Ssh => format!("{indent}{key} {value}"),
+        }
+    }
+
+    fn replace_value(&mut self, i: usize, value: &str) {
+        let LineKind::Entry { key, range, .. } = &self.kinds[i] else {
+            return;
+        };
+        let line = &self.lines[i];
+        let new_line = match range {
+            Some((s, e)) => format!("{}{}{}", &line[..*s], self.render_value(value), &line[*e..]),
+            None => self.render_entry(leading_ws(line), key, value),
+        };
+        self.kinds[i] = classify_line(self.dialect, &new_line);
+        self.lines[i] = new_line;
+    }
+
+    /// Indentation used for entries, learned from the file.
+    fn entry_indent(&self, section: &[&str]) -> String {
+        if section.is_empty() {
+            return String::new();
+        }
+        let sections = self.sections();
+        let entry_ws = |want_section: bool| {
+            self.kinds.iter().enumerate().find_map(|(i, k)| {
+                let in_section = self.section_eq(&sections[i], section);
+                (matches!(k, LineKind::Entry { .. })
+                    && !sections[i].is_empty()
+                    && in_section == want_section)
+                    .then(|| leading_ws(&self.lines[i]).to_string())
+            })
+        };
+        entry_ws(true)
+            .or_else(|| entry_ws(false))
+            .unwrap_or_else(|| match self.dialect {
+                Dialect::Ini => "\t".to_string(),
+                Dialect::Ssh => "    ".to_string(),
+            })
+    }
+
+    fn insert_entry(&mut self, section: &[&str], key: &str, value: &str, after: Option<usize>) {
+        let indent = self.entry_indent(section);
+        let line = self.render_entry(&indent, key, value);
+        let sections = self.sections();
+
+        let at = after.map(|i| i + 1).or_else(|| {
+            if section.is_empty() {
+                // Globals must come before the first section header.
+                let last_global = self
+                    .kinds
+                    .iter()
+                    .enumerate()
+                    .take_while(|(_, k)| !matches!(k, LineKind::Section(_)))
+                    .filter(|(_, k)| matches!(k, LineKind::Entry { .. }))
+                    .map(|(i, _)| i + 1)
+                    .last();
+                let first_header = self
+                    .kinds
+                    .iter()
+                    .position(|k| matches!(k, LineKind::Section(_)))?;
+                let mut at = first_header;
+                while at > 0 && matches!(self.kinds[at - 1], LineKind::Other) {
+                    at -= 1;
+                }
+                Some(last_global.unwrap_or(at))
+            } else {
+                // After the last entry (or header) of the section's final occurrence.
+                (0..self.kinds.len())
+                    .rev()
+                    .find(|&i| {
+                        self.section_eq(&sections[i], section)
+                            && !matches!(self.kinds[i], LineKind::Other)
+                    })
+                    .map(|i| i + 1)
+            }
+        });
+
+        match at {
+            Some(at) => {
+                self.kinds.insert(at, classify_line(self.dialect, &line));
+                self.lines.insert(at, line);
+            }
+            None => {
+                if !section.is_empty() {
+                    if self.lines.last().is_some_and(|l| !l.trim().is_empty()) {
+                        self.lines.push(String::new());
+                        self.kinds.push(LineKind::Other);
+                    }
+                    let header = self.render_header(section);
+                    self.kinds.push(classify_line(self.dialect, &header));
+                    self.lines.push(header);
+                }
+                self.kinds.push(classify_line(self.dialect, &line));
+                self.lines.push(line);
+                self.trailing_newline = true;
+            }
+        }
+    }
+
+    fn render_header(&self, section: &[&str]) -> String {
+        match (self.dialect, section) {
+            (Dialect::Ini, [name]) => format!("[{name}]"),
+            (Dialect::Ini, [name, sub]) => format!("[{name} \"{}\"]", escape_double(sub)),
+            (Dialect::Ssh, [kind, pattern]) => {
+                let kind = if kind.eq_ignore_ascii_case("match") {
+                    "Match"
+                } else {
+                    "Host"
+                };
+                format!("{kind} {pattern}")
+            }
+            _ => unreachable!("validated by resolve"),
+        }
+    }
+}
+
+fn classify_line(dialect: Dialect, line: &str) -> LineKind {
+    match dialect {
+        Dialect::Ini => classify_ini(line),
+        Dialect::Ssh => classify_ssh(line),
+    }
+}
+
+fn classify_ini(line: &str) -> LineKind {
+    let trimmed = line.trim();
+    if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with(';') {
+        return LineKind::Other;
+    }
+    if trimmed.starts_with('[') {
+        let Some(close) = trimmed.rfind(']') else {
+            return LineKind::Other;
+        };
+        let inner = &trimmed[1..close];
+        return match inner.find('"') {
+            Some(q) => {
+                let name = inner[..q].trim().to_string();
+                let sub = inner[q + 1..]
+                    .trim_end()
+                    .trim_end_matches('"')
+                    .replace("\\\"", "\"")
+                    .replace("\\\\", "\\");
+                LineKind::Section(vec![name, sub])
+            }
+            None => LineKind::Section(vec![inner.trim().to_string()]),
+        };
+    }
+
+    let Some(eq) = line.find('=') else {
+        // git allows a bare key meaning "true".
+        let key = trimmed.to_string();
+        if key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return LineKind::Entry {
+                key,
+                value: "true".to_string(),
+                range: None,
+            };
+        }
+        return LineKind::Other;
+    };
+
+    let key = line[..eq].trim().to_string();
+    let after = &line[eq + 1..];
+    let start = eq + 1 + (after.len() - after.trim_start().len());
+    let raw = &line[start..];
+
+    // Find the end of the value: an unquoted '#' or ';' starts a comment.
+    let mut in_quotes = false;
+    let mut end = raw.len();
+    let mut prev = ' ';
+    for (i, c) in raw.char_indices() {
+        match c {
+            '"' if prev != '\\' => in_quotes = !in_quotes,
+            '#' | ';' if !in_quotes && prev.is_whitespace() => {
+                end = i;
+                break;
+            }
+            _ => {}
+        }
+        prev = c;
+    }
+    let raw_value = raw[..end].trim_end();
+    let value = match raw_value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+    {
+        Some(inner) => inner
+            .replace("\\\"", "\"")
+            .replace("\\n", "\n")
+            .replace("\\\\", "\\"),
+        None => raw_value.to_string(),
+    };
+
+    LineKind::Entry {
+        key,
+        value,
+        range: Some((start, start + raw_value.len())),
+    }
+}
+
+fn classify_ssh(line: &str) -> LineKind {
+    let trimmed = line.trim_start();
+    if trimmed.is_empty() || trimmed.starts_with('#') {
+        return LineKind::Other;
+    }
+    let offset = line.len() - trimmed.len();
+    let key_len = trimmed
+        .find(|c: char| c.is_whitespace() || c == '=')
+        .unwrap_or(trimmed.len());
+    let key = &trimmed[..key_len];
+    let rest = &trimmed[key_len..];
+    let sep = rest
+        .find(|c: char| !c.is_whitespace() && c != '=')
+        .unwrap_or(rest.len());
+    let start = offset + key_len + sep;
+    let raw = line[start..].trim_end();
+    let value = raw
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(raw)
+        .to_string();
+
+    if key.eq_ignore_ascii_case("host") {
+        return LineKind::Section(vec!["Host".to_string(), value]);
+    }
+    if key.eq_ignore_ascii_case("match") {
+        return LineKind::Section(vec!["Match".to_string(), value]);
+    }
+    LineKind::Entry {
+        key: key.to_string(),
+        value,
+        range: Some((start, start + raw.len())),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// YAML
+// ---------------------------------------------------------------------------
+//
+// A line-oriented parser for the block-style YAML found in config files
+// (docker-compose, CI pipelines, Kubernetes manifests). It records where
+// every node lives in the source so edits can splice individual lines.
+// Anchors, aliases, tags and multi-document streams are not interpreted.
+
+/// A logical YAML line: either a sequence dash or content starting at `col`.
+#[derive(Debug, Clone)]
+struct YLine {
+    phys: usize,
+    col: usize,
+    dash: bool,
+    text: String,
+    /// Raw lines of a `|` / `>` block scalar introduced by this line.
+    block: Option<(Vec<String>, usize)>,
+}
+
+#[derive(Debug, Clone)]
+enum YNode {
+    Map {
+        col: usize,
+        entries: Vec<YEntry>,
+        end: usize,
+    },
+    Seq {
+        col: usize,
+        items: Vec<YItem>,
+        end: usize,
+    },
+    Scalar {
+        phys: usize,
+        start: usize,
+        stop: usize,
+        raw: String,
+        block: Option<(Vec<String>, usize)>,
+    },
+    /// A key or dash with nothing after it; `at` is where a value would go.
+    Null { phys: usize, at: usize },
+}
+
+#[derive(Debug, Clone)]
+struct YEntry {
+    key: String,
+    phys: usize,
+    col: usize,
+    value: YNode,
+    end: usize,
+}
+
+#[derive(Debug, Clone)]
+struct YItem {
+    phys: usize,
+    col: usize,
+    value: YNode,
+    end: usize,
+}
+
+impl YNode {
+    fn end(&self) -> usize {
+        match self {
+            YNode::Map { end, .. } | YNode::Seq { end, .. } => *end,
+            YNode::Scalar { phys, block, .. } => block.as_ref().map(|b| b.1).unwrap_or(*phys),
+            YNode::Null { phys, .. } => *phys,
+        }
+    }
+
+    fn start(&self) -> usize {
+        match self {
+            YNode::Map { entries, .. } => entries[0].phys,
+            YNode::Seq { items, .. } => items[0].phys,
+            YNode::Scalar { phys, .. } | YNode::Null { phys, .. } => *phys,
+        }
+    }
+}
+
+struct YamlDoc {
+    lines: Vec<String>,
+    trailing_newline: bool,
+    root: Option<YNode>,
+    /// Indentation step used by the file (defaults to two spaces).
+    unit: usize,
+}
+
+/// Byte offset of an unquoted ` #` comment in `line`, if any.
+fn yaml_comment_start(line: &str) -> Option<usize> {
+    let mut quote: Option<char> = None;
+    let mut prev = ' ';
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'')
+                if prev.is_whitespace() || matches!(prev, '[' | '{' | ',' | ':') || i == 0 =>
+            {
+                quote = Some(c)
+            }
+            (Some(q), c) if c == q && !(q == '"' && prev == '\\') => quote = None,
+            (None, '#') if prev.is_whitespace() || i == 0 => return Some(i),
+            _ => {}
+        }
+        prev = c;
+    }
+    None
+}
+
+/// Locate the `:` separating a mapping key from its value.
+///
+/// Returns `(key, rest_offset)` where `rest_offset` is the byte offset just
+/// past the colon.
+fn yaml_split_entry(text: &str) -> Option<(String, usize)> {
+    if text.starts_with(['[', '{', '|', '>']) {
+        return None;
+    }
+    if let Some(q) = text.chars().next().filter(|c| *c == '"' || *c == '\'') {
+        let close = text[1..].find(q)? + 1;
+        let after = &text[close + 1..];
+        let rest = after.strip_prefix(':')?;
+        if !(rest.is_empty() || rest.starts_with(' ')) {
+            return None;
+        }
+        return Some((text[1..close].to_string(), close + 2));
+    }
+    let mut search = 0;
+    while let Some(pos) = text[search..].find(':') {
+        let i = search + pos;
+        let next = text[i + 1..].chars().next();
+        if next.is_none() || next == Some(' ') {
+            return Some((text[..i].trim_end().to_string(), i + 1));
+        }
+        search = i + 1;
+    }
+    None
+}
+
+fn is_block_indicator(s: &str) -> bool {
+    let mut chars = s.chars();
+    matches!(chars.next(), Some('|' | '>'))
+        && chars.all(|c| matches!(c, '+' | '-') || c.is_ascii_digit())
+}
+
+impl YamlDoc {
+    fn parse(content: &str) -> Result<Self> {
+        let (lines, trailing_newline) = split_lines(content);
+        let mut ylines: Vec<YLine> = Vec::new();
+        let mut seen_content = false;
+        let mut i = 0;
+
+        while i < lines.len() {
+            let line = &lines[i];
+            let code = match yaml_comment_start(line) {
+                Some(c) => line[..c].trim_end(),
+                None => line.trim_end(),
+            };
+            let trimmed = code.trim_start();
+            if trimmed.is_empty() || trimmed.starts_with('%') {
+                i += 1;
+                continue;
+            }
+            if trimmed == "---" || trimmed == "..." {
+                if seen_content && trimmed == "---" {
+                    anyhow::bail!("multi-document YAML is not supported");
+                }
+                i += 1;
+                continue;
+            }
+            if code[..code.len() - trimmed.len()].contains('\t') {
+                anyhow::bail!("tabs are not allowed in YAML indentation (line {})", i + 1);
+            }
+            seen_content = true;
+
+            let mut col = code.len() - trimmed.len();
+            let mut rest = trimmed;
+            while rest == "-" || rest.starts_with("- ") {
+                ylines.push(YLine {
+                    phys: i,
+                    col,
+                    dash: true,
+                    text: "-".to_string(),
+                    block: None,
+                });
+                let after = &rest[1..];
+                let skipped = after.len() - after.trim_start().len();
+                col += 1 + skipped;
+                rest = after.trim_start();
+            }
+
+            let owner_col = ylines
+                .last()
+                .filter(|l| l.phys == i)
+                .map(|l| l.col)
+                .unwrap_or(col);
+            let mut block = None;
+            if !rest.is_empty() {
+                // Detect `key: |`, `- |` or a bare `|` introducing a block scalar.
+                let value_part = match yaml_split_entry(rest) {
+                    Some((_, off)) => rest[off..].trim(),
+                    None => rest,
+                };
+                if is_block_indicator(value_part) {
+                    let parent = if yaml_split_entry(rest).is_some() {
+                        col
+                    } else {
+                        owner_col
+                    };
+                    let mut body = Vec::new();
+                    let mut j = i + 1;
+                    while j < lines.len() {
+                        let l = &lines[j];
+                        let ind = l.len() - l.trim_start().len();
+                        if !l.trim().is_empty() && ind <= parent {
+                            break;
+                        }
+                        body.push(l.clone());
+                        j += 1;
+                    }
+                    while body.last().is_some_and(|l| l.trim().is_empty()) {
+                        body.pop();
+                        j -= 1;
+                    }
+                    let end = if body.is_empty() { i } else { j - 1 };
+                    block = Some((body, end));
+                }
+                ylines.push(YLine {
+                    phys: i,
+                    col,
+                    dash: false,
+                    text: rest.to_string(),
+                    block: block.clone(),
+                });
+            }
+            i = match &block {
+                Some((_, end)) => end + 1,
+                None => i + 1,
+            };
+        }
+
+        let unit = ylines
+            .windows(2)
+            .filter_map(|w| w[1].col.checked_sub(w[0].col).filter(|d| *d > 0))
+            .min()
+            .unwrap_or(2)
+            .clamp(1, 8);
+
+        let mut idx = 0;
+        let root = if ylines.is_empty() {
+            None
+        } else {
+            let node = Self::parse_node(&ylines, &mut idx)?;
+            if idx < ylines.len() {
+                anyhow::bail!(
+                    "unsupported YAML structure at line {}",
+                    ylines[idx].phys + 1
+                );
+            }
+            Some(node)
+        };
+
+        Ok(YamlDoc {
+            lines,
+            trailing_newline,
+            root,
+            unit,
+        })
+    }
+
+    fn parse_node(ylines: &[YLine], idx: &mut usize) -> Result<YNode> {
+        let first = &ylines[*idx];
+        if first.dash {
+            return Self::parse_seq(ylines, idx);
+        }
+        if yaml_split_entry(&first.text).is_some() {
+            return Self::parse_map(ylines, idx);
+        }
+        *idx += 1;
+        Ok(YNode::Scalar {
+            phys: first.phys,
+            start: first.col,
+            stop: first.col + first.text.len(),
+            raw: first.text.clone(),
+            block: first.block.clone(),
+        })
+    }
+
+    fn parse_seq(ylines: &[YLine], idx: &mut usize) -> Result<YNode> {
+        let col = ylines[*idx].col;
+        let mut items = Vec::new();
+        while *idx < ylines.len() {
+            let dash = &ylines[*idx];
+            if dash.col < col || (dash.col == col && !dash.dash) {
+                break;
+            }
+            if dash.col > col {
+                anyhow::bail!("unexpected indentation at line {}", dash.phys + 1);
+            }
+            let phys = dash.phys;
+            *idx += 1;
+            let value = match ylines.get(*idx) {
+                Some(next) if next.phys == phys || next.col > col => Self::parse_node(ylines, idx)?,
+                _ => YNode::Null { phys, at: col + 1 },
+            };
+            let end = value.end().max(phys);
+            items.push(YItem {
+                phys,
+                col,
+                value,
+                end,
+            });
+        }
+        let end = items.last().map(|i| i.end).unwrap_or(0);
+        Ok(YNode::Seq { col, items, end })
+    }
+
+    fn parse_map(ylines: &[YLine], idx: &mut usize) -> Result<YNode> {
+        let col = ylines[*idx].col;
+        let mut entries = Vec::new();
+        while *idx < ylines.len() {
+            let line = &ylines[*idx];
+            if line.col < col || line.dash {
+                break;
+            }
+            if line.col > col {
+                anyhow::bail!("unexpected indentation at line {}", line.phys + 1);
+            }
+            let Some((key, off)) = yaml_split_entry(&line.text) else {
+                anyhow::bail!("expected a mapping entry at line {}", line.phys + 1);
+            };
+            let phys = line.phys;
+            let rest = &line.text[off..];
+            let lead = rest.len() - rest.trim_start().len();
+            *idx += 1;
+
+            let value = if !rest.trim().is_empty() {
+                let start = col + off + lead;
+                YNode::Scalar {
+                    phys,
+                    start,
+                    stop: col + line.text.len(),
+                    raw: rest.trim().to_string(),
+                    block: line.block.clone(),
+                }
+            } else {
+                match ylines.get(*idx) {
+                    Some(next)
+                        if next.phys != phys
+                            && (next.col > col || (next.dash && next.col == col)) =>
+                    {
+                        Self::parse_node(ylines, idx)?
+                    }
+                    _ => YNode::Null {
+                        phys,
+                        at: col + off,
+                    },
+                }
+            };
+            let end = value.end().max(phys);
+            entries.push(YEntry {
+                key,
+                phys,
+                col,
+                value,
+                end,
+            });
+        }
+        let end = entries.last().map(|e| e.end).unwrap_or(0);
+        Ok(YNode::Map { col, entries, end })
+    }
+
+    fn to_json(node: &YNode) -> Value {
+        match node {
+            YNode::Map { entries, .. } => Value::Object(
+                entries
+                    .iter()
+                    .map(|e| (e.key.clone(), Self::to_json(&e.value)))
+                    .collect(),
+            ),
+            YNode::Seq { items, .. } => {
+                Value::Array(items.iter().map(|i| Self::to_json(&i.value)).collect())
+            }
+            YNode::Scalar { raw, block, .. } => match block {
+                Some((body, _)) => yaml_block_to_string(raw, body),
+                None => yaml_scalar_to_json(raw),
+            },
+            YNode::Null { .. } => Value::Null,
+        }
+    }
+
+    fn edit(mut self, path: &[PathSegment], op: &EditOp) -> Result<String> {
+        let Some(root) = self.root.clone() else {
+            // Empty document: every path is missing.
+            return match op {
+                EditOp::Delete => Ok(join_lines(&self.lines, self.trailing_newline)),
+                _ => {
+                    let new = self.render_missing(path, op, 0)?;
+                    self.lines.extend(new);
+                    self.trailing_newline = true;
+                    Ok(join_lines(&self.lines, self.trailing_newline))
+                }
+            };
+        };
+
+        let mut node = &root;
+        let mut parent: Option<(&YNode, usize)> = None;
+        for (depth, seg) in path.iter().enumerate() {
+            let child = match (node, seg) {
+                (YNode::Map { entries, .. }, PathSegment::Key(k)) => {
+                    entries.iter().position(|e| &e.key == k)
+                }
+                (YNode::Seq { items, .. }, PathSegment::Index(i)) => {
+                    (*i < items.len()).then_some(*i)
+                }
+                (YNode::Seq { .. }, PathSegment::Key(k)) => {
+                    anyhow::bail!("expected an index for sequence, got key {k}")
+                }
+                (YNode::Map { .. }, PathSegment::Index(i)) => {
+                    anyhow::bail!("expected a key for mapping, got index {i}")
+                }
+                (YNode::Null { .. }, _) if depth > 0 => None,
+                (YNode::Scalar { raw, .. }, _) if raw.starts_with(['[', '{']) => {
+                    if depth == path.len() - 1 || matches!(op, EditOp::Append(_)) {
+                        None
+                    } else {
+                        anyhow::bail!("editing inside flow collections is not supported")
+                    }
+                }
+                _ => anyhow::bail!("cannot descend into a scalar value"),
+            };
+
+            let Some(pos) = child else {
+                return self.edit_missing(node, parent, &path[depth..], op);
+            };
+            parent = Some((node, pos));
+            node = match node {
+                YNode::Map { entries, .. } => &entries[pos].value,
+                YNode::Seq { items, .. } => &items[pos].value,
+                _ => unreachable!(),
+            };
+        }
+
+        let (container, pos) = parent.expect("path is non-empty");
+        self.edit_existing(container, pos, op)
+    }
+
+    /// Apply `op` to an existing child `pos` of `container`.
+    fn edit_existing(mut self, container: &YNode, pos: usize, op: &EditOp) -> Result<String> {
+        let (phys, col, value, end, is_entry) = match container {
+            YNode::Map { entries, .. } => {
+                let e = &entries[pos];
+                (e.phys, e.col, &e.value, e.end, true)
+            }
+            YNode::Seq { items, .. } => {
+                let i = &items[pos];
+                (i.phys, i.col, &i.value, i.end, false)
+            }
+            _ => unreachable!(),
+        };
+        // The first key of a `- key: value` item shares its line with the dash.
+        let shares_dash_line = is_entry && leading_ws(&self.lines[phys]).len() < col;
+
+        match op {
+            EditOp::Set(v) => {
+                let rendered = yaml_render_scalar(v);
+                match value {
+                    YNode::Scalar {
+                        phys: p,
+                        start,
+                        stop,
+                        block,
+                        ..
+                    } => {
+                        let line = &self.lines[*p];
+                        self.lines[*p] =
+                            format!("{}{}{}", &line[..*start], rendered, &line[*stop..]);
+                        if let Some((_, block_end)) = block {
+                            if *block_end > *p {
+                                self.lines.drain(p + 1..=*block_end);
+                            }
+                        }
+                    }
+                    YNode::Null { phys: p, at } => {
+                        let line = &self.lines[*p];
+                        self.lines[*p] = format!("{} {}{}", &line[..*at], rendered, &line[*at..]);
+                    }
+                    YNode::Map { .. } | YNode::Seq { .. } => {
+                        if value.start() == phys {
+                            anyhow::bail!("replacing an inline collection is not supported");
+                        }
+                        let key_line = &self.lines[phys];
+                        let at = yaml_value_insert_at(key_line, col, is_entry);
+                        self.lines[phys] =
+                            format!("{} {}{}", &key_line[..at], rendered, &key_line[at..]);
+                        self.lines.drain(value.start()..=value.end());
+                    }
+                }
+            }
+            EditOp::Delete => {
+                if shares_dash_line {
+                    anyhow::bail!("cannot delete the first key of an inline sequence item; delete the item instead");
+                }
+                self.lines.drain(phys..=end);
+            }
+            EditOp::Append(v) => {
+                let rendered = yaml_render_scalar(v);
+                match value {
+                    YNode::Seq {
+                        col: seq_col,
+                        end: seq_end,
+                        ..
+                    } => {
+                        self.lines.insert(
+                            seq_end + 1,
+                            format!("{}- {}", " ".repeat(*seq_col), rendered),
+                        );
+                    }
+                    YNode::Scalar {
+                        phys: p, stop, raw, ..
+                    } if raw.starts_with('[') && raw.ends_with(']') => {
+                        let inner = raw[1..raw.len() - 1].trim();
+                        let close = stop - 1;
+                        let line = &self.lines[*p];
+                        let insert = if inner.is_empty() {
+                            rendered
+                        } else {
+                            format!(", {rendered}")
+                        };
+                        // Keep any spacing before the closing bracket.
+                        let before = line[..close].trim_end().len();
+                        self.lines[*p] =
+                            format!("{}{}{}", &line[..before], insert, &line[before..]);
+                    }
+                    YNode::Null { phys: p, .. } => {
+                        let indent = " ".repeat(col + self.unit);
+                        self.lines.insert(p + 1, format!("{indent}- {rendered}"));
+                    }
+                    _ => anyhow::bail!("cannot append to a non-sequence YAML value"),
+                }
+            }
+        }
+        Ok(join_lines(&self.lines, self.trailing_newline))
+    }
+
+    /// Create the missing tail of `path` under `node`.
+    fn edit_missing(
+        mut self,
+        node: &YNode,
+        parent: Option<(&YNode, usize)>,
+        rest: &[PathSegment],
+        op: &EditOp,
+    ) -> Result<String> {
+        if matches!(op, EditOp::Delete) {
+            return Ok(join_lines(&self.lines, self.trailing_newline));
+        }
+        match node {
+            YNode::Map { col, end, .. } => {
+                let new = self.render_missing(rest, op, *col)?;
+                let at = end + 1;
+                self.lines.splice(at..at, new);
+            }
+            YNode::Null { phys, .. } => {
+                let (_, pos) = parent.context("missing parent for empty value")?;
+                let parent_col = match parent.map(|p| p.0) {
+                    Some(YNode::Map { entries, .. }) => entries[pos].col,
+                    Some(YNode::Seq { items, .. }) => items[pos].col,
+                    _ => 0,
+                };
+                let new = self.render_missing(rest, op, parent_col + self.unit)?;
+                self.lines.splice(phys + 1..phys + 1, new);
+            }
+            YNode::Seq { .. } => {
+                anyhow::bail!("array index out of range; use the append operation")
+            }
+            YNode::Scalar { .. } => {
+                // Only reached for flow collections targeted directly.
+                let (container, pos) = parent.context("missing parent for flow value")?;
+                if rest.len() == 1 && matches!(op, EditOp::Append(_)) {
+                    return self.edit_existing(container, pos, op);
+                }
+                anyhow::bail!("editing inside flow collections is not supported")
+            }
+        }
+        Ok(join_lines(&self.lines, self.trailing_newline))
+    }
+
+    /// Render new lines creating `path` at indentation `col`.
+    fn render_missing(&self, path: &[PathSegment], op: &EditOp, col: usize) -> Result<Vec<String>> {
+        let mut out = Vec::new();
+        let mut indent = col;
+        for (i, seg) in path.iter().enumerate() {
+            let PathSegment::Key(k) = seg else {
+                anyhow::bail!("array index out of range; use the append operation");
+            };
+            let key = yaml_render_key(k);
+            let pad = " ".repeat(indent);
+            if i + 1 < path.len() {
+                out.push(format!("{pad}{key}:"));
+                indent += self.unit;
+                continue;
+            }
+            match op {
+                EditOp::Set(v) => out.push(format!("{pad}{key}: {}", yaml_render_scalar(v))),
+                EditOp::Append(v) => {
+                    out.push(format!("{pad}{key}:"));
+                    out.push(format!(
+                        "{}- {}",
+                        " ".repeat(indent + self.unit),
+                        yaml_render_scalar(v)
+                    ));
+                }
+                EditOp::Delete => {}
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Byte offset just after the `:` (or `-`) on a key line.
+fn yaml_value_insert_at(line: &str, col: usize, is_entry: bool) -> usize {
+    let text = &line[col..];
+    let code_len = yaml_comment_start(text).unwrap_or(text.len());
+    let code = text[..code_len].trim_end();
+    if is_entry {
+        match yaml_split_entry(code) {
+            Some((_, off)) => col + off,
+            None => col + code.len(),
+        }
+    } else {
+        col + 1
+    }
+}
+
+fn yaml_render_key(key: &str) -> String {
+    let plain = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '/'));
+    if plain {
+        key.to_string()
+    } else {
+        format!("\"{}\"", escape_double(key))
+    }
+}
+
+/// Render a user-supplied value as a YAML scalar.
+///
+/// Numbers, booleans, null and already-quoted strings are written
+/// verbatim; other strings are quoted only when plain style would change
+/// their meaning.
+fn yaml_render_scalar(value: &str) -> String {
+    let quoted = value.len() >= 2
+        && ((value.starts_with('"') && value.ends_with('"'))
+            || (value.starts_with('\'') && value.ends_with('\'')));
+    if quoted || !matches!(yaml_scalar_to_json(value), Value::String(_)) && !value.is_empty() {
+        return value.to_string();
+    }
+    let needs_quotes = value.is_empty()
+        || value != value.trim()
+        || value.starts_with([
+            '-', '?', ':', ',', '[', ']', '{', '}', '#', '&', '*', '!', '|', '>', '\'', '"', '%',
+            '@', '`',
+        ])
+        || value.contains(": ")
+        || value.ends_with(':')
+        // YAML 1.1 parsers read `12:30` as a base-60 number.
+        || (value.contains(':') && value.chars().all(|c| c.is_ascii_digit() || c == ':'))
+        || value.contains(" #")
+        || value.contains('\n')
+        // YAML 1.1 parsers read these as booleans.
+        || matches!(
+            value.to_ascii_lowercase().as_str(),
+            "y" | "n" | "yes" | "no" | "on" | "off"
+        );
+    if needs_quotes {
+        format!("\"{}\"", escape_double(value))
+    } else {
+        value.to_string()
+    }
+}
+
+fn yaml_block_to_string(indicator: &str, body: &[String]) -> Value {
+    let indent = body
+        .iter()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| leading_ws(l).len())
+        .min()
+        .unwrap_or(0);
+    let lines: Vec<&str> = body
+        .iter()
+        .map(|l| if l.len() >= indent { &l[indent..] } else { "" })
+        .collect();
+    let folded = indicator.contains('>');
+    let mut text = if folded {
+        yaml_fold(&lines)
+    } else {
+        lines.join("\n")
+    };
+    if !indicator.contains('-') {
+        text.push('\n');
+    }
+    Value::String(text)
+}
+
+/// Fold the lines of a `>` block scalar: a line break between two text
+/// lines becomes a space, each blank line a newline, and breaks next to a
+/// more-indented line are kept as they are.
+fn yaml_fold(lines: &[&str]) -> String {
+    let mut out = String::new();
+    let mut prev: Option<&str> = None;
+    let mut blanks = 0;
+    for &line in lines {
+        if line.trim().is_empty() {
+            blanks += 1;
+            continue;
+        }
+        if let Some(prev) = prev {
+            let more_indented = prev.starts_with([' ', '\t']) || line.starts_with([' ', '\t']);
+            if more_indented {
+                out.push('\n');
+            } else if blanks == 0 {
+                out.push(' ');
+            }
+        }
+        out.push_str(&"\n".repeat(blanks));
+        out.push_str(line);
+        prev = Some(line);
+        blanks = 0;
+    }
+    out
+}
+
+/// Interpret a plain, quoted or single-line flow scalar.
+fn yaml_scalar_to_json(raw: &str) -> Value {
+    let raw = raw.trim();
+    if let Some(inner) = raw.strip_prefix('"').and_then(|r| r.strip_suffix('"')) {
+        let mut out = String::new();
+        let mut chars = inner.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                out.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some('r') => out.push('\r'),
+                Some('0') => out.push('\0'),
+                Some('u') => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    if let Some(ch) = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                        out.push(ch);
+                    }
+                }
+                Some(other) => out.push(other),
+                None => {}
+            }
+        }
+        return Value::String(out);
+    }
+    if let Some(inner) = raw.strip_prefix('\'').and_then(|r| r.strip_suffix('\'')) {
+        return Value::String(inner.replace("''", "'"));
+    }
+    if raw.starts_with('[') || raw.starts_with('{') {
+        if let Some(v) = yaml_flow_to_json(raw) {
+            return v;
+        }
+    }
+    match raw {
+        "" | "~" | "null" | "Null" | "NULL" => return Value::Null,
+        "true" | "True" | "TRUE" => return Value::Bool(true),
+        "false" | "False" | "FALSE" => return Value::Bool(false),
+        _ => {}
+    }
+    if let Ok(i) = raw.parse::<i64>() {
+        return Value::from(i);
+    }
+    if let Some(hex) = raw.strip_prefix("0x") {
+        if let Ok(i) = i64::from_str_radix(hex, 16) {
+            return Value::from(i);
+        }
+    }
+    if let Some(oct) = raw.strip_prefix("0o") {
+        if let Ok(i) = i64::from_str_radix(oct, 8) {
+            return Value::from(i);
+        }
+    }
+    let looks_numeric =
+        raw.starts_with(|c: char| c.is_ascii_digit() || matches!(c, '-' | '+' | '.'));
+    if looks_numeric {
+        if let Some(n) = raw
+            .parse::<f64>()
+            .ok()
+            .and_then(serde_json::Number::from_f64)
+        {
+            return Value::Number(n);
+        }
+    }
+    Value::String(raw.to_string())
+}
+
+/// Parse a single-line flow collection such as `[a, "b", {c: 1}]`.
+fn yaml_flow_to_json(raw: &str) -> Option<Value> {
+    fn split_top(inner: &str) -> Vec<&str> {
+        let mut parts = Vec::new();
+        let mut depth = 0i32;
+        let mut quote: Option<char> = None;
+        let mut start = 0;
+        for (i, c) in inner.char_indices() {
+            match (quote, c) {
+                (Some(q), c) if c == q => quote = None,
+                (Some(_), _) => {}
+                (None, '"' | '\'') => quote = Some(c),
+                (None, '[' | '{') => depth += 1,
+                (None, ']' | '}') => depth -= 1,
+                (None, ',') if depth == 0 => {
+                    parts.push(&inner[start..i]);
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        parts.push(&inner[start..]);
+        parts.into_iter().filter(|p| !p.trim().is_empty()).collect()
+    }
+
+    if let Some(inner) = raw.strip_prefix('[').and_then(|r| r.strip_suffix(']')) {
+        return Some(Value::Array(
+            split_top(inner)
+                .into_iter()
+                .map(yaml_scalar_to_json)
+                .collect(),
+        ));
+    }
+    if let Some(inner) = raw.strip_prefix('{').and_then(|r| r.strip_suffix('}')) {
+        let mut map = Map::new();
+        for part in split_top(inner) {
+            let part = part.trim();
+            let (key, off) = yaml_split_entry(part)?;
+            let key = match yaml_scalar_to_json(&key) {
+                Value::String(s) => s,
+                other => other.to_string(),
+            };
+            map.insert(key, yaml_scalar_to_json(&part[off..]));
+        }
+        return Some(Value::Object(map));
+    }
+    None
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn path(key: &str) -> Vec<PathSegment> {
+        parse_key_path(key).unwrap()
+    }
+
+    fn apply(format: ConfigFormat, content: &str, key: &str, op: EditOp) -> String {
+        edit(format, content, &path(key), &op).unwrap()
+    }
+
+    // ---- key paths ----
+
+    #[test]
+    fn test_parse_key_path() {
+        assert_eq!(
+            path("servers[1].host"),
+            vec![
+                PathSegment::Key("servers".into()),
+                PathSegment::Index(1),
+                PathSegment::Key("host".into()),
+            ]
+        );
+        assert_eq!(
+            path("Host.\"github.com\".User"),
+            vec![
+                PathSegment::Key("Host".into()),
+                PathSegment::Key("github.com".into()),
+                PathSegment::Key("User".into()),
+            ]
+        );
+        assert!(parse_key_path("").is_err());
+        assert!(parse_key_path("a..b").is_err());
+        assert!(parse_key_path("a[x]").is_err());
+        assert!(parse_key_path("\"open").is_err());
+    }
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(
+            ConfigFormat::detect("/home/u/.gitconfig"),
+            ConfigFormat::Ini
+        );
+        assert_eq!(ConfigFormat::detect("/repo/.git/config"), ConfigFormat::Ini);
+        assert_eq!(
+            ConfigFormat::detect("/home/u/.ssh/config"),
+            ConfigFormat::SshConfig
+        );
+        assert_eq!(ConfigFormat::detect("/app/.env"), ConfigFormat::Dotenv);
+        assert_eq!(
+            ConfigFormat::detect("/app/.env.local"),
+            ConfigFormat::Dotenv
+        );
+        assert_eq!(
+            ConfigFormat::detect("/app/docker-compose.yml"),
+            ConfigFormat::Yaml
+        );
+        assert_eq!(ConfigFormat::detect("/etc/php.ini"), ConfigFormat::Ini);
+    }
+
+    // ---- TOML ----
+
+    #[test]
+    fn test_toml_set_preserves_comments() {
+        let src = "# top comment\n[server]\nhost = \"localhost\" # the host\nport = 8080\n";
+        let out = apply(
+            ConfigFormat::Toml,
+            src,
+            "server.port",
+            EditOp::Set("9090".into()),
+        );
+        assert_eq!(
+            out,
+            "# top comment\n[server]\nhost = \"localhost\" # the host\nport = 9090\n"
+        );
+
+        let out = apply(
+            ConfigFormat::Toml,
+            src,
+            "server.host",
+            EditOp::Set("0.0.0.0".into()),
+        );
+        assert!(out.contains("host = \"0.0.0.0\" # the host"));
+    }
+
+    #[test]
+    fn test_toml_delete_and_append() {
+        let src = "[tool]\nitems = [1, 2]\nname = \"x\"\n";
+        let out = apply(
+            ConfigFormat::Toml,
+            src,
+            "tool.items",
+            EditOp::Append("3".into()),
+        );
+        assert!(out.contains("items = [1, 2, 3]"));
+        let out = apply(ConfigFormat::Toml, &out, "tool.name", EditOp::Delete);
+        assert!(!out.contains("name"));
+        let out = apply(ConfigFormat::Toml, &out, "tool.items[0]", EditOp::Delete);
+        assert_eq!(
+            read(ConfigFormat::Toml, &out).unwrap()["tool"]["items"],
+            json!([2, 3])
+        );
+    }
+
+    // ---- JSON ----
+
+    #[test]
+    fn test_json_keeps_order_and_indent() {
+        let src = "{\n    \"zeta\": 1,\n    \"alpha\": {\n        \"b\": 2\n    }\n}\n";
+        let out = apply(
+            ConfigFormat::Json,
+            src,
+            "alpha.c",
+            EditOp::Set("true".into()),
+        );
+        assert_eq!(
+            out,
+            "{\n    \"zeta\": 1,\n    \"alpha\": {\n        \"b\": 2,\n        \"c\": true\n    }\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_json_array_ops() {
+        let src = r#"{"plugins":["a","b"]}"#;
+        let out = apply(
+            ConfigFormat::Json,
+            src,
+            "plugins",
+            EditOp::Append("c".into()),
+        );
+        let out = apply(ConfigFormat::Json, &out, "plugins[0]", EditOp::Delete);
+        let out = apply(
+            ConfigFormat::Json,
+            &out,
+            "plugins[1]",
+            EditOp::Set("z".into()),
+        );
+        assert_eq!(
+            read(ConfigFormat::Json, &out).unwrap()["plugins"],
+            json!(["b", "z"])
+        );
+    }
+
+    #[test]
+    fn test_json_edit_only_touches_the_value() {
+        let src =
+            "{\n  \"name\": \"app\",\n\n  \"ports\": [80, 443],\n  \"env\": {\"A\": \"1\"}\n}\n";
+        let out = apply(ConfigFormat::Json, src, "name", EditOp::Set("web".into()));
+        assert_eq!(out, src.replace("\"app\"", "\"web\""));
+
+        let out = apply(
+            ConfigFormat::Json,
+            src,
+            "ports",
+            EditOp::Append("8080".into()),
+        );
+        assert_eq!(out, src.replace("[80, 443]", "[80, 443, 8080]"));
+
+        let out = apply(ConfigFormat::Json, src, "env.B", EditOp::Set("2".into()));
+        assert_eq!(
+            out,
+            src.replace("{\"A\": \"1\"}", "{\"A\": \"1\", \"B\": 2}")
+        );
+
+        let out = apply(ConfigFormat::Json, src, "ports[0]", EditOp::Delete);
+        assert_eq!(out, src.replace("[80, 443]", "[443]"));
+
+        let out = apply(ConfigFormat::Json, src, "env", EditOp::Delete);
+        assert_eq!(
+            out,
+            "{\n  \"name\": \"app\",\n\n  \"ports\": [80, 443]\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_json_edit_creates_missing_paths() {
+        let src = "{\n    \"a\": {}\n}\n";
+        let out = apply(ConfigFormat::Json, src, "a.b.c", EditOp::Set("1".into()));
+        assert_eq!(
+            out,
+            "{\n    \"a\": {\n        \"b\": {\n            \"c\": 1\n        }\n    }\n}\n"
+        );
+
+        let out = apply(ConfigFormat::Json, "", "list", EditOp::Append("x".into()));
+        assert_eq!(
+            read(ConfigFormat::Json, &out).unwrap(),
+            json!({ "list": ["x"] })
+        );
+
+        let out = apply(ConfigFormat::Json, src, "missing.key", EditOp::Delete);
+        assert_eq!(out, src);
+    }
+
+    // ---- YAML ----
+
+    const COMPOSE: &str = "\
+# dev stack
+version: \"3.8\"
+services:
+  db:
+    image: postgres:15 # pinned
+    ports:
+      - \"5432:5432\"
+    environment:
+      POSTGRES_PASSWORD: secret
+  web:
+    image: nginx
+    command: |
+      nginx -g 'daemon off;'
+      # not a comment
+";
+
+    #[test]
+    fn test_yaml_read() {
+        let v = read(ConfigFormat::Yaml, COMPOSE).unwrap();
+        assert_eq!(v["version"], "3.8");
+        assert_eq!(v["services"]["db"]["image"], "postgres:15");
+        assert_eq!(v["services"]["db"]["ports"], json!(["5432:5432"]));
+        assert_eq!(
+            v["services"]["db"]["environment"]["POSTGRES_PASSWORD"],
+            "secret"
+        );
+        assert_eq!(
+            v["services"]["web"]["command"],
+            "nginx -g 'daemon off;'\n# not a comment\n"
+        );
+    }
+
+    #[test]
+    fn test_yaml_read_sequences_of_maps_and_flow() {
+        let src = "steps:\n- name: build\n  run: make\n- name: test\n  with: {a: 1, b: [x, y]}\nempty:\nnum: 0x1F\n";
+        let v = read(ConfigFormat::Yaml, src).unwrap();
+        assert_eq!(v["steps"][0]["name"], "build");
+        assert_eq!(v["steps"][0]["run"], "make");
+        assert_eq!(v["steps"][1]["with"], json!({"a": 1, "b": ["x", "y"]}));
+        assert_eq!(v["empty"], Value::Null);
+        assert_eq!(v["num"], 31);
+    }
+
+    #[test]
+    fn test_yaml_set_existing_keeps_comment() {
+        let out = apply(
+            ConfigFormat::Yaml,
+            COMPOSE,
+            "services.db.image",
+            EditOp::Set("postgres:16".into()),
+        );
+        assert!(out.contains("    image: postgres:16 # pinned\n"));
+        assert!(out.starts_with("# dev stack\n"));
+        assert_eq!(out.lines().count(), COMPOSE.lines().count());
+    }
+
+    #[test]
+    fn test_yaml_set_new_nested_key() {
+        let out = apply(
+            ConfigFormat::Yaml,
+            COMPOSE,
+            "services.db.environment.POSTGRES_USER",
+            EditOp::Set("dev".into()),
+        );
+        assert!(out.contains("      POSTGRES_PASSWORD: secret\n      POSTGRES_USER: dev\n  web:"));
+
+        let out = apply(
+            ConfigFormat::Yaml,
+            COMPOSE,
+            "services.cache.image",
+            EditOp::Set("redis:7".into()),
+        );
+        let v = read(ConfigFormat::Yaml, &out).unwrap();
+        assert_eq!(v["services"]["cache"]["image"], "redis:7");
+        assert_eq!(v["services"]["web"]["image"], "nginx");
+    }
+
+    #[test]
+    fn test_yaml_append_and_delete() {
+        let out = apply(
+            ConfigFormat::Yaml,
+            COMPOSE,
+            "services.db.ports",
+            EditOp::Append("6543:5432".into()),
+        );
+        assert!(out.contains("      - \"5432:5432\"\n      - \"6543:5432\"\n"));
+
+        let out = apply(
+            ConfigFormat::Yaml,
+            &out,
+            "services.db.ports[0]",
+            EditOp::Delete,
+        );
+        let v = read(ConfigFormat::Yaml, &out).unwrap();
+        assert_eq!(v["services"]["db"]["ports"], json!(["6543:5432"]));
+
+        let out = apply(ConfigFormat::Yaml, &out, "services.web", EditOp::Delete);
+        let v = read(ConfigFormat::Yaml, &out).unwrap();
+        assert!(v["services"].get("web").is_none());
+        assert!(out.starts_with("# dev stack\n"));
+    }
+
+    #[test]
+    fn test_yaml_set_replaces_block_scalar() {
+        let out = apply(
+            ConfigFormat::Yaml,
+            COMPOSE,
+            "services.web.command",
+            EditOp::Set("nginx".into()),
+        );
+        let v = read(ConfigFormat::Yaml, &out).unwrap();
+        assert_eq!(v["services"]["web"]["command"], "nginx");
+        assert!(!out.contains("not a comment"));
+    }
+
+    #[test]
+    fn test_yaml_flow_append_and_empty_doc() {
+        let out = apply(
+            ConfigFormat::Yaml,
+            "tags: [a, b] # t\n",
+            "tags",
+            EditOp::Append("c".into()),
+        );
+        assert_eq!(out, "tags: [a, b, c] # t\n");
+
+        let out = apply(ConfigFormat::Yaml, "", "a.b", EditOp::Set("1".into()));
+        assert_eq!(out, "a:\n  b: 1\n");
+    }
+
+    #[test]
+    fn test_yaml_render_scalar_quoting() {
+        assert_eq!(yaml_render_scalar("plain"), "plain");
+        assert_eq!(yaml_render_scalar("8080"), "8080");
+        assert_eq!(yaml_render_scalar("a: b"), "\"a: b\"");
+        assert_eq!(yaml_render_scalar("*star"), "\"*star\"");
+        assert_eq!(yaml_render_scalar("'kept'"), "'kept'");
+        assert_eq!(yaml_render_scalar(""), "\"\"");
+        assert_eq!(yaml_render_scalar("yes"), "\"yes\"");
+        assert_eq!(yaml_render_scalar("Off"), "\"Off\"");
+        assert_eq!(yaml_render_scalar("true"), "true");
+    }
+
+    #[test]
+    fn test_yaml_folded_block_scalar() {
+        let src =
+            "msg: >\n  two  spaces\n  joined\n\n  next\n    kept\n  last\nstrip: >-\n  a\n  b\n";
+        let v = read(ConfigFormat::Yaml, src).unwrap();
+        assert_eq!(v["msg"], "two  spaces joined\nnext\n  kept\nlast\n");
+        assert_eq!(v["strip"], "a b");
+    }
+
+    // ---- INI / gitconfig ----
+
+    const GITCONFIG: &str = "\
+[user]
+\tname = Jane Dev
+\temail = jane@example.com ; work
+[remote \"origin\"]
+\turl = git@github.com:acme/app.git
+\tfetch = +refs/heads/*:refs/remotes/origin/*
+[core]
+\tbare
+";
+
+    #[test]
+    fn test_ini_read() {
+        let v = read(ConfigFormat::Ini, GITCONFIG).unwrap();
+        assert_eq!(v["user"]["name"], "Jane Dev");
+        assert_eq!(v["user"]["email"], "jane@example.com");
+        assert_eq!(v["remote"]["origin"]["url"], "git@github.com:acme/app.git");
+        assert_eq!(v["core"]["bare"], "true");
+    }
+
+    #[test]
+    fn test_ini_set_existing_and_new() {
+        let out = apply(
+            ConfigFormat::Ini,
+            GITCONFIG,
+            "user.email",
+            EditOp::Set("j@home.dev".into()),
+        );
+        assert!(out.contains("\temail = j@home.dev ; work\n"));
+
+        let out = apply(
+            ConfigFormat::Ini,
+            GITCONFIG,
+            "user.signingkey",
+            EditOp::Set("ABC123".into()),
+        );
+        assert!(out.contains("\temail = jane@example.com ; work\n\tsigningkey = ABC123\n[remote"));
+
+        let out = apply(
+            ConfigFormat::Ini,
+            GITCONFIG,
+            "init.defaultBranch",
+            EditOp::Set("main".into()),
+        );
+        assert!(out.ends_with("\tbare\n\n[init]\n\tdefaultBranch = main\n"));
+
+        let out = apply(
+            ConfigFormat::Ini,
+            GITCONFIG,
+            "remote.upstream.url",
+            EditOp::Set("x".into()),
+        );
+        assert!(out.contains("[remote \"upstream\"]\n\turl = x\n"));
+    }
+
+    #[test]
+    fn test_ini_multi_valued_keys() {
+        let out = apply(
+            ConfigFormat::Ini,
+            GITCONFIG,
+            "remote.origin.fetch",
+            EditOp::Append("+refs/tags/*:refs/tags/*".into()),
+        );
+        let v = read(ConfigFormat::Ini, &out).unwrap();
+        assert_eq!(v["remote"]["origin"]["fetch"].as_array().unwrap().len(), 2);
+
+        // Ambiguous set without an index is refused.
+        assert!(edit(
+            ConfigFormat::Ini,
+            &out,
+            &path("remote.origin.fetch"),
+            &EditOp::Set("x".into())
+        )
+        .is_err());
+
+        let out = apply(
+            ConfigFormat::Ini,
+            &out,
+            "remote.origin.fetch[0]",
+            EditOp::Delete,
+        );
+        let v = read(ConfigFormat::Ini, &out).unwrap();
+        assert_eq!(v["remote"]["origin"]["fetch"], "+refs/tags/*:refs/tags/*");
+    }
+
+    // ---- dotenv ----
+
+    #[test]
+    fn test_dotenv_read_and_edit() {
+        let src = "# app\nexport API_URL=\"http://localhost\" # dev\nDEBUG=1\nNAME='a b'\n";
+        let v = read(ConfigFormat::Dotenv, src).unwrap();
+        assert_eq!(v["API_URL"], "http://localhost");
+        assert_eq!(v["NAME"], "a b");
+
+        let out = apply(
+            ConfigFormat::Dotenv,
+            src,
+            "API_URL",
+            EditOp::Set("https://api".into()),
+        );
+        assert!(out.contains("export API_URL=\"https://api\" # dev\n"));
+        let out = apply(ConfigFormat::Dotenv, &out, "DEBUG", EditOp::Delete);
+        assert!(!out.contains("DEBUG"));
+        let out = apply(
+            ConfigFormat::Dotenv,
+            &out,
+            "NEW_KEY",
+            EditOp::Set("has space".into()),
+        );
+        assert!(out.ends_with("NEW_KEY=\"has space\"\n"));
+        assert!(out.starts_with("# app\n"));
+    }
+
+    // ---- ssh_config ----
+
+    const SSH: &str = "\
+# global
+ServerAliveInterval 60
+
+Host github.com
+    User git
+    IdentityFile ~/.ssh/id_ed25519
+
+Host *.internal
+    ProxyJump bastion
+";
+
+    #[test]
+    fn test_ssh_read() {
+        let v = read(ConfigFormat::SshConfig, SSH).unwrap();
+        assert_eq!(v["ServerAliveInterval"], "60");
+        assert_eq!(v["Host"]["github.com"]["User"], "git");
+        assert_eq!(v["Host"]["*.internal"]["ProxyJump"], "bastion");
+    }
+
+    #[test]
+    fn test_ssh_edit() {
+        let out = apply(
+            ConfigFormat::SshConfig,
+            SSH,
+            "Host.\"github.com\".User",
+            EditOp::Set("me".into()),
+        );
+        assert!(out.contains("    User me\n"));
+
+        let out = apply(
+            ConfigFormat::SshConfig,
+            SSH,
+            "Host.\"github.com\".IdentityFile",
+            EditOp::Append("~/.ssh/work".into()),
+        );
+        assert!(out.contains("    IdentityFile ~/.ssh/id_ed25519\n    IdentityFile ~/.ssh/work\n"));
+
+        let out = apply(
+            ConfigFormat::SshConfig,
+            SSH,
+            "ForwardAgent",
+            EditOp::Set("no".into()),
+        );
+        assert!(out.contains("ServerAliveInterval 60\nForwardAgent no\n\nHost github.com"));
+
+        let out = apply(
+            ConfigFormat::SshConfig,
+            SSH,
+            "Host.devbox.HostName",
+            EditOp::Set("10.0.0.5".into()),
+        );
+        assert!(out.ends_with("\nHost devbox\n    HostName 10.0.0.5\n"));
+
+        let out = apply(
+            ConfigFormat::SshConfig,
+            SSH,
+            "Host.\"*.internal\".ProxyJump",
+            EditOp::Delete,
+        );
+        assert!(!out.contains("ProxyJump"));
+    }
+}
@@ -1,8 +1,8 @@
 //! Connection state machine for daemon ↔ cloud orchestrator link.
 //!
-//! Tracks connectivity, queues outbound messages while offline,
-//! flushes the queue on reconnection, and emits state-change events
-//! so the UI (Tauri) layer can update in real-time.
+//! Tracks connectivity, queues outbound messages while offline, flushes the
+//! queue on reconnection, and emits state-change events so the UI (Tauri)
+//! layer can update in real-time.
 
 use std::collections::VecDeque;
 use std::sync::Arc;
@@ -12,6 +12,8 @@ use serde::{Deserialize, Serialize};
 use tokio::sync::{broadcast, Mutex, RwLock};
 use tokio::time;
 
+use crate::outbound_queue::OutboundQueue;
+
 // ---------------------------------------------------------------------------
 // Connection state enum
 // ---------------------------------------------------------------------------
@@ -82,6 +84,8 @@ pub struct ConnectionConfig {
     pub max_reconnect_attempts: u32,
     /// Base delay between reconnection attempts (doubles each attempt).
     pub reconnect_base_delay: Duration,
+    /// Upper bound on the delay between reconnection attempts.
+    pub max_reconnect_delay: Duration,
     /// Maximum number of messages to buffer while offline.
     pub max_queue_size: usize,
 }
@@ -93,6 +97,7 @@ impl Default for ConnectionConfig {
             ping_timeout: Duration::from_secs(10),
             max_reconnect_attempts: 10,
             reconnect_base_delay: Duration::from_secs(1),
+            max_reconnect_delay: Duration::from_secs(30),
             max_queue_size: 500,
         }
     }
@@ -106,6 +111,8 @@ impl Default for ConnectionConfig {
 pub struct ConnectionStateMachine {
     state: RwLock<ConnectionState>,
     queue: Mutex<VecDeque<QueuedMessage>>,
+    /// The relay's persistent queue, counted in [`Self::queue_len`] only.
+    outbound: Option<Arc<OutboundQueue>>,
     event_tx: broadcast::Sender<ConnectionStateEvent>,
     config: ConnectionConfig,
     reconnect_attempts: Mutex<u32>,
@@ -114,10 +121,29 @@ pub struct ConnectionStateMachine {
 impl ConnectionStateMachine {
     /// Create a new state machine starting in `Offline`.
     pub fn new(config: ConnectionConfig) -> (Arc<Self>, broadcast::Receiver<ConnectionStateEvent>) {
+        Self::build(config, None)
+    }
+
+    /// Like [`ConnectionStateMachine::new`], but also reporting the
+    /// undelivered messages in `outbound` in [`Self::queue_len`]. The relay
+    /// and the cloud client send and acknowledge those messages; this state
+    /// machine never enqueues into or drains it.
+    pub fn with_outbound_status(
+        config: ConnectionConfig,
+        outbound: Arc<OutboundQueue>,
+    ) -> (Arc<Self>, broadcast::Receiver<ConnectionStateEvent>) {
+        Self::build(config, Some(outbound))
+    }
+
+    fn build(
+        config: ConnectionConfig,
+        outbound: Option<Arc<OutboundQueue>>,
+    ) -> (Arc<Self>, broadcast::Receiver<ConnectionStateEvent>) {
         let (event_tx, event_rx) = broadcast::channel(64);
         let sm = Arc::new(Self {
             state: RwLock::new(ConnectionState::Offline),
             queue: Mutex::new(VecDeque::new()),
+            outbound,
             event_tx,
             config,
             reconnect_attempts: Mutex::new(0),
@@ -132,13 +158,30 @@ impl ConnectionStateMachine {
     }
 
     pub async fn queue_len(&self) -> usize {
-        self.queue.lock().await.len()
+        let persisted = self
+            .outbound
+            .as_ref()
+            .and_then(|outbound| outbound.len().ok())
+            .unwrap_or(0);
+        self.queue.lock().await.len() + persisted
     }
 
     pub fn subscribe(&self) -> broadcast::Receiver<ConnectionStateEvent> {
         self.event_tx.subscribe()
     }
 
+    pub fn config(&self) -> &ConnectionConfig {
+        &self.config
+    }
+
+    /// Whether messages can be sent to the cloud right now.
+    pub async fn is_connected(&self) -> bool {
+        matches!(
+            self.state().await,
+            ConnectionState::Online | ConnectionState::Degraded
+        )
+    }
+
     // -- Transitions --------------------------------------------------------
 
     /// Transition to a new state if the transition is valid.
@@ -245,7 +288,11 @@ impl ConnectionStateMachine {
         if *attempts >= self.config.max_reconnect_attempts {
             return None;
         }
-        let delay = self.config.reconnect_base_delay * 2u32.saturating_pow(*attempts);
+        let delay = self
+            .config
+            .reconnect_base_delay
+            .saturating_mul(2u32.saturating_pow(*attempts))
+            .min(self.config.max_reconnect_delay);
         *attempts += 1;
         Some(delay)
     }
@@ -284,31 +331,37 @@ impl ConnectionStateMachine {
                 }
 
                 let pong = ping_fn().await;
-
-                match (current, pong) {
-                    (ConnectionState::Online, false) => {
-                        let _ = sm
-                            .transition(ConnectionState::Degraded, "health check: ping timeout")
-                            .await;
-                    }
-                    (ConnectionState::Degraded, true) => {
-                        let _ = sm
-                            .transition(ConnectionState::Online, "health check: recovered")
-                            .await;
-                    }
-                    (ConnectionState::Degraded, false) => {
-                        let _ = sm
-                            .transition(
-                                ConnectionState::Reconnecting,
-                                "health check: consecutive failures",
-                            )
-                            .await;
-                    }
-                    _ => {}
-                }
+                sm.record_ping(pong).await;
             }
         })
     }
+
+    /// Apply the outcome of one health-check ping and return the resulting
+    /// state.
+    ///
+    /// A missed pong degrades an `Online` connection; a second consecutive
+    /// miss moves it to `Reconnecting`, and the caller should drop the link.
+    /// A pong while `Degraded` restores `Online`.
+    pub async fn record_ping(&self, pong: bool) -> ConnectionState {
+        let current = self.state().await;
+        let (target, message) = match (current, pong) {
+            (ConnectionState::Online, false) => {
+                (ConnectionState::Degraded, "health check: ping timeout")
+            }
+            (ConnectionState::Degraded, true) => {
+                (ConnectionState::Online, "health check: recovered")
+            }
+            (ConnectionState::Degraded, false) => (
+                ConnectionState::Reconnecting,
+                "health check: consecutive failures",
+            ),
+            _ => return current,
+        };
+        match self.transition(target, message).await {
+            Ok(event) => event.current,
+            Err(_) => self.state().await,
+        }
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -440,6 +493,36 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[tokio::test]
+    async fn test_outbound_queue_is_reported_but_not_drained() {
+        use crate::local_db::LocalDb;
+        use crate::outbound_queue::OutboundQueueConfig;
+
+        let outbound = Arc::new(OutboundQueue::new(
+            Arc::new(LocalDb::open_in_memory().unwrap()),
+            OutboundQueueConfig::default(),
+        ));
+        outbound.enqueue("a", b"a").unwrap();
+        outbound.enqueue("b", b"b").unwrap();
+
+        let (sm, _rx) = ConnectionStateMachine::with_outbound_status(cfg(), Arc::clone(&outbound));
+        sm.enqueue(QueuedMessage {
+            id: "c".into(),
+            payload: b"c".to_vec(),
+            queued_at: Instant::now(),
+        })
+        .await
+        .unwrap();
+        assert_eq!(sm.queue_len().await, 3);
+
+        let flushed = sm.flush_queue().await;
+        let ids: Vec<_> = flushed.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, ["c"]);
+        assert_eq!(outbound.len().unwrap(), 2);
+        assert!(!outbound.is_delivered("a").unwrap());
+        assert_eq!(sm.queue_len().await, 2);
+    }
+
     #[tokio::test]
     async fn test_reconnect_delay_exponential_backoff() {
         let (sm, _rx) = ConnectionStateMachine::new(cfg());
@@ -519,6 +602,35 @@ mod tests {
         handle.abort();
     }
 
+    #[tokio::test]
+    async fn test_record_ping_degrades_then_recovers_or_drops() {
+        let (sm, _rx) = ConnectionStateMachine::new(cfg());
+        assert_eq!(sm.record_ping(false).await, ConnectionState::Offline);
+
+        sm.transition(ConnectionState::Online, "up").await.unwrap();
+        assert_eq!(sm.record_ping(true).await, ConnectionState::Online);
+        assert_eq!(sm.record_ping(false).await, ConnectionState::Degraded);
+        assert!(sm.is_connected().await);
+        assert_eq!(sm.record_ping(true).await, ConnectionState::Online);
+
+        sm.record_ping(false).await;
+        assert_eq!(sm.record_ping(false).await, ConnectionState::Reconnecting);
+        assert!(!sm.is_connected().await);
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_delay_is_capped() {
+        let (sm, _rx) = ConnectionStateMachine::new(ConnectionConfig {
+            max_reconnect_attempts: 10,
+            max_reconnect_delay: Duration::from_millis(30),
+            ..cfg()
+        });
+        for expected in [10, 20, 30, 30] {
+            let d = sm.next_reconnect_delay().await.unwrap();
+            assert_eq!(d, Duration::from_millis(expected));
+        }
+    }
+
     #[tokio::test]
     async fn test_full_lifecycle() {
         let (sm, _rx) = ConnectionStateMachine::new(cfg());
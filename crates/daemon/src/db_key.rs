@@ -0,0 +1,468 @@
+//! Keys for the encrypted local database.
+//!
+//! With `database.encryption.enabled`, [`open_database`] opens the SQLCipher
+//! database with a passphrase from the configured environment variable or,
+//! when that is unset, a random 256-bit key kept hex-encoded in the key file
+//! (generated on first use, owner-readable only).  A plaintext database is
+//! encrypted on first open, together with its pre-migration backups.
+//!
+//! [`DbEncryption::rotate`] re-encrypts the live database under a new key.
+//! A new key file is written next to the old one (`<key_file>.new`) before
+//! the database is re-keyed and renamed over it afterwards, so a crash
+//! part-way through is finished on the next [`open_database`].  Encrypted
+//! pre-migration backups are re-encrypted along with the database.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{bail, ensure, Context, Result};
+use d1_common::EncryptionConfig;
+use tracing::{info, warn};
+
+use crate::local_db::{self, LocalDb};
+
+/// Length in bytes of a generated key.
+const KEY_LEN: usize = 32;
+
+/// Key material for an encrypted database.
+#[derive(Clone)]
+pub enum DbKey {
+    /// Raw 256-bit key, used as-is.
+    Raw([u8; KEY_LEN]),
+    /// Passphrase; SQLCipher derives the key from it.
+    Passphrase(String),
+}
+
+impl std::fmt::Debug for DbKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DbKey::Raw(_) => f.write_str("DbKey::Raw(..)"),
+            DbKey::Passphrase(_) => f.write_str("DbKey::Passphrase(..)"),
+        }
+    }
+}
+
+impl DbKey {
+    /// A fresh random key.
+    pub fn generate() -> Result<Self> {
+        let mut key = [0u8; KEY_LEN];
+        getrandom::getrandom(&mut key).context("generating database key")?;
+        Ok(DbKey::Raw(key))
+    }
+
+    /// Value for `PRAGMA key` / `PRAGMA rekey` / `ATTACH ... KEY`.  Raw keys
+    /// use SQLCipher's `x'<hex>'` form, which skips key derivation.
+    pub(crate) fn pragma_value(&self) -> String {
+        match self {
+            DbKey::Raw(key) => format!("x'{}'", hex::encode(key)),
+            DbKey::Passphrase(passphrase) => passphrase.clone(),
+        }
+    }
+
+    /// Reads a hex-encoded key file, refusing files that other users can
+    /// read.
+    pub fn load_file(path: &Path) -> Result<Self> {
+        check_key_file_permissions(path)?;
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("reading key file {}", path.display()))?;
+        let bytes = hex::decode(text.trim())
+            .with_context(|| format!("key file {} is not hex", path.display()))?;
+        let key: [u8; KEY_LEN] = bytes.try_into().map_err(|bytes: Vec<u8>| {
+            anyhow::anyhow!(
+                "key file {} holds {} bytes, expected {KEY_LEN}",
+                path.display(),
+                bytes.len()
+            )
+        })?;
+        Ok(DbKey::Raw(key))
+    }
+
+    /// Writes a raw key to `path`, readable by the owner only.  Overwrites
+    /// an existing file.
+    pub fn write_file(&self, path: &Path) -> Result<()> {
+        let DbKey::Raw(key) = self else {
+            bail!("passphrases are never written to disk");
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options
+            .open(path)
+            .with_context(|| format!("creating key file {}", path.display()))?;
+        writeln!(file, "{}", hex::encode(key))?;
+        file.sync_all()?;
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn check_key_file_permissions(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = fs::metadata(path)
+        .with_context(|| format!("reading key file {}", path.display()))?
+        .permissions()
+        .mode();
+    ensure!(
+        mode & 0o077 == 0,
+        "key file {} is accessible by other users (mode {:o}); run chmod 600 on it",
+        path.display(),
+        mode & 0o777
+    );
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_key_file_permissions(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+/// Where the key of an encrypted database comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeySource {
+    /// Key file at this path.
+    File(PathBuf),
+    /// Passphrase from the configured environment variable.
+    Passphrase,
+}
+
+/// Key file written during a rotation that has not finished yet.
+fn pending_key_file(key_file: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.new", key_file.display()))
+}
+
+/// Opens the daemon database according to `config`.  Returns the key
+/// source, or `None` when encryption is disabled.
+pub fn open_database(
+    path: &str,
+    config: &EncryptionConfig,
+) -> Result<(LocalDb, Option<KeySource>)> {
+    let db_path = Path::new(path);
+    if !config.enabled {
+        ensure!(
+            !local_db::is_encrypted_database(db_path)?,
+            "database {path} is encrypted; enable database.encryption to open it"
+        );
+        return Ok((LocalDb::open(path)?, None));
+    }
+
+    if let Some(passphrase) = std::env::var(&config.passphrase_env)
+        .ok()
+        .filter(|p| !p.is_empty())
+    {
+        let db = LocalDb::open_with_key(path, &DbKey::Passphrase(passphrase))?;
+        return Ok((db, Some(KeySource::Passphrase)));
+    }
+
+    let key_file = &config.key_file;
+    let pending = pending_key_file(key_file);
+    if !key_file.exists() {
+        ensure!(
+            !local_db::is_encrypted_database(db_path)?,
+            "database {path} is encrypted but key file {} is missing and ${} is not set",
+            key_file.display(),
+            config.passphrase_env
+        );
+        DbKey::generate()?.write_file(key_file)?;
+        info!(key_file = %key_file.display(), "Generated database key");
+    }
+
+    let key = DbKey::load_file(key_file)?;
+    let db = match LocalDb::open_with_key(path, &key) {
+        Ok(db) => {
+            if pending.exists() {
+                // The rotation never re-keyed the database; drop its key.
+                fs::remove_file(&pending)?;
+                warn!("Discarded key from an unfinished key rotation");
+            }
+            db
+        }
+        Err(e) if pending.exists() => {
+            let db = LocalDb::open_with_key(path, &DbKey::load_file(&pending)?).map_err(|_| e)?;
+            fs::rename(&pending, key_file)?;
+            info!("Finished interrupted key rotation");
+            db
+        }
+        Err(e) => return Err(e),
+    };
+    Ok((db, Some(KeySource::File(key_file.clone()))))
+}
+
+/// Key management for the open daemon database.
+pub struct DbEncryption {
+    db: Arc<LocalDb>,
+    source: Option<KeySource>,
+    passphrase_env: String,
+}
+
+impl DbEncryption {
+    pub fn new(db: Arc<LocalDb>, source: Option<KeySource>, config: &EncryptionConfig) -> Self {
+        Self {
+            db,
+            source,
+            passphrase_env: config.passphrase_env.clone(),
+        }
+    }
+
+    /// Key source of the database, or `None` if it is not encrypted.
+    pub fn source(&self) -> Option<&KeySource> {
+        self.source.as_ref()
+    }
+
+    /// Re-encrypts the database under a new key.  Key-file databases get a
+    /// freshly generated key; passphrase databases need `new_passphrase`,
+    /// which the caller must also put in the environment variable before
+    /// the daemon next starts.
+    pub fn rotate(&self, new_passphrase: Option<&str>) -> Result<()> {
+        match &self.source {
+            None => bail!("database encryption is disabled"),
+            Some(KeySource::Passphrase) => {
+                let passphrase = new_passphrase
+                    .filter(|p| !p.is_empty())
+                    .context("a new passphrase is required")?;
+                self.db.rekey(&DbKey::Passphrase(passphrase.to_string()))?;
+                warn!(
+                    env = %self.passphrase_env,
+                    "Database passphrase rotated; update the environment before restarting"
+                );
+            }
+            Some(KeySource::File(key_file)) => {
+                ensure!(
+                    new_passphrase.is_none(),
+                    "database uses a key file; set ${} to use a passphrase",
+                    self.passphrase_env
+                );
+                let key = DbKey::generate()?;
+                let pending = pending_key_file(key_file);
+                key.write_file(&pending)?;
+                if let Err(e) = self.db.rekey(&key) {
+                    let _ = fs::remove_file(&pending);
+                    return Err(e);
+                }
+                fs::rename(&pending, key_file)
+                    .with_context(|| format!("replacing key file {}", key_file.display()))?;
+                info!(key_file = %key_file.display(), "Database key rotated");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encryption_config(dir: &Path, enabled: bool) -> EncryptionConfig {
+        EncryptionConfig {
+            enabled,
+            key_file: dir.join("db.key"),
+            passphrase_env: format!("D1_TEST_PASSPHRASE_{}", uuid::Uuid::new_v4().simple()),
+        }
+    }
+
+    fn count_profiles(db: &LocalDb) -> i64 {
+        db.conn()
+            .query_row("SELECT count(*) FROM profile_memory", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    fn insert_profile(db: &LocalDb) {
+        db.conn()
+            .execute(
+                "INSERT INTO profile_memory (id, category, key, value, source)
+                 VALUES ('p1', 'system', 'os', 'secret-os-name', 'user')",
+                [],
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_key_file_roundtrip_and_permissions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("db.key");
+        let key = DbKey::generate().unwrap();
+        key.write_file(&path).unwrap();
+        let loaded = DbKey::load_file(&path).unwrap();
+        assert_eq!(loaded.pragma_value(), key.pragma_value());
+        assert!(loaded.pragma_value().starts_with("x'"));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+            let err = DbKey::load_file(&path).unwrap_err().to_string();
+            assert!(err.contains("chmod 600"), "{err}");
+        }
+
+        assert!(DbKey::Passphrase("pw".into()).write_file(&path).is_err());
+        assert_eq!(format!("{key:?}"), "DbKey::Raw(..)");
+    }
+
+    #[test]
+    fn test_encrypted_database_needs_its_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("d1.db");
+        let path_str = path.to_str().unwrap();
+        let config = encryption_config(dir.path(), true);
+
+        let (db, source) = open_database(path_str, &config).unwrap();
+        assert_eq!(source, Some(KeySource::File(config.key_file.clone())));
+        insert_profile(&db);
+        drop(db);
+
+        assert!(local_db::is_encrypted_database(&path).unwrap());
+        let raw = fs::read(&path).unwrap();
+        assert!(!raw.windows(14).any(|w| w == b"secret-os-name"));
+
+        assert!(LocalDb::open_with_key(path_str, &DbKey::generate().unwrap()).is_err());
+        assert!(open_database(path_str, &encryption_config(dir.path(), false)).is_err());
+
+        let (db, _) = open_database(path_str, &config).unwrap();
+        assert_eq!(count_profiles(&db), 1);
+    }
+
+    #[test]
+    fn test_plaintext_database_is_migrated() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("d1.db");
+        let path_str = path.to_str().unwrap();
+        let db = LocalDb::open(path_str).unwrap();
+        insert_profile(&db);
+        drop(db);
+        assert!(local_db::is_plaintext_database(&path).unwrap());
+
+        let config = encryption_config(dir.path(), true);
+        let (db, _) = open_database(path_str, &config).unwrap();
+        assert_eq!(count_profiles(&db), 1);
+        drop(db);
+        assert!(local_db::is_encrypted_database(&path).unwrap());
+        assert!(!dir.path().join("d1.db.encrypting").exists());
+    }
+
+    #[test]
+    fn test_passphrase_from_environment() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("d1.db");
+        let path_str = path.to_str().unwrap();
+        let config = encryption_config(dir.path(), true);
+        std::env::set_var(&config.passphrase_env, "correct horse");
+
+        let (db, source) = open_database(path_str, &config).unwrap();
+        assert_eq!(source, Some(KeySource::Passphrase));
+        assert!(!config.key_file.exists(), "no key file in passphrase mode");
+        insert_profile(&db);
+        drop(db);
+
+        std::env::set_var(&config.passphrase_env, "wrong horse");
+        assert!(open_database(path_str, &config).is_err());
+        std::env::remove_var(&config.passphrase_env);
+        let err = open_database(path_str, &config)
+            .map(|_| ())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("key file"), "{err}");
+    }
+
+    #[test]
+    fn test_rotate_key_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("d1.db");
+        let path_str = path.to_str().unwrap();
+        let config = encryption_config(dir.path(), true);
+
+        let (db, source) = open_database(path_str, &config).unwrap();
+        insert_profile(&db);
+        let old_key = fs::read_to_string(&config.key_file).unwrap();
+        let db = Arc::new(db);
+        let encryption = DbEncryption::new(Arc::clone(&db), source, &config);
+        assert!(encryption.rotate(Some("not allowed")).is_err());
+        encryption.rotate(None).unwrap();
+        assert_eq!(count_profiles(&db), 1);
+        drop(encryption);
+        drop(db);
+
+        assert_ne!(fs::read_to_string(&config.key_file).unwrap(), old_key);
+        assert!(!pending_key_file(&config.key_file).exists());
+        let (db, _) = open_database(path_str, &config).unwrap();
+        assert_eq!(count_profiles(&db), 1);
+    }
+
+    #[test]
+    fn test_rotation_reencrypts_backups() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("d1.db");
+        let config = encryption_config(dir.path(), true);
+        let (db, source) = open_database(path.to_str().unwrap(), &config).unwrap();
+        insert_profile(&db);
+        let backup = dir.path().join("d1.db.v4-20260101T000000.bak");
+        let stale = dir.path().join("d1.db.v3-20250101T000000.bak");
+        let other_key = DbKey::generate().unwrap();
+        db.conn()
+            .execute("VACUUM INTO ?1", [backup.to_str().unwrap()])
+            .unwrap();
+        // A backup the current key cannot open
+        drop(LocalDb::open_with_key(stale.to_str().unwrap(), &other_key).unwrap());
+        assert!(local_db::is_encrypted_database(&backup).unwrap());
+
+        let db = Arc::new(db);
+        DbEncryption::new(Arc::clone(&db), source, &config)
+            .rotate(None)
+            .unwrap();
+
+        let new_key = DbKey::load_file(&config.key_file).unwrap();
+        let restored = LocalDb::open_with_key(backup.to_str().unwrap(), &new_key).unwrap();
+        assert_eq!(count_profiles(&restored), 1);
+        assert!(!stale.exists());
+    }
+
+    #[test]
+    fn test_interrupted_rotation_is_finished_on_open() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("d1.db");
+        let path_str = path.to_str().unwrap();
+        let config = encryption_config(dir.path(), true);
+        let (db, _) = open_database(path_str, &config).unwrap();
+        insert_profile(&db);
+
+        // Crash between re-keying the database and renaming the key file.
+        let key = DbKey::generate().unwrap();
+        key.write_file(&pending_key_file(&config.key_file)).unwrap();
+        db.rekey(&key).unwrap();
+        drop(db);
+
+        let (db, _) = open_database(path_str, &config).unwrap();
+        assert_eq!(count_profiles(&db), 1);
+        assert!(!pending_key_file(&config.key_file).exists());
+        assert_eq!(
+            DbKey::load_file(&config.key_file).unwrap().pragma_value(),
+            key.pragma_value()
+        );
+    }
+
+    #[test]
+    fn test_rotate_requires_encryption() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("d1.db");
+        let (db, source) = open_database(
+            path.to_str().unwrap(),
+            &encryption_config(dir.path(), false),
+        )
+        .unwrap();
+        let encryption =
+            DbEncryption::new(Arc::new(db), source, &encryption_config(dir.path(), false));
+        assert!(encryption.source().is_none());
+        assert!(encryption.rotate(None).is_err());
+    }
+}
@@ -0,0 +1,587 @@
+//! Disk usage analysis and dry-run cleanup planning.
+//!
+//! [`analyze`] walks a sandbox-validated directory on a pool of worker
+//! threads and reports the largest directories and files. [`known_caches`]
+//! lists the well-known developer caches (cargo, npm, pip, Homebrew, Docker,
+//! Xcode DerivedData) and [`cleanup_plan`] turns the ones that exist into
+//! cleanup steps. The plan never deletes anything itself: each step carries
+//! the shell command to run and the [`SecurityLayer`] classification that
+//! command will receive when it is sent through the command relay, so
+//! destructive steps go through the normal approval prompt.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::security::{PermissionDecision, SecurityLayer};
+
+/// Default number of directories / files reported.
+pub const DEFAULT_TOP_N: usize = 20;
+
+/// Default depth below the root for directories in `largest_dirs`.
+pub const DEFAULT_MAX_DEPTH: usize = 3;
+
+/// Stop walking after this many entries and mark the report truncated.
+pub const DEFAULT_MAX_ENTRIES: u64 = 2_000_000;
+
+// ---------------------------------------------------------------------------
+// Types
+// ---------------------------------------------------------------------------
+
+/// Options for [`analyze`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiskUsageOptions {
+    #[serde(default = "default_top_n")]
+    pub top_n: usize,
+    #[serde(default = "default_max_depth")]
+    pub max_depth: usize,
+    #[serde(default = "default_max_entries")]
+    pub max_entries: u64,
+}
+
+fn default_top_n() -> usize {
+    DEFAULT_TOP_N
+}
+
+fn default_max_depth() -> usize {
+    DEFAULT_MAX_DEPTH
+}
+
+fn default_max_entries() -> u64 {
+    DEFAULT_MAX_ENTRIES
+}
+
+impl Default for DiskUsageOptions {
+    fn default() -> Self {
+        DiskUsageOptions {
+            top_n: DEFAULT_TOP_N,
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_entries: DEFAULT_MAX_ENTRIES,
+        }
+    }
+}
+
+/// A path and the bytes it occupies on disk.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SizedPath {
+    pub path: String,
+    pub bytes: u64,
+    /// Name of the known cache this path is or lives in, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache: Option<String>,
+}
+
+/// Result of [`analyze`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiskUsageReport {
+    pub root: String,
+    pub total_bytes: u64,
+    pub file_count: u64,
+    pub dir_count: u64,
+    pub largest_dirs: Vec<SizedPath>,
+    pub largest_files: Vec<SizedPath>,
+    /// Entries that could not be read (permissions, races).
+    pub errors: u64,
+    /// `true` when the walk stopped at `max_entries`.
+    pub truncated: bool,
+}
+
+/// A well-known cache directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KnownCache {
+    pub name: String,
+    pub path: PathBuf,
+    /// Command that clears the cache using the owning tool where possible.
+    pub cleanup_command: String,
+}
+
+/// One step of a [`CleanupPlan`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CleanupStep {
+    pub cache: String,
+    pub path: String,
+    /// Estimated bytes reclaimed.
+    pub bytes: u64,
+    pub command: String,
+    /// Risk level from [`SecurityLayer::classify_command`].
+    pub risk: String,
+    pub requires_approval: bool,
+    /// `false` when the security layer would deny the command outright.
+    pub allowed: bool,
+    pub reason: String,
+}
+
+/// Dry-run cleanup plan. Nothing is deleted when the plan is built.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CleanupPlan {
+    pub dry_run: bool,
+    pub steps: Vec<CleanupStep>,
+    pub reclaimable_bytes: u64,
+}
+
+// ---------------------------------------------------------------------------
+// Analysis
+// ---------------------------------------------------------------------------
+
+/// Walk `path` (validated against the sandbox) and summarise disk usage.
+pub fn analyze(
+    security: &SecurityLayer,
+    path: &str,
+    opts: &DiskUsageOptions,
+) -> Result<DiskUsageReport> {
+    let root = security.validate_path(path)?;
+    if !root.is_dir() {
+        anyhow::bail!("not a directory: {}", root.display());
+    }
+    let stats = walk(&root, opts.top_n.max(1), opts.max_entries);
+    let home = security
+        .sandbox_root
+        .canonicalize()
+        .unwrap_or_else(|_| security.sandbox_root.clone());
+    let caches = known_caches(&home);
+
+    let dir_sizes = stats.rolled_up_dir_sizes();
+    let total_bytes = dir_sizes.get(&root).copied().unwrap_or(0);
+    let mut dirs: Vec<(PathBuf, u64)> = dir_sizes
+        .into_iter()
+        .filter(|(p, _)| {
+            p != &root
+                && p.strip_prefix(&root)
+                    .map(|rel| rel.components().count() <= opts.max_depth)
+                    .unwrap_or(false)
+        })
+        .collect();
+    dirs.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    dirs.truncate(opts.top_n);
+
+    let label = |path: PathBuf, bytes: u64| SizedPath {
+        cache: cache_for(&caches, &path).map(|c| c.name.clone()),
+        path: path.to_string_lossy().to_string(),
+        bytes,
+    };
+    Ok(DiskUsageReport {
+        root: root.to_string_lossy().to_string(),
+        total_bytes,
+        file_count: stats.file_count,
+        dir_count: stats.dir_count,
+        largest_dirs: dirs.into_iter().map(|(p, b)| label(p, b)).collect(),
+        largest_files: stats
+            .largest_files
+            .into_iter()
+            .map(|(b, p)| label(p, b))
+            .collect(),
+        errors: stats.errors,
+        truncated: stats.truncated,
+    })
+}
+
+/// The known cache that contains `path`, if any.
+fn cache_for<'a>(caches: &'a [KnownCache], path: &Path) -> Option<&'a KnownCache> {
+    caches.iter().find(|c| path.starts_with(&c.path))
+}
+
+/// Well-known developer caches under `home` for the current platform.
+pub fn known_caches(home: &Path) -> Vec<KnownCache> {
+    let cache = |name: &str, path: PathBuf, command: String| KnownCache {
+        name: name.to_string(),
+        path,
+        cleanup_command: command,
+    };
+    let rm = |path: &Path| format!("rm -rf '{}'", path.to_string_lossy().replace('\'', "'\\''"));
+
+    let cargo_cache = home.join(".cargo").join("registry").join("cache");
+    let cargo_git = home.join(".cargo").join("git").join("checkouts");
+    let mut caches = vec![
+        cache("cargo registry", cargo_cache.clone(), rm(&cargo_cache)),
+        cache("cargo git checkouts", cargo_git.clone(), rm(&cargo_git)),
+        cache(
+            "npm",
+            home.join(".npm").join("_cacache"),
+            "npm cache clean --force".to_string(),
+        ),
+    ];
+
+    if cfg!(target_os = "macos") {
+        let library = home.join("Library");
+        let derived = library.join("Developer").join("Xcode").join("DerivedData");
+        caches.extend([
+            cache(
+                "pip",
+                library.join("Caches").join("pip"),
+                "pip cache purge".to_string(),
+            ),
+            cache(
+                "Homebrew",
+                library.join("Caches").join("Homebrew"),
+                "brew cleanup --prune=all".to_string(),
+            ),
+            cache(
+                "Docker",
+                library
+                    .join("Containers")
+                    .join("com.docker.docker")
+                    .join("Data"),
+                "docker system prune".to_string(),
+            ),
+            cache("Xcode DerivedData", derived.clone(), rm(&derived)),
+        ]);
+    } else {
+        let xdg_cache = home.join(".cache");
+        caches.extend([
+            cache("pip", xdg_cache.join("pip"), "pip cache purge".to_string()),
+            cache(
+                "Homebrew",
+                xdg_cache.join("Homebrew"),
+                "brew cleanup --prune=all".to_string(),
+            ),
+            cache(
+                "Docker",
+                home.join(".local").join("share").join("docker"),
+                "docker system prune".to_string(),
+            ),
+        ]);
+    }
+    caches
+}
+
+/// Build a dry-run plan for clearing the known caches that exist under `home`.
+pub fn cleanup_plan(security: &SecurityLayer, home: &Path) -> CleanupPlan {
+    let mut steps = Vec::new();
+    for cache in known_caches(home) {
+        if !cache.path.is_dir() {
+            continue;
+        }
+        let bytes = walk(&cache.path, 1, DEFAULT_MAX_ENTRIES)
+            .rolled_up_dir_sizes()
+            .get(&cache.path)
+            .copied()
+            .unwrap_or(0);
+        if bytes == 0 {
+            continue;
+        }
+        let classification = security.classify_command(&cache.cleanup_command);
+        let decision = security.check_permission(&cache.cleanup_command);
+        steps.push(CleanupStep {
+            cache: cache.name,
+            path: cache.path.to_string_lossy().to_string(),
+            bytes,
+            command: cache.cleanup_command,
+            risk: classification.risk_level.as_str().to_string(),
+            requires_approval: matches!(decision, PermissionDecision::RequireApproval { .. }),
+            allowed: !matches!(decision, PermissionDecision::Deny { .. }),
+            reason: classification.reason,
+        });
+    }
+    steps.sort_by(|a, b| b.bytes.cmp(&a.bytes));
+    CleanupPlan {
+        dry_run: true,
+        reclaimable_bytes: steps.iter().filter(|s| s.allowed).map(|s| s.bytes).sum(),
+        steps,
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Parallel walker
+// ---------------------------------------------------------------------------
+
+#[derive(Default)]
+struct WalkStats {
+    /// Bytes of files directly inside each directory.
+    dir_own: HashMap<PathBuf, u64>,
+    /// Largest files, biggest first.
+    largest_files: Vec<(u64, PathBuf)>,
+    file_count: u64,
+    dir_count: u64,
+    errors: u64,
+    truncated: bool,
+}
+
+impl WalkStats {
+    /// Total bytes per directory, including everything below it.
+    fn rolled_up_dir_sizes(&self) -> HashMap<PathBuf, u64> {
+        let mut totals = self.dir_own.clone();
+        let mut dirs: Vec<&PathBuf> = self.dir_own.keys().collect();
+        // Deepest first so children are complete before adding to parents.
+        dirs.sort_by_key(|p| Reverse(p.components().count()));
+        for dir in dirs {
+            let size = totals[dir];
+            if let Some(parent) = dir.parent() {
+                if let Some(total) = totals.get_mut(parent) {
+                    *total += size;
+                }
+            }
+        }
+        totals
+    }
+}
+
+struct Queue {
+    pending: Vec<PathBuf>,
+    active: usize,
+}
+
+/// Walk `root` on a pool of threads without following symlinks.
+fn walk(root: &Path, top_n: usize, max_entries: u64) -> WalkStats {
+    let queue = Mutex::new(Queue {
+        pending: vec![root.to_path_buf()],
+        active: 0,
+    });
+    let ready = Condvar::new();
+    let entries = AtomicU64::new(0);
+    let workers = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4)
+        .min(16);
+
+    let partials: Vec<WalkStats> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| scope.spawn(|| walk_worker(&queue, &ready, &entries, top_n, max_entries)))
+            .collect();
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap_or_default())
+            .collect()
+    });
+
+    let mut stats = WalkStats::default();
+    let mut heap = BinaryHeap::new();
+    for part in partials {
+        for (dir, bytes) in part.dir_own {
+            *stats.dir_own.entry(dir).or_default() += bytes;
+        }
+        for file in part.largest_files {
+            push_bounded(&mut heap, file, top_n);
+        }
+        stats.file_count += part.file_count;
+        stats.dir_count += part.dir_count;
+        stats.errors += part.errors;
+        stats.truncated |= part.truncated;
+    }
+    stats.largest_files = sorted_desc(heap);
+    stats
+}
+
+fn walk_worker(
+    queue: &Mutex<Queue>,
+    ready: &Condvar,
+    entries: &AtomicU64,
+    top_n: usize,
+    max_entries: u64,
+) -> WalkStats {
+    let mut stats = WalkStats::default();
+    let mut heap = BinaryHeap::new();
+    loop {
+        let dir = {
+            let mut q = queue.lock().unwrap_or_else(|e| e.into_inner());
+            loop {
+                if let Some(dir) = q.pending.pop() {
+                    q.active += 1;
+                    break Some(dir);
+                }
+                if q.active == 0 {
+                    break None;
+                }
+                q = ready.wait(q).unwrap_or_else(|e| e.into_inner());
+            }
+        };
+        let Some(dir) = dir else {
+            ready.notify_all();
+            break;
+        };
+
+        stats.dir_count += 1;
+        let mut own = 0u64;
+        let mut subdirs = Vec::new();
+        match std::fs::read_dir(&dir) {
+            Ok(read) => {
+                for entry in read {
+                    if entries.fetch_add(1, Ordering::Relaxed) >= max_entries {
+                        stats.truncated = true;
+                        break;
+                    }
+                    let Ok(entry) = entry else {
+                        stats.errors += 1;
+                        continue;
+                    };
+                    let Ok(meta) = entry.path().symlink_metadata() else {
+                        stats.errors += 1;
+                        continue;
+                    };
+                    if meta.is_dir() {
+                        subdirs.push(entry.path());
+                    } else {
+                        let bytes = allocated_size(&meta);
+                        own += bytes;
+                        stats.file_count += 1;
+                        if meta.is_file() {
+                            push_bounded(&mut heap, (bytes, entry.path()), top_n);
+                        }
+                    }
+                }
+            }
+            Err(_) => stats.errors += 1,
+        }
+        stats.dir_own.insert(dir, own);
+
+        let mut q = queue.lock().unwrap_or_else(|e| e.into_inner());
+        q.pending.extend(subdirs);
+        q.active -= 1;
+        ready.notify_all();
+    }
+    stats.largest_files = sorted_desc(heap);
+    stats
+}
+
+/// Keep the `n` largest entries in a min-heap.
+fn push_bounded(heap: &mut BinaryHeap<Reverse<(u64, PathBuf)>>, item: (u64, PathBuf), n: usize) {
+    heap.push(Reverse(item));
+    if heap.len() > n {
+        heap.pop();
+    }
+}
+
+fn sorted_desc(heap: BinaryHeap<Reverse<(u64, PathBuf)>>) -> Vec<(u64, PathBuf)> {
+    let mut items: Vec<_> = heap.into_iter().map(|Reverse(i)| i).collect();
+    items.sort_by(|a, b| b.cmp(a));
+    items
+}
+
+/// Bytes actually allocated on disk (sparse files and block rounding).
+#[cfg(unix)]
+fn allocated_size(meta: &std::fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    meta.blocks() * 512
+}
+
+#[cfg(not(unix))]
+fn allocated_size(meta: &std::fs::Metadata) -> u64 {
+    meta.len()
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, bytes: usize) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, vec![b'x'; bytes]).unwrap();
+    }
+
+    fn sample_home() -> tempfile::TempDir {
+        let home = tempfile::tempdir().unwrap();
+        let h = home.path();
+        write(&h.join("projects/app/target/debug/big.bin"), 256 * 1024);
+        write(&h.join("projects/app/src/main.rs"), 4 * 1024);
+        write(
+            &h.join(".cargo/registry/cache/index/crate.crate"),
+            128 * 1024,
+        );
+        write(&h.join(".npm/_cacache/content/blob"), 64 * 1024);
+        write(&h.join("notes.txt"), 100);
+        home
+    }
+
+    #[test]
+    fn test_analyze_reports_largest_entries() {
+        let home = sample_home();
+        let security = SecurityLayer::with_sandbox_root(home.path().to_path_buf());
+        let report = analyze(
+            &security,
+            home.path().to_str().unwrap(),
+            &DiskUsageOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(report.file_count, 5);
+        assert!(!report.truncated);
+        assert!(report.total_bytes >= (256 + 4 + 128 + 64) * 1024);
+
+        let biggest = &report.largest_files[0];
+        assert!(biggest.path.ends_with("big.bin"));
+        assert_eq!(biggest.cache, None);
+
+        let projects = report
+            .largest_dirs
+            .iter()
+            .find(|d| d.path.ends_with("projects"))
+            .unwrap();
+        assert!(projects.bytes >= 260 * 1024);
+        assert_eq!(report.largest_dirs[0].path, projects.path);
+
+        let cargo_file = report
+            .largest_files
+            .iter()
+            .find(|f| f.path.ends_with("crate.crate"))
+            .unwrap();
+        assert_eq!(cargo_file.cache.as_deref(), Some("cargo registry"));
+    }
+
+    #[test]
+    fn test_analyze_respects_depth_and_limits() {
+        let home = sample_home();
+        let security = SecurityLayer::with_sandbox_root(home.path().to_path_buf());
+        let opts = DiskUsageOptions {
+            top_n: 2,
+            max_depth: 1,
+            max_entries: DEFAULT_MAX_ENTRIES,
+        };
+        let report = analyze(&security, home.path().to_str().unwrap(), &opts).unwrap();
+        assert_eq!(report.largest_files.len(), 2);
+        assert!(report.largest_dirs.len() <= 2);
+        for dir in &report.largest_dirs {
+            let rel = Path::new(&dir.path).strip_prefix(&report.root).unwrap();
+            assert_eq!(rel.components().count(), 1);
+        }
+
+        let opts = DiskUsageOptions {
+            max_entries: 3,
+            ..DiskUsageOptions::default()
+        };
+        let report = analyze(&security, home.path().to_str().unwrap(), &opts).unwrap();
+        assert!(report.truncated);
+    }
+
+    #[test]
+    fn test_analyze_rejects_paths_outside_sandbox() {
+        let home = sample_home();
+        let outside = tempfile::tempdir().unwrap();
+        let security = SecurityLayer::with_sandbox_root(home.path().to_path_buf());
+        assert!(analyze(
+            &security,
+            outside.path().to_str().unwrap(),
+            &DiskUsageOptions::default()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_cleanup_plan_is_classified_dry_run() {
+        let home = sample_home();
+        let security = SecurityLayer::with_sandbox_root(home.path().to_path_buf());
+        let plan = cleanup_plan(&security, home.path());
+
+        assert!(plan.dry_run);
+        assert_eq!(plan.steps.len(), 2);
+        assert_eq!(plan.steps[0].cache, "cargo registry");
+        assert_eq!(plan.steps[0].risk, "high");
+        assert!(plan.steps[0].requires_approval);
+        assert_eq!(plan.steps[1].cache, "npm");
+        assert_eq!(plan.steps[1].command, "npm cache clean --force");
+        assert_eq!(
+            plan.reclaimable_bytes,
+            plan.steps.iter().map(|s| s.bytes).sum::<u64>()
+        );
+
+        // Nothing was deleted.
+        assert!(home.path().join(".cargo/registry/cache").exists());
+    }
+}
@@ -0,0 +1,189 @@
+//! Local text embeddings for semantic memory recall.
+//!
+//! [`Embedder`] is the extension point: anything that can turn text into a
+//! fixed-size vector on the CPU can back [`crate::memory_store::MemoryStore`].
+//! The default [`HashingEmbedder`] needs no model files — it hashes word and
+//! character-trigram features into a signed vector, which is deterministic
+//! (handy for tests) and tolerant of inflections and word order, so
+//! "permission denied" still lands near "denied permissions".
+
+/// Turns text into a fixed-size, L2-normalised vector.
+pub trait Embedder: Send + Sync {
+    /// Short identifier, e.g. for logging which embedder is active.
+    fn name(&self) -> &str;
+
+    /// Length of every vector returned by [`Embedder::embed`].
+    fn dimensions(&self) -> usize;
+
+    /// Embed `text`.  Must always return exactly [`Embedder::dimensions`] values.
+    fn embed(&self, text: &str) -> Vec<f32>;
+}
+
+/// Default vector size for [`HashingEmbedder`].
+pub const DEFAULT_DIMENSIONS: usize = 512;
+
+/// Weight of a whole-word feature relative to a character trigram.
+const WORD_WEIGHT: f32 = 1.0;
+const TRIGRAM_WEIGHT: f32 = 0.5;
+
+/// Feature-hashing embedder: words and padded character trigrams are hashed
+/// (FNV-1a) into buckets with a hash-derived sign.
+#[derive(Debug, Clone)]
+pub struct HashingEmbedder {
+    dimensions: usize,
+}
+
+impl HashingEmbedder {
+    pub fn new(dimensions: usize) -> Self {
+        Self {
+            dimensions: dimensions.max(1),
+        }
+    }
+
+    fn add_feature(&self, vector: &mut [f32], feature: &str, weight: f32) {
+        let hash = fnv1a(feature.as_bytes());
+        let bucket = (hash % self.dimensions as u64) as usize;
+        let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+        vector[bucket] += sign * weight;
+    }
+}
+
+impl Default for HashingEmbedder {
+    fn default() -> Self {
+        Self::new(DEFAULT_DIMENSIONS)
+    }
+}
+
+impl Embedder for HashingEmbedder {
+    fn name(&self) -> &str {
+        "hashing"
+    }
+
+    fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    fn embed(&self, text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0; self.dimensions];
+        for token in tokenize(text) {
+            self.add_feature(&mut vector, &token, WORD_WEIGHT);
+            let padded: Vec<char> = format!("#{token}#").chars().collect();
+            for window in padded.windows(3) {
+                let trigram: String = window.iter().collect();
+                self.add_feature(&mut vector, &trigram, TRIGRAM_WEIGHT);
+            }
+        }
+        normalize(&mut vector);
+        vector
+    }
+}
+
+/// Lowercased alphanumeric runs of `text`.
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Cosine similarity of two vectors; 0.0 if the lengths differ or either is zero.
+pub fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+/// Serialise a vector as a little-endian `f32` BLOB.
+pub fn to_blob(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+/// Inverse of [`to_blob`].  Returns `None` for a BLOB of the wrong size.
+pub fn from_blob(blob: &[u8], dimensions: usize) -> Option<Vec<f32>> {
+    if blob.len() != dimensions * 4 {
+        return None;
+    }
+    Some(
+        blob.chunks_exact(4)
+            .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect(),
+    )
+}
+
+fn normalize(vector: &mut [f32]) {
+    let norm: f32 = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        for v in vector.iter_mut() {
+            *v /= norm;
+        }
+    }
+}
+
+/// 64-bit FNV-1a; stable across platforms and Rust versions, unlike `DefaultHasher`.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in bytes {
+        hash ^= u64::from(*b);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hashing_embedder_is_deterministic_and_normalized() {
+        let embedder = HashingEmbedder::default();
+        let a = embedder.embed("npm install failed");
+        let b = embedder.embed("npm install failed");
+        assert_eq!(a, b);
+        assert_eq!(a.len(), DEFAULT_DIMENSIONS);
+        let norm: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+        assert!((norm - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_paraphrases_score_higher_than_unrelated_text() {
+        let embedder = HashingEmbedder::default();
+        let error = embedder.embed("EACCES: permission denied writing /usr/lib");
+        let paraphrase = embedder.embed("denied permissions when writing to usr lib");
+        let unrelated = embedder.embed("configure postgresql connection pool");
+        assert!(cosine(&error, &paraphrase) > 0.4);
+        assert!(cosine(&error, &paraphrase) > cosine(&error, &unrelated) + 0.2);
+    }
+
+    #[test]
+    fn test_empty_text_embeds_to_zero_vector() {
+        let embedder = HashingEmbedder::new(8);
+        let v = embedder.embed("  --  ");
+        assert!(v.iter().all(|x| *x == 0.0));
+        assert_eq!(cosine(&v, &embedder.embed("anything")), 0.0);
+    }
+
+    #[test]
+    fn test_blob_roundtrip() {
+        let v = vec![0.5, -1.25, 3.0];
+        let blob = to_blob(&v);
+        assert_eq!(blob.len(), 12);
+        assert_eq!(from_blob(&blob, 3).unwrap(), v);
+        assert!(from_blob(&blob, 4).is_none());
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("ERESOLVE: can't resolve peer-deps!"),
+            vec!["eresolve", "can", "t", "resolve", "peer", "deps"]
+        );
+    }
+}
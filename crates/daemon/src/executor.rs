@@ -97,6 +97,8 @@ impl Executor {
 
         cmd.stdout(std::process::Stdio::piped());
         cmd.stderr(std::process::Stdio::piped());
+        // Dropping the execution (timeout or cancellation) kills the child.
+        cmd.kill_on_drop(true);
 
         let start = Instant::now();
 
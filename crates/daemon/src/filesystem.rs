@@ -9,8 +9,11 @@ use regex::Regex;
 use std::fmt::Write as FmtWrite;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tracing::debug;
 
+use crate::file_policy::{FileDecision, FilePolicy};
+
 /// Maximum file size for read operations (1 MB).
 const MAX_READ_SIZE: u64 = 1_048_576;
 
@@ -21,6 +24,8 @@ pub struct FilesystemOps {
     workspace_root: PathBuf,
     /// Directory where backups are stored.
     backup_dir: PathBuf,
+    /// Checks reads of sensitive files (`None` reads everything as is).
+    file_policy: Option<Arc<FilePolicy>>,
 }
 
 impl FilesystemOps {
@@ -29,9 +34,17 @@ impl FilesystemOps {
         Self {
             workspace_root,
             backup_dir,
+            file_policy: None,
         }
     }
 
+    /// Check [`FilesystemOps::read_file`] reads against `policy`. There is
+    /// no one to ask here, so files needing approval are refused.
+    pub fn with_file_policy(mut self, policy: Arc<FilePolicy>) -> Self {
+        self.file_policy = Some(policy);
+        self
+    }
+
     /// Create a new `FilesystemOps` using default paths:
     /// - workspace_root: user home directory
     /// - backup_dir: `~/.d1doctor/backups/`
@@ -124,9 +137,25 @@ impl FilesystemOps {
             );
         }
 
-        let content = fs::read_to_string(&abs)
+        let mut decision = self
+            .file_policy
+            .as_ref()
+            .and_then(|policy| policy.check_path(&abs));
+        self.refuse_read(&abs, decision.as_ref())?;
+
+        let mut content = fs::read_to_string(&abs)
             .with_context(|| format!("failed to read file: {}", abs.display()))?;
 
+        if let Some(policy) = &self.file_policy {
+            if decision.is_none() {
+                decision = policy.check_content(&content);
+                self.refuse_read(&abs, decision.as_ref())?;
+            }
+            if let Some(decision) = &decision {
+                content = policy.release(&abs, decision, content);
+            }
+        }
+
         let lines: Vec<&str> = content.lines().collect();
         let start = offset.unwrap_or(0);
         let end = limit
@@ -145,6 +174,16 @@ impl FilesystemOps {
         Ok(out)
     }
 
+    /// Fail if the file policy refuses a read under `decision`.
+    fn refuse_read(&self, abs: &Path, decision: Option<&FileDecision>) -> Result<()> {
+        if let (Some(policy), Some(decision)) = (&self.file_policy, decision) {
+            if let Some(refusal) = policy.refuse(abs, decision, false) {
+                bail!("cannot read {}: {refusal}", abs.display());
+            }
+        }
+        Ok(())
+    }
+
     // ----------------------------------------------------------------
     // write_file
     // ----------------------------------------------------------------
@@ -629,9 +668,17 @@ impl FilesystemOps {
             .ok_or_else(|| anyhow!("path has no filename"))?
             .to_string_lossy();
 
-        let timestamp = Utc::now().format("%Y%m%d_%H%M%S");
-        let backup_name = format!("{}_{}", filename, timestamp);
-        let backup_path = self.backup_dir.join(&backup_name);
+        // Microseconds keep rapid edits apart; the counter covers the rest,
+        // so an earlier backup (possibly the original file) is never replaced.
+        let timestamp = Utc::now().format("%Y%m%d_%H%M%S_%6f");
+        let mut backup_path = self.backup_dir.join(format!("{}_{}", filename, timestamp));
+        let mut n = 1;
+        while backup_path.exists() {
+            backup_path = self
+                .backup_dir
+                .join(format!("{}_{}_{}", filename, timestamp, n));
+            n += 1;
+        }
 
         fs::copy(abs_path, &backup_path).with_context(|| {
             format!(
@@ -736,6 +783,49 @@ mod tests {
         assert!(result.unwrap_err().to_string().contains("file too large"));
     }
 
+    #[test]
+    fn test_read_file_applies_file_policy() {
+        use crate::redactor::Redactor;
+        use d1_common::config::{FileAction, FilePolicyConfig};
+
+        let (tmp, ops) = setup_test_workspace();
+        let redactor = Arc::new(Redactor::new());
+        let config = FilePolicyConfig {
+            secret_content_action: FileAction::Approve,
+            ..FilePolicyConfig::default()
+        };
+        let ops = ops.with_file_policy(Arc::new(FilePolicy::from_config(
+            &config,
+            Arc::clone(&redactor),
+        )));
+
+        // Known secret file: values masked, line numbers kept
+        let env = tmp.path().join(".env");
+        fs::write(&env, "# app\nDB_PASSWORD=hunter2\n").unwrap();
+        let result = ops.read_file(env.to_str().unwrap(), None, None).unwrap();
+        assert!(result.contains("2\tDB_PASSWORD=[REDACTED:secret_file]"));
+        assert!(!result.contains("hunter2"));
+
+        // Sniffed secret needing approval: refused
+        let notes = tmp.path().join("notes.txt");
+        fs::write(&notes, "key: sk-abcdefghijklmnopqrstuvwxyz012345\n").unwrap();
+        let err = ops
+            .read_file(notes.to_str().unwrap(), None, None)
+            .unwrap_err();
+        assert!(err.to_string().contains("requires approval"));
+
+        // Ordinary files are untouched and not audited
+        let plain = tmp.path().join("plain.txt");
+        fs::write(&plain, "hello\n").unwrap();
+        assert!(ops
+            .read_file(plain.to_str().unwrap(), None, None)
+            .unwrap()
+            .contains("1\thello"));
+
+        let outcomes: Vec<_> = redactor.audit().files.iter().map(|f| f.outcome).collect();
+        assert_eq!(outcomes, ["masked", "denied"]);
+    }
+
     // ---- write_file tests ----
 
     #[test]
@@ -774,6 +864,25 @@ mod tests {
         assert_eq!(backup_content, "original");
     }
 
+    #[test]
+    fn test_rapid_overwrites_keep_every_backup() {
+        let (tmp, ops) = setup_test_workspace();
+        let file = tmp.path().join("rapid.txt");
+        fs::write(&file, "original").unwrap();
+
+        let path = file.to_str().unwrap();
+        ops.write_file(path, "second").unwrap();
+        ops.write_file(path, "third").unwrap();
+        ops.backup(path).unwrap();
+
+        let mut contents: Vec<String> = fs::read_dir(tmp.path().join("backups"))
+            .unwrap()
+            .map(|e| fs::read_to_string(e.unwrap().path()).unwrap())
+            .collect();
+        contents.sort();
+        assert_eq!(contents, vec!["original", "second", "third"]);
+    }
+
     // ---- edit_file tests ----
 
     #[test]
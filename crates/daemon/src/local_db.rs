@@ -1,37 +1,94 @@
 //! Local SQLite database for daemon state persistence.
 //!
 //! Manages the memory schema: profile_memory, session_memory, task_memory,
-//! agent_memory, task_memory_fts (FTS5), and audit_log tables.
+//! agent_memory, their FTS5 indexes (`*_fts`), and audit_log tables, plus
+//! daemon bookkeeping (maintenance_runs, maintenance_flags, the outbound
+//! cloud queue).
+//!
+//! The schema evolves through the ordered [`MIGRATIONS`] list.  Applied
+//! versions are recorded in `schema_version`; databases created before
+//! versioning report version 0 and are brought forward by the same
+//! (idempotent) migrations.
+//!
+//! The database can be encrypted at rest with SQLCipher: open it with
+//! [`LocalDb::open_with_key`] and a [`DbKey`].  A plaintext database found
+//! at the path is encrypted in place on first keyed open.
 
+use anyhow::Context;
 use rusqlite::Connection;
 use std::fs;
-use std::path::Path;
-use tracing::{debug, info};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use tracing::{debug, info, warn};
+
+use crate::db_key::DbKey;
 
 /// Local SQLite database handle for the daemon.
+///
+/// The connection sits behind a mutex so the handle can be shared across
+/// tasks (e.g. the REST API and the MCP memory server) via `Arc<LocalDb>`.
 pub struct LocalDb {
-    conn: Connection,
+    conn: Mutex<Connection>,
+    /// Database file; `None` for in-memory databases.
+    path: Option<PathBuf>,
+    /// Current key, kept to re-key the backups along with the database.
+    key: Mutex<Option<DbKey>>,
 }
 
 impl LocalDb {
     /// Opens (or creates) the SQLite database at the given path,
-    /// enables WAL mode, and runs the idempotent schema migration.
+    /// enables WAL mode, and applies pending schema migrations.
     pub fn open(path: &str) -> anyhow::Result<Self> {
+        Self::open_inner(path, None)
+    }
+
+    /// Like [`LocalDb::open`], but for a database encrypted with `key`.
+    /// A plaintext database at `path` is encrypted first.
+    pub fn open_with_key(path: &str, key: &DbKey) -> anyhow::Result<Self> {
+        Self::open_inner(path, Some(key))
+    }
+
+    fn open_inner(path: &str, key: Option<&DbKey>) -> anyhow::Result<Self> {
         // Ensure parent directory exists
         if let Some(parent) = Path::new(path).parent() {
             fs::create_dir_all(parent)?;
             debug!(?parent, "Ensured database directory exists");
         }
 
+        if let Some(key) = key {
+            if is_plaintext_database(Path::new(path))? {
+                encrypt_plaintext_database(Path::new(path), key)?;
+            }
+            // Also finishes the backups of a migration that was interrupted.
+            encrypt_backups(Path::new(path), key)?;
+        }
+
         let conn = Connection::open(path)?;
-        info!("Opened SQLite database at {}", path);
+        if let Some(key) = key {
+            conn.pragma_update(None, "key", key.pragma_value())?;
+            // SQLCipher only checks the key on first read.
+            conn.query_row("SELECT count(*) FROM sqlite_master", [], |_| Ok(()))
+                .context("database key rejected; wrong key file or passphrase?")?;
+        }
+        info!(
+            encrypted = key.is_some(),
+            "Opened SQLite database at {}", path
+        );
+
+        // Only takes effect on a new database; see `vacuum`.
+        conn.pragma_update(None, "auto_vacuum", "INCREMENTAL")?;
 
         // Enable WAL mode for concurrent reads
         conn.pragma_update(None, "journal_mode", "WAL")?;
         debug!("WAL mode enabled");
 
-        let db = Self { conn };
+        let db = Self {
+            conn: Mutex::new(conn),
+            path: Some(PathBuf::from(path)),
+            key: Mutex::new(key.cloned()),
+        };
         db.init_schema()?;
+        db.finish_fts_rebuild()?;
         info!("Database schema initialized");
 
         Ok(db)
@@ -41,27 +98,536 @@ impl LocalDb {
     #[cfg(test)]
     pub fn open_in_memory() -> anyhow::Result<Self> {
         let conn = Connection::open_in_memory()?;
+        conn.pragma_update(None, "auto_vacuum", "INCREMENTAL")?;
         conn.pragma_update(None, "journal_mode", "WAL")?;
-        let db = Self { conn };
+        let db = Self {
+            conn: Mutex::new(conn),
+            path: None,
+            key: Mutex::new(None),
+        };
         db.init_schema()?;
         Ok(db)
     }
 
-    /// Returns a reference to the underlying connection.
-    pub fn conn(&self) -> &Connection {
-        &self.conn
+    /// Locks and returns the underlying connection.
+    ///
+    /// Hold the guard only for the duration of one logical operation.
+    pub fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
     }
 
-    /// Runs the idempotent schema migration (CREATE TABLE IF NOT EXISTS).
+    /// Brings the schema up to [`SCHEMA_VERSION`], backing up the database
+    /// file first if it holds an older schema.  Refuses to touch a database
+    /// written by a newer daemon.
     fn init_schema(&self) -> anyhow::Result<()> {
-        self.conn.execute_batch(SCHEMA_SQL)?;
+        let mut conn = self.conn();
+        let current = schema_version(&conn)?;
+        anyhow::ensure!(
+            current <= SCHEMA_VERSION,
+            "database schema version {current} is newer than this daemon supports \
+             ({SCHEMA_VERSION}); upgrade d1-daemon or restore an older backup"
+        );
+        if current == SCHEMA_VERSION {
+            debug!(version = current, "Schema up to date");
+            return Ok(());
+        }
+
+        if let Some(path) = &self.path {
+            if has_memory_tables(&conn)? {
+                let backup = backup_before_migration(&conn, path, current)?;
+                info!(from = current, backup = %backup.display(), "Backed up database before migration");
+            }
+        }
+        run_migrations(&mut conn, MIGRATIONS)?;
         debug!("Schema migration completed");
         Ok(())
     }
+
+    /// The schema version recorded in the database.
+    pub fn schema_version(&self) -> anyhow::Result<u32> {
+        schema_version(&self.conn())
+    }
+
+    /// Re-encrypts an encrypted database under `new_key`.  The old key stops
+    /// working as soon as this returns.
+    ///
+    /// Encrypted pre-migration backups are re-encrypted too; one that cannot
+    /// be is deleted, as it would be unreadable without the old key.  Once
+    /// the database itself is re-keyed this never fails.
+    pub fn rekey(&self, new_key: &DbKey) -> anyhow::Result<()> {
+        let conn = self.conn();
+        conn.pragma_update(None, "rekey", new_key.pragma_value())
+            .context("re-encrypting database")?;
+        info!("Database re-encrypted with a new key");
+
+        let old_key = self
+            .key
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .replace(new_key.clone());
+        if let (Some(path), Some(old_key)) = (&self.path, old_key) {
+            if let Err(e) = rekey_backups(path, &old_key, new_key) {
+                warn!("Could not re-encrypt database backups: {e:#}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Merges the segments of every FTS5 index.
+    pub fn optimize_fts(&self) -> anyhow::Result<()> {
+        fts_command(&self.conn(), "optimize")
+    }
+
+    /// Copies the WAL into the database file and truncates it.
+    pub fn checkpoint_wal(&self) -> anyhow::Result<()> {
+        self.conn()
+            .query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+            .context("checkpointing WAL")
+    }
+
+    /// Reclaims space left by deleted rows.
+    ///
+    /// Uses incremental auto-vacuum, which frees pages without touching
+    /// rowids; the external-content FTS indexes and `list_records` cursors
+    /// are keyed on those.  A database created before incremental mode is
+    /// converted once with a full VACUUM, which may renumber rowids, so the
+    /// FTS indexes are rebuilt after it.  The rebuild is flagged before the
+    /// VACUUM starts and finished on the next open if the daemon dies in
+    /// between.
+    pub fn vacuum(&self) -> anyhow::Result<()> {
+        let conn = self.conn();
+        let mode: i64 = conn.query_row("PRAGMA auto_vacuum", [], |row| row.get(0))?;
+        if mode == AUTO_VACUUM_INCREMENTAL {
+            return conn
+                .execute_batch("PRAGMA incremental_vacuum")
+                .context("vacuuming database");
+        }
+
+        conn.execute(
+            "INSERT OR IGNORE INTO maintenance_flags (name) VALUES (?1)",
+            [FTS_REBUILD_FLAG],
+        )?;
+        conn.pragma_update(None, "auto_vacuum", "INCREMENTAL")?;
+        conn.execute_batch("VACUUM").context("vacuuming database")?;
+        info!("Switched database to incremental auto-vacuum");
+        rebuild_fts(&conn)
+    }
+
+    /// Completes an FTS rebuild interrupted after a VACUUM.
+    fn finish_fts_rebuild(&self) -> anyhow::Result<()> {
+        let conn = self.conn();
+        let pending: bool = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM maintenance_flags WHERE name = ?1)",
+            [FTS_REBUILD_FLAG],
+            |row| row.get(0),
+        )?;
+        if pending {
+            warn!("Previous VACUUM was interrupted; rebuilding full-text indexes");
+            rebuild_fts(&conn)?;
+        }
+        Ok(())
+    }
+
+    /// Deletes all but the `keep` newest pre-migration backups.  Returns how
+    /// many were deleted.
+    pub fn prune_backups(&self, keep: usize) -> anyhow::Result<usize> {
+        let Some(path) = &self.path else {
+            return Ok(0);
+        };
+        let backups = migration_backups(path)?;
+        let excess = backups.len().saturating_sub(keep);
+        for backup in &backups[..excess] {
+            fs::remove_file(backup)
+                .with_context(|| format!("removing backup {}", backup.display()))?;
+            info!(backup = %backup.display(), "Pruned database backup");
+        }
+        Ok(excess)
+    }
+}
+
+/// `PRAGMA auto_vacuum` value for incremental mode.
+const AUTO_VACUUM_INCREMENTAL: i64 = 2;
+
+/// `maintenance_flags` entry set while the FTS indexes may be stale.
+const FTS_REBUILD_FLAG: &str = "fts_rebuild";
+
+/// Rebuilds every FTS index from its content table and clears
+/// [`FTS_REBUILD_FLAG`] in the same transaction.
+fn rebuild_fts(conn: &Connection) -> anyhow::Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    fts_command(&tx, "rebuild")?;
+    tx.execute(
+        "DELETE FROM maintenance_flags WHERE name = ?1",
+        [FTS_REBUILD_FLAG],
+    )?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Runs an FTS5 special command (`optimize`, `rebuild`) on every index.
+fn fts_command(conn: &Connection, command: &str) -> anyhow::Result<()> {
+    for fts in [
+        "profile_memory_fts",
+        "session_memory_fts",
+        "task_memory_fts",
+        "agent_memory_fts",
+    ] {
+        conn.execute(&format!("INSERT INTO {fts} ({fts}) VALUES (?1)"), [command])
+            .with_context(|| format!("{command} {fts}"))?;
+    }
+    Ok(())
+}
+
+/// Header every unencrypted SQLite database file starts with.
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// Whether `path` holds an unencrypted SQLite database.  Missing and empty
+/// files are not plaintext databases.
+pub fn is_plaintext_database(path: &Path) -> anyhow::Result<bool> {
+    Ok(read_header(path)?.is_some_and(|header| &header == SQLITE_HEADER))
+}
+
+/// Whether `path` holds a database that is not plaintext, i.e. one that
+/// needs a key to open.
+pub fn is_encrypted_database(path: &Path) -> anyhow::Result<bool> {
+    Ok(read_header(path)?.is_some_and(|header| &header != SQLITE_HEADER))
+}
+
+fn read_header(path: &Path) -> anyhow::Result<Option<[u8; 16]>> {
+    use std::io::Read;
+
+    let mut file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("reading {}", path.display())),
+    };
+    let mut header = [0u8; 16];
+    match file.read_exact(&mut header) {
+        Ok(()) => Ok(Some(header)),
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("reading {}", path.display())),
+    }
+}
+
+/// Encrypts the plaintext database at `path` in place and removes its
+/// plaintext WAL.
+fn encrypt_plaintext_database(path: &Path, key: &DbKey) -> anyhow::Result<()> {
+    export_encrypted(path, key)?;
+    for suffix in ["-wal", "-shm"] {
+        let stale = PathBuf::from(format!("{}{suffix}", path.display()));
+        if stale.exists() {
+            fs::remove_file(&stale)?;
+        }
+    }
+    info!(path = %path.display(), "Encrypted plaintext database");
+    Ok(())
+}
+
+/// Exports the plaintext database at `path` into an encrypted copy, then
+/// swaps the copy in.  The plaintext file is only replaced once the export
+/// has completed.
+fn export_encrypted(path: &Path, key: &DbKey) -> anyhow::Result<()> {
+    let encrypted = PathBuf::from(format!("{}.encrypting", path.display()));
+    if encrypted.exists() {
+        fs::remove_file(&encrypted)?;
+    }
+
+    let conn = Connection::open(path)?;
+    conn.execute(
+        "ATTACH DATABASE ?1 AS encrypted KEY ?2",
+        [encrypted.to_string_lossy().as_ref(), &key.pragma_value()],
+    )
+    .context("creating encrypted copy")?;
+    conn.query_row("SELECT sqlcipher_export('encrypted')", [], |_| Ok(()))
+        .context("exporting plaintext database")?;
+    conn.execute_batch("DETACH DATABASE encrypted")?;
+    // Closing the last connection checkpoints and removes the WAL.
+    drop(conn);
+
+    fs::rename(&encrypted, path)
+        .with_context(|| format!("replacing {} with encrypted copy", path.display()))
+}
+
+/// Encrypts the plaintext migration backups of `path` with `key`, deleting
+/// those that cannot be encrypted, so that no plaintext copy of an
+/// encrypted database is left next to it.
+fn encrypt_backups(path: &Path, key: &DbKey) -> anyhow::Result<()> {
+    for backup in plaintext_backups(path)? {
+        match export_encrypted(&backup, key) {
+            Ok(()) => info!(backup = %backup.display(), "Encrypted database backup"),
+            Err(e) => {
+                warn!(
+                    backup = %backup.display(),
+                    %e,
+                    "Deleting plaintext database backup that could not be encrypted"
+                );
+                let partial = PathBuf::from(format!("{}.encrypting", backup.display()));
+                if partial.exists() {
+                    fs::remove_file(&partial)?;
+                }
+                fs::remove_file(&backup)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Moves the encrypted migration backups of `path` from `old_key` to
+/// `new_key`, deleting those that cannot be moved.
+fn rekey_backups(path: &Path, old_key: &DbKey, new_key: &DbKey) -> anyhow::Result<()> {
+    for backup in migration_backups(path)? {
+        if !is_encrypted_database(&backup)? {
+            continue;
+        }
+        let rekeyed = Connection::open(&backup).and_then(|conn| {
+            conn.pragma_update(None, "key", old_key.pragma_value())?;
+            conn.query_row("SELECT count(*) FROM sqlite_master", [], |_| Ok(()))?;
+            conn.pragma_update(None, "rekey", new_key.pragma_value())
+        });
+        match rekeyed {
+            Ok(()) => debug!(backup = %backup.display(), "Re-encrypted database backup"),
+            Err(e) => {
+                warn!(
+                    backup = %backup.display(),
+                    %e,
+                    "Deleting database backup that could not be re-encrypted"
+                );
+                fs::remove_file(&backup)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Migration backups (`<db>.v<N>-<stamp>.bak`) next to `path` that are
+/// plaintext.
+fn plaintext_backups(path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut plaintext = Vec::new();
+    for backup in migration_backups(path)? {
+        if is_plaintext_database(&backup)? {
+            plaintext.push(backup);
+        }
+    }
+    Ok(plaintext)
+}
+
+/// Migration backups (`<db>.v<N>-<stamp>.bak`) next to `path`, oldest
+/// first.
+fn migration_backups(path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return Ok(Vec::new());
+    };
+    let prefix = format!("{}.v", name.to_string_lossy());
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    })? {
+        let entry = entry?;
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        let Some(stamp) = file_name
+            .strip_prefix(&prefix)
+            .and_then(|rest| rest.strip_suffix(".bak"))
+            .and_then(|rest| rest.split_once('-'))
+            .map(|(_, stamp)| stamp.to_string())
+        else {
+            continue;
+        };
+        backups.push((stamp, entry.path()));
+    }
+    backups.sort();
+    Ok(backups.into_iter().map(|(_, path)| path).collect())
+}
+
+/// Schema version this build writes.  Always the last entry of [`MIGRATIONS`].
+pub const SCHEMA_VERSION: u32 = 6;
+
+/// One forward schema change.
+struct Migration {
+    version: u32,
+    description: &'static str,
+    apply: fn(&Connection) -> anyhow::Result<()>,
+}
+
+/// Ordered forward migrations.  Append new entries; never edit a shipped one.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "baseline memory schema",
+        apply: migrate_v1_baseline,
+    },
+    Migration {
+        version: 2,
+        description: "embedding column on memory tables",
+        apply: migrate_v2_embeddings,
+    },
+    Migration {
+        version: 3,
+        description: "FTS5 indexes for profile, session and agent memory",
+        apply: migrate_v3_memory_fts,
+    },
+    Migration {
+        version: 4,
+        description: "maintenance run history",
+        apply: migrate_v4_maintenance_runs,
+    },
+    Migration {
+        version: 5,
+        description: "persistent outbound queue and dead letters",
+        apply: migrate_v5_outbound_queue,
+    },
+    Migration {
+        version: 6,
+        description: "maintenance recovery flags",
+        apply: migrate_v6_maintenance_flags,
+    },
+];
+
+fn migrate_v1_baseline(conn: &Connection) -> anyhow::Result<()> {
+    conn.execute_batch(V1_BASELINE_SQL)?;
+    Ok(())
+}
+
+fn migrate_v2_embeddings(conn: &Connection) -> anyhow::Result<()> {
+    for table in [
+        "profile_memory",
+        "session_memory",
+        "task_memory",
+        "agent_memory",
+    ] {
+        add_column_if_missing(conn, table, "embedding", "BLOB")?;
+    }
+    Ok(())
+}
+
+fn migrate_v3_memory_fts(conn: &Connection) -> anyhow::Result<()> {
+    conn.execute_batch(V3_MEMORY_FTS_SQL)?;
+    // External-content indexes start empty; index rows that predate them.
+    for fts in [
+        "profile_memory_fts",
+        "session_memory_fts",
+        "agent_memory_fts",
+    ] {
+        conn.execute(&format!("INSERT INTO {fts} ({fts}) VALUES ('rebuild')"), [])?;
+    }
+    Ok(())
+}
+
+fn migrate_v4_maintenance_runs(conn: &Connection) -> anyhow::Result<()> {
+    conn.execute_batch(V4_MAINTENANCE_RUNS_SQL)?;
+    Ok(())
+}
+
+fn migrate_v5_outbound_queue(conn: &Connection) -> anyhow::Result<()> {
+    conn.execute_batch(V5_OUTBOUND_QUEUE_SQL)?;
+    Ok(())
+}
+
+fn migrate_v6_maintenance_flags(conn: &Connection) -> anyhow::Result<()> {
+    conn.execute_batch(V6_MAINTENANCE_FLAGS_SQL)?;
+    Ok(())
+}
+
+/// Apply every migration newer than the recorded version, each in its own
+/// transaction together with its `schema_version` row.
+fn run_migrations(conn: &mut Connection, migrations: &[Migration]) -> anyhow::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS schema_version (
+            version     INTEGER PRIMARY KEY,
+            description TEXT NOT NULL,
+            applied_at  TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+        )",
+    )?;
+    let current = schema_version(conn)?;
+
+    for migration in migrations.iter().filter(|m| m.version > current) {
+        let tx = conn.transaction()?;
+        (migration.apply)(&tx).with_context(|| {
+            format!(
+                "schema migration v{} ({}) failed",
+                migration.version, migration.description
+            )
+        })?;
+        tx.execute(
+            "INSERT INTO schema_version (version, description) VALUES (?1, ?2)",
+            rusqlite::params![migration.version, migration.description],
+        )?;
+        tx.commit()?;
+        info!(
+            version = migration.version,
+            description = migration.description,
+            "Applied schema migration"
+        );
+    }
+    Ok(())
 }
 
-/// Idempotent schema DDL — safe to run on every startup.
-const SCHEMA_SQL: &str = r#"
+/// Highest applied migration; 0 for a new or pre-versioning database.
+fn schema_version(conn: &Connection) -> anyhow::Result<u32> {
+    if !table_exists(conn, "schema_version")? {
+        return Ok(0);
+    }
+    let version: Option<u32> =
+        conn.query_row("SELECT MAX(version) FROM schema_version", [], |row| {
+            row.get(0)
+        })?;
+    Ok(version.unwrap_or(0))
+}
+
+fn table_exists(conn: &Connection, name: &str) -> anyhow::Result<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        [name],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// Whether the database already holds memory data worth backing up.
+fn has_memory_tables(conn: &Connection) -> anyhow::Result<bool> {
+    table_exists(conn, "profile_memory")
+}
+
+/// Write a consistent copy of the database next to it, named after the
+/// version being migrated from, e.g. `memory.db.v2-20260101T120000.bak`.
+fn backup_before_migration(
+    conn: &Connection,
+    db_path: &Path,
+    from_version: u32,
+) -> anyhow::Result<PathBuf> {
+    let stamp = chrono::Utc::now().format("%Y%m%dT%H%M%S");
+    let backup = PathBuf::from(format!("{}.v{from_version}-{stamp}.bak", db_path.display()));
+    // VACUUM INTO includes WAL contents, unlike copying the main file.
+    conn.execute("VACUUM INTO ?1", [backup.to_string_lossy().as_ref()])
+        .with_context(|| format!("backing up database to {}", backup.display()))?;
+    Ok(backup)
+}
+
+/// `ALTER TABLE ... ADD COLUMN` unless `table` already has `column`.
+fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    decl: &str,
+) -> anyhow::Result<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({table})"))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .filter_map(|r| r.ok())
+        .any(|name| name == column);
+    if !exists {
+        conn.execute_batch(&format!("ALTER TABLE {table} ADD COLUMN {column} {decl}"))?;
+        info!(%table, %column, "Added missing column");
+    }
+    Ok(())
+}
+
+/// v1: the original memory schema.
+///
+/// Uses `IF NOT EXISTS` throughout because databases created before schema
+/// versioning already contain these objects.
+const V1_BASELINE_SQL: &str = r#"
 -- Profile memory: long-lived key/value facts about the user/environment
 CREATE TABLE IF NOT EXISTS profile_memory (
     id          TEXT PRIMARY KEY,
@@ -191,6 +757,133 @@ CREATE TABLE IF NOT EXISTS agent_memory_archive (
 );
 "#;
 
+/// v3: FTS5 indexes for profile, session and agent memory, same pattern as
+/// `task_memory_fts`.
+const V3_MEMORY_FTS_SQL: &str = r#"
+CREATE VIRTUAL TABLE IF NOT EXISTS profile_memory_fts USING fts5(
+    category,
+    key,
+    value,
+    content='profile_memory',
+    content_rowid='rowid'
+);
+
+CREATE TRIGGER IF NOT EXISTS profile_memory_ai AFTER INSERT ON profile_memory BEGIN
+    INSERT INTO profile_memory_fts (rowid, category, key, value)
+    VALUES (new.rowid, new.category, new.key, new.value);
+END;
+
+CREATE TRIGGER IF NOT EXISTS profile_memory_ad AFTER DELETE ON profile_memory BEGIN
+    INSERT INTO profile_memory_fts (profile_memory_fts, rowid, category, key, value)
+    VALUES ('delete', old.rowid, old.category, old.key, old.value);
+END;
+
+CREATE TRIGGER IF NOT EXISTS profile_memory_au AFTER UPDATE ON profile_memory BEGIN
+    INSERT INTO profile_memory_fts (profile_memory_fts, rowid, category, key, value)
+    VALUES ('delete', old.rowid, old.category, old.key, old.value);
+    INSERT INTO profile_memory_fts (rowid, category, key, value)
+    VALUES (new.rowid, new.category, new.key, new.value);
+END;
+
+CREATE VIRTUAL TABLE IF NOT EXISTS session_memory_fts USING fts5(
+    event_type,
+    content,
+    content='session_memory',
+    content_rowid='rowid'
+);
+
+CREATE TRIGGER IF NOT EXISTS session_memory_ai AFTER INSERT ON session_memory BEGIN
+    INSERT INTO session_memory_fts (rowid, event_type, content)
+    VALUES (new.rowid, new.event_type, new.content);
+END;
+
+CREATE TRIGGER IF NOT EXISTS session_memory_ad AFTER DELETE ON session_memory BEGIN
+    INSERT INTO session_memory_fts (session_memory_fts, rowid, event_type, content)
+    VALUES ('delete', old.rowid, old.event_type, old.content);
+END;
+
+CREATE TRIGGER IF NOT EXISTS session_memory_au AFTER UPDATE ON session_memory BEGIN
+    INSERT INTO session_memory_fts (session_memory_fts, rowid, event_type, content)
+    VALUES ('delete', old.rowid, old.event_type, old.content);
+    INSERT INTO session_memory_fts (rowid, event_type, content)
+    VALUES (new.rowid, new.event_type, new.content);
+END;
+
+CREATE VIRTUAL TABLE IF NOT EXISTS agent_memory_fts USING fts5(
+    memory_type,
+    content,
+    content='agent_memory',
+    content_rowid='rowid'
+);
+
+CREATE TRIGGER IF NOT EXISTS agent_memory_ai AFTER INSERT ON agent_memory BEGIN
+    INSERT INTO agent_memory_fts (rowid, memory_type, content)
+    VALUES (new.rowid, new.memory_type, new.content);
+END;
+
+CREATE TRIGGER IF NOT EXISTS agent_memory_ad AFTER DELETE ON agent_memory BEGIN
+    INSERT INTO agent_memory_fts (agent_memory_fts, rowid, memory_type, content)
+    VALUES ('delete', old.rowid, old.memory_type, old.content);
+END;
+
+CREATE TRIGGER IF NOT EXISTS agent_memory_au AFTER UPDATE ON agent_memory BEGIN
+    INSERT INTO agent_memory_fts (agent_memory_fts, rowid, memory_type, content)
+    VALUES ('delete', old.rowid, old.memory_type, old.content);
+    INSERT INTO agent_memory_fts (rowid, memory_type, content)
+    VALUES (new.rowid, new.memory_type, new.content);
+END;
+"#;
+
+const V4_MAINTENANCE_RUNS_SQL: &str = r#"
+CREATE TABLE IF NOT EXISTS maintenance_runs (
+    id                 TEXT PRIMARY KEY,
+    started_at         TEXT NOT NULL,
+    finished_at        TEXT NOT NULL,
+    compressed         INTEGER NOT NULL DEFAULT 0,
+    archived_sessions  INTEGER NOT NULL DEFAULT 0,
+    fts_optimized      INTEGER NOT NULL DEFAULT 0,
+    wal_checkpointed   INTEGER NOT NULL DEFAULT 0,
+    vacuumed           INTEGER NOT NULL DEFAULT 0,
+    backups_pruned     INTEGER NOT NULL DEFAULT 0,
+    error              TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_maintenance_runs_started ON maintenance_runs(started_at);
+"#;
+
+/// v5: cloud-bound messages kept across restarts.  `seq` is the replay
+/// order; delivered rows stay until they expire so re-sent ids are ignored.
+const V5_OUTBOUND_QUEUE_SQL: &str = r#"
+CREATE TABLE IF NOT EXISTS outbound_queue (
+    seq           INTEGER PRIMARY KEY AUTOINCREMENT,
+    id            TEXT NOT NULL UNIQUE,
+    payload       BLOB NOT NULL,
+    attempts      INTEGER NOT NULL DEFAULT 0,
+    queued_at     TEXT NOT NULL,
+    expires_at    TEXT NOT NULL,
+    delivered_at  TEXT,
+    last_error    TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_outbound_queue_pending ON outbound_queue(delivered_at, seq);
+
+CREATE TABLE IF NOT EXISTS outbound_dead_letters (
+    id          TEXT PRIMARY KEY,
+    payload     BLOB NOT NULL,
+    attempts    INTEGER NOT NULL,
+    queued_at   TEXT NOT NULL,
+    failed_at   TEXT NOT NULL,
+    reason      TEXT NOT NULL
+);
+"#;
+
+const V6_MAINTENANCE_FLAGS_SQL: &str = r#"
+CREATE TABLE IF NOT EXISTS maintenance_flags (
+    name    TEXT PRIMARY KEY,
+    set_at  TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+);
+"#;
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -198,8 +891,8 @@ mod tests {
 
     /// Helper: list all user tables in the database.
     fn list_tables(db: &LocalDb) -> Vec<String> {
-        let mut stmt = db
-            .conn()
+        let conn = db.conn();
+        let mut stmt = conn
             .prepare(
                 "SELECT name FROM sqlite_master WHERE type IN ('table', 'trigger') ORDER BY name",
             )
@@ -246,11 +939,15 @@ mod tests {
         );
 
         // FTS5 virtual table (shows up as a table in sqlite_master)
-        let mut stmt = db
-            .conn()
-            .prepare("SELECT name FROM sqlite_master WHERE type='table' AND name='task_memory_fts'")
-            .unwrap();
-        let _count: i64 = stmt.query_row([], |row| row.get(0)).unwrap_or(0);
+        {
+            let conn = db.conn();
+            let mut stmt = conn
+                .prepare(
+                    "SELECT name FROM sqlite_master WHERE type='table' AND name='task_memory_fts'",
+                )
+                .unwrap();
+            let _count: i64 = stmt.query_row([], |row| row.get(0)).unwrap_or(0);
+        }
         // FTS5 tables are present — just verify we can query them
         let result: Result<Vec<String>, _> = db
             .conn()
@@ -486,4 +1183,401 @@ mod tests {
         assert_eq!(tbl, "profile_memory");
         assert_eq!(rid, "pm-001");
     }
+
+    /// Build a file database as it looked after `version` migrations.  With
+    /// `tracked == false` no `schema_version` table is written, like the
+    /// databases created before migrations were versioned.
+    fn historical_db(dir: &Path, version: u32, tracked: bool) -> PathBuf {
+        let path = dir.join("memory.db");
+        let mut conn = Connection::open(&path).unwrap();
+        let upto = &MIGRATIONS[..version as usize];
+        if tracked {
+            run_migrations(&mut conn, upto).unwrap();
+        } else {
+            for m in upto {
+                (m.apply)(&conn).unwrap();
+            }
+        }
+        conn.execute_batch(
+            "INSERT INTO profile_memory (id, category, key, value) VALUES ('p1', 'env', 'shell', 'zsh');
+             INSERT INTO session_memory (id, session_id, step_number, agent_name, event_type, content)
+                 VALUES ('s1', 'sess', 0, 'bob', 'action', 'ran brew upgrade');
+             INSERT INTO task_memory (id, task_description, outcome)
+                 VALUES ('t1', 'install node', 'success');
+             INSERT INTO agent_memory (id, agent_name, memory_type, content)
+                 VALUES ('a1', 'bob', 'fact', 'user prefers tabs');",
+        )
+        .unwrap();
+        path
+    }
+
+    fn assert_fully_migrated(db: &LocalDb) {
+        assert_eq!(db.schema_version().unwrap(), SCHEMA_VERSION);
+        let conn = db.conn();
+        for table in [
+            "profile_memory",
+            "session_memory",
+            "task_memory",
+            "agent_memory",
+        ] {
+            let has_embedding = conn
+                .prepare(&format!("PRAGMA table_info({table})"))
+                .unwrap()
+                .query_map([], |row| row.get::<_, String>(1))
+                .unwrap()
+                .filter_map(|r| r.ok())
+                .any(|name| name == "embedding");
+            assert!(has_embedding, "{table} is missing the embedding column");
+        }
+        for (fts, term, id) in [
+            ("profile_memory_fts", "zsh", "p1"),
+            ("session_memory_fts", "brew", "s1"),
+            ("task_memory_fts", "node", "t1"),
+            ("agent_memory_fts", "tabs", "a1"),
+        ] {
+            let table = fts.trim_end_matches("_fts");
+            let matched: String = conn
+                .query_row(
+                    &format!(
+                        "SELECT t.id FROM {table} t JOIN {fts} ON t.rowid = {fts}.rowid
+                         WHERE {fts} MATCH ?1"
+                    ),
+                    [term],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            assert_eq!(matched, id, "{fts} is missing pre-existing rows");
+        }
+        assert!(table_exists(&conn, "maintenance_runs").unwrap());
+        assert!(table_exists(&conn, "outbound_queue").unwrap());
+        assert!(table_exists(&conn, "outbound_dead_letters").unwrap());
+    }
+
+    fn backups(dir: &Path) -> Vec<String> {
+        fs::read_dir(dir)
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .map(|e| e.file_name().to_string_lossy().into_owned())
+            .filter(|name| name.ends_with(".bak"))
+            .collect()
+    }
+
+    #[test]
+    fn test_new_database_is_at_latest_version() {
+        let db = LocalDb::open_in_memory().unwrap();
+        assert_eq!(db.schema_version().unwrap(), SCHEMA_VERSION);
+        assert_eq!(MIGRATIONS.last().unwrap().version, SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_fresh_file_database_is_not_backed_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("memory.db");
+        LocalDb::open(path.to_str().unwrap()).unwrap();
+        assert!(backups(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn test_upgrade_from_every_historical_schema() {
+        for version in 1..SCHEMA_VERSION {
+            for tracked in [false, true] {
+                let dir = tempfile::tempdir().unwrap();
+                let path = historical_db(dir.path(), version, tracked);
+
+                let db = LocalDb::open(path.to_str().unwrap()).unwrap();
+                assert_fully_migrated(&db);
+
+                let from = if tracked { version } else { 0 };
+                let backups = backups(dir.path());
+                assert_eq!(backups.len(), 1, "v{version} tracked={tracked}");
+                assert!(
+                    backups[0].starts_with(&format!("memory.db.v{from}-")),
+                    "unexpected backup name {}",
+                    backups[0]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_upgrade_from_unversioned_latest_schema() {
+        // Databases written just before versioning already had every object.
+        let dir = tempfile::tempdir().unwrap();
+        let path = historical_db(dir.path(), SCHEMA_VERSION, false);
+        let db = LocalDb::open(path.to_str().unwrap()).unwrap();
+        assert_fully_migrated(&db);
+    }
+
+    #[test]
+    fn test_backup_holds_pre_migration_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = historical_db(dir.path(), 1, true);
+        LocalDb::open(path.to_str().unwrap()).unwrap();
+
+        let backup = dir.path().join(&backups(dir.path())[0]);
+        let conn = Connection::open(backup).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), 1);
+        let value: String = conn
+            .query_row(
+                "SELECT value FROM profile_memory WHERE id = 'p1'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(value, "zsh");
+    }
+
+    #[test]
+    fn test_encrypting_an_old_plaintext_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = historical_db(dir.path(), 1, true);
+        let key = DbKey::generate().unwrap();
+        let db = LocalDb::open_with_key(path.to_str().unwrap(), &key).unwrap();
+        assert_fully_migrated(&db);
+        drop(db);
+
+        assert!(is_encrypted_database(&path).unwrap());
+        let backup = dir.path().join(&backups(dir.path())[0]);
+        assert!(
+            is_encrypted_database(&backup).unwrap(),
+            "backups of an encrypted database must be encrypted too"
+        );
+        let conn = Connection::open(backup).unwrap();
+        conn.pragma_update(None, "key", key.pragma_value()).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_no_plaintext_backup_survives_encryption() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = historical_db(dir.path(), 1, true);
+        // Upgraded while still plaintext: the backup is plaintext too
+        drop(LocalDb::open(path.to_str().unwrap()).unwrap());
+        assert_eq!(plaintext_backups(&path).unwrap().len(), 1);
+        // A damaged backup cannot be encrypted and is removed instead
+        let damaged = dir.path().join("memory.db.v0-20200101T000000.bak");
+        fs::write(&damaged, b"SQLite format 3\0not really a database").unwrap();
+
+        let key = DbKey::generate().unwrap();
+        drop(LocalDb::open_with_key(path.to_str().unwrap(), &key).unwrap());
+
+        assert!(plaintext_backups(&path).unwrap().is_empty());
+        assert!(!damaged.exists());
+        let backups = backups(dir.path());
+        assert_eq!(backups.len(), 1, "{backups:?}");
+        let backup = dir.path().join(&backups[0]);
+        assert!(is_encrypted_database(&backup).unwrap());
+        let conn = Connection::open(backup).unwrap();
+        conn.pragma_update(None, "key", key.pragma_value()).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_prune_backups_keeps_newest() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("memory.db");
+        let db = LocalDb::open(path.to_str().unwrap()).unwrap();
+        for name in [
+            "memory.db.v1-20260101T000000.bak",
+            "memory.db.v2-20260301T000000.bak",
+            "memory.db.v1-20260201T000000.bak",
+            "other.db.v1-20250101T000000.bak",
+        ] {
+            fs::write(dir.path().join(name), b"").unwrap();
+        }
+
+        assert_eq!(db.prune_backups(2).unwrap(), 1);
+        let mut left = backups(dir.path());
+        left.sort();
+        assert_eq!(
+            left,
+            [
+                "memory.db.v1-20260201T000000.bak",
+                "memory.db.v2-20260301T000000.bak",
+                "other.db.v1-20250101T000000.bak",
+            ]
+        );
+        assert_eq!(db.prune_backups(2).unwrap(), 0);
+        assert_eq!(
+            LocalDb::open_in_memory().unwrap().prune_backups(0).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_optimize_checkpoint_and_vacuum() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("memory.db");
+        let db = LocalDb::open(path.to_str().unwrap()).unwrap();
+        let mode: i64 = db
+            .conn()
+            .query_row("PRAGMA auto_vacuum", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(mode, AUTO_VACUUM_INCREMENTAL);
+        // Deleting the first rows leaves a rowid gap VACUUM could close.
+        db.conn()
+            .execute_batch(
+                "INSERT INTO agent_memory (id, agent_name, memory_type, content)
+                 VALUES ('a0', 'bob', 'fact', 'user prefers spaces'),
+                        ('a1', 'bob', 'fact', 'build uses make'),
+                        ('a2', 'bob', 'fact', 'user prefers tabs');
+                 DELETE FROM agent_memory WHERE id IN ('a0', 'a1');",
+            )
+            .unwrap();
+
+        db.optimize_fts().unwrap();
+        db.checkpoint_wal().unwrap();
+        let wal = dir.path().join("memory.db-wal");
+        assert_eq!(fs::metadata(&wal).map(|m| m.len()).unwrap_or(0), 0);
+        db.vacuum().unwrap();
+
+        let matched: String = db
+            .conn()
+            .query_row(
+                "SELECT a.id FROM agent_memory a
+                 JOIN agent_memory_fts f ON a.rowid = f.rowid
+                 WHERE agent_memory_fts MATCH 'tabs'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(matched, "a2");
+        let rowid: i64 = db
+            .conn()
+            .query_row("SELECT rowid FROM agent_memory", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(rowid, 3, "incremental vacuum keeps rowids");
+    }
+
+    fn fts_match(db: &LocalDb, term: &str) -> Option<String> {
+        db.conn()
+            .query_row(
+                "SELECT a.id FROM agent_memory a
+                 JOIN agent_memory_fts f ON a.rowid = f.rowid
+                 WHERE agent_memory_fts MATCH ?1",
+                [term],
+                |row| row.get(0),
+            )
+            .ok()
+    }
+
+    #[test]
+    fn test_vacuum_converts_older_databases_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("memory.db");
+        let path = path.to_str().unwrap();
+        // A database created before incremental auto-vacuum
+        Connection::open(path)
+            .unwrap()
+            .execute_batch("PRAGMA auto_vacuum = NONE; CREATE TABLE legacy (x);")
+            .unwrap();
+        let db = LocalDb::open(path).unwrap();
+        db.conn()
+            .execute_batch(
+                "INSERT INTO agent_memory (id, agent_name, memory_type, content)
+                 VALUES ('a0', 'bob', 'fact', 'user prefers spaces'),
+                        ('a1', 'bob', 'fact', 'user prefers tabs');
+                 DELETE FROM agent_memory WHERE id = 'a0';",
+            )
+            .unwrap();
+
+        db.vacuum().unwrap();
+        let mode: i64 = db
+            .conn()
+            .query_row("PRAGMA auto_vacuum", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(mode, AUTO_VACUUM_INCREMENTAL);
+        assert_eq!(fts_match(&db, "tabs").as_deref(), Some("a1"));
+        let pending: i64 = db
+            .conn()
+            .query_row("SELECT COUNT(*) FROM maintenance_flags", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(pending, 0);
+    }
+
+    #[test]
+    fn test_interrupted_fts_rebuild_finishes_on_open() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("memory.db");
+        let path = path.to_str().unwrap();
+        let db = LocalDb::open(path).unwrap();
+        db.conn()
+            .execute_batch(
+                "INSERT INTO agent_memory (id, agent_name, memory_type, content)
+                 VALUES ('a1', 'bob', 'fact', 'user prefers tabs');
+                 DELETE FROM agent_memory_fts;",
+            )
+            .unwrap();
+        db.conn()
+            .execute(
+                "INSERT INTO maintenance_flags (name) VALUES (?1)",
+                [FTS_REBUILD_FLAG],
+            )
+            .unwrap();
+        assert!(fts_match(&db, "tabs").is_none());
+        drop(db);
+
+        let db = LocalDb::open(path).unwrap();
+        assert_eq!(fts_match(&db, "tabs").as_deref(), Some("a1"));
+    }
+
+    #[test]
+    fn test_reopening_current_database_is_a_no_op() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("memory.db");
+        let path = path.to_str().unwrap();
+        drop(LocalDb::open(path).unwrap());
+        let db = LocalDb::open(path).unwrap();
+        assert_eq!(db.schema_version().unwrap(), SCHEMA_VERSION);
+        assert!(backups(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn test_refuses_newer_schema_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("memory.db");
+        let path = path.to_str().unwrap();
+        drop(LocalDb::open(path).unwrap());
+        Connection::open(path)
+            .unwrap()
+            .execute(
+                "INSERT INTO schema_version (version, description) VALUES (?1, 'future')",
+                [SCHEMA_VERSION + 1],
+            )
+            .unwrap();
+
+        let err = LocalDb::open(path)
+            .err()
+            .expect("newer schema must be rejected");
+        assert!(err.to_string().contains("newer than this daemon supports"));
+    }
+
+    #[test]
+    fn test_failed_migration_rolls_back() {
+        fn create_then_fail(conn: &Connection) -> anyhow::Result<()> {
+            conn.execute_batch("CREATE TABLE half_done (x INTEGER)")?;
+            anyhow::bail!("boom")
+        }
+        let migrations = [
+            Migration {
+                version: 1,
+                description: "baseline memory schema",
+                apply: migrate_v1_baseline,
+            },
+            Migration {
+                version: 2,
+                description: "broken",
+                apply: create_then_fail,
+            },
+        ];
+
+        let mut conn = Connection::open_in_memory().unwrap();
+        let err = run_migrations(&mut conn, &migrations).unwrap_err();
+        assert!(format!("{err:#}").contains("v2 (broken)"));
+        assert_eq!(schema_version(&conn).unwrap(), 1);
+        assert!(!table_exists(&conn, "half_done").unwrap());
+        assert!(table_exists(&conn, "profile_memory").unwrap());
+    }
 }
@@ -3,7 +3,7 @@
 //! The daemon runs on the user's machine and bridges local CLI/Mac App
 //! connections to the cloud platform. It provides:
 //! - A WebSocket endpoint (`/chat`) for real-time chat relay
-//! - REST API endpoints for health and memory search
+//! - REST API endpoints for health and memory search/curation
 //! - A cloud WebSocket client for upstream connectivity
 //! - Local SQLite storage for agent memory
 //! - MCP tool servers (filesystem, shell, memory, system, QMD)
@@ -11,38 +11,55 @@
 // ---------------------------------------------------------------------------
 // Module declarations — every .rs file in this crate except main.rs
 // ---------------------------------------------------------------------------
+pub mod api_token;
+pub mod approvals;
 pub mod chat_relay;
 pub mod cloud_ws;
 pub mod command_relay;
+pub mod config_formats;
 pub mod connection_state;
+pub mod db_key;
+pub mod disk_usage;
+pub mod embedding;
 pub mod executor;
+pub mod file_policy;
 pub mod filesystem;
 pub mod fingerprint;
 pub mod health;
 pub mod local_db;
+pub mod maintenance;
 pub mod mcp_filesystem;
 pub mod mcp_memory;
 pub mod mcp_qmd;
 pub mod mcp_registry;
 pub mod mcp_shell;
 pub mod mcp_system;
+pub mod memory_bundle;
 pub mod memory_store;
+pub mod net_diag;
+pub mod outbound_queue;
 pub mod profile_detect;
 pub mod qmd;
 pub mod redactor;
 pub mod rest_api;
+pub mod secret_detector;
 pub mod security;
+pub mod shell_env;
+pub mod summarizer;
 pub mod system_ops;
+pub mod token_vault;
 pub mod ws_app;
 pub mod ws_client;
 
 // ---------------------------------------------------------------------------
 // Imports
 // ---------------------------------------------------------------------------
+use std::sync::atomic::AtomicUsize;
 use std::sync::Arc;
 
 use axum::extract::ws::{Message as AxumWsMessage, WebSocket, WebSocketUpgrade};
-use axum::extract::State;
+use axum::extract::{FromRef, Query, State};
+use axum::http::StatusCode;
 use axum::response::IntoResponse;
 use axum::routing::get;
 use axum::Router;
@@ -50,21 +67,46 @@ use futures::{SinkExt, StreamExt};
 use tokio::signal;
 use tracing::{debug, error, info, warn};
 
-use chat_relay::{ChatMessage, ChatRelay};
-use cloud_ws::{CloudWsClient, CloudWsConfig, ConnectionState};
-use d1_common::Config;
+use api_token::ApiToken;
+use approvals::ApprovalBroker;
+use chat_relay::{ChatMessage, ChatRelay, CloudConnectionState};
+use cloud_ws::{CloudWsClient, CloudWsConfig, OutboundFrame};
+use command_relay::{CommandRelay, CommandRequest};
+use connection_state::{ConnectionConfig, ConnectionState, ConnectionStateMachine};
+use d1_common::protocol::{CloudMessage, Envelope};
+use d1_common::{Config, SummarizerKind};
+use db_key::DbEncryption;
+use file_policy::FilePolicy;
 use fingerprint::DeviceFingerprint;
+use maintenance::MaintenanceScheduler;
+use memory_store::MemoryStore;
+use outbound_queue::{OutboundQueue, OutboundQueueConfig};
 use redactor::Redactor;
+use summarizer::{CloudSummarizer, Summarizer};
 
 // ---------------------------------------------------------------------------
 // Shared state for Axum handlers
 // ---------------------------------------------------------------------------
 
-/// State shared across all Axum handlers (WebSocket + REST).
+/// State shared across the WebSocket handlers.  The REST routes get their
+/// own [`rest_api::AppState`].
 #[derive(Clone)]
 struct DaemonState {
     relay: Arc<ChatRelay>,
+    connection: Arc<ConnectionStateMachine>,
     redactor: Arc<Redactor>,
+    api_token: Arc<ApiToken>,
+    commands: Arc<CommandRelay>,
+    approvals: Arc<ApprovalBroker>,
+    active_tasks: Arc<AtomicUsize>,
+    device_id: String,
+    orchestrator_url: String,
+}
+
+impl FromRef<DaemonState> for Arc<ApiToken> {
+    fn from_ref(state: &DaemonState) -> Self {
+        Arc::clone(&state.api_token)
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -98,27 +140,121 @@ async fn main() -> anyhow::Result<()> {
     // 2. Create Redactor
     let redactor = Arc::new(Redactor::from_config(&config.redaction));
 
-    // 3. Open SQLite
+    // 3. Open SQLite (encrypted at rest when configured)
     let db_path = config.database.path.to_string_lossy().to_string();
-    let _db = local_db::LocalDb::open(&db_path)?;
-    info!(%db_path, "SQLite database opened");
+    let (db, key_source) = db_key::open_database(&db_path, &config.database.encryption)?;
+    let db = Arc::new(db);
+    info!(%db_path, encrypted = key_source.is_some(), "SQLite database opened");
+    let encryption = Arc::new(DbEncryption::new(
+        Arc::clone(&db),
+        key_source,
+        &config.database.encryption,
+    ));
+
+    // Cloud WS channels, created early so the cloud summarizer can use them.
+    let (cloud_outbound_tx, cloud_outbound_rx) = tokio::sync::mpsc::channel::<OutboundFrame>(256);
+    let (cloud_inbound_tx, mut cloud_inbound_rx) = tokio::sync::mpsc::channel::<String>(256);
+
+    let cloud_summarizer = match config.memory.summarizer {
+        SummarizerKind::Local => None,
+        SummarizerKind::Cloud => Some(Arc::new(CloudSummarizer::new(
+            cloud_outbound_tx.clone(),
+            Arc::clone(&redactor),
+        ))),
+    };
+    let mut memory = MemoryStore::new(Arc::clone(&db));
+    if let Some(summarizer) = &cloud_summarizer {
+        memory = memory.with_summarizer(Arc::clone(summarizer) as Arc<dyn Summarizer>);
+    }
+    let memory = Arc::new(memory);
+
+    // Embed rows written before embeddings existed (or by another embedder).
+    let memory_for_backfill = Arc::clone(&memory);
+    tokio::task::spawn_blocking(move || match memory_for_backfill.backfill_embeddings() {
+        Ok(0) => {}
+        Ok(count) => info!(count, "Backfilled memory embeddings"),
+        Err(e) => warn!(%e, "Embedding backfill failed"),
+    });
 
-    // 4. Create ChatRelay
+    // Cloud-bound chat messages are persisted until sent, so a dropped link
+    // or a restart doesn't lose them.
+    let outbound_queue = Arc::new(OutboundQueue::new(
+        Arc::clone(&db),
+        OutboundQueueConfig::default(),
+    ));
+
+    // Retention and database upkeep on a schedule.
+    let maintenance = Arc::new(MaintenanceScheduler::new(
+        Arc::clone(&memory),
+        db,
+        config.memory.maintenance.clone(),
+    ));
+    let _maintenance_handle = Arc::clone(&maintenance).spawn();
+
+    // 4. Create ChatRelay and the cloud connection state machine; the relay
+    //    fills the persistent queue and the state machine reports its length
     let (relay, mut cloud_rx) = ChatRelay::new();
-    let relay = Arc::new(relay);
+    let relay = Arc::new(relay.with_outbound_queue(Arc::clone(&outbound_queue)));
+    let (connection, _) = ConnectionStateMachine::with_outbound_status(
+        ConnectionConfig::default(),
+        Arc::clone(&outbound_queue),
+    );
+
+    // Cloud commands run through the CommandRelay; risky ones are approved
+    // by the user in the app.
+    let approvals = Arc::new(ApprovalBroker::default());
+    let file_policy = Arc::new(FilePolicy::from_config(
+        &config.redaction.files,
+        Arc::clone(&redactor),
+    ));
+    let mut commands = CommandRelay::with_approval_handler(Arc::clone(&approvals) as _)
+        .with_redactor(Arc::clone(&redactor))
+        .with_file_policy(Arc::clone(&file_policy));
+    if let Some(vault) = redactor.vault() {
+        commands = commands.with_token_vault(Arc::clone(vault));
+    }
+    let commands = Arc::new(commands);
+
+    let device_fp = DeviceFingerprint::generate()
+        .map(|fp| fp.fingerprint)
+        .unwrap_or_else(|e| {
+            warn!(%e, "Failed to generate device fingerprint, using fallback");
+            "unknown-device".to_string()
+        });
+
+    // Sensitive REST endpoints require this token; only the owner can read it.
+    let api_token = Arc::new(ApiToken::generate()?);
+    let api_token_path = ApiToken::default_path();
+    api_token.write_file(&api_token_path)?;
+    info!(path = %api_token_path.display(), "API token written");
 
     // 5. Build Axum router: /chat (WS) + /api/* (REST)
     let daemon_state = DaemonState {
         relay: Arc::clone(&relay),
+        connection: Arc::clone(&connection),
+        redactor: Arc::clone(&redactor),
+        api_token: Arc::clone(&api_token),
+        commands: Arc::clone(&commands),
+        approvals,
+        active_tasks: Arc::default(),
+        device_id: device_fp.clone(),
+        orchestrator_url: config.orchestrator_url.clone(),
+    };
+    let rest_state = rest_api::AppState {
+        connection: Arc::clone(&connection),
+        memory: Arc::clone(&memory),
         redactor: Arc::clone(&redactor),
+        file_policy,
+        encryption,
+        maintenance,
+        api_token,
     };
 
     let app = Router::new()
         .route("/ws", get(ws_app_handler))
         .route("/chat", get(ws_chat_handler))
-        .route("/api/health", get(rest_api::health_check))
-        .route("/api/memory/search", get(rest_api::memory_search))
-        .with_state(daemon_state);
+        .with_state(daemon_state)
+        .merge(rest_api::build_router_with_state(rest_state));
 
     let addr = format!("127.0.0.1:{}", config.daemon_port);
     let listener = tokio::net::TcpListener::bind(&addr).await?;
@@ -142,14 +278,8 @@ async fn main() -> anyhow::Result<()> {
         info!("Authentication token loaded");
     }
 
-    let device_fp = DeviceFingerprint::generate()
-        .map(|fp| fp.fingerprint)
-        .unwrap_or_else(|e| {
-            warn!(%e, "Failed to generate device fingerprint, using fallback");
-            "unknown-device".to_string()
-        });
-
-    let cloud_client = CloudWsClient::new();
+    let cloud_client =
+        CloudWsClient::new(Arc::clone(&connection)).with_outbound_queue(outbound_queue);
     let mut state_rx = cloud_client.subscribe_state();
 
     let cloud_config = CloudWsConfig {
@@ -158,10 +288,6 @@ async fn main() -> anyhow::Result<()> {
         device_fingerprint: device_fp,
     };
 
-    // Create channels for cloud WS communication
-    let (cloud_outbound_tx, cloud_outbound_rx) = tokio::sync::mpsc::channel::<String>(256);
-    let (cloud_inbound_tx, mut cloud_inbound_rx) = tokio::sync::mpsc::channel::<String>(256);
-
     let _cloud_handle = cloud_client.spawn(cloud_config, cloud_outbound_rx, cloud_inbound_tx);
     info!("Cloud WebSocket client spawned");
 
@@ -169,36 +295,42 @@ async fn main() -> anyhow::Result<()> {
     let relay_for_state = Arc::clone(&relay);
     let state_watcher = tokio::spawn(async move {
         loop {
-            if state_rx.changed().await.is_err() {
-                break;
-            }
-            let state = *state_rx.borrow();
-            match state {
-                ConnectionState::Connected => {
+            let event = match state_rx.recv().await {
+                Ok(event) => event,
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            };
+            match event.current {
+                ConnectionState::Online if event.previous != ConnectionState::Degraded => {
                     info!("Cloud connected — flushing relay queue");
                     if let Err(e) = relay_for_state.set_connected().await {
                         warn!(%e, "Failed to flush relay queue on connect");
                     }
                 }
-                ConnectionState::Disconnected => {
-                    info!("Cloud disconnected");
+                ConnectionState::Reconnecting | ConnectionState::Offline
+                    if relay_for_state.cloud_state().await == CloudConnectionState::Connected =>
+                {
+                    info!(reason = %event.message, "Cloud disconnected");
                     relay_for_state.set_disconnected("").await;
                 }
                 _ => {
-                    // Connecting / Authenticating — no relay action needed
+                    // Degraded keeps the link; repeated disconnects need no action
                 }
             }
         }
     });
 
-    // 8. Cloud writer task: relay cloud_rx → redact → serialize → send to cloud WS
+    // 8. Cloud writer task: relay cloud_rx → redact structurally → serialize → send to cloud WS
     let redactor_for_writer = Arc::clone(&redactor);
+    let command_outbound_tx = cloud_outbound_tx.clone();
     let cloud_writer = tokio::spawn(async move {
         while let Some(msg) = cloud_rx.recv().await {
-            match serde_json::to_string(&msg) {
-                Ok(json) => {
-                    let redacted = redactor_for_writer.redact(&json);
-                    if let Err(e) = cloud_outbound_tx.send(redacted).await {
+            match serde_json::to_value(&msg) {
+                Ok(value) => {
+                    let redacted = redactor_for_writer
+                        .redact_json_for_session(&value, &msg.payload.session_id);
+                    let frame = OutboundFrame::queued(redacted.to_string(), &msg.id);
+                    if let Err(e) = cloud_outbound_tx.send(frame).await {
                         warn!("Failed to send to cloud: {}", e);
                     }
                 }
@@ -208,29 +340,67 @@ async fn main() -> anyhow::Result<()> {
         info!("Cloud writer task ended (channel closed)");
     });
 
-    // 8b. Cloud reader task: cloud WS → parse → relay to local clients
+    // 8b. Cloud reader task: cloud WS → decode → summaries and commands are
+    //     handled here; chat messages get their tokens restored and go to
+    //     local clients
     let relay_for_reader = Arc::clone(&relay);
+    let redactor_for_reader = Arc::clone(&redactor);
     let cloud_reader = tokio::spawn(async move {
         while let Some(text) = cloud_inbound_rx.recv().await {
-            match serde_json::from_str::<ChatMessage>(&text) {
-                Ok(msg) => {
-                    if let Err(e) = relay_for_reader.send_to_local(msg) {
-                        // NoLocalClients is normal if nobody is connected
-                        debug!("No local clients for inbound message: {}", e);
-                    }
+            let envelope = match Envelope::<CloudMessage>::parse(&text) {
+                Ok(envelope) => envelope,
+                Err(e) => {
+                    warn!("Invalid message from cloud: {}", e);
+                    continue;
+                }
+            };
+            if let Some(summarizer) = &cloud_summarizer {
+                if summarizer.handle_response(&envelope.message) {
+                    continue;
                 }
-                Err(e) => warn!("Invalid ChatMessage from cloud: {}", e),
+            }
+            let envelope = match envelope.message {
+                CloudMessage::CommandRequest(request) => {
+                    run_cloud_command(&commands, request, command_outbound_tx.clone());
+                    continue;
+                }
+                message => Envelope {
+                    message,
+                    ..envelope
+                },
+            };
+            let msg_type = envelope.msg_type().to_string();
+            let Some(mut msg) = envelope.into_chat() else {
+                debug!(%msg_type, "Ignoring non-chat message from cloud");
+                continue;
+            };
+            let session_id = &msg.payload.session_id;
+            msg.payload.content = redactor_for_reader.restore(session_id, &msg.payload.content);
+            msg.payload.metadata = msg
+                .payload
+                .metadata
+                .as_ref()
+                .map(|m| redactor_for_reader.restore_json(session_id, m));
+            if let Err(e) = relay_for_reader.send_to_local(msg) {
+                // NoLocalClients is normal if nobody is connected
+                debug!("No local clients for inbound message: {}", e);
             }
         }
         info!("Cloud reader task ended (channel closed)");
     });
 
-    // 9. Background system profile detection
-    tokio::spawn(async move {
+    // 9. Background system profile detection, persisted as profile memory
+    let memory_for_profile = Arc::clone(&memory);
+    tokio::task::spawn_blocking(move || {
         let facts = profile_detect::detect_system_profile();
         info!(fact_count = facts.len(), "System profile detected");
         for fact in &facts {
             tracing::debug!(key = %fact.key, value = %fact.value, source = %fact.source, "profile fact");
+            if let Err(e) =
+                memory_for_profile.store_profile(&fact.source, &fact.key, &fact.value, "detected")
+            {
+                warn!(%e, key = %fact.key, "Failed to store profile fact");
+            }
         }
     });
 
@@ -249,6 +419,28 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+// ---------------------------------------------------------------------------
+// Cloud commands
+// ---------------------------------------------------------------------------
+
+/// Run a cloud `command.request` and stream its responses back to the cloud.
+fn run_cloud_command(
+    commands: &Arc<CommandRelay>,
+    request: CommandRequest,
+    cloud_tx: tokio::sync::mpsc::Sender<OutboundFrame>,
+) {
+    let commands = Arc::clone(commands);
+    tokio::spawn(async move {
+        let mut responses = commands.execute(request).await;
+        while let Some(response) = responses.recv().await {
+            let msg = Envelope::new(CloudMessage::from(response));
+            if cloud_tx.send(msg.to_json().into()).await.is_err() {
+                break;
+            }
+        }
+    });
+}
+
 // ---------------------------------------------------------------------------
 // /chat WebSocket handler
 // ---------------------------------------------------------------------------
@@ -258,41 +450,101 @@ async fn ws_app_handler(
     ws: WebSocketUpgrade,
     State(state): State<DaemonState>,
 ) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| ws_app::handle_app_ws(socket, state.relay, state.redactor))
+    let ctx = ws_app::AppContext {
+        relay: state.relay,
+        redactor: state.redactor,
+        connection: state.connection,
+        commands: state.commands,
+        approvals: state.approvals,
+        active_tasks: state.active_tasks,
+        device_id: state.device_id,
+        orchestrator_url: state.orchestrator_url,
+    };
+    ws.on_upgrade(move |socket| ws_app::handle_app_ws(socket, ctx))
+}
+
+/// Query parameters of the /chat endpoint.
+#[derive(Debug, Default, serde::Deserialize)]
+struct ChatWsParams {
+    /// Receive every session's messages instead of only the client's own.
+    #[serde(default)]
+    observe: bool,
+    /// The API token, for clients that cannot set an `Authorization` header.
+    token: Option<String>,
 }
 
 /// Axum handler that upgrades HTTP to WebSocket for the /chat endpoint.
+///
+/// `/chat?observe=true` connects a read-only observer (e.g. a dashboard).
+/// Observers see every session's messages with their secrets restored, so
+/// they need the API token, as a bearer header or `token` parameter.
 async fn ws_chat_handler(
     ws: WebSocketUpgrade,
+    authorized: Option<rest_api::Authorized>,
+    Query(params): Query<ChatWsParams>,
     State(state): State<DaemonState>,
 ) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| handle_chat_ws(socket, state))
+    if params.observe {
+        let token_param = params
+            .token
+            .as_deref()
+            .is_some_and(|token| state.api_token.verify(token));
+        if authorized.is_none() && !token_param {
+            return (StatusCode::UNAUTHORIZED, "observing requires the API token").into_response();
+        }
+        ws.on_upgrade(move |socket| handle_chat_observer_ws(socket, state))
+            .into_response()
+    } else {
+        ws.on_upgrade(move |socket| handle_chat_ws(socket, state))
+            .into_response()
+    }
 }
 
 /// Bidirectional bridge between a local WebSocket client and the ChatRelay.
 ///
-/// - Broadcasts from cloud (via relay) are forwarded to the WS client.
-/// - Messages from the WS client are redacted and sent to the cloud (via relay).
+/// - Cloud responses for the sessions the client has sent messages in are
+///   forwarded to the WS client.
+/// - Messages from the WS client are redacted for their session and sent to
+///   the cloud (via relay). A session another client opened is refused.
 async fn handle_chat_ws(ws: WebSocket, state: DaemonState) {
     let (mut ws_tx, mut ws_rx) = ws.split();
-    let mut broadcast_rx = state.relay.subscribe_local();
+    let (subscriber, mut session_rx) = state.relay.subscribe_sessions();
+    let (refusal_tx, mut refusal_rx) = tokio::sync::mpsc::channel::<ChatMessage>(16);
 
-    // Task 1: broadcast (cloud responses) -> client WS
+    // Task 1: session messages (cloud responses) and refusals -> client WS
     let tx_task = tokio::spawn(async move {
-        while let Ok(msg) = broadcast_rx.recv().await {
+        loop {
+            let msg = tokio::select! {
+                Some(msg) = session_rx.recv() => msg,
+                Some(msg) = refusal_rx.recv() => msg,
+                else => break,
+            };
             if let Ok(json) = serde_json::to_string(&msg) {
-                if ws_tx.send(AxumWsMessage::Text(json.into())).await.is_err() {
+                if ws_tx.send(AxumWsMessage::Text(json)).await.is_err() {
                     break;
                 }
             }
         }
     });
 
-    // Task 2: client WS -> relay (redact content, send to cloud)
+    // Task 2: client WS -> relay (join session, redact content, send to cloud)
     while let Some(Ok(msg)) = ws_rx.next().await {
         if let AxumWsMessage::Text(text) = msg {
             if let Ok(mut chat_msg) = serde_json::from_str::<ChatMessage>(&text) {
-                chat_msg.payload.content = state.redactor.redact(&chat_msg.payload.content);
+                let session_id = chat_msg.payload.session_id.clone();
+                if !subscriber.join(&session_id) {
+                    warn!(%session_id, "Chat client named another client's session");
+                    let _ = refusal_tx
+                        .send(ChatMessage::error(
+                            session_id,
+                            "session belongs to another client".to_string(),
+                        ))
+                        .await;
+                    continue;
+                }
+                chat_msg.payload.content = state
+                    .redactor
+                    .redact_for_session(&chat_msg.payload.content, &chat_msg.payload.session_id);
                 let _ = state.relay.send_to_cloud(chat_msg).await;
             }
         }
@@ -300,3 +552,36 @@ async fn handle_chat_ws(ws: WebSocket, state: DaemonState) {
 
     tx_task.abort();
 }
+
+/// Read-only bridge that forwards every cloud message to an observer.
+async fn handle_chat_observer_ws(ws: WebSocket, state: DaemonState) {
+    let (mut ws_tx, mut ws_rx) = ws.split();
+    let mut observer_rx = state.relay.subscribe_observer();
+
+    let tx_task = tokio::spawn(async move {
+        loop {
+            match observer_rx.recv().await {
+                Ok(msg) => {
+                    if let Ok(json) = serde_json::to_string(&msg) {
+                        if ws_tx.send(AxumWsMessage::Text(json)).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                    debug!(skipped, "Chat observer lagging");
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+
+    // Observers cannot send; just wait for the client to go away
+    while let Some(Ok(msg)) = ws_rx.next().await {
+        if let AxumWsMessage::Close(_) = msg {
+            break;
+        }
+    }
+
+    tx_task.abort();
+}
@@ -0,0 +1,461 @@
+//! Scheduled background maintenance of the memory database.
+//!
+//! Every `interval_minutes` the [`MaintenanceScheduler`] compresses old task
+//! and agent memories, archives expired session events, optimizes the FTS
+//! indexes, checkpoints the WAL and prunes old pre-migration backups.  A
+//! `VACUUM` runs at its own, longer interval.  Each run is recorded in
+//! `maintenance_runs`, so the schedule survives restarts and the history is
+//! available to the status endpoint.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use d1_common::MaintenanceConfig;
+use rusqlite::{params, Row};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::local_db::LocalDb;
+use crate::memory_store::MemoryStore;
+
+/// Delay before the first run after startup, so maintenance does not compete
+/// with daemon initialisation.
+const STARTUP_DELAY_SECS: i64 = 60;
+
+/// Runs kept in `maintenance_runs`; older ones are dropped.
+const MAX_RECORDED_RUNS: i64 = 100;
+
+/// Runs returned by [`MaintenanceScheduler::status`].
+const STATUS_RUNS: usize = 10;
+
+/// Statistics of one maintenance run.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MaintenanceRun {
+    pub id: String,
+    pub started_at: String,
+    pub finished_at: String,
+    /// Task and agent memories compressed
+    pub compressed: u64,
+    /// Session events archived
+    pub archived_sessions: u64,
+    pub fts_optimized: bool,
+    pub wal_checkpointed: bool,
+    pub vacuumed: bool,
+    /// Pre-migration backups deleted
+    pub backups_pruned: u64,
+    /// Failed steps, `"; "`-separated; the other steps still ran
+    pub error: Option<String>,
+}
+
+impl MaintenanceRun {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            started_at: row.get(1)?,
+            finished_at: row.get(2)?,
+            compressed: row.get(3)?,
+            archived_sessions: row.get(4)?,
+            fts_optimized: row.get(5)?,
+            wal_checkpointed: row.get(6)?,
+            vacuumed: row.get(7)?,
+            backups_pruned: row.get(8)?,
+            error: row.get(9)?,
+        })
+    }
+}
+
+/// Scheduler state reported by the status endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintenanceStatus {
+    pub enabled: bool,
+    pub interval_minutes: u64,
+    pub running: bool,
+    /// When the scheduler next wakes up; `None` until it has started
+    pub next_run_at: Option<String>,
+    /// Most recent runs, newest first
+    pub recent_runs: Vec<MaintenanceRun>,
+}
+
+/// Periodically runs memory retention and database upkeep.
+pub struct MaintenanceScheduler {
+    store: Arc<MemoryStore>,
+    db: Arc<LocalDb>,
+    config: MaintenanceConfig,
+    created_at: DateTime<Utc>,
+    running: AtomicBool,
+    next_run_at: Mutex<Option<DateTime<Utc>>>,
+}
+
+/// Returned by [`MaintenanceScheduler::run_now`] when another run holds the
+/// running flag.
+#[derive(Debug, thiserror::Error)]
+#[error("maintenance is already running")]
+pub struct AlreadyRunning;
+
+/// Clears the running flag when a run ends, even by panic.
+struct RunningGuard<'a>(&'a AtomicBool);
+
+impl Drop for RunningGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+impl MaintenanceScheduler {
+    pub fn new(store: Arc<MemoryStore>, db: Arc<LocalDb>, config: MaintenanceConfig) -> Self {
+        Self {
+            store,
+            db,
+            config,
+            created_at: Utc::now(),
+            running: AtomicBool::new(false),
+            next_run_at: Mutex::new(None),
+        }
+    }
+
+    /// Starts the background loop.  Returns `None` when maintenance is
+    /// disabled.
+    pub fn spawn(self: Arc<Self>) -> Option<JoinHandle<()>> {
+        if !self.config.enabled {
+            info!("Memory maintenance disabled");
+            return None;
+        }
+        Some(tokio::spawn(async move {
+            loop {
+                let due = match self.next_due() {
+                    Ok(due) => due,
+                    Err(e) => {
+                        warn!(%e, "Could not read maintenance history");
+                        Utc::now() + self.interval()
+                    }
+                };
+                *self.next_run_at.lock().unwrap_or_else(|e| e.into_inner()) = Some(due);
+                tokio::time::sleep((due - Utc::now()).to_std().unwrap_or_default()).await;
+
+                let scheduler = Arc::clone(&self);
+                match tokio::task::spawn_blocking(move || scheduler.run_now()).await {
+                    Ok(Ok(run)) => info!(
+                        compressed = run.compressed,
+                        archived_sessions = run.archived_sessions,
+                        vacuumed = run.vacuumed,
+                        backups_pruned = run.backups_pruned,
+                        error = run.error.as_deref(),
+                        "Memory maintenance finished"
+                    ),
+                    Ok(Err(e)) => warn!(%e, "Memory maintenance skipped"),
+                    Err(e) => error!(%e, "Memory maintenance task failed"),
+                }
+            }
+        }))
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::minutes(self.config.interval_minutes.max(1) as i64)
+    }
+
+    /// One interval after the last recorded run, but not before the startup
+    /// delay has passed.
+    fn next_due(&self) -> Result<DateTime<Utc>> {
+        let earliest = self.created_at + Duration::seconds(STARTUP_DELAY_SECS);
+        let last: Option<String> = self
+            .db
+            .conn()
+            .query_row("SELECT MAX(started_at) FROM maintenance_runs", [], |row| {
+                row.get(0)
+            })
+            .context("reading last maintenance run")?;
+        Ok(match last.as_deref().and_then(parse_timestamp) {
+            Some(last) => (last + self.interval()).max(earliest),
+            None => earliest,
+        })
+    }
+
+    /// Whether the last VACUUM is older than `vacuum_interval_hours`.
+    fn vacuum_due(&self) -> Result<bool> {
+        if self.config.vacuum_interval_hours == 0 {
+            return Ok(false);
+        }
+        let last: Option<String> = self
+            .db
+            .conn()
+            .query_row(
+                "SELECT MAX(finished_at) FROM maintenance_runs WHERE vacuumed = 1",
+                [],
+                |row| row.get(0),
+            )
+            .context("reading last vacuum")?;
+        let interval = Duration::hours(self.config.vacuum_interval_hours as i64);
+        Ok(last
+            .as_deref()
+            .and_then(parse_timestamp)
+            .is_none_or(|last| Utc::now() - last >= interval))
+    }
+
+    /// Runs every maintenance step now and records the result.  A failing
+    /// step is noted in [`MaintenanceRun::error`] without stopping the
+    /// others.  Fails with [`AlreadyRunning`] if a run is already in
+    /// progress, or if the result cannot be recorded.
+    pub fn run_now(&self) -> Result<MaintenanceRun> {
+        if self.running.swap(true, Ordering::SeqCst) {
+            return Err(AlreadyRunning.into());
+        }
+        let _guard = RunningGuard(&self.running);
+
+        let mut run = MaintenanceRun {
+            id: Uuid::new_v4().to_string(),
+            started_at: timestamp(Utc::now()),
+            ..Default::default()
+        };
+        let mut errors = Vec::new();
+        let mut step = |name: &str, result: Result<u64>| match result {
+            Ok(count) => Some(count),
+            Err(e) => {
+                warn!(step = name, "Maintenance step failed: {e:#}");
+                errors.push(format!("{name}: {e:#}"));
+                None
+            }
+        };
+
+        run.compressed = step(
+            "compression",
+            self.store.run_compression(self.config.compress_after_days),
+        )
+        .unwrap_or(0);
+        run.archived_sessions = step(
+            "session archival",
+            self.store
+                .archive_old_sessions(self.config.archive_sessions_after_hours),
+        )
+        .unwrap_or(0);
+        run.fts_optimized = step("fts optimize", self.db.optimize_fts().map(|_| 0)).is_some();
+        if step("vacuum check", self.vacuum_due().map(u64::from)) == Some(1) {
+            run.vacuumed = step("vacuum", self.db.vacuum().map(|_| 0)).is_some();
+        }
+        run.wal_checkpointed =
+            step("wal checkpoint", self.db.checkpoint_wal().map(|_| 0)).is_some();
+        run.backups_pruned = step(
+            "backup pruning",
+            self.db
+                .prune_backups(self.config.keep_backups)
+                .map(|n| n as u64),
+        )
+        .unwrap_or(0);
+
+        run.finished_at = timestamp(Utc::now());
+        run.error = (!errors.is_empty()).then(|| errors.join("; "));
+        self.record(&run)?;
+        Ok(run)
+    }
+
+    fn record(&self, run: &MaintenanceRun) -> Result<()> {
+        let conn = self.db.conn();
+        conn.execute(
+            "INSERT INTO maintenance_runs
+                 (id, started_at, finished_at, compressed, archived_sessions,
+                  fts_optimized, wal_checkpointed, vacuumed, backups_pruned, error)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                run.id,
+                run.started_at,
+                run.finished_at,
+                run.compressed,
+                run.archived_sessions,
+                run.fts_optimized,
+                run.wal_checkpointed,
+                run.vacuumed,
+                run.backups_pruned,
+                run.error,
+            ],
+        )
+        .context("recording maintenance run")?;
+        conn.execute(
+            "DELETE FROM maintenance_runs WHERE id NOT IN (
+                 SELECT id FROM maintenance_runs ORDER BY started_at DESC, rowid DESC LIMIT ?1)",
+            params![MAX_RECORDED_RUNS],
+        )
+        .context("trimming maintenance history")?;
+        Ok(())
+    }
+
+    /// The `limit` most recent runs, newest first.
+    pub fn recent_runs(&self, limit: usize) -> Result<Vec<MaintenanceRun>> {
+        let conn = self.db.conn();
+        let mut stmt = conn.prepare(
+            "SELECT id, started_at, finished_at, compressed, archived_sessions,
+                    fts_optimized, wal_checkpointed, vacuumed, backups_pruned, error
+             FROM maintenance_runs ORDER BY started_at DESC, rowid DESC LIMIT ?1",
+        )?;
+        let runs = stmt
+            .query_map(params![limit as i64], MaintenanceRun::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("reading maintenance runs")?;
+        Ok(runs)
+    }
+
+    pub fn status(&self) -> Result<MaintenanceStatus> {
+        let next_run_at = *self.next_run_at.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(MaintenanceStatus {
+            enabled: self.config.enabled,
+            interval_minutes: self.config.interval_minutes,
+            running: self.running.load(Ordering::SeqCst),
+            next_run_at: next_run_at.map(timestamp),
+            recent_runs: self.recent_runs(STATUS_RUNS)?,
+        })
+    }
+}
+
+fn timestamp(t: DateTime<Utc>) -> String {
+    t.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+fn parse_timestamp(s: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(s)
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scheduler_with(config: MaintenanceConfig) -> (MaintenanceScheduler, Arc<LocalDb>) {
+        let db = Arc::new(LocalDb::open_in_memory().expect("in-memory db"));
+        let store = Arc::new(MemoryStore::new(Arc::clone(&db)));
+        (
+            MaintenanceScheduler::new(store, Arc::clone(&db), config),
+            db,
+        )
+    }
+
+    fn count(db: &LocalDb, sql: &str) -> i64 {
+        db.conn().query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn test_run_compresses_archives_and_records() {
+        let (scheduler, db) = scheduler_with(MaintenanceConfig::default());
+        let long = "The user keeps their dotfiles in a git repository. ".repeat(4);
+        db.conn()
+            .execute(
+                "INSERT INTO agent_memory (id, agent_name, memory_type, content, created_at, updated_at)
+                 VALUES ('am-old', 'bob', 'fact', ?1,
+                         strftime('%Y-%m-%dT%H:%M:%SZ', 'now', '-100 days'),
+                         strftime('%Y-%m-%dT%H:%M:%SZ', 'now', '-100 days'))",
+                [&long],
+            )
+            .unwrap();
+        db.conn()
+            .execute(
+                "INSERT INTO session_memory (id, session_id, step_number, agent_name, event_type, content, created_at)
+                 VALUES ('s-old', 'sess', 0, 'bob', 'action', 'ran brew upgrade',
+                         strftime('%Y-%m-%dT%H:%M:%SZ', 'now', '-30 days'))",
+                [],
+            )
+            .unwrap();
+
+        let run = scheduler.run_now().unwrap();
+        assert_eq!(run.compressed, 1);
+        assert_eq!(run.archived_sessions, 1);
+        assert!(run.fts_optimized && run.wal_checkpointed && run.vacuumed);
+        assert_eq!(run.backups_pruned, 0);
+        assert!(run.error.is_none(), "{:?}", run.error);
+
+        let recorded = scheduler.recent_runs(5).unwrap();
+        assert_eq!(recorded.len(), 1);
+        assert_eq!(recorded[0].id, run.id);
+        assert_eq!(recorded[0].compressed, 1);
+        assert_eq!(
+            count(
+                &db,
+                "SELECT COUNT(*) FROM session_memory WHERE event_type != 'archive_summary'"
+            ),
+            0
+        );
+    }
+
+    #[test]
+    fn test_vacuum_runs_at_its_own_interval() {
+        let (scheduler, _db) = scheduler_with(MaintenanceConfig::default());
+        assert!(scheduler.run_now().unwrap().vacuumed);
+        let second = scheduler.run_now().unwrap();
+        assert!(!second.vacuumed, "vacuumed again within a week");
+        assert!(second.fts_optimized);
+
+        let (never, _db) = scheduler_with(MaintenanceConfig {
+            vacuum_interval_hours: 0,
+            ..Default::default()
+        });
+        assert!(!never.run_now().unwrap().vacuumed);
+    }
+
+    #[test]
+    fn test_failed_step_is_recorded_and_others_still_run() {
+        let (scheduler, db) = scheduler_with(MaintenanceConfig::default());
+        db.conn()
+            .execute_batch("DROP TABLE agent_memory_archive")
+            .unwrap();
+
+        let run = scheduler.run_now().unwrap();
+        let error = run.error.unwrap();
+        assert!(error.starts_with("compression:"), "{error}");
+        assert!(run.fts_optimized && run.wal_checkpointed);
+        assert_eq!(
+            scheduler.recent_runs(1).unwrap()[0].error.as_deref(),
+            Some(error.as_str())
+        );
+    }
+
+    #[test]
+    fn test_concurrent_run_is_refused() {
+        let (scheduler, _db) = scheduler_with(MaintenanceConfig::default());
+        scheduler.running.store(true, Ordering::SeqCst);
+        assert!(scheduler.run_now().unwrap_err().is::<AlreadyRunning>());
+        scheduler.running.store(false, Ordering::SeqCst);
+        assert!(scheduler.run_now().is_ok());
+        assert!(!scheduler.status().unwrap().running);
+    }
+
+    #[test]
+    fn test_next_due_follows_last_run() {
+        let (scheduler, db) = scheduler_with(MaintenanceConfig {
+            interval_minutes: 60,
+            ..Default::default()
+        });
+        let startup = scheduler.created_at + Duration::seconds(STARTUP_DELAY_SECS);
+        assert_eq!(scheduler.next_due().unwrap(), startup);
+
+        // A recent run pushes the next one out by an interval.
+        scheduler.run_now().unwrap();
+        let due = scheduler.next_due().unwrap();
+        assert!(due > Utc::now() + Duration::minutes(58), "{due}");
+
+        // Long-overdue maintenance runs right after the startup delay.
+        db.conn()
+            .execute(
+                "UPDATE maintenance_runs SET started_at = '2020-01-01T00:00:00Z'",
+                [],
+            )
+            .unwrap();
+        assert_eq!(scheduler.next_due().unwrap(), startup);
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let (scheduler, db) = scheduler_with(MaintenanceConfig {
+            vacuum_interval_hours: 0,
+            ..Default::default()
+        });
+        for _ in 0..MAX_RECORDED_RUNS + 5 {
+            scheduler.run_now().unwrap();
+        }
+        assert_eq!(
+            count(&db, "SELECT COUNT(*) FROM maintenance_runs"),
+            MAX_RECORDED_RUNS
+        );
+        assert_eq!(scheduler.status().unwrap().recent_runs.len(), STATUS_RUNS);
+    }
+}
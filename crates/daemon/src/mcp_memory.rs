@@ -8,7 +8,10 @@ use std::sync::Arc;
 use anyhow::{bail, Context, Result};
 use serde_json::{json, Value};
 
-use crate::memory_store::{MemoryScope, MemoryStore};
+use crate::memory_store::{MemoryScope, MemoryStore, MemoryTable};
+
+/// Audit-log actor recorded for confidence changes made through MCP tools.
+const MCP_ACTOR: &str = "mcp";
 
 // ---------------------------------------------------------------------------
 // ToolDefinition
@@ -86,7 +89,7 @@ impl MemoryServer {
             // -- recall_similar_tasks ---------------------------------------
             ToolDefinition {
                 name: "recall_similar_tasks".into(),
-                description: "FTS5-ranked search over task memory.".into(),
+                description: "Hybrid (FTS5 + semantic) search over task memory.".into(),
                 input_schema: json!({
                     "type": "object",
                     "properties": {
@@ -106,7 +109,8 @@ impl MemoryServer {
             // -- recall_error_patterns --------------------------------------
             ToolDefinition {
                 name: "recall_error_patterns".into(),
-                description: "FTS5 search scoped to error_patterns and fix_patterns columns."
+                description: "Hybrid search whose keyword match is scoped to the error_patterns \
+                              and fix_patterns columns."
                     .into(),
                 input_schema: json!({
                     "type": "object",
@@ -230,6 +234,11 @@ impl MemoryServer {
                         "session_id": {
                             "type": "string",
                             "description": "Session that produced this outcome."
+                        },
+                        "used_memory_ids": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "Agent memory ids that informed the task; reinforced on success, penalised on failure."
                         }
                     },
                     "required": [
@@ -266,6 +275,31 @@ impl MemoryServer {
                     "required": ["agent_name", "memory_type", "content", "confidence"]
                 }),
             },
+            // -- record_memory_outcome --------------------------------------
+            ToolDefinition {
+                name: "record_memory_outcome".into(),
+                description: "Report whether a recalled memory turned out to be right, adjusting its confidence."
+                    .into(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "table": {
+                            "type": "string",
+                            "enum": ["profile", "agent"],
+                            "description": "Memory level the record belongs to."
+                        },
+                        "id": {
+                            "type": "string",
+                            "description": "Record id."
+                        },
+                        "success": {
+                            "type": "boolean",
+                            "description": "Whether acting on the memory worked."
+                        }
+                    },
+                    "required": ["table", "id", "success"]
+                }),
+            },
             // -- forget -----------------------------------------------------
             ToolDefinition {
                 name: "forget".into(),
@@ -304,6 +338,7 @@ impl MemoryServer {
             "store_session" => self.handle_store_session(params),
             "store_task_outcome" => self.handle_store_task_outcome(params),
             "store_agent_learning" => self.handle_store_agent_learning(params),
+            "record_memory_outcome" => self.handle_record_memory_outcome(params),
             "forget" => self.handle_forget(params),
             _ => bail!("unknown tool: {tool}"),
         }
@@ -313,7 +348,7 @@ impl MemoryServer {
 
     fn handle_recall(&self, params: Value) -> Result<Value> {
         let query = param_str(&params, "query")?;
-        let scope = parse_scope(param_str(&params, "scope")?)?;
+        let scope = MemoryScope::parse(param_str(&params, "scope")?)?;
         let limit = param_i32_or(&params, "limit", 10);
 
         let entries = self.store.recall(query, scope, limit)?;
@@ -327,6 +362,8 @@ impl MemoryServer {
                     "metadata": e.metadata,
                     "confidence": e.confidence,
                     "created_at": e.created_at,
+                    "score": e.score,
+                    "snippet": e.snippet,
                 })
             })
             .collect();
@@ -418,7 +455,20 @@ impl MemoryServer {
             system_context,
             session_id,
         )?;
-        Ok(json!({ "id": id }))
+
+        let used_memory_ids: Vec<String> = params
+            .get("used_memory_ids")
+            .and_then(|v| v.as_array())
+            .map(|ids| {
+                ids.iter()
+                    .filter_map(|v| v.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let adjusted = self
+            .store
+            .apply_task_feedback(outcome, &used_memory_ids, MCP_ACTOR)?;
+        Ok(json!({ "id": id, "adjusted": adjusted }))
     }
 
     fn handle_store_agent_learning(&self, params: Value) -> Result<Value> {
@@ -436,6 +486,21 @@ impl MemoryServer {
         Ok(json!({ "id": id }))
     }
 
+    fn handle_record_memory_outcome(&self, params: Value) -> Result<Value> {
+        let table = MemoryTable::parse(param_str(&params, "table")?)?;
+        let id = param_str(&params, "id")?;
+        let success = params
+            .get("success")
+            .and_then(|v| v.as_bool())
+            .context("missing or invalid param: success")?;
+
+        let confidence = self
+            .store
+            .record_memory_outcome(table, id, success, MCP_ACTOR)?
+            .with_context(|| format!("no {} record with id {id}", table.table_name()))?;
+        Ok(json!({ "id": id, "confidence": confidence }))
+    }
+
     fn handle_forget(&self, params: Value) -> Result<Value> {
         let id = param_str(&params, "id")?;
         let table = param_str(&params, "table")?;
@@ -482,18 +547,6 @@ fn param_i64(params: &Value, key: &str) -> Result<i64> {
         .with_context(|| format!("missing or invalid param: {key}"))
 }
 
-/// Parse a scope string into a [`MemoryScope`].
-fn parse_scope(s: &str) -> Result<MemoryScope> {
-    match s {
-        "profile" => Ok(MemoryScope::Profile),
-        "session" => Ok(MemoryScope::Session),
-        "task" => Ok(MemoryScope::Task),
-        "agent" => Ok(MemoryScope::Agent),
-        "all" => Ok(MemoryScope::All),
-        other => bail!("invalid scope: {other}"),
-    }
-}
-
 /// Convert a `Vec<TaskEntry>` into a JSON array.
 fn task_entries_to_json(entries: Vec<crate::memory_store::TaskEntry>) -> Vec<Value> {
     entries
@@ -537,7 +590,7 @@ mod tests {
     fn test_tool_definitions_count_and_names() {
         let server = test_server();
         let defs = server.tool_definitions();
-        assert_eq!(defs.len(), 9, "expected 9 tool definitions");
+        assert_eq!(defs.len(), 10, "expected 10 tool definitions");
 
         let names: Vec<&str> = defs.iter().map(|d| d.name.as_str()).collect();
         assert!(names.contains(&"recall"));
@@ -548,6 +601,7 @@ mod tests {
         assert!(names.contains(&"store_session"));
         assert!(names.contains(&"store_task_outcome"));
         assert!(names.contains(&"store_agent_learning"));
+        assert!(names.contains(&"record_memory_outcome"));
         assert!(names.contains(&"forget"));
     }
 
@@ -770,6 +824,94 @@ mod tests {
         assert!(entries[0]["content"].as_str().unwrap().contains("verbose"));
     }
 
+    // -- dispatch: record_memory_outcome + task feedback --------------------
+
+    #[test]
+    fn test_dispatch_record_memory_outcome() {
+        let server = test_server();
+        let id = server
+            .handle_tool_call(
+                "store_agent_learning",
+                json!({
+                    "agent_name": "dr_bob",
+                    "memory_type": "fact",
+                    "content": "Port 8080 is taken by the proxy",
+                    "confidence": 0.5
+                }),
+            )
+            .unwrap()["id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        let result = server
+            .handle_tool_call(
+                "record_memory_outcome",
+                json!({ "table": "agent", "id": id, "success": true }),
+            )
+            .unwrap();
+        assert!(result["confidence"].as_f64().unwrap() > 0.5);
+
+        let missing = server.handle_tool_call(
+            "record_memory_outcome",
+            json!({ "table": "agent", "id": "nope", "success": true }),
+        );
+        assert!(missing.is_err());
+
+        let wrong_table = server.handle_tool_call(
+            "record_memory_outcome",
+            json!({ "table": "session", "id": id, "success": true }),
+        );
+        assert!(wrong_table.is_err());
+    }
+
+    #[test]
+    fn test_dispatch_store_task_outcome_adjusts_used_memories() {
+        let server = test_server();
+        let id = server
+            .handle_tool_call(
+                "store_agent_learning",
+                json!({
+                    "agent_name": "dr_bob",
+                    "memory_type": "fix",
+                    "content": "Clearing the npm cache fixes EINTEGRITY",
+                    "confidence": 0.6
+                }),
+            )
+            .unwrap()["id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        let result = server
+            .handle_tool_call(
+                "store_task_outcome",
+                json!({
+                    "task_description": "Fix npm install",
+                    "category": "dependency",
+                    "outcome": "failure",
+                    "procedure_steps": "[\"npm cache clean --force\"]",
+                    "error_patterns": "[\"EINTEGRITY\"]",
+                    "fix_patterns": "[]",
+                    "duration_seconds": 10,
+                    "system_context": "{}",
+                    "session_id": "s1",
+                    "used_memory_ids": [id]
+                }),
+            )
+            .unwrap();
+        assert_eq!(result["adjusted"], 1);
+
+        let recall = server
+            .handle_tool_call(
+                "recall",
+                json!({ "query": "EINTEGRITY", "scope": "agent", "limit": 1 }),
+            )
+            .unwrap();
+        let confidence = recall["entries"][0]["confidence"].as_f64().unwrap();
+        assert!(confidence < 0.6, "failure should penalise: {confidence}");
+    }
+
     // -- dispatch: forget ---------------------------------------------------
 
     #[test]
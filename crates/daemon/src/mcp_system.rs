@@ -1,15 +1,23 @@
 //! MCP system server — wraps [`SystemOps`] into a tool-dispatch interface.
 //!
-//! Exposes 10 tools (package_search, package_install, package_remove,
-//! service_status, service_control, config_read, config_set, env_get,
-//! env_set, network_check) with JSON Schema definitions and a single
-//! `handle_tool_call` dispatcher.
+//! Exposes 21 tools (package_search, package_install, package_remove,
+//! service_status, service_control, service_logs, service_create_user_unit,
+//! config_read, config_set, env_get, env_set, env_persist, env_persist_list,
+//! network_check, http_probe, tls_inspect, traceroute, proxy_detect,
+//! dns_compare, disk_usage, disk_cleanup_plan) with JSON Schema definitions
+//! and a single `handle_tool_call` dispatcher.
 
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use tracing::warn;
 
-use crate::system_ops::SystemOps;
+use crate::config_formats::EditOp;
+use crate::disk_usage::DiskUsageOptions;
+use crate::filesystem::FilesystemOps;
+use crate::net_diag::{HttpProbeOptions, RecordType, DEFAULT_RESOLVERS};
+use crate::shell_env::{EnvEdit, PathPosition, ShellTarget};
+use crate::system_ops::{ServiceScope, SystemOps, UserUnitSpec, MAX_LOG_LINES};
 
 // ---------------------------------------------------------------------------
 // Types
@@ -29,11 +37,33 @@ pub struct SystemServer {
     ops: SystemOps,
 }
 
+impl Default for SystemServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SystemServer {
+    /// Create a server whose config edits are backed up to
+    /// `~/.d1doctor/backups/`.  If that location cannot be set up, config
+    /// and rc file writes are refused rather than made without a backup.
     pub fn new() -> Self {
-        SystemServer {
-            ops: SystemOps::new(),
-        }
+        let ops = match FilesystemOps::with_defaults() {
+            Ok(fs) => SystemOps::with_backups(fs),
+            Err(e) => {
+                warn!(
+                    "Config backups unavailable, config writes disabled: {:#}",
+                    e
+                );
+                SystemOps::backups_unavailable(format!("{e:#}"))
+            }
+        };
+        SystemServer { ops }
+    }
+
+    /// Create a server around an existing [`SystemOps`].
+    pub fn with_ops(ops: SystemOps) -> Self {
+        SystemServer { ops }
     }
 
     /// Return the list of all tool definitions with JSON Schema descriptions.
@@ -83,13 +113,18 @@ impl SystemServer {
             },
             ToolDefinition {
                 name: "service_status".into(),
-                description: "Query the status of a system service (launchctl on macOS, systemctl on Linux)".into(),
+                description: "Query the status of a service (launchctl on macOS; systemctl, OpenRC or runit on Linux). Use scope 'user' for systemd user units or launchd agents".into(),
                 input_schema: json!({
                     "type": "object",
                     "properties": {
                         "name": {
                             "type": "string",
                             "description": "Service name or label"
+                        },
+                        "scope": {
+                            "type": "string",
+                            "enum": ["system", "user"],
+                            "description": "Service manager scope (default: system)"
                         }
                     },
                     "required": ["name"]
@@ -97,7 +132,7 @@ impl SystemServer {
             },
             ToolDefinition {
                 name: "service_control".into(),
-                description: "Control a system service: start, stop, or restart".into(),
+                description: "Control a service: start, stop, restart, enable, or disable".into(),
                 input_schema: json!({
                     "type": "object",
                     "properties": {
@@ -107,16 +142,94 @@ impl SystemServer {
                         },
                         "action": {
                             "type": "string",
-                            "enum": ["start", "stop", "restart"],
+                            "enum": ["start", "stop", "restart", "enable", "disable"],
                             "description": "Action to perform"
+                        },
+                        "scope": {
+                            "type": "string",
+                            "enum": ["system", "user"],
+                            "description": "Service manager scope (default: system)"
                         }
                     },
                     "required": ["name", "action"]
                 }),
             },
+            ToolDefinition {
+                name: "service_logs".into(),
+                description: "Fetch recent log lines for a service (journalctl on systemd, log show on macOS, log files on OpenRC/runit)".into(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "name": {
+                            "type": "string",
+                            "description": "Service name or label"
+                        },
+                        "scope": {
+                            "type": "string",
+                            "enum": ["system", "user"],
+                            "description": "Service manager scope (default: system)"
+                        },
+                        "since": {
+                            "type": "string",
+                            "description": "Start of the time range (e.g. '1 hour ago', '2024-01-01 10:00')"
+                        },
+                        "until": {
+                            "type": "string",
+                            "description": "End of the time range"
+                        },
+                        "lines": {
+                            "type": "integer",
+                            "minimum": 1,
+                            "maximum": MAX_LOG_LINES,
+                            "description": "Maximum number of lines to return (default: 100)"
+                        }
+                    },
+                    "required": ["name"]
+                }),
+            },
+            ToolDefinition {
+                name: "service_create_user_unit".into(),
+                description: "Create a systemd user unit (~/.config/systemd/user/<name>.service) from a template and optionally enable it".into(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "name": {
+                            "type": "string",
+                            "description": "Unit name without the .service suffix"
+                        },
+                        "description": {
+                            "type": "string",
+                            "description": "Human-readable unit description"
+                        },
+                        "exec_start": {
+                            "type": "string",
+                            "description": "Command line to run (absolute path to the binary)"
+                        },
+                        "working_directory": {
+                            "type": "string",
+                            "description": "Optional working directory"
+                        },
+                        "environment": {
+                            "type": "object",
+                            "additionalProperties": { "type": "string" },
+                            "description": "Optional environment variables"
+                        },
+                        "restart": {
+                            "type": "string",
+                            "enum": ["no", "on-success", "on-failure", "on-abnormal", "on-watchdog", "on-abort", "always"],
+                            "description": "Restart policy (default: on-failure)"
+                        },
+                        "enable": {
+                            "type": "boolean",
+                            "description": "Enable and start the unit after creating it (default: false)"
+                        }
+                    },
+                    "required": ["name", "description", "exec_start"]
+                }),
+            },
             ToolDefinition {
                 name: "config_read".into(),
-                description: "Read and parse a configuration file (TOML or JSON) into a JSON value".into(),
+                description: "Read and parse a configuration file (TOML, JSON, YAML, INI/gitconfig, dotenv or ssh_config) into a JSON value".into(),
                 input_schema: json!({
                     "type": "object",
                     "properties": {
@@ -126,8 +239,8 @@ impl SystemServer {
                         },
                         "format": {
                             "type": "string",
-                            "enum": ["toml", "json"],
-                            "description": "File format (toml or json)"
+                            "enum": ["auto", "toml", "json", "yaml", "ini", "dotenv", "ssh_config"],
+                            "description": "File format, or 'auto' to detect from the path"
                         }
                     },
                     "required": ["path", "format"]
@@ -135,7 +248,7 @@ impl SystemServer {
             },
             ToolDefinition {
                 name: "config_set".into(),
-                description: "Set a value in a configuration file using dotted key notation (e.g. 'section.key')".into(),
+                description: "Set, delete or append a value in a configuration file without disturbing comments or ordering. The file is backed up first".into(),
                 input_schema: json!({
                     "type": "object",
                     "properties": {
@@ -145,14 +258,24 @@ impl SystemServer {
                         },
                         "key": {
                             "type": "string",
-                            "description": "Dotted key path (e.g. 'server.port')"
+                            "description": "Dotted key path (e.g. 'server.port', 'services.web.ports[0]', 'Host.\"github.com\".User')"
                         },
                         "value": {
                             "type": "string",
-                            "description": "Value to set (parsed as int/float/bool/string automatically)"
+                            "description": "Value to set or append (parsed as int/float/bool/string automatically)"
+                        },
+                        "operation": {
+                            "type": "string",
+                            "enum": ["set", "delete", "append"],
+                            "description": "Edit to apply (default: set)"
+                        },
+                        "format": {
+                            "type": "string",
+                            "enum": ["auto", "toml", "json", "yaml", "ini", "dotenv", "ssh_config"],
+                            "description": "File format (default: detect from the path)"
                         }
                     },
-                    "required": ["path", "key", "value"]
+                    "required": ["path", "key"]
                 }),
             },
             ToolDefinition {
@@ -187,6 +310,66 @@ impl SystemServer {
                     "required": ["key", "value"]
                 }),
             },
+            ToolDefinition {
+                name: "env_persist".into(),
+                description: "Persist an environment variable or PATH entry in the user's shell rc file (bash, zsh or fish), inside a managed block. Idempotent; the rc file is backed up before it is changed".into(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "operation": {
+                            "type": "string",
+                            "enum": ["set", "unset", "path_add", "path_remove"],
+                            "description": "Change to make to the managed block"
+                        },
+                        "key": {
+                            "type": "string",
+                            "description": "Variable name (set / unset)"
+                        },
+                        "value": {
+                            "type": "string",
+                            "description": "Variable value (set)"
+                        },
+                        "dir": {
+                            "type": "string",
+                            "description": "Directory to add to or remove from PATH (path_add / path_remove)"
+                        },
+                        "position": {
+                            "type": "string",
+                            "enum": ["prepend", "append"],
+                            "description": "Where to add the directory to PATH (default: prepend)"
+                        },
+                        "shell": {
+                            "type": "string",
+                            "enum": ["bash", "zsh", "fish"],
+                            "description": "Target shell (default: detected from $SHELL)"
+                        },
+                        "rc_file": {
+                            "type": "string",
+                            "description": "Override the rc file path (default: the shell's standard rc file)"
+                        }
+                    },
+                    "required": ["operation"]
+                }),
+            },
+            ToolDefinition {
+                name: "env_persist_list".into(),
+                description: "List the environment variables and PATH entries persisted in the managed block of the user's shell rc file".into(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "shell": {
+                            "type": "string",
+                            "enum": ["bash", "zsh", "fish"],
+                            "description": "Target shell (default: detected from $SHELL)"
+                        },
+                        "rc_file": {
+                            "type": "string",
+                            "description": "Override the rc file path (default: the shell's standard rc file)"
+                        }
+                    },
+                    "required": []
+                }),
+            },
             ToolDefinition {
                 name: "network_check".into(),
                 description: "Run a network diagnostic check: ping, dns lookup, port connectivity, or internet connectivity test".into(),
@@ -206,6 +389,140 @@ impl SystemServer {
                     "required": ["check_type", "target"]
                 }),
             },
+            ToolDefinition {
+                name: "http_probe".into(),
+                description: "Probe an HTTP(S) URL and report status, redirect chain and a timing breakdown (DNS, connect, TLS, server)".into(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "url": {
+                            "type": "string",
+                            "description": "http:// or https:// URL to probe"
+                        },
+                        "follow_redirects": {
+                            "type": "boolean",
+                            "description": "Follow redirects (default: true)"
+                        },
+                        "max_redirects": {
+                            "type": "integer",
+                            "description": "Maximum redirects to follow (default: 10)"
+                        },
+                        "timeout_secs": {
+                            "type": "integer",
+                            "description": "Overall timeout in seconds (default: 10)"
+                        },
+                        "insecure": {
+                            "type": "boolean",
+                            "description": "Skip certificate verification (default: false)"
+                        },
+                        "no_proxy": {
+                            "type": "string",
+                            "description": "Comma-separated hosts to reach without a proxy, overriding NO_PROXY ('*' for all)"
+                        }
+                    },
+                    "required": ["url"]
+                }),
+            },
+            ToolDefinition {
+                name: "tls_inspect".into(),
+                description: "Inspect the TLS certificate chain of a server: subjects, issuers, expiry and whether it is trusted".into(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "host": {
+                            "type": "string",
+                            "description": "Server hostname"
+                        },
+                        "port": {
+                            "type": "integer",
+                            "description": "Server port (default: 443)"
+                        }
+                    },
+                    "required": ["host"]
+                }),
+            },
+            ToolDefinition {
+                name: "traceroute".into(),
+                description: "Trace the network path to a host with per-hop latency and packet loss".into(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "target": {
+                            "type": "string",
+                            "description": "Target hostname or IP"
+                        },
+                        "max_hops": {
+                            "type": "integer",
+                            "description": "Maximum number of hops (default: 30)"
+                        }
+                    },
+                    "required": ["target"]
+                }),
+            },
+            ToolDefinition {
+                name: "proxy_detect".into(),
+                description: "Detect proxy settings from environment variables (HTTP_PROXY, NO_PROXY, ...) and the OS, including PAC files".into(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {},
+                    "required": []
+                }),
+            },
+            ToolDefinition {
+                name: "dns_compare".into(),
+                description: "Resolve a name against several DNS resolvers and report whether their answers agree".into(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "name": {
+                            "type": "string",
+                            "description": "Hostname to resolve"
+                        },
+                        "record_type": {
+                            "type": "string",
+                            "enum": ["A", "AAAA"],
+                            "description": "Record type (default: A)"
+                        },
+                        "resolvers": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "Resolvers to compare: \"system\" or an ip[:port] (default: system, 1.1.1.1, 8.8.8.8)"
+                        }
+                    },
+                    "required": ["name"]
+                }),
+            },
+            ToolDefinition {
+                name: "disk_usage".into(),
+                description: "Analyse disk usage under a directory in the user's home: largest directories and files, with well-known caches labelled".into(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "path": {
+                            "type": "string",
+                            "description": "Directory to analyse (must be inside the home directory)"
+                        },
+                        "top_n": {
+                            "type": "integer",
+                            "description": "Number of directories and files to report (default: 20)"
+                        },
+                        "max_depth": {
+                            "type": "integer",
+                            "description": "Deepest directory level to report, relative to path (default: 3)"
+                        }
+                    },
+                    "required": ["path"]
+                }),
+            },
+            ToolDefinition {
+                name: "disk_cleanup_plan".into(),
+                description: "Produce a dry-run plan for clearing developer caches (cargo, npm, pip, Homebrew, Docker, Xcode DerivedData) with estimated savings and the risk level of each command. Nothing is deleted".into(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {},
+                    "required": []
+                }),
+            },
         ]
     }
 
@@ -231,13 +548,40 @@ impl SystemServer {
             }
             "service_status" => {
                 let name = param_str(params, "name")?;
-                let info = self.ops.service_status(&name).await?;
+                let scope = param_scope(params)?;
+                let info = self.ops.service_status(&name, scope).await?;
                 Ok(serde_json::to_value(info)?)
             }
             "service_control" => {
                 let name = param_str(params, "name")?;
                 let action = param_str(params, "action")?;
-                let output = self.ops.service_control(&name, &action).await?;
+                let scope = param_scope(params)?;
+                let output = self.ops.service_control(&name, &action, scope).await?;
+                Ok(json!({ "output": output }))
+            }
+            "service_logs" => {
+                let name = param_str(params, "name")?;
+                let scope = param_scope(params)?;
+                let since = params.get("since").and_then(|v| v.as_str());
+                let until = params.get("until").and_then(|v| v.as_str());
+                let lines = params
+                    .get("lines")
+                    .and_then(|v| v.as_u64())
+                    .map(|n| n as usize);
+                let logs = self
+                    .ops
+                    .service_logs(&name, scope, since, until, lines)
+                    .await?;
+                Ok(serde_json::to_value(logs)?)
+            }
+            "service_create_user_unit" => {
+                let spec: UserUnitSpec = serde_json::from_value(params.clone())
+                    .context("invalid user unit parameters")?;
+                let enable = params
+                    .get("enable")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false);
+                let output = self.ops.create_user_unit(&spec, enable).await?;
                 Ok(json!({ "output": output }))
             }
             "config_read" => {
@@ -249,9 +593,19 @@ impl SystemServer {
             "config_set" => {
                 let path = param_str(params, "path")?;
                 let key = param_str(params, "key")?;
-                let value = param_str(params, "value")?;
-                self.ops.config_set(&path, &key, &value).await?;
-                Ok(json!({ "success": true }))
+                let operation = params
+                    .get("operation")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("set");
+                let value = params.get("value").and_then(|v| v.as_str());
+                let op = EditOp::parse(operation, value)?;
+                let format = params.get("format").and_then(|v| v.as_str());
+                let result = self.ops.config_edit(&path, &key, &op, format).await?;
+                Ok(json!({
+                    "success": true,
+                    "changed": result.changed,
+                    "backup": result.backup,
+                }))
             }
             "env_get" => {
                 let key = param_str(params, "key")?;
@@ -264,12 +618,92 @@ impl SystemServer {
                 self.ops.env_set(&key, &value)?;
                 Ok(json!({ "success": true }))
             }
+            "env_persist" => {
+                let target = param_shell_target(params)?;
+                let edit = match param_str(params, "operation")?.as_str() {
+                    "set" => EnvEdit::Set {
+                        key: param_str(params, "key")?,
+                        value: param_str(params, "value")?,
+                    },
+                    "unset" => EnvEdit::Unset {
+                        key: param_str(params, "key")?,
+                    },
+                    "path_add" => EnvEdit::PathAdd {
+                        dir: param_str(params, "dir")?,
+                        position: match params.get("position").and_then(|v| v.as_str()) {
+                            Some(p) => PathPosition::parse(p)?,
+                            None => PathPosition::Prepend,
+                        },
+                    },
+                    "path_remove" => EnvEdit::PathRemove {
+                        dir: param_str(params, "dir")?,
+                    },
+                    other => anyhow::bail!("unsupported env_persist operation: {other}"),
+                };
+                let result = self.ops.env_persist(&target, &edit).await?;
+                Ok(serde_json::to_value(result)?)
+            }
+            "env_persist_list" => {
+                let target = param_shell_target(params)?;
+                let listing = self.ops.env_persist_list(&target).await?;
+                Ok(serde_json::to_value(listing)?)
+            }
             "network_check" => {
                 let check_type = param_str(params, "check_type")?;
                 let target = param_str(params, "target")?;
                 let result = self.ops.network_check(&check_type, &target).await?;
                 Ok(serde_json::to_value(result)?)
             }
+            "http_probe" => {
+                let opts: HttpProbeOptions = serde_json::from_value(params.clone())
+                    .context("invalid http_probe parameters")?;
+                let result = self.ops.http_probe(&opts).await?;
+                Ok(serde_json::to_value(result)?)
+            }
+            "tls_inspect" => {
+                let host = param_str(params, "host")?;
+                let port = match params.get("port").and_then(|v| v.as_u64()) {
+                    Some(p) => u16::try_from(p).context("port out of range")?,
+                    None => 443,
+                };
+                let report = self.ops.tls_inspect(&host, port).await?;
+                Ok(serde_json::to_value(report)?)
+            }
+            "traceroute" => {
+                let target = param_str(params, "target")?;
+                let max_hops = params
+                    .get("max_hops")
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(30) as u32;
+                let result = self.ops.traceroute(&target, max_hops).await?;
+                Ok(serde_json::to_value(result)?)
+            }
+            "proxy_detect" => Ok(serde_json::to_value(self.ops.proxy_detect().await)?),
+            "dns_compare" => {
+                let name = param_str(params, "name")?;
+                let record_type = match params.get("record_type").and_then(|v| v.as_str()) {
+                    Some(t) => RecordType::parse(t)?,
+                    None => RecordType::A,
+                };
+                let resolvers: Vec<String> = match params.get("resolvers") {
+                    Some(v) => serde_json::from_value(v.clone())
+                        .context("resolvers must be an array of strings")?,
+                    None => DEFAULT_RESOLVERS.iter().map(|r| r.to_string()).collect(),
+                };
+                let result = self.ops.dns_compare(&name, record_type, &resolvers).await?;
+                Ok(serde_json::to_value(result)?)
+            }
+            "disk_usage" => {
+                let path = param_str(params, "path")?;
+                let opts: DiskUsageOptions = serde_json::from_value(params.clone())
+                    .context("invalid disk_usage parameters")?;
+                let report = self.ops.disk_usage(&path, &opts).await?;
+                Ok(serde_json::to_value(report)?)
+            }
+            "disk_cleanup_plan" => {
+                let plan = self.ops.disk_cleanup_plan().await?;
+                Ok(serde_json::to_value(plan)?)
+            }
             unknown => anyhow::bail!("unknown tool: {unknown}"),
         }
     }
@@ -285,6 +719,22 @@ fn param_str(params: &Value, key: &str) -> Result<String> {
         .with_context(|| format!("missing or invalid parameter: {key}"))
 }
 
+/// Extract the optional `scope` parameter, defaulting to the system scope.
+fn param_scope(params: &Value) -> Result<ServiceScope> {
+    match params.get("scope").and_then(|v| v.as_str()) {
+        Some(scope) => ServiceScope::parse(scope),
+        None => Ok(ServiceScope::System),
+    }
+}
+
+/// Resolve the optional `shell` / `rc_file` parameters into a target.
+fn param_shell_target(params: &Value) -> Result<ShellTarget> {
+    ShellTarget::resolve(
+        params.get("shell").and_then(|v| v.as_str()),
+        params.get("rc_file").and_then(|v| v.as_str()),
+    )
+}
+
 // ---------------------------------------------------------------------------
 // Tests
 // ---------------------------------------------------------------------------
@@ -297,7 +747,7 @@ mod tests {
     fn test_tool_definitions_count() {
         let server = SystemServer::new();
         let defs = server.tool_definitions();
-        assert_eq!(defs.len(), 10, "should expose exactly 10 tools");
+        assert_eq!(defs.len(), 21, "should expose exactly 21 tools");
     }
 
     #[test]
@@ -406,8 +856,9 @@ mod tests {
             .await
             .unwrap();
 
-        let server = SystemServer::new();
-        server
+        let fs = FilesystemOps::new(dir.path().to_path_buf(), dir.path().join("backups"));
+        let server = SystemServer::with_ops(SystemOps::with_backups(fs));
+        let set = server
             .handle_tool_call(
                 "config_set",
                 &json!({
@@ -418,6 +869,8 @@ mod tests {
             )
             .await
             .unwrap();
+        assert_eq!(set["changed"], true);
+        assert!(set["backup"].is_string());
 
         let result = server
             .handle_tool_call(
@@ -430,6 +883,44 @@ mod tests {
         assert_eq!(result["section"]["key"], "new_val");
     }
 
+    #[tokio::test]
+    async fn test_dispatch_config_set_operations() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("compose.yaml");
+        tokio::fs::write(&path, "services:\n  web:\n    ports:\n      - \"80:80\"\n")
+            .await
+            .unwrap();
+        let p = path.to_str().unwrap();
+
+        let server = SystemServer::with_ops(SystemOps::new());
+        server
+            .handle_tool_call(
+                "config_set",
+                &json!({ "path": p, "key": "services.web.ports", "operation": "append", "value": "443:443" }),
+            )
+            .await
+            .unwrap();
+        server
+            .handle_tool_call(
+                "config_set",
+                &json!({ "path": p, "key": "services.web.ports[0]", "operation": "delete" }),
+            )
+            .await
+            .unwrap();
+
+        let result = server
+            .handle_tool_call("config_read", &json!({ "path": p, "format": "auto" }))
+            .await
+            .unwrap();
+        assert_eq!(result["services"]["web"]["ports"], json!(["443:443"]));
+
+        // set/append without a value is rejected
+        let err = server
+            .handle_tool_call("config_set", &json!({ "path": p, "key": "x" }))
+            .await;
+        assert!(err.is_err());
+    }
+
     #[tokio::test]
     async fn test_dispatch_network_check_connectivity() {
         let server = SystemServer::new();
@@ -463,6 +954,27 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[tokio::test]
+    async fn test_dispatch_service_invalid_scope() {
+        let server = SystemServer::new();
+        let result = server
+            .handle_tool_call(
+                "service_status",
+                &json!({ "name": "ollama", "scope": "global" }),
+            )
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_param_scope_default() {
+        assert_eq!(param_scope(&json!({})).unwrap(), ServiceScope::System);
+        assert_eq!(
+            param_scope(&json!({ "scope": "user" })).unwrap(),
+            ServiceScope::User
+        );
+    }
+
     #[test]
     fn test_param_str_extraction() {
         let params = json!({ "name": "nginx", "port": 80 });
@@ -471,4 +983,89 @@ mod tests {
         // port is a number, not a string
         assert!(param_str(&params, "port").is_err());
     }
+
+    #[tokio::test]
+    async fn test_dispatch_env_persist() {
+        let dir = tempfile::tempdir().unwrap();
+        let rc = dir.path().join(".bashrc");
+        let rc_str = rc.to_str().unwrap();
+        let server = SystemServer::with_ops(SystemOps::new());
+
+        let result = server
+            .handle_tool_call(
+                "env_persist",
+                &json!({
+                    "operation": "path_add",
+                    "dir": "/opt/tools/bin",
+                    "position": "append",
+                    "shell": "bash",
+                    "rc_file": rc_str,
+                }),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result["changed"], true);
+        assert_eq!(result["shell"], "bash");
+
+        server
+            .handle_tool_call(
+                "env_persist",
+                &json!({
+                    "operation": "set",
+                    "key": "EDITOR",
+                    "value": "vim",
+                    "shell": "bash",
+                    "rc_file": rc_str,
+                }),
+            )
+            .await
+            .unwrap();
+
+        let listing = server
+            .handle_tool_call(
+                "env_persist_list",
+                &json!({ "shell": "bash", "rc_file": rc_str }),
+            )
+            .await
+            .unwrap();
+        assert_eq!(listing["vars"]["EDITOR"], "vim");
+        assert_eq!(listing["path_append"][0], "/opt/tools/bin");
+
+        let err = server
+            .handle_tool_call(
+                "env_persist",
+                &json!({ "operation": "rename", "shell": "bash", "rc_file": rc_str }),
+            )
+            .await;
+        assert!(err.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_network_diagnostics_validation() {
+        let server = SystemServer::new();
+        assert!(server
+            .handle_tool_call("http_probe", &json!({ "url": "ftp://example.com" }))
+            .await
+            .is_err());
+        assert!(server
+            .handle_tool_call(
+                "tls_inspect",
+                &json!({ "host": "example.com", "port": 70000 })
+            )
+            .await
+            .is_err());
+        assert!(server
+            .handle_tool_call(
+                "dns_compare",
+                &json!({ "name": "example.com", "record_type": "MX" })
+            )
+            .await
+            .is_err());
+
+        let proxies = server
+            .handle_tool_call("proxy_detect", &json!({}))
+            .await
+            .unwrap();
+        assert!(proxies["proxies"].is_array());
+    }
 }
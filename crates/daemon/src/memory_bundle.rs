@@ -0,0 +1,443 @@
+//! Portable memory bundles for moving memory between machines.
+//!
+//! A bundle is JSON lines: a [`BundleHeader`], one [`BundleLine`] per row,
+//! and a final `{"signature": "hmac-sha256:<hex>"}` line computed over every
+//! byte before it with a key shared by the exporting and importing side.
+//! Rows pass through the [`Redactor`] on export, so secrets captured in
+//! memory do not leave the machine; embeddings are left out and recomputed
+//! on import.
+
+use anyhow::{Context, Result};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::Sha256;
+
+use crate::memory_store::{ConflictPolicy, ImportOutcome, MemoryRecord, MemoryStore, MemoryTable};
+use crate::redactor::Redactor;
+
+/// `format` field of every bundle header.
+pub const BUNDLE_FORMAT: &str = "d1-memory-bundle";
+
+/// Bundle layout version written by this build.  Bundles with a higher
+/// version are rejected.
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// Prefix of the signature value.
+const SIGNATURE_SCHEME: &str = "hmac-sha256:";
+
+/// First line of a bundle.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BundleHeader {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: String,
+    /// Table names (`profile_memory`, ...) included in the bundle.
+    pub tables: Vec<String>,
+    /// Profile categories the export was limited to; empty means all.
+    #[serde(default)]
+    pub profile_categories: Vec<String>,
+    /// Number of row lines that follow.
+    pub records: usize,
+}
+
+/// One exported row.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BundleLine {
+    pub table: String,
+    pub record: MemoryRecord,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SignatureLine {
+    signature: String,
+}
+
+/// What to put in a bundle.
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    pub tables: Vec<MemoryTable>,
+    /// Limit profile memory to these categories; empty exports all of them.
+    pub profile_categories: Vec<String>,
+}
+
+/// Row counts from [`import_bundle`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ImportSummary {
+    pub inserted: usize,
+    pub replaced: usize,
+    pub skipped: usize,
+}
+
+/// Columns copied verbatim: identifiers and timestamps that the redactor's
+/// token and path patterns could otherwise mangle.
+fn is_verbatim_column(column: &str) -> bool {
+    column == "id" || column == "session_id" || column.ends_with("_at")
+}
+
+/// Export the selected tables as a signed, redacted bundle.
+pub fn export_bundle(
+    store: &MemoryStore,
+    redactor: &Redactor,
+    options: &ExportOptions,
+    key: &[u8],
+) -> Result<String> {
+    anyhow::ensure!(!key.is_empty(), "a signing key is required");
+    anyhow::ensure!(!options.tables.is_empty(), "no tables selected for export");
+
+    let mut lines = Vec::new();
+    for table in &options.tables {
+        for record in store.export_records(*table, &options.profile_categories)? {
+            let record = record
+                .into_iter()
+                .map(|(column, value)| {
+                    let value = if is_verbatim_column(&column) {
+                        value
+                    } else {
+                        redactor.redact_json(&value)
+                    };
+                    (column, value)
+                })
+                .collect();
+            lines.push(BundleLine {
+                table: table.table_name().to_string(),
+                record,
+            });
+        }
+    }
+
+    let header = BundleHeader {
+        format: BUNDLE_FORMAT.to_string(),
+        version: BUNDLE_VERSION,
+        exported_at: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+        tables: options
+            .tables
+            .iter()
+            .map(|t| t.table_name().to_string())
+            .collect(),
+        profile_categories: options.profile_categories.clone(),
+        records: lines.len(),
+    };
+
+    let mut body = serde_json::to_string(&header)?;
+    body.push('\n');
+    for line in &lines {
+        body.push_str(&serde_json::to_string(line)?);
+        body.push('\n');
+    }
+    let signature = SignatureLine {
+        signature: format!("{SIGNATURE_SCHEME}{}", sign(key, &body)),
+    };
+    body.push_str(&serde_json::to_string(&signature)?);
+    body.push('\n');
+    Ok(body)
+}
+
+/// Check a bundle's signature and structure without importing it.
+pub fn read_bundle(bundle: &str, key: &[u8]) -> Result<(BundleHeader, Vec<BundleLine>)> {
+    let trimmed = bundle.trim_end_matches('\n');
+    let (body, signature_line) = match trimmed.rfind('\n') {
+        Some(i) => (&bundle[..=i], &trimmed[i + 1..]),
+        None => anyhow::bail!("bundle is missing its signature line"),
+    };
+    let signature: SignatureLine =
+        serde_json::from_str(signature_line).context("bundle is missing its signature line")?;
+    let expected = signature
+        .signature
+        .strip_prefix(SIGNATURE_SCHEME)
+        .and_then(|h| hex::decode(h).ok())
+        .context("unsupported bundle signature")?;
+    anyhow::ensure!(
+        bundle_mac(key, body).verify_slice(&expected).is_ok(),
+        "bundle signature does not match; wrong key or modified bundle"
+    );
+
+    let mut lines = body.lines();
+    let header: BundleHeader =
+        serde_json::from_str(lines.next().unwrap_or_default()).context("invalid bundle header")?;
+    anyhow::ensure!(
+        header.format == BUNDLE_FORMAT,
+        "not a memory bundle: {}",
+        header.format
+    );
+    anyhow::ensure!(
+        header.version <= BUNDLE_VERSION,
+        "bundle version {} is newer than this daemon supports ({BUNDLE_VERSION})",
+        header.version
+    );
+
+    let records = lines
+        .enumerate()
+        .map(|(i, line)| {
+            let line: BundleLine = serde_json::from_str(line)
+                .with_context(|| format!("invalid bundle record on line {}", i + 2))?;
+            MemoryTable::parse(&line.table)?;
+            Ok(line)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    anyhow::ensure!(
+        records.len() == header.records,
+        "bundle header lists {} records but {} are present",
+        header.records,
+        records.len()
+    );
+    Ok((header, records))
+}
+
+/// Verify and import a bundle.  Nothing is written unless the whole bundle
+/// verifies and parses.
+pub fn import_bundle(
+    store: &MemoryStore,
+    bundle: &str,
+    key: &[u8],
+    policy: ConflictPolicy,
+    actor: &str,
+) -> Result<ImportSummary> {
+    let (_, records) = read_bundle(bundle, key)?;
+    let mut summary = ImportSummary::default();
+    for line in &records {
+        let table = MemoryTable::parse(&line.table)?;
+        let outcome = store
+            .import_record(table, &line.record, policy, actor)
+            .with_context(|| {
+                let id = line.record.get("id").and_then(Value::as_str);
+                format!("importing {} row {}", line.table, id.unwrap_or("?"))
+            })?;
+        match outcome {
+            ImportOutcome::Inserted => summary.inserted += 1,
+            ImportOutcome::Replaced => summary.replaced += 1,
+            ImportOutcome::Skipped => summary.skipped += 1,
+        }
+    }
+    Ok(summary)
+}
+
+type HmacSha256 = Hmac<Sha256>;
+
+fn bundle_mac(key: &[u8], body: &str) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(body.as_bytes());
+    mac
+}
+
+fn sign(key: &[u8], body: &str) -> String {
+    hex::encode(bundle_mac(key, body).finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::local_db::LocalDb;
+    use std::sync::Arc;
+
+    const KEY: &[u8] = b"team-shared-secret";
+
+    fn store() -> MemoryStore {
+        MemoryStore::new(Arc::new(LocalDb::open_in_memory().unwrap()))
+    }
+
+    fn all_tables() -> ExportOptions {
+        ExportOptions {
+            tables: vec![
+                MemoryTable::Profile,
+                MemoryTable::Session,
+                MemoryTable::Task,
+                MemoryTable::Agent,
+            ],
+            profile_categories: Vec::new(),
+        }
+    }
+
+    fn seeded() -> MemoryStore {
+        let store = store();
+        store
+            .store_profile("system", "os", "macOS", "detected")
+            .unwrap();
+        store
+            .store_profile("editor", "name", "helix", "user")
+            .unwrap();
+        store
+            .store_session("sess-1", 0, "bob", "action", "ran brew upgrade", None)
+            .unwrap();
+        store
+            .store_task_outcome(
+                "install node",
+                "dev",
+                "success",
+                r#"["brew install node"]"#,
+                "[]",
+                "[]",
+                12,
+                "{}",
+                "sess-1",
+            )
+            .unwrap();
+        store
+            .store_agent_learning(
+                "bob",
+                "fact",
+                "the API key is sk-abcdefghijklmnopqrstuvwxyz123456",
+                0.8,
+            )
+            .unwrap();
+        store
+    }
+
+    #[test]
+    fn test_hmac_sha256_matches_rfc_4231() {
+        // RFC 4231 test case 2.
+        assert_eq!(
+            sign(b"Jefe", "what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_roundtrip_to_new_device() {
+        let source = seeded();
+        let bundle = export_bundle(&source, &Redactor::new(), &all_tables(), KEY).unwrap();
+
+        let target = store();
+        let summary = import_bundle(&target, &bundle, KEY, ConflictPolicy::Skip, "import").unwrap();
+        assert_eq!(
+            summary,
+            ImportSummary {
+                inserted: 5,
+                replaced: 0,
+                skipped: 0
+            }
+        );
+
+        let hits = target
+            .recall("brew", crate::memory_store::MemoryScope::All, 5)
+            .unwrap();
+        assert!(hits.len() >= 2, "imported rows are indexed and embedded");
+        let agent = target
+            .export_records(MemoryTable::Agent, &[])
+            .unwrap()
+            .remove(0);
+        assert!(
+            !agent["content"].as_str().unwrap().contains("sk-abcdef"),
+            "secrets are redacted on export"
+        );
+        let id = agent["id"].as_str().unwrap();
+        let history = target.audit_history(MemoryTable::Agent, id).unwrap();
+        assert_eq!(history[0].agent_name.as_deref(), Some("import"));
+        assert!(history[0].old_value.is_none());
+    }
+
+    #[test]
+    fn test_profile_category_filter() {
+        let source = seeded();
+        let options = ExportOptions {
+            tables: vec![MemoryTable::Profile],
+            profile_categories: vec!["editor".into()],
+        };
+        let bundle = export_bundle(&source, &Redactor::new(), &options, KEY).unwrap();
+        let (header, records) = read_bundle(&bundle, KEY).unwrap();
+        assert_eq!(header.tables, vec!["profile_memory"]);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].record["value"], "helix");
+    }
+
+    #[test]
+    fn test_tampered_or_wrongly_keyed_bundle_is_rejected() {
+        let bundle = export_bundle(&seeded(), &Redactor::new(), &all_tables(), KEY).unwrap();
+        let target = store();
+
+        let err =
+            import_bundle(&target, &bundle, b"wrong", ConflictPolicy::Skip, "import").unwrap_err();
+        assert!(err.to_string().contains("signature does not match"));
+
+        let tampered = bundle.replace("helix", "vim");
+        assert!(import_bundle(&target, &tampered, KEY, ConflictPolicy::Skip, "import").is_err());
+
+        let unsigned = bundle.lines().take(3).collect::<Vec<_>>().join("\n") + "\n";
+        assert!(read_bundle(&unsigned, KEY).is_err());
+        assert!(target
+            .export_records(MemoryTable::Profile, &[])
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_newer_bundle_version_is_rejected() {
+        let header = BundleHeader {
+            format: BUNDLE_FORMAT.into(),
+            version: BUNDLE_VERSION + 1,
+            exported_at: "2026-01-01T00:00:00Z".into(),
+            tables: vec![],
+            profile_categories: vec![],
+            records: 0,
+        };
+        let body = format!("{}\n", serde_json::to_string(&header).unwrap());
+        let bundle = format!(
+            "{body}{{\"signature\":\"{SIGNATURE_SCHEME}{}\"}}\n",
+            sign(KEY, &body)
+        );
+        let err = read_bundle(&bundle, KEY).unwrap_err();
+        assert!(err.to_string().contains("newer than this daemon supports"));
+    }
+
+    #[test]
+    fn test_conflict_policies() {
+        // The same fact on both machines under different ids.
+        let remote = store();
+        remote
+            .store_profile("editor", "name", "helix", "user")
+            .unwrap();
+        let options = ExportOptions {
+            tables: vec![MemoryTable::Profile],
+            profile_categories: vec![],
+        };
+        let bundle = export_bundle(&remote, &Redactor::new(), &options, KEY).unwrap();
+
+        let db = Arc::new(LocalDb::open_in_memory().unwrap());
+        let local = MemoryStore::new(Arc::clone(&db));
+        local
+            .store_profile("editor", "name", "vim", "user")
+            .unwrap();
+        let set_local_updated_at = |when: &str| {
+            db.conn()
+                .execute("UPDATE profile_memory SET updated_at = ?1", [when])
+                .unwrap();
+        };
+        let rows = || local.export_records(MemoryTable::Profile, &[]).unwrap();
+        let import = |policy| import_bundle(&local, &bundle, KEY, policy, "import").unwrap();
+
+        assert_eq!(import(ConflictPolicy::Skip).skipped, 1);
+        assert_eq!(rows()[0]["value"], "vim");
+
+        set_local_updated_at("2999-01-01T00:00:00Z");
+        assert_eq!(import(ConflictPolicy::KeepNewer).skipped, 1);
+        assert_eq!(rows()[0]["value"], "vim");
+
+        set_local_updated_at("2000-01-01T00:00:00Z");
+        assert_eq!(import(ConflictPolicy::KeepNewer).replaced, 1);
+        let rows_now = rows();
+        assert_eq!(rows_now.len(), 1);
+        assert_eq!(rows_now[0]["value"], "helix");
+        let id = rows_now[0]["id"].as_str().unwrap();
+        let history = local.audit_history(MemoryTable::Profile, id).unwrap();
+        let replaced = history
+            .iter()
+            .find(|h| h.agent_name.as_deref() == Some("import"));
+        assert!(replaced
+            .unwrap()
+            .old_value
+            .as_deref()
+            .unwrap()
+            .contains("vim"));
+
+        local
+            .update_record(
+                MemoryTable::Profile,
+                id,
+                &serde_json::from_value(serde_json::json!({ "value": "emacs" })).unwrap(),
+                "test",
+            )
+            .unwrap();
+        assert_eq!(import(ConflictPolicy::Overwrite).replaced, 1);
+        assert_eq!(rows()[0]["value"], "helix");
+    }
+}
@@ -2,17 +2,24 @@
 //!
 //! [`MemoryStore`] wraps a [`LocalDb`] and provides high-level methods for
 //! storing profile facts, session events, task outcomes, and agent learnings,
-//! plus FTS5-powered recall queries.
+//! plus hybrid recall queries that blend FTS5 BM25 relevance with vector
+//! similarity from a local [`Embedder`].
 
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use anyhow::{Context, Result};
-use chrono::Utc;
-use rusqlite::params;
-use tracing::debug;
+use chrono::{DateTime, Utc};
+use rusqlite::types::{Value as SqlValue, ValueRef};
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tracing::{debug, info, warn};
 use uuid::Uuid;
 
+use crate::embedding::{self, Embedder, HashingEmbedder};
 use crate::local_db::LocalDb;
+use crate::summarizer::{StructuredSummarizer, Summarizer, SummaryInput};
 
 // ---------------------------------------------------------------------------
 // Types
@@ -28,6 +35,20 @@ pub enum MemoryScope {
     All,
 }
 
+impl MemoryScope {
+    /// Parse a scope name (`profile`, `session`, `task`, `agent`, `all`).
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "profile" => Ok(MemoryScope::Profile),
+            "session" => Ok(MemoryScope::Session),
+            "task" => Ok(MemoryScope::Task),
+            "agent" => Ok(MemoryScope::Agent),
+            "all" => Ok(MemoryScope::All),
+            other => anyhow::bail!("invalid scope: {other}"),
+        }
+    }
+}
+
 /// Generic memory entry returned by [`MemoryStore::recall`].
 #[derive(Debug, Clone)]
 pub struct MemoryEntry {
@@ -37,6 +58,120 @@ pub struct MemoryEntry {
     pub metadata: Option<String>,
     pub confidence: f64,
     pub created_at: String,
+    /// Hybrid relevance score; higher is better.
+    pub score: f64,
+    /// Best-matching fragment with query terms wrapped in [`SNIPPET_OPEN`] /
+    /// [`SNIPPET_CLOSE`]; `None` when the row matched on similarity alone.
+    pub snippet: Option<String>,
+}
+
+/// Marker inserted before each highlighted term in [`MemoryEntry::snippet`].
+pub const SNIPPET_OPEN: &str = "**";
+/// Marker inserted after each highlighted term in [`MemoryEntry::snippet`].
+pub const SNIPPET_CLOSE: &str = "**";
+/// Maximum number of tokens in a snippet.
+const SNIPPET_TOKENS: i32 = 12;
+
+/// How [`MemoryStore::recall`] blends its ranking signals.
+///
+/// `score = (lexical * bm25 + semantic * cosine
+///         + recency * 0.5^(age / half_life) + usage * use_boost)
+///        * (1 - confidence + confidence * effective_confidence)`
+///
+/// where `effective_confidence` comes from the store's [`ConfidenceModel`]
+/// (always 1.0 for session and task memory).
+#[derive(Debug, Clone, Copy)]
+pub struct RecallWeights {
+    pub lexical: f64,
+    pub semantic: f64,
+    pub recency: f64,
+    pub usage: f64,
+    /// How strongly effective confidence scales the score (0.0 ignores it).
+    pub confidence: f64,
+    /// Age, in days, at which the recency boost halves.
+    pub recency_half_life_days: f64,
+    /// Rows without any keyword match need at least this cosine similarity.
+    pub min_similarity: f64,
+}
+
+impl Default for RecallWeights {
+    fn default() -> Self {
+        Self {
+            lexical: 0.45,
+            semantic: 0.40,
+            recency: 0.10,
+            usage: 0.05,
+            confidence: 0.5,
+            recency_half_life_days: 30.0,
+            min_similarity: 0.2,
+        }
+    }
+}
+
+/// How the confidence of profile and agent memory evolves.
+///
+/// Stored confidence decays from the row's `updated_at` unless the memory
+/// is reinforced: `effective = confidence * 0.5^(age / half_life)`, never
+/// below `floor`.  A successful use moves confidence `reinforcement` of the
+/// way towards 1.0; a failure scales it by `1 - penalty`.  Both start from
+/// the decayed value and restart the decay clock.
+#[derive(Debug, Clone, Copy)]
+pub struct ConfidenceModel {
+    pub half_life_days: f64,
+    pub reinforcement: f64,
+    pub penalty: f64,
+    pub floor: f64,
+}
+
+impl Default for ConfidenceModel {
+    fn default() -> Self {
+        Self {
+            half_life_days: 90.0,
+            reinforcement: 0.2,
+            penalty: 0.3,
+            floor: 0.05,
+        }
+    }
+}
+
+impl ConfidenceModel {
+    /// `confidence` after decaying since `since` (an RFC 3339 timestamp).
+    pub fn decay(&self, confidence: f64, since: &str) -> f64 {
+        if DateTime::parse_from_rfc3339(since).is_err() {
+            return confidence;
+        }
+        let decayed = confidence * recency_boost(since, self.half_life_days);
+        decayed.max(self.floor.min(confidence))
+    }
+
+    pub fn reinforce(&self, confidence: f64) -> f64 {
+        (confidence + self.reinforcement * (1.0 - confidence)).min(1.0)
+    }
+
+    pub fn penalize(&self, confidence: f64) -> f64 {
+        (confidence * (1.0 - self.penalty)).max(self.floor.min(confidence))
+    }
+}
+
+/// Profile sources from most to least trusted.  When two sources disagree
+/// about a fact, the more trusted one wins; unlisted sources rank last.
+pub const PROFILE_SOURCE_PRIORITY: &[&str] = &["user", "detected", "system", "env", "agent"];
+
+fn source_rank(source: &str) -> usize {
+    PROFILE_SOURCE_PRIORITY
+        .iter()
+        .position(|s| *s == source)
+        .unwrap_or(PROFILE_SOURCE_PRIORITY.len())
+}
+
+/// Whether a task outcome counts as a success (`Some(true)`), a failure
+/// (`Some(false)`), or neither (partial, unknown).
+fn outcome_success(outcome: &str) -> Option<bool> {
+    match outcome.to_ascii_lowercase().as_str() {
+        "success" | "succeeded" | "ok" | "fixed" | "resolved" => Some(true),
+        "failure" | "failed" | "error" | "aborted" => Some(false),
+        _ => None,
+    }
 }
 
 /// A row from `profile_memory`.
@@ -67,42 +202,362 @@ pub struct TaskEntry {
     pub created_at: String,
 }
 
+/// A memory table addressable by the curation API (list/get/update/delete).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryTable {
+    Profile,
+    Session,
+    Task,
+    Agent,
+}
+
+impl MemoryTable {
+    /// Parse a table name, accepting both `agent` and `agent_memory` forms.
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.strip_suffix("_memory").unwrap_or(s) {
+            "profile" => Ok(MemoryTable::Profile),
+            "session" => Ok(MemoryTable::Session),
+            "task" => Ok(MemoryTable::Task),
+            "agent" => Ok(MemoryTable::Agent),
+            _ => anyhow::bail!("invalid memory table: {s}"),
+        }
+    }
+
+    /// The SQLite table backing this memory level.
+    pub fn table_name(self) -> &'static str {
+        match self {
+            MemoryTable::Profile => "profile_memory",
+            MemoryTable::Session => "session_memory",
+            MemoryTable::Task => "task_memory",
+            MemoryTable::Agent => "agent_memory",
+        }
+    }
+
+    /// Columns usable as equality filters in [`MemoryStore::list_records`].
+    pub fn filter_columns(self) -> &'static [&'static str] {
+        match self {
+            MemoryTable::Profile => &["category", "key", "source"],
+            MemoryTable::Session => &["session_id", "agent_name", "event_type"],
+            MemoryTable::Task => &["task_category", "outcome", "session_id"],
+            MemoryTable::Agent => &["agent_name", "memory_type"],
+        }
+    }
+
+    /// Columns that [`MemoryStore::update_record`] may modify.
+    pub fn editable_columns(self) -> &'static [&'static str] {
+        match self {
+            MemoryTable::Profile => &["value", "confidence", "source"],
+            MemoryTable::Session => &["event_type", "content", "metadata"],
+            MemoryTable::Task => &[
+                "task_description",
+                "task_category",
+                "outcome",
+                "procedure_steps",
+                "error_patterns",
+                "fix_patterns",
+                "duration_seconds",
+                "system_context",
+            ],
+            MemoryTable::Agent => &["memory_type", "content", "confidence"],
+        }
+    }
+
+    fn has_updated_at(self) -> bool {
+        matches!(self, MemoryTable::Profile | MemoryTable::Agent)
+    }
+
+    const ALL: [MemoryTable; 4] = [
+        MemoryTable::Profile,
+        MemoryTable::Session,
+        MemoryTable::Task,
+        MemoryTable::Agent,
+    ];
+
+    fn level(self) -> &'static str {
+        match self {
+            MemoryTable::Profile => "profile",
+            MemoryTable::Session => "session",
+            MemoryTable::Task => "task",
+            MemoryTable::Agent => "agent",
+        }
+    }
+
+    /// FTS5 index kept in sync with this table by triggers.
+    fn fts_table(self) -> &'static str {
+        match self {
+            MemoryTable::Profile => "profile_memory_fts",
+            MemoryTable::Session => "session_memory_fts",
+            MemoryTable::Task => "task_memory_fts",
+            MemoryTable::Agent => "agent_memory_fts",
+        }
+    }
+
+    /// SQL expression for the text that gets embedded.
+    fn source_text_sql(self) -> &'static str {
+        match self {
+            MemoryTable::Profile => "category || ' ' || key || ' ' || value",
+            MemoryTable::Session => "event_type || ' ' || content",
+            MemoryTable::Task => {
+                "task_description || ' ' || COALESCE(task_category, '') || ' '
+                 || COALESCE(procedure_steps, '') || ' ' || COALESCE(error_patterns, '')
+                 || ' ' || COALESCE(fix_patterns, '')"
+            }
+            MemoryTable::Agent => "memory_type || ' ' || content",
+        }
+    }
+
+    /// Columns for recall candidates: id, content, metadata, confidence,
+    /// created_at, last-touched timestamp, use count, embedding, and the
+    /// timestamp confidence decays from (NULL when it does not decay).
+    fn recall_columns_sql(self) -> &'static str {
+        match self {
+            MemoryTable::Profile => {
+                "id, key || '=' || value, NULL, confidence, created_at, updated_at, 0, embedding,
+                 updated_at"
+            }
+            MemoryTable::Session => {
+                "id, content, metadata, 1.0, created_at, created_at, 0, embedding, NULL"
+            }
+            MemoryTable::Task => {
+                "id, task_description, outcome, 1.0, created_at, created_at, 0, embedding, NULL"
+            }
+            MemoryTable::Agent => {
+                "id, content, memory_type, confidence, created_at,
+                 COALESCE(last_used_at, updated_at), use_count, embedding, updated_at"
+            }
+        }
+    }
+
+    /// For tables that [`MemoryStore::run_compression`] touches: the archive
+    /// table, the summarized column, and the columns copied to the archive.
+    fn compression(self) -> Option<(&'static str, &'static str, &'static str)> {
+        match self {
+            MemoryTable::Profile | MemoryTable::Session => None,
+            MemoryTable::Task => Some((
+                "task_memory_archive",
+                "procedure_steps",
+                "id, task_description, task_category, outcome, procedure_steps,
+                 error_patterns, fix_patterns, duration_seconds, system_context,
+                 session_id, created_at",
+            )),
+            MemoryTable::Agent => Some((
+                "agent_memory_archive",
+                "content",
+                "id, agent_name, memory_type, content, confidence, use_count,
+                 last_used_at, created_at, updated_at",
+            )),
+        }
+    }
+}
+
+impl From<MemoryScope> for Vec<MemoryTable> {
+    fn from(scope: MemoryScope) -> Self {
+        match scope {
+            MemoryScope::Profile => vec![MemoryTable::Profile],
+            MemoryScope::Session => vec![MemoryTable::Session],
+            MemoryScope::Task => vec![MemoryTable::Task],
+            MemoryScope::Agent => vec![MemoryTable::Agent],
+            MemoryScope::All => MemoryTable::ALL.to_vec(),
+        }
+    }
+}
+
+/// A full memory row as a column-name → JSON value map.
+pub type MemoryRecord = Map<String, Value>;
+
+/// Filters and cursor for [`MemoryStore::list_records`].
+#[derive(Debug, Clone, Default)]
+pub struct ListQuery {
+    /// Equality filters as `(column, value)` pairs; see [`MemoryTable::filter_columns`].
+    pub filters: Vec<(String, String)>,
+    /// Opaque cursor returned as `next_cursor` by the previous page.
+    pub cursor: Option<String>,
+    pub limit: usize,
+}
+
+/// One page of memory rows, in insertion order.
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordPage {
+    pub records: Vec<MemoryRecord>,
+    /// Pass back as [`ListQuery::cursor`] to fetch the next page; `None` on the last page.
+    pub next_cursor: Option<String>,
+}
+
+/// What [`MemoryStore::import_record`] does when the row already exists
+/// (same id, or for profile memory the same category and key).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// Keep the local row.
+    #[default]
+    Skip,
+    /// Replace the local row with the imported one.
+    Overwrite,
+    /// Replace the local row only if the imported one was modified later
+    /// (`updated_at`, or `created_at` for tables without it).
+    KeepNewer,
+}
+
+/// Result of importing one row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportOutcome {
+    Inserted,
+    Replaced,
+    Skipped,
+}
+
+/// A row from `audit_log`.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    pub id: String,
+    pub table_name: String,
+    pub record_id: String,
+    pub agent_name: Option<String>,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    pub created_at: String,
+}
+
 // ---------------------------------------------------------------------------
 // MemoryStore
 // ---------------------------------------------------------------------------
 
-/// Truncate a UTF-8 string to at most `max_bytes` bytes on a character
-/// boundary, appending `"..."` if truncation occurred.
-fn truncate_utf8(s: &str, max_bytes: usize) -> String {
-    if s.len() <= max_bytes {
-        return s.to_string();
-    }
-    let end = s
-        .char_indices()
-        .take_while(|(i, _)| *i < max_bytes)
-        .last()
-        .map(|(i, c)| i + c.len_utf8())
-        .unwrap_or(0);
-    format!("{}...", &s[..end])
+/// Convert a result row (from column `skip` onward) into a [`MemoryRecord`].
+fn row_to_record(row: &Row<'_>, skip: usize) -> rusqlite::Result<MemoryRecord> {
+    let stmt = row.as_ref();
+    let mut record = Map::new();
+    for idx in skip..stmt.column_count() {
+        let name = stmt.column_name(idx)?;
+        if name == "embedding" {
+            continue;
+        }
+        let value = match row.get_ref(idx)? {
+            ValueRef::Null => Value::Null,
+            ValueRef::Integer(i) => Value::from(i),
+            ValueRef::Real(f) => Value::from(f),
+            ValueRef::Text(t) => Value::String(String::from_utf8_lossy(t).into_owned()),
+            ValueRef::Blob(b) => Value::String(hex::encode(b)),
+        };
+        record.insert(name.to_string(), value);
+    }
+    Ok(record)
+}
+
+/// Convert a JSON value into a SQLite parameter.  Arrays and objects are
+/// stored as JSON text, matching the schema's JSON columns.
+fn json_to_sql(value: &Value) -> SqlValue {
+    match value {
+        Value::Null => SqlValue::Null,
+        Value::Bool(b) => SqlValue::Integer(i64::from(*b)),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => SqlValue::Integer(i),
+            None => SqlValue::Real(n.as_f64().unwrap_or_default()),
+        },
+        Value::String(s) => SqlValue::Text(s.clone()),
+        other => SqlValue::Text(other.to_string()),
+    }
+}
+
+/// A recall candidate before scoring.
+struct Candidate {
+    entry: MemoryEntry,
+    touched_at: String,
+    use_count: i64,
+    embedding: Option<Vec<u8>>,
+    confidence_since: Option<String>,
+}
+
+/// A full-text hit: positive BM25 magnitude plus highlighted snippet.
+struct FtsMatch {
+    bm25: f64,
+    snippet: String,
+}
+
+/// Quote each token as a prefix term so arbitrary user text is a valid
+/// FTS5 OR-query (`"npm"* OR "install"*`).
+fn fts_or_query(tokens: &[String]) -> String {
+    tokens
+        .iter()
+        .map(|t| format!("\"{t}\"*"))
+        .collect::<Vec<_>>()
+        .join(" OR ")
+}
+
+/// `0.5^(age / half_life)` for an RFC 3339 timestamp; 0.0 if unparseable.
+fn recency_boost(timestamp: &str, half_life_days: f64) -> f64 {
+    let Ok(ts) = DateTime::parse_from_rfc3339(timestamp) else {
+        return 0.0;
+    };
+    let age_days = (Utc::now() - ts.with_timezone(&Utc)).num_seconds().max(0) as f64 / 86_400.0;
+    0.5_f64.powf(age_days / half_life_days.max(f64::EPSILON))
+}
+
+/// Logarithmic use-count boost that saturates at ten uses.
+fn usage_boost(use_count: i64) -> f64 {
+    ((1 + use_count.max(0)) as f64).ln().min(11f64.ln()) / 11f64.ln()
 }
 
 /// High-level CRUD + recall wrapper around [`LocalDb`].
 pub struct MemoryStore {
     db: Arc<LocalDb>,
+    embedder: Arc<dyn Embedder>,
+    summarizer: Arc<dyn Summarizer>,
+    weights: RecallWeights,
+    confidence: ConfidenceModel,
 }
 
 impl MemoryStore {
-    /// Create a new `MemoryStore` backed by the given database.
+    /// Create a new `MemoryStore` backed by the given database, using the
+    /// built-in [`HashingEmbedder`].
     pub fn new(db: Arc<LocalDb>) -> Self {
-        Self { db }
+        Self::with_embedder(db, Arc::new(HashingEmbedder::default()))
+    }
+
+    /// Create a `MemoryStore` with a custom embedder.  Rows embedded by a
+    /// different-sized embedder are re-embedded by [`Self::backfill_embeddings`].
+    pub fn with_embedder(db: Arc<LocalDb>, embedder: Arc<dyn Embedder>) -> Self {
+        Self {
+            db,
+            embedder,
+            summarizer: Arc::new(StructuredSummarizer::new()),
+            weights: RecallWeights::default(),
+            confidence: ConfidenceModel::default(),
+        }
+    }
+
+    /// Override how confidence decays and responds to feedback.
+    pub fn with_confidence_model(mut self, model: ConfidenceModel) -> Self {
+        self.confidence = model;
+        self
+    }
+
+    /// Override the summarizer used by [`Self::run_compression`].
+    pub fn with_summarizer(mut self, summarizer: Arc<dyn Summarizer>) -> Self {
+        self.summarizer = summarizer;
+        self
+    }
+
+    /// Override the hybrid recall weights.
+    pub fn with_recall_weights(mut self, weights: RecallWeights) -> Self {
+        self.weights = weights;
+        self
     }
 
     // -- Store methods ------------------------------------------------------
 
-    /// Insert or replace a profile memory entry.
+    /// Insert or update a profile memory entry, resolving conflicts with an
+    /// existing fact for the same (`category`, `key`) pair:
+    ///
+    /// - same value: the fact is reinforced (see [`ConfidenceModel`]) and
+    ///   keeps its id, taking the more trusted of the two sources;
+    /// - different value from a less trusted source (see
+    ///   [`PROFILE_SOURCE_PRIORITY`]): the existing fact is kept and the
+    ///   rejected value is recorded in `audit_log`;
+    /// - otherwise the old value is logged to `audit_log` and the row is
+    ///   replaced.
     ///
-    /// On conflict (`category`, `key` pair already exists) the old value is
-    /// logged to `audit_log` and the row is replaced.  Returns the new row id.
+    /// Returns the id of the row that holds the fact afterwards.
     pub fn store_profile(
         &self,
         category: &str,
@@ -111,20 +566,65 @@ impl MemoryStore {
         source: &str,
     ) -> Result<String> {
         let conn = self.db.conn();
-        let id = Uuid::new_v4().to_string();
 
-        // Check for an existing row so we can audit the replacement.
-        let existing: Option<(String, String)> = conn
+        let existing: Option<(String, String, String, f64, String)> = conn
             .query_row(
-                "SELECT id, value FROM profile_memory WHERE category = ?1 AND key = ?2",
+                "SELECT id, value, source, confidence, updated_at
+                 FROM profile_memory WHERE category = ?1 AND key = ?2",
                 params![category, key],
-                |row| Ok((row.get(0)?, row.get(1)?)),
+                |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                    ))
+                },
             )
-            .ok();
+            .optional()
+            .context("store_profile select")?;
+
+        if let Some((old_id, old_value, old_source, confidence, updated_at)) = existing {
+            if old_value == value {
+                let confidence = self
+                    .confidence
+                    .reinforce(self.confidence.decay(confidence, &updated_at));
+                let source = if source_rank(source) < source_rank(&old_source) {
+                    source
+                } else {
+                    old_source.as_str()
+                };
+                conn.execute(
+                    "UPDATE profile_memory
+                     SET confidence = ?1, source = ?2,
+                         updated_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now')
+                     WHERE id = ?3",
+                    params![confidence, source, old_id],
+                )
+                .context("store_profile reinforce")?;
+                debug!(%old_id, %category, %key, confidence, "Reinforced profile memory");
+                return Ok(old_id);
+            }
 
-        if let Some((old_id, old_value)) = existing {
-            // Audit the replacement.
             let audit_id = Uuid::new_v4().to_string();
+            if source_rank(source) > source_rank(&old_source) {
+                let rejected = serde_json::json!({
+                    "rejected": value,
+                    "source": source,
+                    "kept_source": old_source,
+                });
+                conn.execute(
+                    "INSERT INTO audit_log (id, table_name, record_id, agent_name, old_value, new_value)
+                     VALUES (?1, 'profile_memory', ?2, ?3, ?4, ?5)",
+                    params![audit_id, old_id, source, old_value, rejected.to_string()],
+                )
+                .context("audit_log insert for profile contradiction")?;
+                info!(%category, %key, %source, kept_source = %old_source, "Kept profile fact over contradiction from less trusted source");
+                return Ok(old_id);
+            }
+
+            // Audit the replacement.
             conn.execute(
                 "INSERT INTO audit_log (id, table_name, record_id, agent_name, old_value, new_value)
                  VALUES (?1, 'profile_memory', ?2, ?3, ?4, ?5)",
@@ -132,14 +632,21 @@ impl MemoryStore {
             )
             .context("audit_log insert for profile replacement")?;
             debug!(old_id, %category, %key, "Audited profile memory replacement");
+
+            // Delete explicitly rather than via INSERT OR REPLACE: REPLACE does
+            // not fire delete triggers, which would leave profile_memory_fts stale.
+            conn.execute("DELETE FROM profile_memory WHERE id = ?1", params![old_id])
+                .context("store_profile delete replaced row")?;
         }
 
+        let id = Uuid::new_v4().to_string();
         conn.execute(
-            "INSERT OR REPLACE INTO profile_memory (id, category, key, value, confidence, source, updated_at)
+            "INSERT INTO profile_memory (id, category, key, value, confidence, source, updated_at)
              VALUES (?1, ?2, ?3, ?4, 1.0, ?5, strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))",
             params![id, category, key, value, source],
         )
         .context("store_profile insert")?;
+        self.embed_row(&conn, MemoryTable::Profile, &id)?;
 
         debug!(%id, %category, %key, "Stored profile memory");
         Ok(id)
@@ -164,6 +671,7 @@ impl MemoryStore {
                 params![id, session_id, step_number, agent_name, event_type, content, metadata],
             )
             .context("store_session insert")?;
+        self.embed_row(&self.db.conn(), MemoryTable::Session, &id)?;
 
         debug!(%id, %session_id, step_number, "Stored session memory");
         Ok(id)
@@ -205,6 +713,7 @@ impl MemoryStore {
                 ],
             )
             .context("store_task_outcome insert")?;
+        self.embed_row(&self.db.conn(), MemoryTable::Task, &id)?;
 
         debug!(%id, %category, "Stored task outcome");
         Ok(id)
@@ -227,6 +736,7 @@ impl MemoryStore {
                 params![id, agent_name, memory_type, content, confidence],
             )
             .context("store_agent_learning insert")?;
+        self.embed_row(&self.db.conn(), MemoryTable::Agent, &id)?;
 
         debug!(%id, %agent_name, %memory_type, "Stored agent learning");
         Ok(id)
@@ -234,32 +744,25 @@ impl MemoryStore {
 
     // -- Recall methods -----------------------------------------------------
 
-    /// General-purpose recall across one or all memory scopes.
+    /// General-purpose hybrid recall across one or all memory scopes.
     ///
-    /// * `Task` and `Agent` scopes use FTS5 MATCH.
-    /// * `Profile` uses LIKE on key and value columns.
-    /// * `Session` uses LIKE on content.
-    /// * `All` unions results from every scope.
+    /// Every row is scored by blending FTS5 BM25 relevance with the cosine
+    /// similarity of its embedding, plus recency and use-count boosts (see
+    /// [`RecallWeights`]).  Rows with neither a full-text hit nor enough
+    /// semantic similarity are dropped.  Results are ordered by score and
+    /// carry a highlighted snippet when they matched on text.
     pub fn recall(&self, query: &str, scope: MemoryScope, limit: i32) -> Result<Vec<MemoryEntry>> {
-        match scope {
-            MemoryScope::Profile => self.recall_profile_generic(query, limit),
-            MemoryScope::Session => self.recall_session_generic(query, limit),
-            MemoryScope::Task => self.recall_task_generic(query, limit),
-            MemoryScope::Agent => self.recall_agent_generic(query, limit),
-            MemoryScope::All => {
-                let per = std::cmp::max(limit / 4, 1);
-                let mut entries = Vec::new();
-                entries.extend(self.recall_profile_generic(query, per)?);
-                entries.extend(self.recall_session_generic(query, per)?);
-                entries.extend(self.recall_task_generic(query, per)?);
-                entries.extend(self.recall_agent_generic(query, per)?);
-                entries.truncate(limit as usize);
-                Ok(entries)
-            }
+        let mut entries = Vec::new();
+        for table in Vec::<MemoryTable>::from(scope) {
+            entries.extend(self.rank(table, query, None)?);
         }
+        entries.sort_by(|a, b| b.score.total_cmp(&a.score));
+        entries.truncate(limit.max(0) as usize);
+        Ok(entries)
     }
 
-    /// Return all profile entries for the given `category`.
+    /// Return all profile entries for the given `category`, with decayed
+    /// confidence.
     pub fn recall_profile(&self, category: &str) -> Result<Vec<ProfileEntry>> {
         let conn = self.db.conn();
         let mut stmt = conn.prepare(
@@ -280,89 +783,80 @@ impl MemoryStore {
                     updated_at: row.get(7)?,
                 })
             })?
+            .map(|entry| {
+                entry.map(|mut e| {
+                    e.confidence = self.confidence.decay(e.confidence, &e.updated_at);
+                    e
+                })
+            })
             .collect::<std::result::Result<Vec<_>, _>>()?;
 
         Ok(rows)
     }
 
-    /// FTS5-ranked search over `task_memory`.
+    /// Hybrid-ranked search over `task_memory` (see [`Self::recall`]).
     pub fn recall_similar_tasks(&self, query: &str, limit: i32) -> Result<Vec<TaskEntry>> {
-        let conn = self.db.conn();
-        let mut stmt = conn.prepare(
-            "SELECT t.id, t.task_description, COALESCE(t.task_category, ''),
-                    COALESCE(t.outcome, ''), COALESCE(t.procedure_steps, ''),
-                    COALESCE(t.error_patterns, ''), COALESCE(t.fix_patterns, ''),
-                    COALESCE(t.duration_seconds, 0), COALESCE(t.session_id, ''),
-                    t.created_at
-             FROM task_memory t
-             JOIN task_memory_fts f ON t.rowid = f.rowid
-             WHERE task_memory_fts MATCH ?1
-             ORDER BY f.rank
-             LIMIT ?2",
-        )?;
-
-        let rows = stmt
-            .query_map(params![query, limit], |row| {
-                Ok(TaskEntry {
-                    id: row.get(0)?,
-                    task_description: row.get(1)?,
-                    task_category: row.get(2)?,
-                    outcome: row.get(3)?,
-                    procedure_steps: row.get(4)?,
-                    error_patterns: row.get(5)?,
-                    fix_patterns: row.get(6)?,
-                    duration_seconds: row.get(7)?,
-                    session_id: row.get(8)?,
-                    created_at: row.get(9)?,
-                })
-            })?
-            .collect::<std::result::Result<Vec<_>, _>>()?;
-
-        Ok(rows)
+        let ranked = self.rank(MemoryTable::Task, query, None)?;
+        self.task_entries(ranked, limit)
     }
 
-    /// FTS5 search scoped to `error_patterns` and `fix_patterns` columns.
+    /// Hybrid search whose keyword component is scoped to the
+    /// `error_patterns` and `fix_patterns` columns.
     pub fn recall_error_patterns(&self, query: &str, limit: i32) -> Result<Vec<TaskEntry>> {
-        let conn = self.db.conn();
-        // Scope the MATCH to error_patterns and fix_patterns columns.
-        let fts_query = format!("{{error_patterns fix_patterns}} : {query}");
-        let mut stmt = conn.prepare(
-            "SELECT t.id, t.task_description, COALESCE(t.task_category, ''),
-                    COALESCE(t.outcome, ''), COALESCE(t.procedure_steps, ''),
-                    COALESCE(t.error_patterns, ''), COALESCE(t.fix_patterns, ''),
-                    COALESCE(t.duration_seconds, 0), COALESCE(t.session_id, ''),
-                    t.created_at
-             FROM task_memory t
-             JOIN task_memory_fts f ON t.rowid = f.rowid
-             WHERE task_memory_fts MATCH ?1
-             ORDER BY f.rank
-             LIMIT ?2",
-        )?;
-
-        let rows = stmt
-            .query_map(params![fts_query, limit], |row| {
-                Ok(TaskEntry {
-                    id: row.get(0)?,
-                    task_description: row.get(1)?,
-                    task_category: row.get(2)?,
-                    outcome: row.get(3)?,
-                    procedure_steps: row.get(4)?,
-                    error_patterns: row.get(5)?,
-                    fix_patterns: row.get(6)?,
-                    duration_seconds: row.get(7)?,
-                    session_id: row.get(8)?,
-                    created_at: row.get(9)?,
-                })
-            })?
-            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let tokens = embedding::tokenize(query);
+        let fts_query = if tokens.is_empty() {
+            None
+        } else {
+            Some(format!(
+                "{{error_patterns fix_patterns}} : ({})",
+                fts_or_query(&tokens)
+            ))
+        };
+        let ranked = self.rank(MemoryTable::Task, query, fts_query.as_deref())?;
+        self.task_entries(ranked, limit)
+    }
 
-        Ok(rows)
+    /// Compute embeddings for rows that have none, or whose embedding was
+    /// produced by an embedder of a different size.  Returns the row count.
+    pub fn backfill_embeddings(&self) -> Result<u64> {
+        let blob_len = (self.embedder.dimensions() * 4) as i64;
+        let mut total = 0u64;
+        for table in MemoryTable::ALL {
+            let conn = self.db.conn();
+            let rows: Vec<(String, String)> = conn
+                .prepare(&format!(
+                    "SELECT id, {} FROM {} WHERE embedding IS NULL OR length(embedding) != ?1",
+                    table.source_text_sql(),
+                    table.table_name()
+                ))?
+                .query_map(params![blob_len], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            for (id, text) in &rows {
+                let blob = embedding::to_blob(&self.embedder.embed(text));
+                conn.execute(
+                    &format!(
+                        "UPDATE {} SET embedding = ?1 WHERE id = ?2",
+                        table.table_name()
+                    ),
+                    params![blob, id],
+                )
+                .context("backfill embedding update")?;
+            }
+            total += rows.len() as u64;
+        }
+        debug!(
+            total,
+            embedder = self.embedder.name(),
+            "Backfilled embeddings"
+        );
+        Ok(total)
     }
 
     // -- Management methods -------------------------------------------------
 
     /// Delete a record from the specified table and log the deletion.
-    pub fn forget(&self, id: &str, table: &str) -> Result<()> {
+    /// Returns `false` if no row had that id.
+    pub fn forget(&self, id: &str, table: &str) -> Result<bool> {
         let conn = self.db.conn();
 
         // Validate table name to prevent SQL injection (only our known tables).
@@ -389,7 +883,103 @@ impl MemoryStore {
             debug!(%id, %table, "Forgot memory record");
         }
 
-        Ok(())
+        Ok(deleted > 0)
+    }
+
+    /// Apply feedback from using a profile or agent memory: reinforce its
+    /// confidence on success, penalise it on failure (see
+    /// [`ConfidenceModel`]).  A successful use of an agent memory also
+    /// counts towards `use_count`.  The change is audited under `actor`.
+    ///
+    /// Returns the new confidence, or `None` if no row had that id.
+    pub fn record_memory_outcome(
+        &self,
+        table: MemoryTable,
+        id: &str,
+        success: bool,
+        actor: &str,
+    ) -> Result<Option<f64>> {
+        anyhow::ensure!(
+            matches!(table, MemoryTable::Profile | MemoryTable::Agent),
+            "{} has no confidence to adjust",
+            table.table_name()
+        );
+        let table_name = table.table_name();
+        let conn = self.db.conn();
+        let row: Option<(f64, String)> = conn
+            .query_row(
+                &format!("SELECT confidence, updated_at FROM {table_name} WHERE id = ?1"),
+                params![id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .context("record_memory_outcome select")?;
+        let Some((stored, since)) = row else {
+            return Ok(None);
+        };
+
+        let current = self.confidence.decay(stored, &since);
+        let updated = if success {
+            self.confidence.reinforce(current)
+        } else {
+            self.confidence.penalize(current)
+        };
+        let usage = if success && table == MemoryTable::Agent {
+            ", use_count = use_count + 1, last_used_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now')"
+        } else {
+            ""
+        };
+        conn.execute(
+            &format!(
+                "UPDATE {table_name}
+                 SET confidence = ?1, updated_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now'){usage}
+                 WHERE id = ?2"
+            ),
+            params![updated, id],
+        )
+        .context("record_memory_outcome update")?;
+
+        let audit_id = Uuid::new_v4().to_string();
+        conn.execute(
+            "INSERT INTO audit_log (id, table_name, record_id, agent_name, old_value, new_value)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                audit_id,
+                table_name,
+                id,
+                actor,
+                serde_json::json!({ "confidence": stored }).to_string(),
+                serde_json::json!({ "confidence": updated }).to_string(),
+            ],
+        )
+        .context("record_memory_outcome audit_log insert")?;
+        debug!(%id, table = table_name, success, confidence = updated, "Recorded memory outcome");
+        Ok(Some(updated))
+    }
+
+    /// Reinforce or penalise the agent memories that informed a task,
+    /// depending on whether `outcome` is a success or a failure.  Outcomes
+    /// that are neither (e.g. "partial") change nothing.  Returns how many
+    /// memories were adjusted; unknown ids are ignored.
+    pub fn apply_task_feedback(
+        &self,
+        outcome: &str,
+        used_memory_ids: &[String],
+        actor: &str,
+    ) -> Result<usize> {
+        let Some(success) = outcome_success(outcome) else {
+            return Ok(0);
+        };
+        let mut adjusted = 0;
+        for id in used_memory_ids {
+            if self
+                .record_memory_outcome(MemoryTable::Agent, id, success, actor)?
+                .is_some()
+            {
+                adjusted += 1;
+            }
+        }
+        Ok(adjusted)
     }
 
     /// Increment the use count and update `last_used_at` for an agent memory.
@@ -415,165 +1005,528 @@ impl MemoryStore {
         Ok(())
     }
 
+    // -- Curation methods ---------------------------------------------------
+
+    /// List rows of `table` in insertion order, one page at a time.
+    pub fn list_records(&self, table: MemoryTable, query: &ListQuery) -> Result<RecordPage> {
+        let mut sql = format!("SELECT rowid, * FROM {} WHERE 1 = 1", table.table_name());
+        let mut args: Vec<SqlValue> = Vec::new();
+
+        for (column, value) in &query.filters {
+            anyhow::ensure!(
+                table.filter_columns().contains(&column.as_str()),
+                "unknown filter for {}: {column}",
+                table.table_name()
+            );
+            args.push(SqlValue::Text(value.clone()));
+            sql.push_str(&format!(" AND {column} = ?{}", args.len()));
+        }
+        if let Some(cursor) = &query.cursor {
+            let after: i64 = cursor
+                .parse()
+                .with_context(|| format!("invalid cursor: {cursor}"))?;
+            args.push(SqlValue::Integer(after));
+            sql.push_str(&format!(" AND rowid > ?{}", args.len()));
+        }
+        let limit = query.limit.max(1);
+        args.push(SqlValue::Integer(limit as i64 + 1));
+        sql.push_str(&format!(" ORDER BY rowid LIMIT ?{}", args.len()));
+
+        let conn = self.db.conn();
+        let mut stmt = conn.prepare(&sql)?;
+        let mut rows = stmt
+            .query_map(params_from_iter(args), |row| {
+                Ok((row.get::<_, i64>(0)?, row_to_record(row, 1)?))
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let next_cursor = if rows.len() > limit {
+            rows.truncate(limit);
+            rows.last().map(|(rowid, _)| rowid.to_string())
+        } else {
+            None
+        };
+
+        Ok(RecordPage {
+            records: rows.into_iter().map(|(_, record)| record).collect(),
+            next_cursor,
+        })
+    }
+
+    /// Fetch a single row by id.
+    pub fn get_record(&self, table: MemoryTable, id: &str) -> Result<Option<MemoryRecord>> {
+        let conn = self.db.conn();
+        let record = conn
+            .query_row(
+                &format!("SELECT * FROM {} WHERE id = ?1", table.table_name()),
+                params![id],
+                |row| row_to_record(row, 0),
+            )
+            .optional()
+            .context("get_record select")?;
+        Ok(record)
+    }
+
+    /// Update editable columns of a row and log old/new values to `audit_log`.
+    ///
+    /// `actor` is recorded as the audit entry's `agent_name`.  Returns the
+    /// updated row, or `None` if no row had that id.
+    pub fn update_record(
+        &self,
+        table: MemoryTable,
+        id: &str,
+        changes: &MemoryRecord,
+        actor: &str,
+    ) -> Result<Option<MemoryRecord>> {
+        anyhow::ensure!(!changes.is_empty(), "no fields to update");
+        for column in changes.keys() {
+            anyhow::ensure!(
+                table.editable_columns().contains(&column.as_str()),
+                "column is not editable in {}: {column}",
+                table.table_name()
+            );
+        }
+
+        {
+            let conn = self.db.conn();
+            let old = conn
+                .query_row(
+                    &format!("SELECT * FROM {} WHERE id = ?1", table.table_name()),
+                    params![id],
+                    |row| row_to_record(row, 0),
+                )
+                .optional()
+                .context("update_record select")?;
+            let Some(old) = old else {
+                return Ok(None);
+            };
+
+            let mut assignments: Vec<String> = Vec::new();
+            let mut args: Vec<SqlValue> = Vec::new();
+            for (column, value) in changes {
+                args.push(json_to_sql(value));
+                assignments.push(format!("{column} = ?{}", args.len()));
+            }
+            if table.has_updated_at() {
+                assignments.push("updated_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now')".into());
+            }
+            args.push(SqlValue::Text(id.to_string()));
+            conn.execute(
+                &format!(
+                    "UPDATE {} SET {} WHERE id = ?{}",
+                    table.table_name(),
+                    assignments.join(", "),
+                    args.len()
+                ),
+                params_from_iter(args),
+            )
+            .context("update_record update")?;
+            self.embed_row(&conn, table, id)?;
+
+            let old_values: MemoryRecord = changes
+                .keys()
+                .map(|column| {
+                    let value = old.get(column).cloned().unwrap_or(Value::Null);
+                    (column.clone(), value)
+                })
+                .collect();
+            let audit_id = Uuid::new_v4().to_string();
+            conn.execute(
+                "INSERT INTO audit_log (id, table_name, record_id, agent_name, old_value, new_value)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    audit_id,
+                    table.table_name(),
+                    id,
+                    actor,
+                    Value::Object(old_values).to_string(),
+                    Value::Object(changes.clone()).to_string(),
+                ],
+            )
+            .context("update_record audit_log insert")?;
+            debug!(%id, table = table.table_name(), %actor, "Updated memory record");
+        }
+
+        self.get_record(table, id)
+    }
+
+    /// Audit trail for one record, oldest first.
+    pub fn audit_history(&self, table: MemoryTable, record_id: &str) -> Result<Vec<AuditEntry>> {
+        let conn = self.db.conn();
+        let mut stmt = conn.prepare(
+            "SELECT id, table_name, record_id, agent_name, old_value, new_value, created_at
+             FROM audit_log
+             WHERE table_name = ?1 AND record_id = ?2
+             ORDER BY created_at, rowid",
+        )?;
+        let rows = stmt
+            .query_map(params![table.table_name(), record_id], |row| {
+                Ok(AuditEntry {
+                    id: row.get(0)?,
+                    table_name: row.get(1)?,
+                    record_id: row.get(2)?,
+                    agent_name: row.get(3)?,
+                    old_value: row.get(4)?,
+                    new_value: row.get(5)?,
+                    created_at: row.get(6)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    // -- Import / export ----------------------------------------------------
+
+    /// Every row of `table` in insertion order, without embeddings.
+    /// For profile memory, a non-empty `profile_categories` keeps only
+    /// those categories.
+    pub fn export_records(
+        &self,
+        table: MemoryTable,
+        profile_categories: &[String],
+    ) -> Result<Vec<MemoryRecord>> {
+        let mut sql = format!("SELECT * FROM {}", table.table_name());
+        let mut args: Vec<SqlValue> = Vec::new();
+        if table == MemoryTable::Profile && !profile_categories.is_empty() {
+            let placeholders: Vec<String> = profile_categories
+                .iter()
+                .enumerate()
+                .map(|(i, category)| {
+                    args.push(SqlValue::Text(category.clone()));
+                    format!("?{}", i + 1)
+                })
+                .collect();
+            sql.push_str(&format!(" WHERE category IN ({})", placeholders.join(", ")));
+        }
+        sql.push_str(" ORDER BY rowid");
+
+        let conn = self.db.conn();
+        let mut stmt = conn.prepare(&sql)?;
+        let records = stmt
+            .query_map(params_from_iter(args), |row| row_to_record(row, 0))?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(records)
+    }
+
+    /// Insert an exported row, resolving a clash with an existing row by
+    /// `policy`.  Inserts and replacements are audited under `actor` with
+    /// the replaced row (if any) as the old value.
+    pub fn import_record(
+        &self,
+        table: MemoryTable,
+        record: &MemoryRecord,
+        policy: ConflictPolicy,
+        actor: &str,
+    ) -> Result<ImportOutcome> {
+        let table_name = table.table_name();
+        let id = record
+            .get("id")
+            .and_then(Value::as_str)
+            .context("imported record has no id")?
+            .to_string();
+
+        let mut conn = self.db.conn();
+        let columns: Vec<String> = conn
+            .prepare(&format!("PRAGMA table_info({table_name})"))?
+            .query_map([], |row| row.get::<_, String>(1))?
+            .collect::<std::result::Result<_, _>>()?;
+        for column in record.keys() {
+            anyhow::ensure!(
+                column != "embedding" && columns.contains(column),
+                "unknown column for {table_name}: {column}"
+            );
+        }
+
+        let tx = conn.transaction()?;
+        let mut existing = tx
+            .query_row(
+                &format!("SELECT * FROM {table_name} WHERE id = ?1"),
+                params![id],
+                |row| row_to_record(row, 0),
+            )
+            .optional()
+            .context("import select")?;
+        if existing.is_none() && table == MemoryTable::Profile {
+            existing = tx
+                .query_row(
+                    "SELECT * FROM profile_memory WHERE category = ?1 AND key = ?2",
+                    params![
+                        record.get("category").and_then(Value::as_str),
+                        record.get("key").and_then(Value::as_str)
+                    ],
+                    |row| row_to_record(row, 0),
+                )
+                .optional()
+                .context("import select")?;
+        }
+
+        if let Some(existing) = &existing {
+            let stamp = if table.has_updated_at() {
+                "updated_at"
+            } else {
+                "created_at"
+            };
+            let replace = match policy {
+                ConflictPolicy::Skip => false,
+                ConflictPolicy::Overwrite => true,
+                ConflictPolicy::KeepNewer => {
+                    let theirs = record.get(stamp).and_then(Value::as_str);
+                    let ours = existing.get(stamp).and_then(Value::as_str);
+                    theirs > ours
+                }
+            };
+            if !replace {
+                return Ok(ImportOutcome::Skipped);
+            }
+            // DELETE + INSERT rather than REPLACE so the FTS triggers fire.
+            tx.execute(
+                &format!("DELETE FROM {table_name} WHERE id = ?1"),
+                params![existing.get("id").and_then(Value::as_str)],
+            )
+            .context("import delete")?;
+        }
+
+        let names: Vec<&str> = record.keys().map(String::as_str).collect();
+        let placeholders: Vec<String> = (1..=names.len()).map(|i| format!("?{i}")).collect();
+        tx.execute(
+            &format!(
+                "INSERT INTO {table_name} ({}) VALUES ({})",
+                names.join(", "),
+                placeholders.join(", ")
+            ),
+            params_from_iter(record.values().map(json_to_sql)),
+        )
+        .context("import insert")?;
+        self.embed_row(&tx, table, &id)?;
+
+        let audit_id = Uuid::new_v4().to_string();
+        tx.execute(
+            "INSERT INTO audit_log (id, table_name, record_id, agent_name, old_value, new_value)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                audit_id,
+                table_name,
+                id,
+                actor,
+                existing
+                    .as_ref()
+                    .map(|e| Value::Object(e.clone()).to_string()),
+                Value::Object(record.clone()).to_string(),
+            ],
+        )
+        .context("import audit_log insert")?;
+        tx.commit()?;
+
+        Ok(if existing.is_some() {
+            ImportOutcome::Replaced
+        } else {
+            ImportOutcome::Inserted
+        })
+    }
+
     // -- Retention policy methods -------------------------------------------
 
     /// Compress old task_memory and agent_memory entries.
     ///
     /// For each entry whose `created_at` (task_memory) or `last_used_at`/`updated_at`
-    /// (agent_memory) is older than `compress_after_days` days ago:
+    /// (agent_memory) is older than `compress_after_days` days ago and that is
+    /// not already archived:
     /// 1. Copy the full original row into the corresponding `_archive` table.
-    /// 2. Truncate `procedure_steps` (task) or `content` (agent) to the first
-    ///    100 characters followed by "…" as an in-place summary.
+    /// 2. Replace `procedure_steps` (task) or `content` (agent) with the
+    ///    configured [`Summarizer`]'s output, if that is shorter.
+    ///
+    /// Summaries are computed without holding the database lock, since a
+    /// remote summarizer may take a while; a row edited in the meantime is
+    /// skipped.  [`Self::restore_compressed`] undoes the compression.
     ///
     /// Profile memory is **never** compressed (no TTL).
     pub fn run_compression(&self, compress_after_days: i64) -> Result<u64> {
-        let conn = self.db.conn();
         let cutoff = (Utc::now() - chrono::Duration::days(compress_after_days))
             .format("%Y-%m-%dT%H:%M:%SZ")
             .to_string();
 
-        let mut total_compressed: u64 = 0;
-
-        // --- task_memory compression ---
-        {
-            let mut select = conn.prepare(
-                "SELECT id, task_description, task_category, outcome, procedure_steps,
-                        error_patterns, fix_patterns, duration_seconds, system_context,
-                        session_id, created_at
+        let candidates: Vec<(MemoryTable, String, SummaryInput)> = {
+            let conn = self.db.conn();
+            let mut tasks = conn.prepare(
+                "SELECT id, COALESCE(procedure_steps, ''), COALESCE(error_patterns, '[]'),
+                        COALESCE(fix_patterns, '[]')
                  FROM task_memory
-                 WHERE created_at < ?1",
+                 WHERE created_at < ?1
+                   AND id NOT IN (SELECT id FROM task_memory_archive)",
             )?;
-
-            let rows: Vec<(
-                String,
-                String,
-                Option<String>,
-                Option<String>,
-                Option<String>,
-                Option<String>,
-                Option<String>,
-                Option<i64>,
-                Option<String>,
-                Option<String>,
-                String,
-            )> = select
+            let mut candidates = tasks
                 .query_map(params![cutoff], |row| {
                     Ok((
+                        MemoryTable::Task,
                         row.get(0)?,
-                        row.get(1)?,
-                        row.get(2)?,
-                        row.get(3)?,
-                        row.get(4)?,
-                        row.get(5)?,
-                        row.get(6)?,
-                        row.get(7)?,
-                        row.get(8)?,
-                        row.get(9)?,
-                        row.get(10)?,
+                        SummaryInput::ProcedureSteps {
+                            steps: row.get(1)?,
+                            error_patterns: row.get(2)?,
+                            fix_patterns: row.get(3)?,
+                        },
                     ))
                 })?
                 .collect::<std::result::Result<Vec<_>, _>>()?;
 
-            for (id, desc, cat, outcome, steps, errors, fixes, dur, ctx, sess, created) in &rows {
-                // 1. Archive the original
-                conn.execute(
-                    "INSERT OR REPLACE INTO task_memory_archive
-                        (id, task_description, task_category, outcome, procedure_steps,
-                         error_patterns, fix_patterns, duration_seconds, system_context,
-                         session_id, created_at)
-                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
-                    params![id, desc, cat, outcome, steps, errors, fixes, dur, ctx, sess, created],
-                )
-                .context("archive task_memory row")?;
-
-                // 2. Summarize procedure_steps in place
-                let summary = match steps {
-                    Some(s) if s.len() > 100 => truncate_utf8(s, 100),
-                    Some(s) => s.clone(),
-                    None => String::new(),
-                };
-
-                conn.execute(
-                    "UPDATE task_memory SET procedure_steps = ?1 WHERE id = ?2",
-                    params![summary, id],
-                )
-                .context("compress task_memory procedure_steps")?;
-
-                total_compressed += 1;
-            }
-
-            debug!(count = rows.len(), "Compressed task_memory entries");
-        }
-
-        // --- agent_memory compression ---
-        {
-            let mut select = conn.prepare(
-                "SELECT id, agent_name, memory_type, content, confidence, use_count,
-                        last_used_at, created_at, updated_at
+            let mut agents = conn.prepare(
+                "SELECT id, memory_type, content
                  FROM agent_memory
-                 WHERE COALESCE(last_used_at, updated_at) < ?1",
+                 WHERE COALESCE(last_used_at, updated_at) < ?1
+                   AND id NOT IN (SELECT id FROM agent_memory_archive)",
             )?;
+            candidates.extend(
+                agents
+                    .query_map(params![cutoff], |row| {
+                        Ok((
+                            MemoryTable::Agent,
+                            row.get(0)?,
+                            SummaryInput::AgentMemory {
+                                memory_type: row.get(1)?,
+                                content: row.get(2)?,
+                            },
+                        ))
+                    })?
+                    .collect::<std::result::Result<Vec<_>, _>>()?,
+            );
+            candidates
+        };
 
-            let rows: Vec<(
-                String,
-                String,
-                String,
-                String,
-                f64,
-                i64,
-                Option<String>,
-                String,
-                String,
-            )> = select
-                .query_map(params![cutoff], |row| {
-                    Ok((
-                        row.get(0)?,
-                        row.get(1)?,
-                        row.get(2)?,
-                        row.get(3)?,
-                        row.get(4)?,
-                        row.get(5)?,
-                        row.get(6)?,
-                        row.get(7)?,
-                        row.get(8)?,
-                    ))
-                })?
-                .collect::<std::result::Result<Vec<_>, _>>()?;
-
-            for (id, agent, mtype, content, conf, use_cnt, last_used, created, updated) in &rows {
-                // 1. Archive the original
-                conn.execute(
-                    "INSERT OR REPLACE INTO agent_memory_archive
-                        (id, agent_name, memory_type, content, confidence, use_count,
-                         last_used_at, created_at, updated_at)
-                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
-                    params![id, agent, mtype, content, conf, use_cnt, last_used, created, updated],
-                )
-                .context("archive agent_memory row")?;
-
-                // 2. Summarize content in place
-                let summary = if content.len() > 100 {
-                    truncate_utf8(content, 100)
-                } else {
-                    content.clone()
-                };
-
-                conn.execute(
-                    "UPDATE agent_memory SET content = ?1 WHERE id = ?2",
-                    params![summary, id],
-                )
-                .context("compress agent_memory content")?;
-
+        let mut total_compressed: u64 = 0;
+        for (table, id, input) in &candidates {
+            let summary = match self.summarizer.summarize(input) {
+                Ok(summary) => summary,
+                Err(e) => {
+                    warn!(%id, table = table.table_name(), error = %format!("{e:#}"), "Summarizer failed; leaving row uncompressed");
+                    continue;
+                }
+            };
+            if self.compress_row(*table, id, input.text(), &summary)? {
                 total_compressed += 1;
             }
-
-            debug!(count = rows.len(), "Compressed agent_memory entries");
         }
 
-        debug!(total_compressed, "Retention compression complete");
+        debug!(
+            total_compressed,
+            summarizer = self.summarizer.name(),
+            "Retention compression complete"
+        );
         Ok(total_compressed)
     }
 
+    /// Archive one row and swap in its summary, provided its text is still
+    /// `original`.  Returns `false` if the row changed or vanished.
+    fn compress_row(
+        &self,
+        table: MemoryTable,
+        id: &str,
+        original: &str,
+        summary: &str,
+    ) -> Result<bool> {
+        let Some((archive, column, columns)) = table.compression() else {
+            return Ok(false);
+        };
+        let mut conn = self.db.conn();
+        let tx = conn.transaction()?;
+        let archived = tx
+            .execute(
+                &format!(
+                    "INSERT OR IGNORE INTO {archive} ({columns})
+                     SELECT {columns} FROM {} WHERE id = ?1 AND COALESCE({column}, '') = ?2",
+                    table.table_name()
+                ),
+                params![id, original],
+            )
+            .with_context(|| format!("archive {} row", table.table_name()))?;
+        if archived == 0 {
+            return Ok(false);
+        }
+        if summary.len() < original.len() {
+            tx.execute(
+                &format!(
+                    "UPDATE {} SET {column} = ?1 WHERE id = ?2",
+                    table.table_name()
+                ),
+                params![summary, id],
+            )
+            .with_context(|| format!("compress {} {column}", table.table_name()))?;
+            self.embed_row(&tx, table, id)?;
+        }
+        tx.commit()?;
+        Ok(true)
+    }
+
+    /// Undo [`Self::run_compression`] for one row: put the archived original
+    /// back into the live table (re-creating the row if it was deleted since)
+    /// and drop the archive copy.  Returns `false` if nothing was archived
+    /// under `id`, which is always the case for profile and session memory.
+    pub fn restore_compressed(&self, table: MemoryTable, id: &str) -> Result<bool> {
+        let Some((archive, column, columns)) = table.compression() else {
+            return Ok(false);
+        };
+        let table_name = table.table_name();
+        let mut conn = self.db.conn();
+        let tx = conn.transaction()?;
+
+        let original: Option<Option<String>> = tx
+            .query_row(
+                &format!("SELECT {column} FROM {archive} WHERE id = ?1"),
+                params![id],
+                |row| row.get(0),
+            )
+            .optional()
+            .context("restore archive select")?;
+        let Some(original) = original else {
+            return Ok(false);
+        };
+        let summary: Option<Option<String>> = tx
+            .query_row(
+                &format!("SELECT {column} FROM {table_name} WHERE id = ?1"),
+                params![id],
+                |row| row.get(0),
+            )
+            .optional()
+            .context("restore live select")?;
+
+        if summary.is_some() {
+            tx.execute(
+                &format!(
+                    "UPDATE {table_name}
+                     SET {column} = (SELECT {column} FROM {archive} WHERE id = ?1)
+                     WHERE id = ?1"
+                ),
+                params![id],
+            )
+        } else {
+            tx.execute(
+                &format!(
+                    "INSERT INTO {table_name} ({columns}) SELECT {columns} FROM {archive} WHERE id = ?1"
+                ),
+                params![id],
+            )
+        }
+        .context("restore live row")?;
+        self.embed_row(&tx, table, id)?;
+        tx.execute(&format!("DELETE FROM {archive} WHERE id = ?1"), params![id])
+            .context("restore archive delete")?;
+
+        let audit_id = Uuid::new_v4().to_string();
+        tx.execute(
+            "INSERT INTO audit_log (id, table_name, record_id, agent_name, old_value, new_value)
+             VALUES (?1, ?2, ?3, 'system', ?4, ?5)",
+            params![
+                audit_id,
+                table_name,
+                id,
+                summary.flatten().unwrap_or_else(|| "deleted".to_string()),
+                original
+            ],
+        )
+        .context("restore audit_log insert")?;
+        tx.commit()?;
+        debug!(%id, table = table_name, "Restored compressed memory record");
+        Ok(true)
+    }
+
     /// Archive old session memory entries.
     ///
     /// For each `session_id` whose most recent entry is older than `max_age_hours`,
@@ -636,107 +1589,184 @@ impl MemoryStore {
 
     // -- Private helpers ----------------------------------------------------
 
-    fn recall_profile_generic(&self, query: &str, limit: i32) -> Result<Vec<MemoryEntry>> {
-        let conn = self.db.conn();
-        let like = format!("%{query}%");
-        let mut stmt = conn.prepare(
-            "SELECT id, 'profile' AS level, key || '=' || value AS content,
-                    NULL AS metadata, confidence, created_at
-             FROM profile_memory
-             WHERE key LIKE ?1 OR value LIKE ?1
-             LIMIT ?2",
-        )?;
-        let rows = stmt
-            .query_map(params![like, limit], |row| {
-                Ok(MemoryEntry {
-                    id: row.get(0)?,
-                    level: row.get(1)?,
-                    content: row.get(2)?,
-                    metadata: row.get(3)?,
-                    confidence: row.get(4)?,
-                    created_at: row.get(5)?,
-                })
-            })?
-            .collect::<std::result::Result<Vec<_>, _>>()?;
-        Ok(rows)
+    /// (Re)compute the embedding of one row from its source text.
+    fn embed_row(&self, conn: &Connection, table: MemoryTable, id: &str) -> Result<()> {
+        let text: String = conn
+            .query_row(
+                &format!(
+                    "SELECT {} FROM {} WHERE id = ?1",
+                    table.source_text_sql(),
+                    table.table_name()
+                ),
+                params![id],
+                |row| row.get(0),
+            )
+            .context("embedding source select")?;
+        let blob = embedding::to_blob(&self.embedder.embed(&text));
+        conn.execute(
+            &format!(
+                "UPDATE {} SET embedding = ?1 WHERE id = ?2",
+                table.table_name()
+            ),
+            params![blob, id],
+        )
+        .context("embedding update")?;
+        Ok(())
     }
 
-    fn recall_session_generic(&self, query: &str, limit: i32) -> Result<Vec<MemoryEntry>> {
-        let conn = self.db.conn();
-        let like = format!("%{query}%");
-        let mut stmt = conn.prepare(
-            "SELECT id, 'session' AS level, content,
-                    metadata, 1.0 AS confidence, created_at
-             FROM session_memory
-             WHERE content LIKE ?1
-             ORDER BY created_at DESC
-             LIMIT ?2",
-        )?;
-        let rows = stmt
-            .query_map(params![like, limit], |row| {
-                Ok(MemoryEntry {
-                    id: row.get(0)?,
-                    level: row.get(1)?,
-                    content: row.get(2)?,
-                    metadata: row.get(3)?,
-                    confidence: row.get(4)?,
-                    created_at: row.get(5)?,
-                })
-            })?
-            .collect::<std::result::Result<Vec<_>, _>>()?;
-        Ok(rows)
+    /// Score every row of `table` against `query`, best first.
+    ///
+    /// `fts_query` overrides the FTS5 MATCH expression; by default it ORs
+    /// the query tokens as prefixes.
+    fn rank(
+        &self,
+        table: MemoryTable,
+        query: &str,
+        fts_query: Option<&str>,
+    ) -> Result<Vec<MemoryEntry>> {
+        let tokens = embedding::tokenize(query);
+        let (candidates, matches) = {
+            let conn = self.db.conn();
+            let candidates = conn
+                .prepare(&format!(
+                    "SELECT {} FROM {}",
+                    table.recall_columns_sql(),
+                    table.table_name()
+                ))?
+                .query_map([], |row| {
+                    Ok(Candidate {
+                        entry: MemoryEntry {
+                            id: row.get(0)?,
+                            level: table.level().to_string(),
+                            content: row.get(1)?,
+                            metadata: row.get(2)?,
+                            confidence: row.get(3)?,
+                            created_at: row.get(4)?,
+                            score: 0.0,
+                            snippet: None,
+                        },
+                        touched_at: row.get(5)?,
+                        use_count: row.get(6)?,
+                        embedding: row.get(7)?,
+                        confidence_since: row.get(8)?,
+                    })
+                })?
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+
+            let matches = if tokens.is_empty() {
+                HashMap::new()
+            } else {
+                let default_query = fts_or_query(&tokens);
+                self.fts_matches(&conn, table, fts_query.unwrap_or(&default_query))?
+            };
+            (candidates, matches)
+        };
+        // Normalise BM25 against the best hit in this table.
+        let best_bm25 = matches.values().map(|m| m.bm25).fold(0.0, f64::max);
+
+        let w = self.weights;
+        let query_vec = self.embedder.embed(query);
+        let dims = self.embedder.dimensions();
+        let mut entries: Vec<MemoryEntry> = candidates
+            .into_iter()
+            .filter_map(|c| {
+                let hit = matches.get(&c.entry.id);
+                let lexical = match hit {
+                    Some(m) if best_bm25 > 0.0 => m.bm25 / best_bm25,
+                    Some(_) => 1.0,
+                    None => 0.0,
+                };
+                let semantic = c
+                    .embedding
+                    .as_deref()
+                    .and_then(|blob| embedding::from_blob(blob, dims))
+                    .map_or(0.0, |v| embedding::cosine(&query_vec, &v).max(0.0) as f64);
+                if hit.is_none() && semantic < w.min_similarity {
+                    return None;
+                }
+                let mut entry = c.entry;
+                if let Some(since) = &c.confidence_since {
+                    entry.confidence = self.confidence.decay(entry.confidence, since);
+                }
+                entry.snippet = hit.map(|m| m.snippet.clone());
+                entry.score = (w.lexical * lexical
+                    + w.semantic * semantic
+                    + w.recency * recency_boost(&c.touched_at, w.recency_half_life_days)
+                    + w.usage * usage_boost(c.use_count))
+                    * (1.0 - w.confidence + w.confidence * entry.confidence);
+                Some(entry)
+            })
+            .collect();
+        entries.sort_by(|a, b| b.score.total_cmp(&a.score));
+        Ok(entries)
     }
 
-    fn recall_task_generic(&self, query: &str, limit: i32) -> Result<Vec<MemoryEntry>> {
-        let conn = self.db.conn();
-        let mut stmt = conn.prepare(
-            "SELECT t.id, 'task' AS level, t.task_description AS content,
-                    t.outcome AS metadata, 1.0 AS confidence, t.created_at
-             FROM task_memory t
-             JOIN task_memory_fts f ON t.rowid = f.rowid
-             WHERE task_memory_fts MATCH ?1
-             ORDER BY f.rank
-             LIMIT ?2",
-        )?;
-        let rows = stmt
-            .query_map(params![query, limit], |row| {
-                Ok(MemoryEntry {
-                    id: row.get(0)?,
-                    level: row.get(1)?,
-                    content: row.get(2)?,
-                    metadata: row.get(3)?,
-                    confidence: row.get(4)?,
-                    created_at: row.get(5)?,
-                })
-            })?
-            .collect::<std::result::Result<Vec<_>, _>>()?;
-        Ok(rows)
+    /// Full-text hits in `table`'s FTS5 index, keyed by row id.
+    fn fts_matches(
+        &self,
+        conn: &Connection,
+        table: MemoryTable,
+        fts_query: &str,
+    ) -> Result<HashMap<String, FtsMatch>> {
+        let fts = table.fts_table();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT t.id, {fts}.rank, snippet({fts}, -1, ?2, ?3, '…', ?4)
+             FROM {} t
+             JOIN {fts} ON t.rowid = {fts}.rowid
+             WHERE {fts} MATCH ?1",
+            table.table_name()
+        ))?;
+        let matches = stmt
+            .query_map(
+                params![fts_query, SNIPPET_OPEN, SNIPPET_CLOSE, SNIPPET_TOKENS],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        FtsMatch {
+                            // rank is bm25(), which is negative; keep the magnitude.
+                            bm25: -row.get::<_, f64>(1)?,
+                            snippet: row.get(2)?,
+                        },
+                    ))
+                },
+            )?
+            .collect::<std::result::Result<HashMap<_, _>, _>>()
+            .with_context(|| format!("{fts} match"))?;
+        Ok(matches)
     }
 
-    fn recall_agent_generic(&self, query: &str, limit: i32) -> Result<Vec<MemoryEntry>> {
+    /// Load full [`TaskEntry`] rows for ranked task results, keeping the order.
+    fn task_entries(&self, ranked: Vec<MemoryEntry>, limit: i32) -> Result<Vec<TaskEntry>> {
         let conn = self.db.conn();
-        let like = format!("%{query}%");
         let mut stmt = conn.prepare(
-            "SELECT id, 'agent' AS level, content,
-                    memory_type AS metadata, confidence, created_at
-             FROM agent_memory
-             WHERE content LIKE ?1
-             ORDER BY confidence DESC
-             LIMIT ?2",
+            "SELECT id, task_description, COALESCE(task_category, ''),
+                    COALESCE(outcome, ''), COALESCE(procedure_steps, ''),
+                    COALESCE(error_patterns, ''), COALESCE(fix_patterns, ''),
+                    COALESCE(duration_seconds, 0), COALESCE(session_id, ''),
+                    created_at
+             FROM task_memory WHERE id = ?1",
         )?;
-        let rows = stmt
-            .query_map(params![like, limit], |row| {
-                Ok(MemoryEntry {
-                    id: row.get(0)?,
-                    level: row.get(1)?,
-                    content: row.get(2)?,
-                    metadata: row.get(3)?,
-                    confidence: row.get(4)?,
-                    created_at: row.get(5)?,
+        ranked
+            .iter()
+            .take(limit.max(0) as usize)
+            .map(|entry| {
+                stmt.query_row(params![entry.id], |row| {
+                    Ok(TaskEntry {
+                        id: row.get(0)?,
+                        task_description: row.get(1)?,
+                        task_category: row.get(2)?,
+                        outcome: row.get(3)?,
+                        procedure_steps: row.get(4)?,
+                        error_patterns: row.get(5)?,
+                        fix_patterns: row.get(6)?,
+                        duration_seconds: row.get(7)?,
+                        session_id: row.get(8)?,
+                        created_at: row.get(9)?,
+                    })
                 })
-            })?
-            .collect::<std::result::Result<Vec<_>, _>>()?;
-        Ok(rows)
+                .context("task_entries select")
+            })
+            .collect()
     }
 }
 
@@ -827,12 +1857,15 @@ mod tests {
             .unwrap();
         assert!(!id2.is_empty());
 
-        // Verify via recall
+        // Verify via recall: the exact phrase ranks first, the related
+        // "install" event is still recalled below it.
         let results = store
             .recall("npm install", MemoryScope::Session, 10)
             .unwrap();
-        assert_eq!(results.len(), 1);
+        assert_eq!(results.len(), 2);
         assert_eq!(results[0].level, "session");
+        assert_eq!(results[0].content, "ran npm install");
+        assert!(results[0].score > results[1].score);
     }
 
     // -- store_task_outcome -------------------------------------------------
@@ -1056,6 +2089,223 @@ mod tests {
         assert_eq!(results2[0].task_description, "Fix database connection");
     }
 
+    // -- hybrid recall ------------------------------------------------------
+
+    #[test]
+    fn test_recall_matches_paraphrased_errors() {
+        let store = test_store();
+        store
+            .store_task_outcome(
+                "Fix npm install",
+                "dependency",
+                "success",
+                r#"["npm ci"]"#,
+                r#"["ERESOLVE could not resolve"]"#,
+                r#"["use --legacy-peer-deps"]"#,
+                10,
+                "{}",
+                "s1",
+            )
+            .unwrap();
+        store
+            .store_task_outcome(
+                "Configure PostgreSQL connection",
+                "database",
+                "success",
+                r#"["edit .env"]"#,
+                r#"["ECONNREFUSED"]"#,
+                r#"["start pg service"]"#,
+                15,
+                "{}",
+                "s2",
+            )
+            .unwrap();
+
+        // No query token occurs in either task's error/fix patterns, so
+        // FTS5 alone finds nothing.
+        let query = "postgres refused connections";
+        let fts_only = store
+            .db
+            .conn()
+            .query_row(
+                "SELECT COUNT(*) FROM task_memory_fts WHERE task_memory_fts MATCH ?1",
+                params![format!(
+                    "{{error_patterns fix_patterns}} : ({})",
+                    fts_or_query(&embedding::tokenize(query))
+                )],
+                |row| row.get::<_, i64>(0),
+            )
+            .unwrap();
+        assert_eq!(fts_only, 0);
+
+        let results = store.recall_error_patterns(query, 5).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].task_description,
+            "Configure PostgreSQL connection"
+        );
+    }
+
+    #[test]
+    fn test_recall_semantic_match_in_every_table() {
+        let store = test_store();
+        store
+            .store_profile("env", "package_manager", "homebrew", "agent")
+            .unwrap();
+        store
+            .store_session("s1", 0, "bob", "action", "ran brew upgrade", None)
+            .unwrap();
+        store
+            .store_task_outcome(
+                "Upgrade homebrew packages",
+                "maintenance",
+                "success",
+                r#"["brew update","brew upgrade"]"#,
+                "[]",
+                "[]",
+                60,
+                "{}",
+                "s1",
+            )
+            .unwrap();
+        store
+            .store_agent_learning(
+                "bob",
+                "preference",
+                "user manages packages with homebrew",
+                0.9,
+            )
+            .unwrap();
+
+        let results = store
+            .recall("homebrews packaging", MemoryScope::All, 10)
+            .unwrap();
+        let levels: Vec<&str> = results.iter().map(|e| e.level.as_str()).collect();
+        for level in ["profile", "task", "agent"] {
+            assert!(
+                levels.contains(&level),
+                "missing {level} result: {levels:?}"
+            );
+        }
+        assert!(results.windows(2).all(|w| w[0].score >= w[1].score));
+    }
+
+    #[test]
+    fn test_recall_use_count_boost() {
+        let store = test_store();
+        let rarely = store
+            .store_agent_learning("bob", "fact", "prefers tabs for indentation", 0.5)
+            .unwrap();
+        let often = store
+            .store_agent_learning("bob", "fact", "prefers tabs for indentation", 0.5)
+            .unwrap();
+        for _ in 0..5 {
+            store.increment_use_count(&often).unwrap();
+        }
+
+        let results = store.recall("tabs", MemoryScope::Agent, 10).unwrap();
+        assert_eq!(results[0].id, often);
+        assert_eq!(results[1].id, rarely);
+    }
+
+    #[test]
+    fn test_recall_snippets_highlight_matches() {
+        let store = test_store();
+        store
+            .store_agent_learning(
+                "bob",
+                "preference",
+                "User prefers verbose output when running cargo builds",
+                0.8,
+            )
+            .unwrap();
+
+        let results = store
+            .recall("verbose cargo", MemoryScope::Agent, 5)
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        let snippet = results[0]
+            .snippet
+            .as_deref()
+            .expect("lexical hit has a snippet");
+        assert!(snippet.contains("**verbose**"), "snippet: {snippet}");
+        assert!(snippet.contains("**cargo**"), "snippet: {snippet}");
+    }
+
+    #[test]
+    fn test_recall_prefix_matches_and_profile_replacement() {
+        let store = test_store();
+        store
+            .store_profile("env", "editor", "neovim", "agent")
+            .unwrap();
+        store
+            .store_profile("env", "editor", "helix", "user")
+            .unwrap();
+
+        // Replaced values must not linger in profile_memory_fts.
+        let stale = store.recall("neovim", MemoryScope::Profile, 5).unwrap();
+        assert!(stale.iter().all(|e| e.snippet.is_none()), "{stale:?}");
+
+        let results = store.recall("edit", MemoryScope::Profile, 5).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].content, "editor=helix");
+        assert!(results[0]
+            .snippet
+            .as_deref()
+            .unwrap()
+            .contains("**editor**"));
+    }
+
+    /// Embedder that maps everything onto one axis, so every row is "similar".
+    struct ConstantEmbedder;
+
+    impl Embedder for ConstantEmbedder {
+        fn name(&self) -> &str {
+            "constant"
+        }
+        fn dimensions(&self) -> usize {
+            2
+        }
+        fn embed(&self, _text: &str) -> Vec<f32> {
+            vec![1.0, 0.0]
+        }
+    }
+
+    #[test]
+    fn test_custom_embedder_and_backfill() {
+        let db = Arc::new(LocalDb::open_in_memory().unwrap());
+        let hashing = MemoryStore::new(Arc::clone(&db));
+        hashing
+            .store_agent_learning("bob", "fact", "likes rust", 0.9)
+            .unwrap();
+        hashing
+            .store_session("s1", 0, "bob", "note", "unrelated", None)
+            .unwrap();
+        assert_eq!(hashing.backfill_embeddings().unwrap(), 0);
+
+        // Switching embedders invalidates the stored 256-d vectors.
+        let constant = MemoryStore::with_embedder(db, Arc::new(ConstantEmbedder));
+        assert!(constant
+            .recall("zzz", MemoryScope::All, 10)
+            .unwrap()
+            .is_empty());
+        assert_eq!(constant.backfill_embeddings().unwrap(), 2);
+        assert_eq!(
+            constant.recall("zzz", MemoryScope::All, 10).unwrap().len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_records_hide_embedding_column() {
+        let store = test_store();
+        let id = store
+            .store_agent_learning("bob", "fact", "likes rust", 0.9)
+            .unwrap();
+        let record = store.get_record(MemoryTable::Agent, &id).unwrap().unwrap();
+        assert!(!record.contains_key("embedding"));
+    }
+
     // -- forget -------------------------------------------------------------
 
     #[test]
@@ -1101,6 +2351,125 @@ mod tests {
         assert!(result.is_err(), "should reject invalid table names");
     }
 
+    #[test]
+    fn test_forget_missing_id() {
+        let store = test_store();
+        assert!(!store.forget("nope", "agent_memory").unwrap());
+    }
+
+    // -- curation -----------------------------------------------------------
+
+    #[test]
+    fn test_memory_table_parse() {
+        assert_eq!(MemoryTable::parse("agent").unwrap(), MemoryTable::Agent);
+        assert_eq!(
+            MemoryTable::parse("session_memory").unwrap(),
+            MemoryTable::Session
+        );
+        assert!(MemoryTable::parse("audit_log").is_err());
+    }
+
+    #[test]
+    fn test_list_records_pagination_and_filters() {
+        let store = test_store();
+        for i in 0..5 {
+            let agent = if i % 2 == 0 { "dr_bob" } else { "dr_alice" };
+            store
+                .store_agent_learning(agent, "fact", &format!("fact {i}"), 0.5)
+                .unwrap();
+        }
+
+        let mut query = ListQuery {
+            limit: 2,
+            ..Default::default()
+        };
+        let first = store.list_records(MemoryTable::Agent, &query).unwrap();
+        assert_eq!(first.records.len(), 2);
+        assert_eq!(first.records[0]["content"], "fact 0");
+        let cursor = first.next_cursor.expect("more pages");
+
+        query.cursor = Some(cursor);
+        query.limit = 10;
+        let rest = store.list_records(MemoryTable::Agent, &query).unwrap();
+        assert_eq!(rest.records.len(), 3);
+        assert_eq!(rest.records[0]["content"], "fact 2");
+        assert!(rest.next_cursor.is_none());
+
+        let filtered = store
+            .list_records(
+                MemoryTable::Agent,
+                &ListQuery {
+                    filters: vec![("agent_name".into(), "dr_alice".into())],
+                    limit: 10,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(filtered.records.len(), 2);
+
+        let bad = store.list_records(
+            MemoryTable::Agent,
+            &ListQuery {
+                filters: vec![("content; --".into(), "x".into())],
+                limit: 10,
+                ..Default::default()
+            },
+        );
+        assert!(bad.is_err(), "unknown filter columns must be rejected");
+    }
+
+    #[test]
+    fn test_update_record_with_audit_history() {
+        let store = test_store();
+        let id = store
+            .store_agent_learning("dr_bob", "preference", "likes cats", 0.5)
+            .unwrap();
+
+        let mut changes = Map::new();
+        changes.insert("content".into(), Value::from("likes dogs"));
+        changes.insert("confidence".into(), Value::from(0.9));
+        let updated = store
+            .update_record(MemoryTable::Agent, &id, &changes, "curator")
+            .unwrap()
+            .expect("row exists");
+        assert_eq!(updated["content"], "likes dogs");
+        assert_eq!(updated["confidence"], 0.9);
+
+        store.forget(&id, "agent_memory").unwrap();
+        assert!(store.get_record(MemoryTable::Agent, &id).unwrap().is_none());
+
+        let history = store.audit_history(MemoryTable::Agent, &id).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].agent_name.as_deref(), Some("curator"));
+        let old: Value = serde_json::from_str(history[0].old_value.as_ref().unwrap()).unwrap();
+        assert_eq!(old["content"], "likes cats");
+        assert_eq!(history[1].old_value.as_deref(), Some("deleted"));
+    }
+
+    #[test]
+    fn test_update_record_rejects_bad_input() {
+        let store = test_store();
+        let id = store
+            .store_profile("system", "os", "macOS", "agent")
+            .unwrap();
+
+        let mut changes = Map::new();
+        changes.insert("id".into(), Value::from("hijacked"));
+        assert!(store
+            .update_record(MemoryTable::Profile, &id, &changes, "curator")
+            .is_err());
+        assert!(store
+            .update_record(MemoryTable::Profile, &id, &Map::new(), "curator")
+            .is_err());
+
+        let mut changes = Map::new();
+        changes.insert("value".into(), Value::from("Linux"));
+        assert!(store
+            .update_record(MemoryTable::Profile, "missing", &changes, "curator")
+            .unwrap()
+            .is_none());
+    }
+
     // -- increment_use_count ------------------------------------------------
 
     #[test]
@@ -1191,46 +2560,252 @@ mod tests {
         assert!(empty.is_empty());
     }
 
+    // -- confidence ---------------------------------------------------------
+
+    #[test]
+    fn test_confidence_model_math() {
+        let model = ConfidenceModel::default();
+        assert!((model.reinforce(0.5) - 0.6).abs() < 1e-9);
+        assert!((model.penalize(0.5) - 0.35).abs() < 1e-9);
+        assert_eq!(model.reinforce(1.0), 1.0);
+        assert_eq!(model.penalize(0.06), model.floor);
+        // Values already under the floor are never raised by a penalty.
+        assert_eq!(model.penalize(0.01), 0.01);
+
+        let half_life_ago = (Utc::now() - chrono::Duration::days(90)).to_rfc3339();
+        assert!((model.decay(0.8, &half_life_ago) - 0.4).abs() < 0.01);
+        let ancient = (Utc::now() - chrono::Duration::days(3650)).to_rfc3339();
+        assert_eq!(model.decay(0.8, &ancient), model.floor);
+        assert_eq!(model.decay(0.8, "not a timestamp"), 0.8);
+    }
+
+    /// Backdate a row's `updated_at` so its confidence has decayed.
+    fn backdate(store: &MemoryStore, table: &str, id: &str, days: i64) {
+        store
+            .db
+            .conn()
+            .execute(
+                &format!(
+                    "UPDATE {table}
+                     SET updated_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now', ?1)
+                     WHERE id = ?2"
+                ),
+                params![format!("-{days} days"), id],
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_recall_profile_decays_confidence() {
+        let store = test_store();
+        let fresh = store.store_profile("env", "shell", "zsh", "user").unwrap();
+        let stale = store.store_profile("env", "editor", "vim", "user").unwrap();
+        backdate(&store, "profile_memory", &stale, 180);
+
+        let entries = store.recall_profile("env").unwrap();
+        let confidence = |id: &str| entries.iter().find(|e| e.id == id).unwrap().confidence;
+        assert_eq!(confidence(&fresh), 1.0);
+        assert!(
+            (confidence(&stale) - 0.25).abs() < 0.01,
+            "{}",
+            confidence(&stale)
+        );
+    }
+
+    #[test]
+    fn test_recall_ranks_confident_memories_first() {
+        let store = test_store();
+        let doubtful = store
+            .store_agent_learning("bob", "fact", "the build cache lives in /var/cache", 0.9)
+            .unwrap();
+        let trusted = store
+            .store_agent_learning("bob", "fact", "the build cache lives in /var/cache", 0.9)
+            .unwrap();
+        backdate(&store, "agent_memory", &doubtful, 365);
+
+        let results = store.recall("build cache", MemoryScope::Agent, 10).unwrap();
+        assert_eq!(results[0].id, trusted);
+        assert_eq!(results[1].id, doubtful);
+    }
+
+    #[test]
+    fn test_record_memory_outcome() {
+        let store = test_store();
+        let id = store
+            .store_agent_learning("bob", "fix", "restart the docker daemon", 0.5)
+            .unwrap();
+
+        let up = store
+            .record_memory_outcome(MemoryTable::Agent, &id, true, "tester")
+            .unwrap()
+            .unwrap();
+        assert!((up - 0.6).abs() < 1e-6);
+        let down = store
+            .record_memory_outcome(MemoryTable::Agent, &id, false, "tester")
+            .unwrap()
+            .unwrap();
+        assert!((down - 0.42).abs() < 1e-6);
+
+        let (use_count, confidence): (i64, f64) = store
+            .db
+            .conn()
+            .query_row(
+                "SELECT use_count, confidence FROM agent_memory WHERE id = ?1",
+                params![id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(use_count, 1, "only the successful use counts");
+        assert!((confidence - down).abs() < 1e-9);
+
+        let history = store.audit_history(MemoryTable::Agent, &id).unwrap();
+        assert_eq!(history.len(), 2);
+        assert!(history
+            .iter()
+            .all(|h| h.agent_name.as_deref() == Some("tester")));
+
+        assert!(store
+            .record_memory_outcome(MemoryTable::Agent, "missing", true, "tester")
+            .unwrap()
+            .is_none());
+        assert!(store
+            .record_memory_outcome(MemoryTable::Task, &id, true, "tester")
+            .is_err());
+    }
+
+    #[test]
+    fn test_apply_task_feedback() {
+        let store = test_store();
+        let a = store
+            .store_agent_learning("bob", "fix", "pin node to 18", 0.5)
+            .unwrap();
+        let b = store
+            .store_agent_learning("bob", "fix", "delete package-lock.json", 0.5)
+            .unwrap();
+        let ids = vec![a.clone(), b.clone(), "unknown".to_string()];
+        let confidence = |id: &str| -> f64 {
+            store
+                .db
+                .conn()
+                .query_row(
+                    "SELECT confidence FROM agent_memory WHERE id = ?1",
+                    params![id],
+                    |row| row.get(0),
+                )
+                .unwrap()
+        };
+
+        assert_eq!(store.apply_task_feedback("partial", &ids, "t").unwrap(), 0);
+        assert_eq!(confidence(&a), 0.5);
+
+        assert_eq!(store.apply_task_feedback("success", &ids, "t").unwrap(), 2);
+        assert!(confidence(&a) > 0.5 && confidence(&b) > 0.5);
+
+        assert_eq!(
+            store.apply_task_feedback("Failed", &ids[..1], "t").unwrap(),
+            1
+        );
+        assert!(confidence(&a) < confidence(&b));
+    }
+
+    #[test]
+    fn test_store_profile_contradictions_respect_source_priority() {
+        let store = test_store();
+        let id = store
+            .store_profile("system", "shell", "fish", "user")
+            .unwrap();
+
+        // A less trusted source cannot overwrite the user's answer.
+        let kept = store
+            .store_profile("system", "shell", "bash", "agent")
+            .unwrap();
+        assert_eq!(kept, id);
+        let entries = store.recall_profile("system").unwrap();
+        assert_eq!(entries[0].value, "fish");
+        assert_eq!(entries[0].source, "user");
+        let history = store.audit_history(MemoryTable::Profile, &id).unwrap();
+        assert_eq!(history.len(), 1);
+        let rejected: serde_json::Value =
+            serde_json::from_str(history[0].new_value.as_deref().unwrap()).unwrap();
+        assert_eq!(rejected["rejected"], "bash");
+        assert_eq!(rejected["kept_source"], "user");
+
+        // An equally trusted source replaces the value.
+        let replaced = store
+            .store_profile("system", "shell", "nu", "user")
+            .unwrap();
+        assert_ne!(replaced, id);
+        assert_eq!(store.recall_profile("system").unwrap()[0].value, "nu");
+    }
+
+    #[test]
+    fn test_store_profile_same_value_reinforces() {
+        let store = test_store();
+        let id = store
+            .store_profile("system", "arch", "arm64", "agent")
+            .unwrap();
+        backdate(&store, "profile_memory", &id, 90);
+
+        let again = store
+            .store_profile("system", "arch", "arm64", "detected")
+            .unwrap();
+        assert_eq!(again, id);
+        let entry = &store.recall_profile("system").unwrap()[0];
+        assert_eq!(entry.source, "detected", "more trusted source is kept");
+        assert!(
+            (entry.confidence - 0.6).abs() < 0.01,
+            "{}",
+            entry.confidence
+        );
+    }
+
     // -- run_compression ----------------------------------------------------
 
     #[test]
     fn test_run_compression_task_memory() {
         let store = test_store();
-        let conn = store.db.conn();
 
         // Insert a task_memory row with a created_at timestamp 100 days ago
         let old_steps = r#"["step one is to check the configuration","step two is to restart the daemon process","step three is to verify logs and confirm that everything is running correctly after restart"]"#;
-        conn.execute(
-            "INSERT INTO task_memory
+        store
+            .db
+            .conn()
+            .execute(
+                "INSERT INTO task_memory
                 (id, task_description, task_category, outcome, procedure_steps,
                  error_patterns, fix_patterns, duration_seconds, system_context,
                  session_id, created_at)
              VALUES ('tm-old', 'Old task', 'test', 'success', ?1,
                      '[\"err\"]', '[\"fix\"]', 10, '{}', 's1',
                      strftime('%Y-%m-%dT%H:%M:%SZ', 'now', '-100 days'))",
-            params![old_steps],
-        )
-        .unwrap();
+                params![old_steps],
+            )
+            .unwrap();
 
         // Insert a recent task_memory row (should NOT be compressed)
-        conn.execute(
-            "INSERT INTO task_memory
+        store
+            .db
+            .conn()
+            .execute(
+                "INSERT INTO task_memory
                 (id, task_description, task_category, outcome, procedure_steps,
                  error_patterns, fix_patterns, duration_seconds, system_context,
                  session_id, created_at)
              VALUES ('tm-new', 'New task', 'test', 'success', '[\"recent steps\"]',
                      '[]', '[]', 5, '{}', 's2',
                      strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))",
-            [],
-        )
-        .unwrap();
+                [],
+            )
+            .unwrap();
 
         // Run compression with 90-day threshold
         let compressed = store.run_compression(90).unwrap();
         assert_eq!(compressed, 1, "should compress exactly 1 task_memory entry");
 
         // Verify archive contains the original full row
-        let archived_steps: String = conn
+        let archived_steps: String = store
+            .db
+            .conn()
             .query_row(
                 "SELECT procedure_steps FROM task_memory_archive WHERE id = 'tm-old'",
                 [],
@@ -1242,23 +2817,29 @@ mod tests {
             "archive should have original procedure_steps"
         );
 
-        // Verify the live row was summarized (truncated to 100 chars + "...")
-        let summary: String = conn
+        // Verify the live row was summarized, still as a JSON array
+        let summary: String = store
+            .db
+            .conn()
             .query_row(
                 "SELECT procedure_steps FROM task_memory WHERE id = 'tm-old'",
                 [],
                 |row| row.get(0),
             )
             .unwrap();
-        assert!(
-            summary.len() <= 103,
-            "summary should be at most 103 chars (100 + '...'), got {}",
-            summary.len()
+        let steps: Vec<String> = serde_json::from_str(&summary).unwrap();
+        assert_eq!(
+            steps,
+            vec![
+                "step one is to check the configuration",
+                "(+2 more steps in archive)"
+            ]
         );
-        assert!(summary.ends_with("..."), "summary should end with '...'");
 
         // Verify the recent row was NOT touched
-        let recent_steps: String = conn
+        let recent_steps: String = store
+            .db
+            .conn()
             .query_row(
                 "SELECT procedure_steps FROM task_memory WHERE id = 'tm-new'",
                 [],
@@ -1268,7 +2849,9 @@ mod tests {
         assert_eq!(recent_steps, r#"["recent steps"]"#);
 
         // Verify no archive row for the recent task
-        let archive_count: i64 = conn
+        let archive_count: i64 = store
+            .db
+            .conn()
             .query_row(
                 "SELECT COUNT(*) FROM task_memory_archive WHERE id = 'tm-new'",
                 [],
@@ -1281,32 +2864,40 @@ mod tests {
     #[test]
     fn test_run_compression_agent_memory() {
         let store = test_store();
-        let conn = store.db.conn();
 
         // Insert an agent_memory row with old updated_at (100 days ago), no last_used_at
-        let long_content = "A".repeat(200);
-        conn.execute(
-            "INSERT INTO agent_memory
+        let long_content = "Postgres on this machine listens on port 5433. \
+                            The user mentioned liking dark themes once. \
+                            Connections failed with ECONNREFUSED until the port was set. \
+                            They also asked about the weather.";
+        store
+            .db
+            .conn()
+            .execute(
+                "INSERT INTO agent_memory
                 (id, agent_name, memory_type, content, confidence, use_count,
                  created_at, updated_at)
              VALUES ('am-old', 'dr_bob', 'fact', ?1, 0.9, 3,
                      strftime('%Y-%m-%dT%H:%M:%SZ', 'now', '-100 days'),
                      strftime('%Y-%m-%dT%H:%M:%SZ', 'now', '-100 days'))",
-            params![long_content],
-        )
-        .unwrap();
+                params![long_content],
+            )
+            .unwrap();
 
         // Insert a recent agent_memory row (should NOT be compressed)
-        conn.execute(
-            "INSERT INTO agent_memory
+        store
+            .db
+            .conn()
+            .execute(
+                "INSERT INTO agent_memory
                 (id, agent_name, memory_type, content, confidence, use_count,
                  created_at, updated_at)
              VALUES ('am-new', 'dr_bob', 'fact', 'recent memory', 0.8, 0,
                      strftime('%Y-%m-%dT%H:%M:%SZ', 'now'),
                      strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))",
-            [],
-        )
-        .unwrap();
+                [],
+            )
+            .unwrap();
 
         let compressed = store.run_compression(90).unwrap();
         assert_eq!(
@@ -1315,7 +2906,9 @@ mod tests {
         );
 
         // Verify archive has original content
-        let archived_content: String = conn
+        let archived_content: String = store
+            .db
+            .conn()
             .query_row(
                 "SELECT content FROM agent_memory_archive WHERE id = 'am-old'",
                 [],
@@ -1325,18 +2918,25 @@ mod tests {
         assert_eq!(archived_content, long_content);
 
         // Verify live row was summarized
-        let summary: String = conn
+        let summary: String = store
+            .db
+            .conn()
             .query_row(
                 "SELECT content FROM agent_memory WHERE id = 'am-old'",
                 [],
                 |row| row.get(0),
             )
             .unwrap();
-        assert_eq!(summary.len(), 103, "should be 100 chars + '...'");
-        assert!(summary.ends_with("..."));
+        assert_eq!(
+            summary,
+            "Postgres on this machine listens on port 5433. … \
+             Connections failed with ECONNREFUSED until the port was set."
+        );
 
         // Recent row untouched
-        let recent: String = conn
+        let recent: String = store
+            .db
+            .conn()
             .query_row(
                 "SELECT content FROM agent_memory WHERE id = 'am-new'",
                 [],
@@ -1349,23 +2949,27 @@ mod tests {
     #[test]
     fn test_run_compression_skips_short_content() {
         let store = test_store();
-        let conn = store.db.conn();
 
         // Insert a task_memory row with short procedure_steps (under 100 chars)
-        conn.execute(
-            "INSERT INTO task_memory
+        store
+            .db
+            .conn()
+            .execute(
+                "INSERT INTO task_memory
                 (id, task_description, procedure_steps, created_at)
              VALUES ('tm-short', 'Short task', '[\"one\"]',
                      strftime('%Y-%m-%dT%H:%M:%SZ', 'now', '-100 days'))",
-            [],
-        )
-        .unwrap();
+                [],
+            )
+            .unwrap();
 
         let compressed = store.run_compression(90).unwrap();
         assert_eq!(compressed, 1, "row should still be processed");
 
         // The procedure_steps should remain unchanged (no '...' since < 100 chars)
-        let steps: String = conn
+        let steps: String = store
+            .db
+            .conn()
             .query_row(
                 "SELECT procedure_steps FROM task_memory WHERE id = 'tm-short'",
                 [],
@@ -1375,7 +2979,9 @@ mod tests {
         assert_eq!(steps, r#"["one"]"#, "short content should stay unchanged");
 
         // But the archive should still have a copy
-        let archive_count: i64 = conn
+        let archive_count: i64 = store
+            .db
+            .conn()
             .query_row(
                 "SELECT COUNT(*) FROM task_memory_archive WHERE id = 'tm-short'",
                 [],
@@ -1388,26 +2994,30 @@ mod tests {
     #[test]
     fn test_profile_memory_never_compressed() {
         let store = test_store();
-        let conn = store.db.conn();
 
         // Insert a profile_memory row with old timestamps
-        conn.execute(
-            "INSERT INTO profile_memory
+        store
+            .db
+            .conn()
+            .execute(
+                "INSERT INTO profile_memory
                 (id, category, key, value, confidence, source,
                  created_at, updated_at)
              VALUES ('pm-old', 'system', 'os', 'macOS', 1.0, 'agent',
                      strftime('%Y-%m-%dT%H:%M:%SZ', 'now', '-200 days'),
                      strftime('%Y-%m-%dT%H:%M:%SZ', 'now', '-200 days'))",
-            [],
-        )
-        .unwrap();
+                [],
+            )
+            .unwrap();
 
         let compressed = store.run_compression(90).unwrap();
         // Profile memory has no TTL, so it should not be affected
         // (run_compression only touches task_memory and agent_memory)
 
         // Verify profile row still exists with original value
-        let val: String = conn
+        let val: String = store
+            .db
+            .conn()
             .query_row(
                 "SELECT value FROM profile_memory WHERE id = 'pm-old'",
                 [],
@@ -1418,35 +3028,165 @@ mod tests {
         assert_eq!(compressed, 0, "profile memory should never be compressed");
     }
 
+    fn insert_old_agent_memory(store: &MemoryStore, id: &str, content: &str) {
+        store
+            .db
+            .conn()
+            .execute(
+                "INSERT INTO agent_memory
+                (id, agent_name, memory_type, content, created_at, updated_at)
+             VALUES (?1, 'dr_bob', 'fact', ?2,
+                     strftime('%Y-%m-%dT%H:%M:%SZ', 'now', '-100 days'),
+                     strftime('%Y-%m-%dT%H:%M:%SZ', 'now', '-100 days'))",
+                params![id, content],
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_compression_keeps_first_original_and_restores_it() {
+        let store = test_store();
+        let original = "Brew lives in /opt/homebrew on this Mac. \
+                        Small talk about the weather went on for a while. \
+                        More small talk followed about lunch plans.";
+        insert_old_agent_memory(&store, "am-1", original);
+
+        assert_eq!(store.run_compression(90).unwrap(), 1);
+        // A second run must not archive the summary over the original.
+        assert_eq!(store.run_compression(90).unwrap(), 0);
+        let archived: String = store
+            .db
+            .conn()
+            .query_row(
+                "SELECT content FROM agent_memory_archive WHERE id = 'am-1'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(archived, original);
+        let live = store
+            .get_record(MemoryTable::Agent, "am-1")
+            .unwrap()
+            .unwrap();
+        assert_eq!(live["content"], "Brew lives in /opt/homebrew on this Mac.");
+
+        assert!(store
+            .restore_compressed(MemoryTable::Agent, "am-1")
+            .unwrap());
+        let live = store
+            .get_record(MemoryTable::Agent, "am-1")
+            .unwrap()
+            .unwrap();
+        assert_eq!(live["content"], original);
+        let hits = store.recall("lunch", MemoryScope::Agent, 5).unwrap();
+        assert_eq!(
+            hits[0].id, "am-1",
+            "restored text should be searchable again"
+        );
+
+        let history = store.audit_history(MemoryTable::Agent, "am-1").unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].new_value.as_deref(), Some(original));
+
+        assert!(!store
+            .restore_compressed(MemoryTable::Agent, "am-1")
+            .unwrap());
+        assert!(!store
+            .restore_compressed(MemoryTable::Profile, "am-1")
+            .unwrap());
+    }
+
+    #[test]
+    fn test_restore_recreates_forgotten_row() {
+        let store = test_store();
+        insert_old_agent_memory(&store, "am-gone", &"npm install failed. ".repeat(10));
+        store.run_compression(90).unwrap();
+        assert!(store.forget("am-gone", "agent_memory").unwrap());
+
+        assert!(store
+            .restore_compressed(MemoryTable::Agent, "am-gone")
+            .unwrap());
+        let live = store
+            .get_record(MemoryTable::Agent, "am-gone")
+            .unwrap()
+            .unwrap();
+        assert_eq!(live["content"], "npm install failed. ".repeat(10));
+        assert_eq!(live["agent_name"], "dr_bob");
+    }
+
+    struct FixedSummarizer(Option<&'static str>);
+
+    impl Summarizer for FixedSummarizer {
+        fn name(&self) -> &str {
+            "fixed"
+        }
+
+        fn summarize(&self, _input: &SummaryInput) -> Result<String> {
+            self.0.map(str::to_string).context("summarizer unavailable")
+        }
+    }
+
+    #[test]
+    fn test_custom_summarizer() {
+        let content = "a long memory ".repeat(20);
+
+        let store = test_store().with_summarizer(Arc::new(FixedSummarizer(Some("short"))));
+        insert_old_agent_memory(&store, "am-1", &content);
+        assert_eq!(store.run_compression(90).unwrap(), 1);
+        let live = store
+            .get_record(MemoryTable::Agent, "am-1")
+            .unwrap()
+            .unwrap();
+        assert_eq!(live["content"], "short");
+
+        // A failing summarizer leaves the row alone and un-archived.
+        let store = test_store().with_summarizer(Arc::new(FixedSummarizer(None)));
+        insert_old_agent_memory(&store, "am-1", &content);
+        assert_eq!(store.run_compression(90).unwrap(), 0);
+        let live = store
+            .get_record(MemoryTable::Agent, "am-1")
+            .unwrap()
+            .unwrap();
+        assert_eq!(live["content"], content.as_str());
+        assert!(!store
+            .restore_compressed(MemoryTable::Agent, "am-1")
+            .unwrap());
+    }
+
     // -- archive_old_sessions -----------------------------------------------
 
     #[test]
     fn test_archive_old_sessions() {
         let store = test_store();
-        let conn = store.db.conn();
 
         // Insert session events with old timestamps (48 hours ago)
         for i in 0..3 {
-            conn.execute(
-                "INSERT INTO session_memory
+            store
+                .db
+                .conn()
+                .execute(
+                    "INSERT INTO session_memory
                     (id, session_id, step_number, agent_name, event_type, content, created_at)
                  VALUES (?1, 'old-sess', ?2, 'dr_bob', 'action', ?3,
                          strftime('%Y-%m-%dT%H:%M:%SZ', 'now', '-48 hours'))",
-                params![format!("sm-old-{}", i), i, format!("old step {}", i)],
-            )
-            .unwrap();
+                    params![format!("sm-old-{}", i), i, format!("old step {}", i)],
+                )
+                .unwrap();
         }
 
         // Insert session events with recent timestamps (should NOT be archived)
         for i in 0..2 {
-            conn.execute(
-                "INSERT INTO session_memory
+            store
+                .db
+                .conn()
+                .execute(
+                    "INSERT INTO session_memory
                     (id, session_id, step_number, agent_name, event_type, content, created_at)
                  VALUES (?1, 'new-sess', ?2, 'dr_bob', 'action', ?3,
                          strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))",
-                params![format!("sm-new-{}", i), i, format!("new step {}", i)],
-            )
-            .unwrap();
+                    params![format!("sm-new-{}", i), i, format!("new step {}", i)],
+                )
+                .unwrap();
         }
 
         // Archive sessions older than 24 hours
@@ -1454,7 +3194,9 @@ mod tests {
         assert_eq!(archived, 3, "should archive 3 old session rows");
 
         // Old session should have exactly 1 summary row
-        let old_count: i64 = conn
+        let old_count: i64 = store
+            .db
+            .conn()
             .query_row(
                 "SELECT COUNT(*) FROM session_memory WHERE session_id = 'old-sess'",
                 [],
@@ -1463,7 +3205,9 @@ mod tests {
             .unwrap();
         assert_eq!(old_count, 1, "old session should have 1 summary row");
 
-        let (event_type, content): (String, String) = conn
+        let (event_type, content): (String, String) = store
+            .db
+            .conn()
             .query_row(
                 "SELECT event_type, content FROM session_memory WHERE session_id = 'old-sess'",
                 [],
@@ -1477,7 +3221,9 @@ mod tests {
         );
 
         // New session should be untouched
-        let new_count: i64 = conn
+        let new_count: i64 = store
+            .db
+            .conn()
             .query_row(
                 "SELECT COUNT(*) FROM session_memory WHERE session_id = 'new-sess'",
                 [],
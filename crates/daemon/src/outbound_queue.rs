@@ -0,0 +1,415 @@
+//! Persistent queue for cloud-bound messages.
+//!
+//! Every cloud-bound chat message is written to the `outbound_queue` table
+//! before it is sent, so it survives a dropped link, a daemon restart or a
+//! crash. It stays queued until the cloud socket writer has sent it and
+//! called [`OutboundQueue::mark_delivered`]; undelivered messages are
+//! replayed oldest first on reconnect:
+//!
+//! - each message keeps its id; enqueueing an id that is already queued or
+//!   was delivered recently is a no-op, so retries do not duplicate requests;
+//! - every replay attempt is counted, and a message that exceeds
+//!   [`OutboundQueueConfig::max_attempts`] or outlives its expiry is moved to
+//!   `outbound_dead_letters` instead of being sent.
+//!
+//! A crash between sending and [`OutboundQueue::mark_delivered`] replays the
+//! message once more under the same id, which the cloud can deduplicate.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, OptionalExtension, Row};
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::local_db::LocalDb;
+
+/// Tuning knobs for the outbound queue.
+#[derive(Debug, Clone)]
+pub struct OutboundQueueConfig {
+    /// Maximum number of undelivered messages.
+    pub max_len: usize,
+    /// Replay attempts before a message is dead-lettered.
+    pub max_attempts: u32,
+    /// How long a message may wait for delivery. Delivered ids are
+    /// remembered for the same time, for deduplication.
+    pub ttl: Duration,
+}
+
+impl Default for OutboundQueueConfig {
+    fn default() -> Self {
+        Self {
+            max_len: 1000,
+            max_attempts: 5,
+            ttl: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+}
+
+/// Result of [`OutboundQueue::enqueue`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnqueueOutcome {
+    Queued,
+    /// The id is already queued or was delivered within the TTL.
+    Duplicate,
+    /// The queue holds [`OutboundQueueConfig::max_len`] messages.
+    Full,
+}
+
+/// An undelivered message, in replay order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingMessage {
+    pub id: String,
+    pub payload: Vec<u8>,
+    /// Replay attempts so far, including the current one once
+    /// [`OutboundQueue::begin_attempt`] has been called.
+    pub attempts: u32,
+    pub queued_at: String,
+}
+
+impl PendingMessage {
+    /// How long the message has been waiting.
+    pub fn age(&self) -> Duration {
+        DateTime::parse_from_rfc3339(&self.queued_at)
+            .ok()
+            .and_then(|queued| (Utc::now() - queued.with_timezone(&Utc)).to_std().ok())
+            .unwrap_or_default()
+    }
+
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            payload: row.get(1)?,
+            attempts: row.get(2)?,
+            queued_at: row.get(3)?,
+        })
+    }
+}
+
+/// A message that was given up on.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeadLetter {
+    pub id: String,
+    #[serde(skip)]
+    pub payload: Vec<u8>,
+    pub attempts: u32,
+    pub queued_at: String,
+    pub failed_at: String,
+    pub reason: String,
+}
+
+/// Outbound message queue backed by the local database.
+pub struct OutboundQueue {
+    db: Arc<LocalDb>,
+    config: OutboundQueueConfig,
+}
+
+impl OutboundQueue {
+    pub fn new(db: Arc<LocalDb>, config: OutboundQueueConfig) -> Self {
+        Self { db, config }
+    }
+
+    /// Queue `payload` under `id`.
+    pub fn enqueue(&self, id: &str, payload: &[u8]) -> Result<EnqueueOutcome> {
+        let conn = self.db.conn();
+        let pending: usize = conn
+            .query_row(
+                "SELECT COUNT(*) FROM outbound_queue WHERE delivered_at IS NULL",
+                [],
+                |row| row.get(0),
+            )
+            .context("counting outbound queue")?;
+        let known: bool = conn
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM outbound_queue WHERE id = ?1)",
+                [id],
+                |row| row.get(0),
+            )
+            .context("checking outbound queue for duplicate")?;
+        if known {
+            return Ok(EnqueueOutcome::Duplicate);
+        }
+        if pending >= self.config.max_len {
+            return Ok(EnqueueOutcome::Full);
+        }
+
+        let now = Utc::now();
+        conn.execute(
+            "INSERT INTO outbound_queue (id, payload, queued_at, expires_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![id, payload, timestamp(now), timestamp(now + self.ttl())],
+        )
+        .context("outbound queue insert")?;
+        Ok(EnqueueOutcome::Queued)
+    }
+
+    /// Undelivered messages, oldest first. Expired messages are
+    /// dead-lettered first and not returned.
+    pub fn pending(&self) -> Result<Vec<PendingMessage>> {
+        self.expire()?;
+        let conn = self.db.conn();
+        let mut stmt = conn.prepare(
+            "SELECT id, payload, attempts, queued_at FROM outbound_queue
+             WHERE delivered_at IS NULL ORDER BY seq",
+        )?;
+        let rows = stmt
+            .query_map([], PendingMessage::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("reading outbound queue")?;
+        Ok(rows)
+    }
+
+    /// Number of undelivered messages.
+    pub fn len(&self) -> Result<usize> {
+        let count = self.db.conn().query_row(
+            "SELECT COUNT(*) FROM outbound_queue WHERE delivered_at IS NULL",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(count)
+    }
+
+    pub fn is_empty(&self) -> Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Count a replay attempt of `id` before sending it. Returns `false`
+    /// (and dead-letters the message) when it has run out of attempts.
+    pub fn begin_attempt(&self, id: &str) -> Result<bool> {
+        let attempts: Option<u32> = {
+            let conn = self.db.conn();
+            conn.query_row(
+                "UPDATE outbound_queue SET attempts = attempts + 1
+                 WHERE id = ?1 AND delivered_at IS NULL RETURNING attempts",
+                [id],
+                |row| row.get(0),
+            )
+            .optional()
+            .context("counting outbound attempt")?
+        };
+        match attempts {
+            Some(n) if n > self.config.max_attempts => {
+                let reason = format!("exceeded {} delivery attempts", self.config.max_attempts);
+                self.dead_letter(id, &reason)?;
+                Ok(false)
+            }
+            Some(_) => Ok(true),
+            None => Ok(false),
+        }
+    }
+
+    /// Whether `id` has already been sent, so a replayed copy can be
+    /// skipped.
+    pub fn is_delivered(&self, id: &str) -> Result<bool> {
+        let delivered = self
+            .db
+            .conn()
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM outbound_queue
+                 WHERE id = ?1 AND delivered_at IS NOT NULL)",
+                [id],
+                |row| row.get(0),
+            )
+            .context("checking outbound delivery")?;
+        Ok(delivered)
+    }
+
+    /// Record that `id` was written to the cloud socket.
+    pub fn mark_delivered(&self, id: &str) -> Result<()> {
+        self.db
+            .conn()
+            .execute(
+                "UPDATE outbound_queue SET delivered_at = ?2, last_error = NULL WHERE id = ?1",
+                params![id, timestamp(Utc::now())],
+            )
+            .context("marking outbound message delivered")?;
+        Ok(())
+    }
+
+    /// Record why sending `id` failed; it stays queued for the next replay.
+    pub fn mark_failed(&self, id: &str, error: &str) -> Result<()> {
+        self.db
+            .conn()
+            .execute(
+                "UPDATE outbound_queue SET last_error = ?2 WHERE id = ?1",
+                params![id, error],
+            )
+            .context("recording outbound failure")?;
+        Ok(())
+    }
+
+    /// Dead-letter undelivered messages past their expiry and forget
+    /// delivered ids older than the TTL. Returns the number dead-lettered.
+    pub fn expire(&self) -> Result<usize> {
+        let now = timestamp(Utc::now());
+        let mut conn = self.db.conn();
+        let tx = conn.transaction()?;
+        let expired = tx.execute(
+            "INSERT OR REPLACE INTO outbound_dead_letters
+                 (id, payload, attempts, queued_at, failed_at, reason)
+             SELECT id, payload, attempts, queued_at, ?1, 'expired'
+             FROM outbound_queue WHERE delivered_at IS NULL AND expires_at <= ?1",
+            [&now],
+        )?;
+        tx.execute("DELETE FROM outbound_queue WHERE expires_at <= ?1", [&now])?;
+        tx.commit().context("expiring outbound queue")?;
+        if expired > 0 {
+            warn!(expired, "Outbound messages expired before delivery");
+        }
+        Ok(expired)
+    }
+
+    /// Messages given up on, most recent first.
+    pub fn dead_letters(&self) -> Result<Vec<DeadLetter>> {
+        let conn = self.db.conn();
+        let mut stmt = conn.prepare(
+            "SELECT id, payload, attempts, queued_at, failed_at, reason
+             FROM outbound_dead_letters ORDER BY failed_at DESC, rowid DESC",
+        )?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok(DeadLetter {
+                    id: row.get(0)?,
+                    payload: row.get(1)?,
+                    attempts: row.get(2)?,
+                    queued_at: row.get(3)?,
+                    failed_at: row.get(4)?,
+                    reason: row.get(5)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("reading outbound dead letters")?;
+        Ok(rows)
+    }
+
+    /// Move `id` from the queue to the dead letters.
+    pub fn dead_letter(&self, id: &str, reason: &str) -> Result<()> {
+        let mut conn = self.db.conn();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT OR REPLACE INTO outbound_dead_letters
+                 (id, payload, attempts, queued_at, failed_at, reason)
+             SELECT id, payload, attempts, queued_at, ?2, ?3
+             FROM outbound_queue WHERE id = ?1",
+            params![id, timestamp(Utc::now()), reason],
+        )?;
+        tx.execute("DELETE FROM outbound_queue WHERE id = ?1", [id])?;
+        tx.commit().context("dead-lettering outbound message")?;
+        info!(id, reason, "Outbound message dead-lettered");
+        Ok(())
+    }
+
+    fn ttl(&self) -> chrono::Duration {
+        chrono::Duration::from_std(self.config.ttl).unwrap_or(chrono::Duration::MAX)
+    }
+}
+
+/// Millisecond timestamps so that rows queued in the same second still
+/// compare correctly against expiry.
+fn timestamp(t: DateTime<Utc>) -> String {
+    t.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue(config: OutboundQueueConfig) -> OutboundQueue {
+        OutboundQueue::new(Arc::new(LocalDb::open_in_memory().unwrap()), config)
+    }
+
+    #[test]
+    fn replays_in_order_and_deduplicates() {
+        let q = queue(OutboundQueueConfig::default());
+        assert_eq!(q.enqueue("m1", b"one").unwrap(), EnqueueOutcome::Queued);
+        assert_eq!(q.enqueue("m2", b"two").unwrap(), EnqueueOutcome::Queued);
+        assert_eq!(
+            q.enqueue("m1", b"one again").unwrap(),
+            EnqueueOutcome::Duplicate
+        );
+        assert_eq!(q.len().unwrap(), 2);
+
+        let pending = q.pending().unwrap();
+        let ids: Vec<_> = pending.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, ["m1", "m2"]);
+        assert_eq!(pending[0].payload, b"one");
+
+        assert!(q.begin_attempt("m1").unwrap());
+        assert!(!q.is_delivered("m1").unwrap());
+        q.mark_delivered("m1").unwrap();
+        assert!(q.is_delivered("m1").unwrap());
+        assert!(!q.is_delivered("unknown").unwrap());
+        assert_eq!(q.len().unwrap(), 1);
+
+        // A delivered id is remembered and not queued again
+        assert_eq!(q.enqueue("m1", b"one").unwrap(), EnqueueOutcome::Duplicate);
+        assert_eq!(q.pending().unwrap()[0].id, "m2");
+    }
+
+    #[test]
+    fn survives_reopening_the_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("memory.db");
+        let path = path.to_str().unwrap();
+        {
+            let db = Arc::new(LocalDb::open(path).unwrap());
+            let q = OutboundQueue::new(db, OutboundQueueConfig::default());
+            q.enqueue("m1", b"before restart").unwrap();
+            q.begin_attempt("m1").unwrap();
+        }
+        let db = Arc::new(LocalDb::open(path).unwrap());
+        let q = OutboundQueue::new(db, OutboundQueueConfig::default());
+        let pending = q.pending().unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].payload, b"before restart");
+        assert_eq!(pending[0].attempts, 1);
+        assert!(pending[0].age() < Duration::from_secs(60));
+    }
+
+    #[test]
+    fn exhausted_retries_are_dead_lettered() {
+        let q = queue(OutboundQueueConfig {
+            max_attempts: 2,
+            ..Default::default()
+        });
+        q.enqueue("m1", b"flaky").unwrap();
+        for _ in 0..2 {
+            assert!(q.begin_attempt("m1").unwrap());
+            q.mark_failed("m1", "connection reset").unwrap();
+        }
+        assert!(!q.begin_attempt("m1").unwrap());
+        assert!(q.is_empty().unwrap());
+
+        let dead = q.dead_letters().unwrap();
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].id, "m1");
+        assert_eq!(dead[0].attempts, 3);
+        assert_eq!(dead[0].reason, "exceeded 2 delivery attempts");
+        assert_eq!(dead[0].payload, b"flaky");
+    }
+
+    #[test]
+    fn expired_messages_are_dead_lettered() {
+        let q = queue(OutboundQueueConfig {
+            ttl: Duration::ZERO,
+            ..Default::default()
+        });
+        q.enqueue("m1", b"stale").unwrap();
+        assert!(q.pending().unwrap().is_empty());
+        assert_eq!(q.dead_letters().unwrap()[0].reason, "expired");
+    }
+
+    #[test]
+    fn full_queue_rejects_new_messages() {
+        let q = queue(OutboundQueueConfig {
+            max_len: 1,
+            ..Default::default()
+        });
+        q.enqueue("m1", b"a").unwrap();
+        assert_eq!(q.enqueue("m2", b"b").unwrap(), EnqueueOutcome::Full);
+        // Duplicates are still recognised
+        assert_eq!(q.enqueue("m1", b"a").unwrap(), EnqueueOutcome::Duplicate);
+    }
+}
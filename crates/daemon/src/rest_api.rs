@@ -1,24 +1,69 @@
-//! REST API server for memory search and health endpoints.
+//! REST API server for memory and health endpoints.
 //!
-//! Provides HTTP endpoints for the client to query agent memory
-//! and check daemon health status.
+//! Provides HTTP endpoints for the client to search and curate agent
+//! memory and check daemon health status.
+//!
+//! The server only listens on 127.0.0.1, but any local process can reach
+//! it, so endpoints that expose or lock away the owner's data take an
+//! [`Authorized`] extractor and require the daemon's [`ApiToken`].
 
 use axum::{
-    extract::Query, extract::State, http::StatusCode, response::IntoResponse, routing::get, Json,
-    Router,
+    extract::{FromRef, FromRequestParts, Path, Query, State},
+    http::{header::AUTHORIZATION, request::Parts, StatusCode},
+    response::sse::{Event, KeepAlive, Sse},
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
 };
+use futures::{Stream, StreamExt};
 use serde::{Deserialize, Serialize};
-use std::net::SocketAddr;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
 use std::sync::Arc;
+use tokio::sync::broadcast;
+
+use d1_common::config::FileAction;
 
+use crate::api_token::ApiToken;
 use crate::connection_state::{ConnectionState, ConnectionStateMachine};
+use crate::db_key::{DbEncryption, KeySource};
+use crate::file_policy::{FileDecision, FilePolicy};
+use crate::maintenance::{AlreadyRunning, MaintenanceRun, MaintenanceScheduler, MaintenanceStatus};
+use crate::memory_bundle::{self, ExportOptions, ImportSummary};
+use crate::memory_store::{
+    AuditEntry, ConflictPolicy, ListQuery, MemoryEntry, MemoryRecord, MemoryScope, MemoryStore,
+    MemoryTable, RecordPage,
+};
+use crate::redactor::{DetectionStats, RedactionAudit, RedactionReport, Redactor};
+
+/// Default number of results returned by `/api/memory/search`.
+const DEFAULT_SEARCH_LIMIT: usize = 10;
+
+/// Upper bound on `limit` for `/api/memory/search`.
+const MAX_SEARCH_LIMIT: usize = 100;
+
+/// Default page size for `/api/memory/{table}` listings.
+const DEFAULT_PAGE_LIMIT: usize = 50;
+
+/// Upper bound on `limit` for `/api/memory/{table}` listings.
+const MAX_PAGE_LIMIT: usize = 500;
+
+/// Actor recorded in `audit_log` for edits made through the REST API.
+const API_ACTOR: &str = "api";
+
+/// Actor recorded in `audit_log` for rows written by a bundle import.
+const IMPORT_ACTOR: &str = "import";
+
+/// Largest text or file `/api/redaction/preview` will scan.
+const MAX_PREVIEW_BYTES: usize = 1024 * 1024;
 
 /// Query parameters for memory search
 #[derive(Debug, Deserialize)]
 pub struct SearchParams {
     /// Search query string
     pub q: String,
-    /// Scope filter (e.g., "system", "user", "session")
+    /// Scope filter: "profile", "session", "task", "agent" or "all" (default)
     pub scope: Option<String>,
     /// Maximum number of results to return
     pub limit: Option<usize>,
@@ -29,16 +74,230 @@ pub struct SearchParams {
 pub struct MemorySearchResult {
     /// Unique identifier for the memory entry
     pub id: String,
-    /// The memory key or category
-    pub key: String,
+    /// Memory scope the entry came from ("profile", "session", "task", "agent")
+    pub scope: String,
     /// The memory content
+    pub content: String,
+    /// Scope-specific metadata (task outcome, agent memory type, ...)
+    pub metadata: Option<String>,
+    /// Confidence score (0.0 to 1.0)
+    pub confidence: f64,
+    /// Timestamp when the memory was created
+    pub created_at: String,
+    /// Hybrid relevance score (keyword + semantic + recency/usage boosts)
+    pub score: f64,
+    /// Matching fragment with query terms highlighted as `**term**`
+    pub snippet: Option<String>,
+}
+
+impl From<MemoryEntry> for MemorySearchResult {
+    fn from(entry: MemoryEntry) -> Self {
+        Self {
+            id: entry.id,
+            scope: entry.level,
+            content: entry.content,
+            metadata: entry.metadata,
+            confidence: entry.confidence,
+            created_at: entry.created_at,
+            score: entry.score,
+            snippet: entry.snippet,
+        }
+    }
+}
+
+/// Error body returned by REST handlers
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub error: String,
+}
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+fn api_error(status: StatusCode, message: impl Into<String>) -> ApiError {
+    (
+        status,
+        Json(ErrorResponse {
+            error: message.into(),
+        }),
+    )
+}
+
+/// Extractor that admits only requests carrying the daemon's [`ApiToken`]
+/// as `Authorization: Bearer <token>`; others get 401.
+pub struct Authorized;
+
+#[axum::async_trait]
+impl<S> FromRequestParts<S> for Authorized
+where
+    Arc<ApiToken>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, ApiError> {
+        let token = Arc::<ApiToken>::from_ref(state);
+        let presented = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        match presented {
+            Some(presented) if token.verify(presented) => Ok(Authorized),
+            _ => Err(api_error(
+                StatusCode::UNAUTHORIZED,
+                "missing or invalid API token",
+            )),
+        }
+    }
+}
+
+/// Body for POST /api/memory/profile
+#[derive(Debug, Deserialize)]
+pub struct CreateProfileRequest {
+    pub category: String,
+    pub key: String,
     pub value: String,
-    /// Source of the memory (e.g., "profile", "session", "user")
+    #[serde(default = "default_profile_source")]
     pub source: String,
-    /// Relevance score (0.0 to 1.0)
-    pub relevance: f64,
-    /// Timestamp when the memory was created/updated
-    pub timestamp: i64,
+}
+
+fn default_profile_source() -> String {
+    "user".to_string()
+}
+
+/// Body for POST /api/memory/session
+#[derive(Debug, Deserialize)]
+pub struct CreateSessionRequest {
+    pub session_id: String,
+    pub step_number: i32,
+    pub agent_name: String,
+    pub event_type: String,
+    pub content: String,
+    /// Arbitrary JSON; stored as text.
+    pub metadata: Option<Value>,
+}
+
+/// Body for POST /api/memory/task
+#[derive(Debug, Deserialize)]
+pub struct CreateTaskRequest {
+    pub task_description: String,
+    #[serde(default)]
+    pub task_category: String,
+    #[serde(default)]
+    pub outcome: String,
+    #[serde(default)]
+    pub procedure_steps: String,
+    #[serde(default)]
+    pub error_patterns: String,
+    #[serde(default)]
+    pub fix_patterns: String,
+    #[serde(default)]
+    pub duration_seconds: i64,
+    #[serde(default)]
+    pub system_context: String,
+    #[serde(default)]
+    pub session_id: String,
+}
+
+/// Body for POST /api/memory/agent
+#[derive(Debug, Deserialize)]
+pub struct CreateAgentRequest {
+    pub agent_name: String,
+    pub memory_type: String,
+    pub content: String,
+    #[serde(default = "default_agent_confidence")]
+    pub confidence: f64,
+}
+
+fn default_agent_confidence() -> f64 {
+    1.0
+}
+
+/// Body for POST /api/memory/maintenance/compress
+#[derive(Debug, Deserialize)]
+pub struct CompressRequest {
+    pub compress_after_days: i64,
+}
+
+/// Body for POST /api/memory/maintenance/archive-sessions
+#[derive(Debug, Deserialize)]
+pub struct ArchiveSessionsRequest {
+    pub max_age_hours: i64,
+}
+
+/// Body for POST /api/memory/{table}/{id}/feedback
+#[derive(Debug, Deserialize)]
+pub struct FeedbackRequest {
+    /// Whether acting on the memory worked
+    pub success: bool,
+}
+
+/// Body for POST /api/redaction/preview: exactly one of `text` or `path`.
+/// Previewing a file requires the [`ApiToken`], and files the
+/// [`FilePolicy`] flags as secret are refused.
+#[derive(Debug, Deserialize)]
+pub struct RedactionPreviewRequest {
+    #[serde(default)]
+    pub text: Option<String>,
+    /// Local file to preview instead of inline text
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+/// Redaction counts for one session.
+#[derive(Debug, Serialize)]
+pub struct SessionRedactionAudit {
+    pub session_id: String,
+    pub counts: BTreeMap<&'static str, u64>,
+}
+
+/// Body for POST /api/database/rotate-key
+#[derive(Debug, Deserialize)]
+pub struct RotateKeyRequest {
+    /// New passphrase; required for passphrase-encrypted databases and
+    /// rejected for key-file ones, which get a generated key
+    #[serde(default)]
+    pub passphrase: Option<String>,
+}
+
+/// Response for GET /api/database/encryption
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EncryptionStatus {
+    pub encrypted: bool,
+    /// "key_file" or "passphrase"; absent when not encrypted
+    pub key_source: Option<String>,
+}
+
+/// Result of a memory maintenance run
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MaintenanceResponse {
+    /// Rows compressed or archived
+    pub affected: u64,
+}
+
+/// Body for POST /api/memory/export
+#[derive(Debug, Deserialize)]
+pub struct ExportRequest {
+    /// Tables to export; all four when omitted
+    #[serde(default)]
+    pub tables: Vec<String>,
+    /// Limit profile memory to these categories
+    #[serde(default)]
+    pub profile_categories: Vec<String>,
+    /// Shared key used to sign the bundle
+    pub key: String,
+}
+
+/// Body for POST /api/memory/import
+#[derive(Debug, Deserialize)]
+pub struct ImportRequest {
+    /// Bundle text as produced by /api/memory/export
+    pub bundle: String,
+    /// Key the bundle was signed with
+    pub key: String,
+    /// How to treat rows that already exist (default "skip")
+    #[serde(default)]
+    pub policy: ConflictPolicy,
 }
 
 /// Health check response
@@ -54,59 +313,773 @@ pub struct HealthResponse {
 pub struct ConnectionStatusResponse {
     pub state: ConnectionState,
     pub queued_messages: usize,
+    pub reconnect_attempts: u32,
 }
 
 /// Shared application state passed to route handlers.
 #[derive(Clone)]
 pub struct AppState {
     pub connection: Arc<ConnectionStateMachine>,
+    pub memory: Arc<MemoryStore>,
+    pub redactor: Arc<Redactor>,
+    pub file_policy: Arc<FilePolicy>,
+    pub encryption: Arc<DbEncryption>,
+    pub maintenance: Arc<MaintenanceScheduler>,
+    pub api_token: Arc<ApiToken>,
+}
+
+impl FromRef<AppState> for Arc<ConnectionStateMachine> {
+    fn from_ref(state: &AppState) -> Self {
+        Arc::clone(&state.connection)
+    }
+}
+
+impl FromRef<AppState> for Arc<MemoryStore> {
+    fn from_ref(state: &AppState) -> Self {
+        Arc::clone(&state.memory)
+    }
 }
 
-/// Build the REST API router (without connection state).
-pub fn build_router() -> Router {
+impl FromRef<AppState> for Arc<Redactor> {
+    fn from_ref(state: &AppState) -> Self {
+        Arc::clone(&state.redactor)
+    }
+}
+
+impl FromRef<AppState> for Arc<FilePolicy> {
+    fn from_ref(state: &AppState) -> Self {
+        Arc::clone(&state.file_policy)
+    }
+}
+
+impl FromRef<AppState> for Arc<DbEncryption> {
+    fn from_ref(state: &AppState) -> Self {
+        Arc::clone(&state.encryption)
+    }
+}
+
+impl FromRef<AppState> for Arc<MaintenanceScheduler> {
+    fn from_ref(state: &AppState) -> Self {
+        Arc::clone(&state.maintenance)
+    }
+}
+
+impl FromRef<AppState> for Arc<ApiToken> {
+    fn from_ref(state: &AppState) -> Self {
+        Arc::clone(&state.api_token)
+    }
+}
+
+/// Memory search, curation and maintenance routes under `/api/memory`.
+///
+/// Generic over the router state so the daemon can mount them next to its
+/// own handlers; the state only needs to provide the [`MemoryStore`] and
+/// the [`ApiToken`] every one of them requires.
+pub fn memory_routes<S>() -> Router<S>
+where
+    Arc<MemoryStore>: FromRef<S>,
+    Arc<ApiToken>: FromRef<S>,
+    S: Clone + Send + Sync + 'static,
+{
     Router::new()
         .route("/api/memory/search", get(memory_search))
-        .route("/api/health", get(health_check))
+        .route(
+            "/api/memory/maintenance/compress",
+            post(run_memory_compression),
+        )
+        .route(
+            "/api/memory/maintenance/archive-sessions",
+            post(archive_memory_sessions),
+        )
+        .route("/api/memory/:table", get(list_memory).post(create_memory))
+        .route(
+            "/api/memory/:table/:id",
+            get(get_memory).patch(update_memory).delete(delete_memory),
+        )
+        .route("/api/memory/:table/:id/history", get(memory_history))
+        .route("/api/memory/:table/:id/restore", post(restore_memory))
+        .route("/api/memory/:table/:id/feedback", post(memory_feedback))
 }
 
-/// Build the REST API router with shared application state.
-pub fn build_router_with_state(state: AppState) -> Router {
+/// Memory bundle export/import routes.  Both require the [`ApiToken`] and
+/// exports pass through the [`Redactor`], so the state must provide both
+/// alongside the store.
+pub fn memory_bundle_routes<S>() -> Router<S>
+where
+    Arc<MemoryStore>: FromRef<S>,
+    Arc<Redactor>: FromRef<S>,
+    Arc<ApiToken>: FromRef<S>,
+    S: Clone + Send + Sync + 'static,
+{
     Router::new()
-        .route("/api/memory/search", get(memory_search))
-        .route("/api/health", get(health_check))
-        .route("/api/connection/status", get(connection_status))
-        .with_state(state)
+        .route("/api/memory/export", post(export_memory))
+        .route("/api/memory/import", post(import_memory))
 }
 
-/// Start the REST API server on the given address
-pub async fn start_rest_server(addr: SocketAddr) -> anyhow::Result<()> {
-    let app = build_router();
-    tracing::info!(%addr, "REST API server starting");
+/// Maintenance scheduler status and on-demand runs.  Runs require the
+/// [`ApiToken`].
+pub fn maintenance_routes<S>() -> Router<S>
+where
+    Arc<MaintenanceScheduler>: FromRef<S>,
+    Arc<ApiToken>: FromRef<S>,
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/api/memory/maintenance/status", get(maintenance_status))
+        .route("/api/memory/maintenance/run", post(run_maintenance))
+}
 
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+/// Database encryption status and key rotation.  Rotation requires the
+/// [`ApiToken`].
+pub fn database_routes<S>() -> Router<S>
+where
+    Arc<DbEncryption>: FromRef<S>,
+    Arc<ApiToken>: FromRef<S>,
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/api/database/encryption", get(encryption_status))
+        .route("/api/database/rotate-key", post(rotate_database_key))
+}
+
+/// Redaction statistics, audit counts and dry-run previews.
+pub fn redaction_routes<S>() -> Router<S>
+where
+    Arc<Redactor>: FromRef<S>,
+    Arc<FilePolicy>: FromRef<S>,
+    Arc<ApiToken>: FromRef<S>,
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/api/redaction/detections", get(redaction_detections))
+        .route("/api/redaction/preview", post(preview_redaction))
+        .route("/api/redaction/audit", get(redaction_audit))
+        .route(
+            "/api/redaction/audit/:session_id",
+            get(session_redaction_audit),
+        )
+}
 
-    Ok(())
+/// Cloud connection status and a server-sent event stream of its changes.
+pub fn connection_routes<S>() -> Router<S>
+where
+    Arc<ConnectionStateMachine>: FromRef<S>,
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/api/connection/status", get(connection_status))
+        .route("/api/connection/events", get(connection_events))
+}
+
+/// Build the REST API router with shared application state.  This is the
+/// one list of REST routes; the daemon merges it next to its WebSocket
+/// endpoints.
+pub fn build_router_with_state(state: AppState) -> Router {
+    memory_routes()
+        .merge(memory_bundle_routes())
+        .merge(database_routes())
+        .merge(maintenance_routes())
+        .merge(redaction_routes())
+        .merge(connection_routes())
+        .route("/api/health", get(health_check))
+        .with_state(state)
 }
 
 /// Handler for GET /api/memory/search?q=...&scope=...&limit=...
-pub async fn memory_search(Query(params): Query<SearchParams>) -> impl IntoResponse {
-    let limit = params.limit.unwrap_or(10).min(100);
-    let scope = params.scope.as_deref().unwrap_or("all");
+///
+/// Searches agent memory through [`MemoryStore::recall`]. An empty query or
+/// unknown scope is rejected with 400.
+pub async fn memory_search(
+    _: Authorized,
+    State(store): State<Arc<MemoryStore>>,
+    Query(params): Query<SearchParams>,
+) -> Result<Json<Vec<MemorySearchResult>>, ApiError> {
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_SEARCH_LIMIT)
+        .clamp(1, MAX_SEARCH_LIMIT);
+    let scope_name = params.scope.as_deref().unwrap_or("all");
+    let scope = MemoryScope::parse(scope_name)
+        .map_err(|e| api_error(StatusCode::BAD_REQUEST, e.to_string()))?;
+    let query = params.q.trim().to_string();
+    if query.is_empty() {
+        return Err(api_error(
+            StatusCode::BAD_REQUEST,
+            "query must not be empty",
+        ));
+    }
 
     tracing::debug!(
-        query = %params.q,
-        scope = %scope,
+        query = %query,
+        scope = %scope_name,
         limit = %limit,
         "Memory search request"
     );
 
-    // Perform a simple substring match against known memory entries.
-    // In production, this would query the local SQLite database
-    // with full-text search or vector similarity.
-    let results = search_memory(&params.q, scope, limit);
+    let entries = run_blocking(move || store.recall(&query, scope, limit as i32)).await?;
+
+    Ok(Json(entries.into_iter().map(Into::into).collect()))
+}
+
+/// Handler for GET /api/memory/{table}?cursor=...&limit=...&{column}=...
+///
+/// Lists rows in insertion order. Any query parameter other than `cursor`
+/// and `limit` is an equality filter on one of the table's filter columns.
+pub async fn list_memory(
+    _: Authorized,
+    State(store): State<Arc<MemoryStore>>,
+    Path(table): Path<String>,
+    Query(mut params): Query<HashMap<String, String>>,
+) -> Result<Json<RecordPage>, ApiError> {
+    let table = parse_table(&table)?;
+    let cursor = params.remove("cursor");
+    let limit = match params.remove("limit") {
+        Some(raw) => raw
+            .parse::<usize>()
+            .map_err(|_| api_error(StatusCode::BAD_REQUEST, format!("invalid limit: {raw}")))?,
+        None => DEFAULT_PAGE_LIMIT,
+    }
+    .clamp(1, MAX_PAGE_LIMIT);
+
+    let mut filters: Vec<(String, String)> = params.into_iter().collect();
+    filters.sort();
+    if let Some((column, _)) = filters
+        .iter()
+        .find(|(column, _)| !table.filter_columns().contains(&column.as_str()))
+    {
+        return Err(api_error(
+            StatusCode::BAD_REQUEST,
+            format!(
+                "unknown filter {column}; expected one of: {}",
+                table.filter_columns().join(", ")
+            ),
+        ));
+    }
+    if let Some(cursor) = &cursor {
+        if cursor.parse::<i64>().is_err() {
+            return Err(api_error(
+                StatusCode::BAD_REQUEST,
+                format!("invalid cursor: {cursor}"),
+            ));
+        }
+    }
+
+    let query = ListQuery {
+        filters,
+        cursor,
+        limit,
+    };
+    let page = run_blocking(move || store.list_records(table, &query)).await?;
+    Ok(Json(page))
+}
+
+/// Handler for POST /api/memory/{table}
+///
+/// The body shape depends on the table (see the `Create*Request` types).
+/// Returns 201 with the stored row.
+pub async fn create_memory(
+    _: Authorized,
+    State(store): State<Arc<MemoryStore>>,
+    Path(table): Path<String>,
+    Json(body): Json<Value>,
+) -> Result<(StatusCode, Json<MemoryRecord>), ApiError> {
+    let table = parse_table(&table)?;
+    let record = match table {
+        MemoryTable::Profile => {
+            let req: CreateProfileRequest = parse_body(body)?;
+            run_blocking(move || {
+                let id = store.store_profile(&req.category, &req.key, &req.value, &req.source)?;
+                store.get_record(table, &id)
+            })
+            .await?
+        }
+        MemoryTable::Session => {
+            let req: CreateSessionRequest = parse_body(body)?;
+            let metadata = req.metadata.map(|m| match m {
+                Value::String(s) => s,
+                other => other.to_string(),
+            });
+            run_blocking(move || {
+                let id = store.store_session(
+                    &req.session_id,
+                    req.step_number,
+                    &req.agent_name,
+                    &req.event_type,
+                    &req.content,
+                    metadata.as_deref(),
+                )?;
+                store.get_record(table, &id)
+            })
+            .await?
+        }
+        MemoryTable::Task => {
+            let req: CreateTaskRequest = parse_body(body)?;
+            run_blocking(move || {
+                let id = store.store_task_outcome(
+                    &req.task_description,
+                    &req.task_category,
+                    &req.outcome,
+                    &req.procedure_steps,
+                    &req.error_patterns,
+                    &req.fix_patterns,
+                    req.duration_seconds,
+                    &req.system_context,
+                    &req.session_id,
+                )?;
+                store.get_record(table, &id)
+            })
+            .await?
+        }
+        MemoryTable::Agent => {
+            let req: CreateAgentRequest = parse_body(body)?;
+            run_blocking(move || {
+                let id = store.store_agent_learning(
+                    &req.agent_name,
+                    &req.memory_type,
+                    &req.content,
+                    req.confidence,
+                )?;
+                store.get_record(table, &id)
+            })
+            .await?
+        }
+    };
+
+    let record = record.ok_or_else(|| {
+        api_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "stored row could not be read back",
+        )
+    })?;
+    Ok((StatusCode::CREATED, Json(record)))
+}
+
+/// Handler for GET /api/memory/{table}/{id}
+pub async fn get_memory(
+    _: Authorized,
+    State(store): State<Arc<MemoryStore>>,
+    Path((table, id)): Path<(String, String)>,
+) -> Result<Json<MemoryRecord>, ApiError> {
+    let table = parse_table(&table)?;
+    let record = run_blocking(move || store.get_record(table, &id)).await?;
+    record.map(Json).ok_or_else(not_found)
+}
+
+/// Handler for PATCH /api/memory/{table}/{id}
+///
+/// The body is a JSON object of column → new value; only the table's
+/// editable columns are accepted. The change is recorded in `audit_log`.
+pub async fn update_memory(
+    _: Authorized,
+    State(store): State<Arc<MemoryStore>>,
+    Path((table, id)): Path<(String, String)>,
+    Json(changes): Json<MemoryRecord>,
+) -> Result<Json<MemoryRecord>, ApiError> {
+    let table = parse_table(&table)?;
+    if changes.is_empty() {
+        return Err(api_error(StatusCode::BAD_REQUEST, "no fields to update"));
+    }
+    if let Some(column) = changes
+        .keys()
+        .find(|column| !table.editable_columns().contains(&column.as_str()))
+    {
+        return Err(api_error(
+            StatusCode::BAD_REQUEST,
+            format!(
+                "column {column} is not editable; expected one of: {}",
+                table.editable_columns().join(", ")
+            ),
+        ));
+    }
+
+    let record = run_blocking(move || store.update_record(table, &id, &changes, API_ACTOR)).await?;
+    record.map(Json).ok_or_else(not_found)
+}
+
+/// Handler for DELETE /api/memory/{table}/{id}
+pub async fn delete_memory(
+    _: Authorized,
+    State(store): State<Arc<MemoryStore>>,
+    Path((table, id)): Path<(String, String)>,
+) -> Result<StatusCode, ApiError> {
+    let table = parse_table(&table)?;
+    let deleted = run_blocking(move || store.forget(&id, table.table_name())).await?;
+    if deleted {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(not_found())
+    }
+}
+
+/// Handler for GET /api/memory/{table}/{id}/history
+///
+/// Returns the record's `audit_log` entries, oldest first. History outlives
+/// the record itself, so a deleted id still returns its trail.
+pub async fn memory_history(
+    _: Authorized,
+    State(store): State<Arc<MemoryStore>>,
+    Path((table, id)): Path<(String, String)>,
+) -> Result<Json<Vec<AuditEntry>>, ApiError> {
+    let table = parse_table(&table)?;
+    let history = run_blocking(move || store.audit_history(table, &id)).await?;
+    Ok(Json(history))
+}
+
+/// Handler for POST /api/memory/{table}/{id}/restore
+///
+/// Undoes compression: the archived original replaces the summary and the
+/// restored record is returned. 404 if the record has no archived original.
+pub async fn restore_memory(
+    _: Authorized,
+    State(store): State<Arc<MemoryStore>>,
+    Path((table, id)): Path<(String, String)>,
+) -> Result<Json<MemoryRecord>, ApiError> {
+    let table = parse_table(&table)?;
+    let record = run_blocking(move || {
+        if store.restore_compressed(table, &id)? {
+            store.get_record(table, &id)
+        } else {
+            Ok(None)
+        }
+    })
+    .await?;
+    record.map(Json).ok_or_else(not_found)
+}
+
+/// Handler for POST /api/memory/{table}/{id}/feedback
+///
+/// Reinforces (success) or penalises (failure) a profile or agent memory's
+/// confidence and returns the updated record.  400 for tables without a
+/// confidence, 404 if the record does not exist.
+pub async fn memory_feedback(
+    _: Authorized,
+    State(store): State<Arc<MemoryStore>>,
+    Path((table, id)): Path<(String, String)>,
+    Json(req): Json<FeedbackRequest>,
+) -> Result<Json<MemoryRecord>, ApiError> {
+    let table = parse_table(&table)?;
+    if !matches!(table, MemoryTable::Profile | MemoryTable::Agent) {
+        return Err(api_error(
+            StatusCode::BAD_REQUEST,
+            format!("{} has no confidence to adjust", table.table_name()),
+        ));
+    }
+    let record = run_blocking(move || {
+        match store.record_memory_outcome(table, &id, req.success, API_ACTOR)? {
+            Some(_) => store.get_record(table, &id),
+            None => Ok(None),
+        }
+    })
+    .await?;
+    record.map(Json).ok_or_else(not_found)
+}
+
+/// Handler for POST /api/memory/export
+///
+/// Returns a signed JSON-lines bundle (`application/x-ndjson`).  401 without
+/// the API token.
+pub async fn export_memory(
+    _: Authorized,
+    State(store): State<Arc<MemoryStore>>,
+    State(redactor): State<Arc<Redactor>>,
+    Json(req): Json<ExportRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    if req.key.is_empty() {
+        return Err(api_error(StatusCode::BAD_REQUEST, "key must not be empty"));
+    }
+    let tables = if req.tables.is_empty() {
+        vec![
+            MemoryTable::Profile,
+            MemoryTable::Session,
+            MemoryTable::Task,
+            MemoryTable::Agent,
+        ]
+    } else {
+        req.tables
+            .iter()
+            .map(|t| MemoryTable::parse(t))
+            .collect::<anyhow::Result<Vec<_>>>()
+            .map_err(|e| api_error(StatusCode::BAD_REQUEST, e.to_string()))?
+    };
+    let options = ExportOptions {
+        tables,
+        profile_categories: req.profile_categories,
+    };
+    let bundle = run_blocking(move || {
+        memory_bundle::export_bundle(&store, &redactor, &options, req.key.as_bytes())
+    })
+    .await?;
+    Ok((
+        [(axum::http::header::CONTENT_TYPE, "application/x-ndjson")],
+        bundle,
+    ))
+}
+
+/// Handler for POST /api/memory/import
+///
+/// 401 without the API token.  A bundle with a bad signature or layout is
+/// rejected with 400 before any row is written.
+pub async fn import_memory(
+    _: Authorized,
+    State(store): State<Arc<MemoryStore>>,
+    Json(req): Json<ImportRequest>,
+) -> Result<Json<ImportSummary>, ApiError> {
+    memory_bundle::read_bundle(&req.bundle, req.key.as_bytes())
+        .map_err(|e| api_error(StatusCode::BAD_REQUEST, format!("{e:#}")))?;
+    let summary = run_blocking(move || {
+        memory_bundle::import_bundle(
+            &store,
+            &req.bundle,
+            req.key.as_bytes(),
+            req.policy,
+            IMPORT_ACTOR,
+        )
+    })
+    .await?;
+    tracing::info!(?summary, "Memory bundle imported via REST");
+    Ok(Json(summary))
+}
+
+/// Handler for POST /api/memory/maintenance/compress
+pub async fn run_memory_compression(
+    _: Authorized,
+    State(store): State<Arc<MemoryStore>>,
+    Json(req): Json<CompressRequest>,
+) -> Result<Json<MaintenanceResponse>, ApiError> {
+    if req.compress_after_days < 0 {
+        return Err(api_error(
+            StatusCode::BAD_REQUEST,
+            "compress_after_days must not be negative",
+        ));
+    }
+    let affected = run_blocking(move || store.run_compression(req.compress_after_days)).await?;
+    tracing::info!(affected, "Memory compression triggered via REST");
+    Ok(Json(MaintenanceResponse { affected }))
+}
+
+/// Handler for POST /api/memory/maintenance/archive-sessions
+pub async fn archive_memory_sessions(
+    _: Authorized,
+    State(store): State<Arc<MemoryStore>>,
+    Json(req): Json<ArchiveSessionsRequest>,
+) -> Result<Json<MaintenanceResponse>, ApiError> {
+    if req.max_age_hours < 0 {
+        return Err(api_error(
+            StatusCode::BAD_REQUEST,
+            "max_age_hours must not be negative",
+        ));
+    }
+    let affected = run_blocking(move || store.archive_old_sessions(req.max_age_hours)).await?;
+    tracing::info!(affected, "Session archival triggered via REST");
+    Ok(Json(MaintenanceResponse { affected }))
+}
+
+/// Handler for GET /api/memory/maintenance/status
+pub async fn maintenance_status(
+    State(scheduler): State<Arc<MaintenanceScheduler>>,
+) -> Result<Json<MaintenanceStatus>, ApiError> {
+    let status = run_blocking(move || scheduler.status()).await?;
+    Ok(Json(status))
+}
+
+/// Handler for POST /api/memory/maintenance/run
+///
+/// Runs all maintenance steps now and returns the recorded run.  409 if a
+/// run is already in progress.
+pub async fn run_maintenance(
+    _: Authorized,
+    State(scheduler): State<Arc<MaintenanceScheduler>>,
+) -> Result<Json<MaintenanceRun>, ApiError> {
+    let run = run_blocking(move || match scheduler.run_now() {
+        Err(e) if e.is::<AlreadyRunning>() => Ok(None),
+        result => result.map(Some),
+    })
+    .await?
+    .ok_or_else(|| api_error(StatusCode::CONFLICT, AlreadyRunning.to_string()))?;
+    tracing::info!(id = %run.id, "Memory maintenance triggered via REST");
+    Ok(Json(run))
+}
+
+/// Handler for GET /api/database/encryption
+pub async fn encryption_status(
+    State(encryption): State<Arc<DbEncryption>>,
+) -> Json<EncryptionStatus> {
+    let key_source = encryption.source().map(|source| match source {
+        KeySource::File(_) => "key_file".to_string(),
+        KeySource::Passphrase => "passphrase".to_string(),
+    });
+    Json(EncryptionStatus {
+        encrypted: key_source.is_some(),
+        key_source,
+    })
+}
+
+/// Handler for GET /api/redaction/detections
+///
+/// Counts of values the entropy/JWT detector redacted, by kind.
+pub async fn redaction_detections(State(redactor): State<Arc<Redactor>>) -> Json<DetectionStats> {
+    Json(redactor.detection_stats())
+}
+
+/// Handler for POST /api/redaction/preview
+///
+/// Dry run: returns what would be redacted from the given text or file,
+/// with findings as byte spans of the input.  Nothing is counted.  401 for
+/// a file without the API token; 403 for a file the policy flags.
+pub async fn preview_redaction(
+    authorized: Option<Authorized>,
+    State(redactor): State<Arc<Redactor>>,
+    State(policy): State<Arc<FilePolicy>>,
+    Json(req): Json<RedactionPreviewRequest>,
+) -> Result<Json<RedactionReport>, ApiError> {
+    let text = match (req.text, req.path) {
+        (Some(text), None) => text,
+        (None, Some(path)) => {
+            if authorized.is_none() {
+                return Err(api_error(
+                    StatusCode::UNAUTHORIZED,
+                    "previewing a file requires the API token",
+                ));
+            }
+            read_preview_file(&policy, &path).await?
+        }
+        _ => {
+            return Err(api_error(
+                StatusCode::BAD_REQUEST,
+                "exactly one of text or path is required",
+            ))
+        }
+    };
+    if text.len() > MAX_PREVIEW_BYTES {
+        return Err(api_error(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("preview is limited to {MAX_PREVIEW_BYTES} bytes"),
+        ));
+    }
+    let report = tokio::task::spawn_blocking(move || redactor.preview(&text))
+        .await
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(Json(report))
+}
+
+async fn read_preview_file(policy: &FilePolicy, path: &str) -> Result<String, ApiError> {
+    // A preview has no approval step, so every flagged file is refused.
+    if let Some(decision) = policy.check_path(std::path::Path::new(path)) {
+        let decision = FileDecision {
+            action: FileAction::Deny,
+            ..decision
+        };
+        let refusal = policy
+            .refuse(std::path::Path::new(path), &decision, false)
+            .unwrap_or_default();
+        return Err(api_error(StatusCode::FORBIDDEN, refusal));
+    }
+    let metadata = tokio::fs::metadata(path)
+        .await
+        .map_err(|e| api_error(StatusCode::NOT_FOUND, format!("{path}: {e}")))?;
+    if metadata.len() > MAX_PREVIEW_BYTES as u64 {
+        return Err(api_error(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("preview is limited to {MAX_PREVIEW_BYTES} bytes"),
+        ));
+    }
+    tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| api_error(StatusCode::BAD_REQUEST, format!("{path}: {e}")))
+}
+
+/// Handler for GET /api/redaction/audit
+pub async fn redaction_audit(State(redactor): State<Arc<Redactor>>) -> Json<RedactionAudit> {
+    Json(redactor.audit())
+}
+
+/// Handler for GET /api/redaction/audit/:session_id
+pub async fn session_redaction_audit(
+    State(redactor): State<Arc<Redactor>>,
+    Path(session_id): Path<String>,
+) -> Result<Json<SessionRedactionAudit>, ApiError> {
+    let counts = redactor
+        .session_audit(&session_id)
+        .ok_or_else(|| api_error(StatusCode::NOT_FOUND, "no redactions for session"))?;
+    Ok(Json(SessionRedactionAudit { session_id, counts }))
+}
+
+/// Handler for POST /api/database/rotate-key
+///
+/// Re-encrypts the database under a new key.  401 without the API token;
+/// 409 when the database is not encrypted; 400 when the passphrase does not
+/// fit the key source.
+pub async fn rotate_database_key(
+    _: Authorized,
+    State(encryption): State<Arc<DbEncryption>>,
+    Json(req): Json<RotateKeyRequest>,
+) -> Result<StatusCode, ApiError> {
+    match (encryption.source(), req.passphrase.as_deref()) {
+        (None, _) => {
+            return Err(api_error(
+                StatusCode::CONFLICT,
+                "database encryption is disabled",
+            ))
+        }
+        (Some(KeySource::Passphrase), None | Some("")) => {
+            return Err(api_error(
+                StatusCode::BAD_REQUEST,
+                "a new passphrase is required",
+            ))
+        }
+        (Some(KeySource::File(_)), Some(_)) => {
+            return Err(api_error(
+                StatusCode::BAD_REQUEST,
+                "database uses a key file; passphrases are set through the environment",
+            ))
+        }
+        _ => {}
+    }
+    run_blocking(move || encryption.rotate(req.passphrase.as_deref())).await?;
+    tracing::info!("Database key rotated via REST");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn parse_table(name: &str) -> Result<MemoryTable, ApiError> {
+    MemoryTable::parse(name).map_err(|e| api_error(StatusCode::NOT_FOUND, e.to_string()))
+}
+
+fn parse_body<T: serde::de::DeserializeOwned>(body: Value) -> Result<T, ApiError> {
+    serde_json::from_value(body)
+        .map_err(|e| api_error(StatusCode::UNPROCESSABLE_ENTITY, e.to_string()))
+}
 
-    Json(results)
+fn not_found() -> ApiError {
+    api_error(StatusCode::NOT_FOUND, "memory record not found")
+}
+
+/// Run a [`MemoryStore`] call on the blocking pool.
+///
+/// SQLite calls block, so they are kept off the async worker threads.
+/// Constraint violations map to 400; anything else is a 500.
+async fn run_blocking<T, F>(f: F) -> Result<T, ApiError>
+where
+    F: FnOnce() -> anyhow::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(|e| {
+            let constraint = e.chain().any(|cause| {
+                matches!(
+                    cause.downcast_ref::<rusqlite::Error>(),
+                    Some(rusqlite::Error::SqliteFailure(err, _))
+                        if err.code == rusqlite::ErrorCode::ConstraintViolation
+                )
+            });
+            if constraint {
+                api_error(StatusCode::BAD_REQUEST, format!("{e:#}"))
+            } else {
+                tracing::warn!(error = %e, "Memory store call failed");
+                api_error(StatusCode::INTERNAL_SERVER_ERROR, format!("{e:#}"))
+            }
+        })
 }
 
 /// Handler for GET /api/health
@@ -121,121 +1094,883 @@ pub async fn health_check() -> impl IntoResponse {
 }
 
 /// Handler for GET /api/connection/status
-async fn connection_status(State(state): State<AppState>) -> impl IntoResponse {
-    let conn_state = state.connection.state().await;
-    let queued = state.connection.queue_len().await;
-
-    let response = ConnectionStatusResponse {
-        state: conn_state,
-        queued_messages: queued,
-    };
-
-    (StatusCode::OK, Json(response))
+pub async fn connection_status(
+    State(connection): State<Arc<ConnectionStateMachine>>,
+) -> impl IntoResponse {
+    (StatusCode::OK, Json(connection_snapshot(&connection).await))
 }
 
-/// Search memory entries by query string.
+/// Handler for GET /api/connection/events
 ///
-/// This is a placeholder implementation that returns matches
-/// based on simple substring matching. A real implementation
-/// would use SQLite FTS5 or vector similarity search.
-fn search_memory(query: &str, scope: &str, limit: usize) -> Vec<MemorySearchResult> {
-    let query_lower = query.to_lowercase();
-
-    // Seed entries from profile detection and system facts
-    let all_entries = get_seed_entries();
-
-    let mut results: Vec<MemorySearchResult> = all_entries
-        .into_iter()
-        .filter(|entry| {
-            // Scope filter
-            if scope != "all" && entry.source != scope {
-                return false;
-            }
-            // Substring relevance
-            entry.key.to_lowercase().contains(&query_lower)
-                || entry.value.to_lowercase().contains(&query_lower)
-        })
-        .map(|mut entry| {
-            // Compute a simple relevance score
-            entry.relevance = compute_relevance(&entry, &query_lower);
-            entry
-        })
-        .collect();
+/// Server-sent events: one `status` event with the current
+/// [`ConnectionStatusResponse`], then a `state` event with each
+/// [`ConnectionStateEvent`](crate::connection_state::ConnectionStateEvent)
+/// as it happens.
+pub async fn connection_events(
+    State(connection): State<Arc<ConnectionStateMachine>>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    // Subscribe before taking the snapshot so no change falls in between.
+    let events = connection.subscribe();
+    let status = sse_event("status", &connection_snapshot(&connection).await);
 
-    // Sort by relevance descending
-    results.sort_by(|a, b| {
-        b.relevance
-            .partial_cmp(&a.relevance)
-            .unwrap_or(std::cmp::Ordering::Equal)
+    let changes = futures::stream::unfold(events, |mut events| async move {
+        loop {
+            match events.recv().await {
+                Ok(event) => return Some((sse_event("state", &event), events)),
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
     });
-    results.truncate(limit);
-    results
-}
-
-/// Compute a simple relevance score based on match quality
-fn compute_relevance(entry: &MemorySearchResult, query: &str) -> f64 {
-    let key_lower = entry.key.to_lowercase();
-    let value_lower = entry.value.to_lowercase();
-
-    if key_lower == query {
-        1.0
-    } else if key_lower.contains(query) && value_lower.contains(query) {
-        0.9
-    } else if key_lower.contains(query) {
-        0.7
-    } else if value_lower.contains(query) {
-        0.5
-    } else {
-        0.1
-    }
+    let stream = futures::stream::once(async move { status })
+        .chain(changes)
+        .map(Ok);
+    Sse::new(stream).keep_alive(KeepAlive::default())
 }
 
-/// Return seed memory entries (system profile facts).
-/// In a full implementation, these would come from the local database.
-fn get_seed_entries() -> Vec<MemorySearchResult> {
-    use crate::profile_detect::detect_system_profile;
-
-    let facts = detect_system_profile();
-    let now = chrono::Utc::now().timestamp();
+async fn connection_snapshot(connection: &ConnectionStateMachine) -> ConnectionStatusResponse {
+    ConnectionStatusResponse {
+        state: connection.state().await,
+        queued_messages: connection.queue_len().await,
+        reconnect_attempts: connection.reconnect_attempts().await,
+    }
+}
 
-    facts
-        .into_iter()
-        .enumerate()
-        .map(|(i, fact)| MemorySearchResult {
-            id: format!("profile-{}", i),
-            key: fact.key,
-            value: fact.value,
-            source: fact.source,
-            relevance: 0.0,
-            timestamp: now,
-        })
-        .collect()
+fn sse_event<T: Serialize>(name: &str, data: &T) -> Event {
+    Event::default()
+        .event(name)
+        .data(serde_json::to_string(data).unwrap_or_default())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::local_db::LocalDb;
 
-    #[test]
-    fn test_memory_search_returns_results() {
-        let results = search_memory("os", "all", 10);
-        assert!(!results.is_empty(), "Should find OS-related entries");
+    fn test_store() -> Arc<MemoryStore> {
+        let db = LocalDb::open_in_memory().expect("in-memory db");
+        let store = MemoryStore::new(Arc::new(db));
+        store
+            .store_profile("system", "os", "macOS", "system")
+            .unwrap();
+        store
+            .store_profile("system", "shell", "/bin/zsh", "env")
+            .unwrap();
+        store
+            .store_agent_learning("dr_bob", "fact", "user prefers zsh over bash", 0.9)
+            .unwrap();
+        Arc::new(store)
     }
 
-    #[test]
-    fn test_memory_search_respects_limit() {
-        let results = search_memory("", "all", 2);
-        // With empty query nothing matches substring, so could be empty
-        // but limit should still be respected if there were matches
-        assert!(results.len() <= 2);
+    fn params(q: &str, scope: Option<&str>, limit: Option<usize>) -> Query<SearchParams> {
+        Query(SearchParams {
+            q: q.to_string(),
+            scope: scope.map(str::to_string),
+            limit,
+        })
     }
 
-    #[test]
-    fn test_memory_search_scope_filter() {
-        let results = search_memory("os", "system", 10);
-        for entry in &results {
-            assert_eq!(entry.source, "system");
+    #[tokio::test]
+    async fn test_memory_search_returns_stored_memory() {
+        let Json(results) =
+            memory_search(Authorized, State(test_store()), params("zsh", None, None))
+                .await
+                .unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().any(|r| r.scope == "profile"));
+        assert!(results.iter().any(|r| r.scope == "agent"));
+    }
+
+    #[tokio::test]
+    async fn test_memory_search_scope_filter() {
+        let Json(results) = memory_search(
+            Authorized,
+            State(test_store()),
+            params("zsh", Some("agent"), None),
+        )
+        .await
+        .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].content, "user prefers zsh over bash");
+        assert_eq!(results[0].metadata.as_deref(), Some("fact"));
+    }
+
+    #[tokio::test]
+    async fn test_memory_search_respects_limit() {
+        let Json(results) = memory_search(
+            Authorized,
+            State(test_store()),
+            params("s", Some("profile"), Some(1)),
+        )
+        .await
+        .unwrap();
+        assert_eq!(results.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_memory_search_rejects_bad_input() {
+        let (status, Json(body)) = memory_search(
+            Authorized,
+            State(test_store()),
+            params("os", Some("system"), None),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.error.contains("invalid scope"));
+
+        let (status, _) = memory_search(Authorized, State(test_store()), params("  ", None, None))
+            .await
+            .unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    /// Serve `router` on an ephemeral local port and return its base URL.
+    async fn serve(router: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+        format!("http://{addr}")
+    }
+
+    /// Send a request to the test server and decode the JSON response.
+    async fn call(
+        base: &str,
+        method: &str,
+        path: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        call_with_token(base, method, path, body, None).await
+    }
+
+    /// Token accepted by test states.
+    const TEST_TOKEN: &str = "test-token";
+
+    /// Like [`call`], presenting [`TEST_TOKEN`].
+    async fn call_authorized(
+        base: &str,
+        method: &str,
+        path: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        call_with_token(base, method, path, body, Some(TEST_TOKEN)).await
+    }
+
+    async fn call_with_token(
+        base: &str,
+        method: &str,
+        path: &str,
+        body: Option<Value>,
+        token: Option<&str>,
+    ) -> (StatusCode, Value) {
+        let method = reqwest::Method::from_bytes(method.as_bytes()).unwrap();
+        let mut request = reqwest::Client::new().request(method, format!("{base}{path}"));
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+        if let Some(json) = body {
+            request = request.json(&json);
         }
+        let response = request.send().await.unwrap();
+        let status = StatusCode::from_u16(response.status().as_u16()).unwrap();
+        let bytes = response.bytes().await.unwrap();
+        let json = if bytes.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_slice(&bytes).unwrap()
+        };
+        (status, json)
+    }
+
+    #[tokio::test]
+    async fn test_memory_crud_roundtrip() {
+        let base = serve(build_router_with_state(app_state(test_store()))).await;
+
+        let (status, created) = call_authorized(
+            &base,
+            "POST",
+            "/api/memory/agent",
+            Some(serde_json::json!({
+                "agent_name": "dr_bob",
+                "memory_type": "preference",
+                "content": "likes cats"
+            })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(created["confidence"], 1.0);
+        let id = created["id"].as_str().unwrap().to_string();
+
+        let (status, fetched) =
+            call_authorized(&base, "GET", &format!("/api/memory/agent/{id}"), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(fetched["content"], "likes cats");
+
+        let (status, updated) = call_authorized(
+            &base,
+            "PATCH",
+            &format!("/api/memory/agent/{id}"),
+            Some(serde_json::json!({ "content": "likes dogs" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(updated["content"], "likes dogs");
+
+        let (status, _) = call_authorized(
+            &base,
+            "PATCH",
+            &format!("/api/memory/agent/{id}"),
+            Some(serde_json::json!({ "agent_name": "mallory" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) =
+            call_authorized(&base, "DELETE", &format!("/api/memory/agent/{id}"), None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) =
+            call_authorized(&base, "DELETE", &format!("/api/memory/agent/{id}"), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, history) = call_authorized(
+            &base,
+            "GET",
+            &format!("/api/memory/agent/{id}/history"),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let history = history.as_array().unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0]["agent_name"], API_ACTOR);
+        assert_eq!(history[1]["old_value"], "deleted");
+    }
+
+    #[tokio::test]
+    async fn test_memory_compress_and_restore() {
+        let db = Arc::new(LocalDb::open_in_memory().expect("in-memory db"));
+        let long = "The user keeps their dotfiles in a git repository. ".repeat(4);
+        db.conn()
+            .execute(
+                "INSERT INTO agent_memory (id, agent_name, memory_type, content, created_at, updated_at)
+                 VALUES ('am-old', 'dr_bob', 'fact', ?1,
+                         strftime('%Y-%m-%dT%H:%M:%SZ', 'now', '-100 days'),
+                         strftime('%Y-%m-%dT%H:%M:%SZ', 'now', '-100 days'))",
+                [&long],
+            )
+            .unwrap();
+        let id = "am-old";
+        let base = serve(build_router_with_state(app_state(Arc::new(
+            MemoryStore::new(db),
+        ))))
+        .await;
+
+        let (status, body) = call_authorized(
+            &base,
+            "POST",
+            "/api/memory/maintenance/compress",
+            Some(serde_json::json!({ "compress_after_days": 30 })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["affected"], 1);
+        let (_, compressed) =
+            call_authorized(&base, "GET", &format!("/api/memory/agent/{id}"), None).await;
+        assert_ne!(compressed["content"], long.as_str());
+
+        let (status, restored) = call_authorized(
+            &base,
+            "POST",
+            &format!("/api/memory/agent/{id}/restore"),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(restored["content"], long.as_str());
+
+        let (status, _) = call_authorized(
+            &base,
+            "POST",
+            &format!("/api/memory/agent/{id}/restore"),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) =
+            call_authorized(&base, "POST", "/api/memory/profile/p1/restore", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_memory_feedback() {
+        let store = Arc::new(MemoryStore::new(Arc::new(
+            LocalDb::open_in_memory().expect("in-memory db"),
+        )));
+        let id = store
+            .store_agent_learning("dr_bob", "fact", "The proxy listens on 8080", 0.5)
+            .unwrap();
+        let base = serve(build_router_with_state(app_state(store))).await;
+
+        let (status, record) = call_authorized(
+            &base,
+            "POST",
+            &format!("/api/memory/agent/{id}/feedback"),
+            Some(serde_json::json!({ "success": true })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(record["confidence"].as_f64().unwrap() > 0.5);
+        assert_eq!(record["use_count"], 1);
+
+        let (status, _) = call_authorized(
+            &base,
+            "POST",
+            "/api/memory/agent/missing/feedback",
+            Some(serde_json::json!({ "success": false })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = call_authorized(
+            &base,
+            "POST",
+            &format!("/api/memory/session/{id}/feedback"),
+            Some(serde_json::json!({ "success": true })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_run_and_status() {
+        let db = Arc::new(LocalDb::open_in_memory().expect("in-memory db"));
+        let store = Arc::new(MemoryStore::new(db));
+        let base = serve(maintenance_routes().with_state(app_state(store))).await;
+
+        let (status, _) = call(&base, "POST", "/api/memory/maintenance/run", None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, run) =
+            call_authorized(&base, "POST", "/api/memory/maintenance/run", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(run["fts_optimized"], true);
+        assert!(run["error"].is_null());
+
+        let (status, body) =
+            call_authorized(&base, "GET", "/api/memory/maintenance/status", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["enabled"], true);
+        assert_eq!(body["running"], false);
+        assert_eq!(body["recent_runs"][0]["id"], run["id"]);
+    }
+
+    #[tokio::test]
+    async fn test_redaction_detection_counts() {
+        let redactor = Arc::new(Redactor::new());
+        redactor.redact("X-Acme: aZ3kP9qL2xW7vB4nM8rT6yH1jD5fG0cS");
+        let base = serve(redaction_routes().with_state(redaction_state(redactor))).await;
+
+        let (status, body) = call(&base, "GET", "/api/redaction/detections", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, serde_json::json!({ "jwt": 0, "hex": 0, "base64": 1 }));
+    }
+
+    #[tokio::test]
+    async fn test_redaction_preview_and_audit() {
+        let redactor = Arc::new(Redactor::new());
+        let base =
+            serve(redaction_routes().with_state(redaction_state(Arc::clone(&redactor)))).await;
+
+        let (status, body) = call(
+            &base,
+            "POST",
+            "/api/redaction/preview",
+            Some(serde_json::json!({ "text": "login password=hunter2" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["redacted"], "login [REDACTED:credential]");
+        assert_eq!(body["findings"][0]["category"], "credential");
+        assert_eq!(body["findings"][0]["rule_id"], "credential.assignment");
+        assert_eq!(body["findings"][0]["start"], 6);
+        assert_eq!(body["findings"][0]["end"], 22);
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("app.conf");
+        std::fs::write(&file, "DATABASE_URL=postgres://u:p@db/app\n").unwrap();
+        let (status, _) = call(
+            &base,
+            "POST",
+            "/api/redaction/preview",
+            Some(serde_json::json!({ "path": file })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, body) = call_authorized(
+            &base,
+            "POST",
+            "/api/redaction/preview",
+            Some(serde_json::json!({ "path": file })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(!body["redacted"].as_str().unwrap().contains("u:p@db"));
+
+        // Secret files are refused outright, and the refusal is audited
+        let secret = dir.path().join(".env");
+        std::fs::write(&secret, "TOKEN=abc\n").unwrap();
+        let (status, body) = call_authorized(
+            &base,
+            "POST",
+            "/api/redaction/preview",
+            Some(serde_json::json!({ "path": secret })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(body["error"]
+            .as_str()
+            .unwrap()
+            .contains("denied by file policy"));
+        let accesses = redactor.audit().files;
+        assert_eq!(accesses.last().unwrap().outcome, "denied");
+
+        let (status, _) = call(
+            &base,
+            "POST",
+            "/api/redaction/preview",
+            Some(serde_json::json!({})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // Previews are not counted; real redactions are, per session
+        let (_, audit) = call(&base, "GET", "/api/redaction/audit", None).await;
+        assert_eq!(audit["totals"], serde_json::json!({}));
+        redactor.redact_for_session("password=hunter2", "s1");
+        let (_, audit) = call(&base, "GET", "/api/redaction/audit", None).await;
+        assert_eq!(audit["totals"]["credential"], 1);
+        let (status, body) = call(&base, "GET", "/api/redaction/audit/s1", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["counts"]["credential"], 1);
+        let (status, _) = call(&base, "GET", "/api/redaction/audit/nope", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    /// State for [`redaction_routes`], with the default file policy.
+    #[derive(Clone)]
+    struct RedactionState {
+        redactor: Arc<Redactor>,
+        policy: Arc<FilePolicy>,
+        token: Arc<ApiToken>,
+    }
+
+    impl FromRef<RedactionState> for Arc<Redactor> {
+        fn from_ref(state: &RedactionState) -> Self {
+            Arc::clone(&state.redactor)
+        }
+    }
+
+    impl FromRef<RedactionState> for Arc<FilePolicy> {
+        fn from_ref(state: &RedactionState) -> Self {
+            Arc::clone(&state.policy)
+        }
+    }
+
+    impl FromRef<RedactionState> for Arc<ApiToken> {
+        fn from_ref(state: &RedactionState) -> Self {
+            Arc::clone(&state.token)
+        }
+    }
+
+    fn redaction_state(redactor: Arc<Redactor>) -> RedactionState {
+        RedactionState {
+            policy: Arc::new(FilePolicy::from_config(
+                &Default::default(),
+                Arc::clone(&redactor),
+            )),
+            redactor,
+            token: Arc::new(ApiToken::new(TEST_TOKEN)),
+        }
+    }
+
+    /// State for [`database_routes`].
+    #[derive(Clone)]
+    struct DatabaseState {
+        encryption: Arc<DbEncryption>,
+        token: Arc<ApiToken>,
+    }
+
+    impl FromRef<DatabaseState> for Arc<DbEncryption> {
+        fn from_ref(state: &DatabaseState) -> Self {
+            Arc::clone(&state.encryption)
+        }
+    }
+
+    impl FromRef<DatabaseState> for Arc<ApiToken> {
+        fn from_ref(state: &DatabaseState) -> Self {
+            Arc::clone(&state.token)
+        }
+    }
+
+    fn database_state(encryption: Arc<DbEncryption>) -> DatabaseState {
+        DatabaseState {
+            encryption,
+            token: Arc::new(ApiToken::new(TEST_TOKEN)),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_database_key_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = d1_common::EncryptionConfig {
+            enabled: true,
+            key_file: dir.path().join("db.key"),
+            passphrase_env: "D1_TEST_REST_PASSPHRASE_UNSET".into(),
+        };
+        let path = dir.path().join("d1.db");
+        let (db, source) = crate::db_key::open_database(path.to_str().unwrap(), &config).unwrap();
+        let encryption = Arc::new(DbEncryption::new(Arc::new(db), source, &config));
+        let base = serve(database_routes().with_state(database_state(encryption))).await;
+
+        let (status, body) = call(&base, "GET", "/api/database/encryption", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["encrypted"], true);
+        assert_eq!(body["key_source"], "key_file");
+
+        // Rotation needs the API token
+        let old_key = std::fs::read_to_string(&config.key_file).unwrap();
+        let (status, _) = call(
+            &base,
+            "POST",
+            "/api/database/rotate-key",
+            Some(serde_json::json!({})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = call_with_token(
+            &base,
+            "POST",
+            "/api/database/rotate-key",
+            Some(serde_json::json!({})),
+            Some("guess"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(std::fs::read_to_string(&config.key_file).unwrap(), old_key);
+
+        let (status, _) = call_authorized(
+            &base,
+            "POST",
+            "/api/database/rotate-key",
+            Some(serde_json::json!({})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert_ne!(std::fs::read_to_string(&config.key_file).unwrap(), old_key);
+
+        let (status, _) = call_authorized(
+            &base,
+            "POST",
+            "/api/database/rotate-key",
+            Some(serde_json::json!({ "passphrase": "hunter2" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_rotate_key_without_encryption() {
+        let db = Arc::new(LocalDb::open_in_memory().expect("in-memory db"));
+        let encryption = Arc::new(DbEncryption::new(db, None, &Default::default()));
+        let base = serve(database_routes().with_state(database_state(encryption))).await;
+
+        let (_, body) = call(&base, "GET", "/api/database/encryption", None).await;
+        assert_eq!(body["encrypted"], false);
+        assert!(body["key_source"].is_null());
+        let (status, _) = call_authorized(
+            &base,
+            "POST",
+            "/api/database/rotate-key",
+            Some(serde_json::json!({})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+    }
+
+    fn app_state(memory: Arc<MemoryStore>) -> AppState {
+        let (connection, _) = ConnectionStateMachine::new(Default::default());
+        let redactor = Arc::new(Redactor::new());
+        let db = Arc::new(LocalDb::open_in_memory().unwrap());
+        AppState {
+            connection,
+            file_policy: Arc::new(FilePolicy::from_config(
+                &Default::default(),
+                Arc::clone(&redactor),
+            )),
+            redactor,
+            encryption: Arc::new(DbEncryption::new(
+                Arc::clone(&db),
+                None,
+                &Default::default(),
+            )),
+            maintenance: Arc::new(MaintenanceScheduler::new(
+                Arc::clone(&memory),
+                db,
+                Default::default(),
+            )),
+            memory,
+            api_token: Arc::new(ApiToken::new(TEST_TOKEN)),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_router_serves_every_route_group() {
+        let base = serve(build_router_with_state(app_state(test_store()))).await;
+        for path in [
+            "/api/health",
+            "/api/memory/search?q=rust",
+            "/api/memory/maintenance/status",
+            "/api/database/encryption",
+            "/api/redaction/detections",
+            "/api/connection/status",
+        ] {
+            let (status, _) = call_authorized(&base, "GET", path, None).await;
+            assert_eq!(status, StatusCode::OK, "{path}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connection_status_and_events() {
+        let state = app_state(test_store());
+        let connection = Arc::clone(&state.connection);
+        let base = serve(build_router_with_state(state)).await;
+
+        let (status, body) = call(&base, "GET", "/api/connection/status", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["state"], "offline");
+        assert_eq!(body["queued_messages"], 0);
+
+        let mut events = reqwest::get(format!("{base}/api/connection/events"))
+            .await
+            .unwrap();
+        assert_eq!(events.headers()["content-type"], "text/event-stream");
+        let mut received = String::new();
+        while !received.contains("event: status") {
+            let chunk = events.chunk().await.unwrap().unwrap();
+            received.push_str(&String::from_utf8_lossy(&chunk));
+        }
+        assert!(received.contains(r#""state":"offline""#));
+
+        connection
+            .transition(ConnectionState::Online, "authenticated")
+            .await
+            .unwrap();
+        while !received.contains("event: state") {
+            let chunk = events.chunk().await.unwrap().unwrap();
+            received.push_str(&String::from_utf8_lossy(&chunk));
+        }
+        assert!(received.contains(r#""current":"online""#));
+        assert!(received.contains(r#""message":"authenticated""#));
+    }
+
+    #[tokio::test]
+    async fn test_memory_export_import_roundtrip() {
+        let source = serve(build_router_with_state(app_state(test_store()))).await;
+        let export = serde_json::json!({
+            "tables": ["profile"],
+            "profile_categories": ["system"],
+            "key": "s3cret"
+        });
+        let (status, body) =
+            call(&source, "POST", "/api/memory/export", Some(export.clone())).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(body["error"].as_str().unwrap().contains("API token"));
+
+        let response = reqwest::Client::new()
+            .post(format!("{source}/api/memory/export"))
+            .bearer_auth(TEST_TOKEN)
+            .json(&export)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+        let bundle = response.text().await.unwrap();
+
+        let db = LocalDb::open_in_memory().unwrap();
+        let target_store = Arc::new(MemoryStore::new(Arc::new(db)));
+        let target = serve(build_router_with_state(app_state(Arc::clone(
+            &target_store,
+        ))))
+        .await;
+
+        // Importing needs the API token too, even with the right bundle key
+        let import =
+            serde_json::json!({ "bundle": bundle, "key": "s3cret", "policy": "overwrite" });
+        let (status, _) = call(&target, "POST", "/api/memory/import", Some(import.clone())).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = call_with_token(
+            &target,
+            "POST",
+            "/api/memory/import",
+            Some(import),
+            Some("wrong"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(target_store
+            .export_records(MemoryTable::Profile, &[])
+            .unwrap()
+            .is_empty());
+
+        let (status, _) = call_authorized(
+            &target,
+            "POST",
+            "/api/memory/import",
+            Some(serde_json::json!({ "bundle": bundle, "key": "wrong" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, summary) = call_authorized(
+            &target,
+            "POST",
+            "/api/memory/import",
+            Some(serde_json::json!({ "bundle": bundle, "key": "s3cret", "policy": "keep_newer" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(summary["inserted"], 2);
+        assert_eq!(
+            target_store
+                .export_records(MemoryTable::Profile, &[])
+                .unwrap()
+                .len(),
+            2
+        );
+
+        let (status, _) = call_authorized(
+            &source,
+            "POST",
+            "/api/memory/export",
+            Some(serde_json::json!({ "tables": ["audit_log"], "key": "k" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_memory_routes_require_token() {
+        let store = test_store();
+        let base = serve(build_router_with_state(app_state(Arc::clone(&store)))).await;
+        let (_, created) = call_authorized(
+            &base,
+            "POST",
+            "/api/memory/profile",
+            Some(serde_json::json!({ "category": "tools", "key": "editor", "value": "vim" })),
+        )
+        .await;
+        let id = created["id"].as_str().unwrap();
+
+        let requests = [
+            ("GET", "/api/memory/search?q=vim".to_string(), None),
+            ("GET", "/api/memory/profile".to_string(), None),
+            (
+                "POST",
+                "/api/memory/profile".to_string(),
+                Some(serde_json::json!({ "category": "tools", "key": "shell", "value": "zsh" })),
+            ),
+            ("GET", format!("/api/memory/profile/{id}"), None),
+            (
+                "PATCH",
+                format!("/api/memory/profile/{id}"),
+                Some(serde_json::json!({ "value": "emacs" })),
+            ),
+            ("DELETE", format!("/api/memory/profile/{id}"), None),
+            ("GET", format!("/api/memory/profile/{id}/history"), None),
+            ("POST", format!("/api/memory/profile/{id}/restore"), None),
+            (
+                "POST",
+                format!("/api/memory/profile/{id}/feedback"),
+                Some(serde_json::json!({ "success": true })),
+            ),
+            (
+                "POST",
+                "/api/memory/maintenance/compress".to_string(),
+                Some(serde_json::json!({ "compress_after_days": 0 })),
+            ),
+            (
+                "POST",
+                "/api/memory/maintenance/archive-sessions".to_string(),
+                Some(serde_json::json!({ "max_age_hours": 0 })),
+            ),
+        ];
+        for (method, path, body) in requests {
+            let (status, _) = call(&base, method, &path, body.clone()).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED, "{method} {path}");
+            let (status, _) = call_with_token(&base, method, &path, body, Some("wrong")).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED, "{method} {path}");
+        }
+
+        // Nothing was changed
+        let (status, record) =
+            call_authorized(&base, "GET", &format!("/api/memory/profile/{id}"), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(record["value"], "vim");
+    }
+
+    #[tokio::test]
+    async fn test_memory_list_pagination_and_filters() {
+        let base = serve(build_router_with_state(app_state(test_store()))).await;
+
+        let (status, page) =
+            call_authorized(&base, "GET", "/api/memory/profile?limit=1", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(page["records"].as_array().unwrap().len(), 1);
+        let cursor = page["next_cursor"].as_str().unwrap().to_string();
+
+        let (_, page) = call_authorized(
+            &base,
+            "GET",
+            &format!("/api/memory/profile?limit=1&cursor={cursor}"),
+            None,
+        )
+        .await;
+        assert_eq!(page["records"][0]["key"], "shell");
+        assert!(page["next_cursor"].is_null());
+
+        let (_, page) = call_authorized(&base, "GET", "/api/memory/profile?source=env", None).await;
+        assert_eq!(page["records"].as_array().unwrap().len(), 1);
+
+        let (status, _) = call_authorized(&base, "GET", "/api/memory/profile?value=x", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = call_authorized(&base, "GET", "/api/memory/audit_log", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_memory_maintenance_endpoints() {
+        let base = serve(build_router_with_state(app_state(test_store()))).await;
+
+        let (status, body) = call_authorized(
+            &base,
+            "POST",
+            "/api/memory/maintenance/compress",
+            Some(serde_json::json!({ "compress_after_days": 30 })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["affected"], 0);
+
+        let (status, _) = call_authorized(
+            &base,
+            "POST",
+            "/api/memory/maintenance/archive-sessions",
+            Some(serde_json::json!({ "max_age_hours": -1 })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
     }
 
     #[test]
@@ -248,18 +1983,4 @@ mod tests {
         let json = serde_json::to_string(&response).unwrap();
         assert!(json.contains("\"status\":\"ok\""));
     }
-
-    #[test]
-    fn test_compute_relevance_exact_match() {
-        let entry = MemorySearchResult {
-            id: "1".to_string(),
-            key: "os".to_string(),
-            value: "macOS".to_string(),
-            source: "system".to_string(),
-            relevance: 0.0,
-            timestamp: 0,
-        };
-        let score = compute_relevance(&entry, "os");
-        assert!((score - 1.0).abs() < f64::EPSILON);
-    }
 }
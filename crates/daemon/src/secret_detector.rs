@@ -0,0 +1,414 @@
+//! Heuristic secret detection for values no vendor pattern knows about.
+//!
+//! The [`Redactor`](crate::redactor::Redactor) patterns only catch secrets
+//! with a recognisable prefix (`sk-`, `AKIA`, `ghp_`, ...) or a `key=value`
+//! shape. [`SecretDetector`] is the secondary pass: it finds JWTs by their
+//! structure and scores every other token-like run of characters by
+//! normalized Shannon entropy, charset and length.
+//!
+//! Known false positives are filtered out before scoring: UUIDs, git SHAs
+//! and SHA-256 digests, SRI hashes in lockfiles, file paths and
+//! identifier-like strings made of words. The text just before a candidate
+//! is taken into account too: `token: ...` lowers the bar, `checksum = ...`
+//! or `commit ...` skips the candidate.
+
+use std::sync::OnceLock;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use regex::Regex;
+use serde::Serialize;
+
+use d1_common::config::RedactionConfig;
+
+/// Shortest run of characters considered for entropy scoring.
+pub const MIN_CANDIDATE_LEN: usize = 20;
+
+/// How far back (in bytes, same line) to look for context keywords.
+const CONTEXT_WINDOW: usize = 32;
+
+/// Threshold reduction when a secret keyword precedes the candidate.
+const KEYWORD_BONUS: f64 = 0.1;
+
+/// Share of a candidate made of word-like letter runs above which it reads
+/// as an identifier (`getUserById2024Handler`) rather than random data.
+const MAX_WORD_FRACTION: f64 = 0.3;
+
+/// What kind of secret a [`Detection`] looks like.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DetectionKind {
+    /// A structurally valid JSON Web Token.
+    Jwt,
+    /// A high-entropy run of hex digits.
+    Hex,
+    /// A high-entropy run of base64 / base64url characters.
+    Base64,
+}
+
+/// A span of text that looks like a secret. Holds no copy of the value.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Detection {
+    pub kind: DetectionKind,
+    /// Byte offsets into the scanned text.
+    pub start: usize,
+    pub end: usize,
+    /// Normalized Shannon entropy (0–1); 1.0 for JWTs.
+    pub entropy: f64,
+}
+
+/// Entropy and structure based secret detector.
+#[derive(Debug, Clone)]
+pub struct SecretDetector {
+    threshold: f64,
+}
+
+impl SecretDetector {
+    /// `threshold` is the normalized entropy (0–1) a candidate must reach.
+    pub fn new(threshold: f64) -> Self {
+        Self {
+            threshold: threshold.clamp(0.0, 1.0),
+        }
+    }
+
+    pub fn from_config(config: &RedactionConfig) -> Self {
+        Self::new(config.entropy_threshold)
+    }
+
+    /// Find every likely secret in `text`, ordered by position.
+    pub fn detect(&self, text: &str) -> Vec<Detection> {
+        let mut detections: Vec<Detection> = jwt_pattern()
+            .find_iter(text)
+            .filter(|m| is_jwt(m.as_str()))
+            .map(|m| Detection {
+                kind: DetectionKind::Jwt,
+                start: m.start(),
+                end: m.end(),
+                entropy: 1.0,
+            })
+            .collect();
+
+        for m in candidate_pattern().find_iter(text) {
+            if detections
+                .iter()
+                .any(|d| m.start() < d.end && d.start < m.end())
+            {
+                continue;
+            }
+            if let Some(detection) = self.score(text, m.start(), m.as_str()) {
+                detections.push(detection);
+            }
+        }
+
+        detections.sort_by_key(|d| d.start);
+        detections
+    }
+
+    fn score(&self, text: &str, start: usize, candidate: &str) -> Option<Detection> {
+        if candidate.len() < MIN_CANDIDATE_LEN
+            || uuid_pattern().is_match(candidate)
+            || sri_hash_pattern().is_match(candidate)
+            || !candidate.bytes().any(|b| b.is_ascii_digit())
+            || !candidate.bytes().any(|b| b.is_ascii_alphabetic())
+        {
+            return None;
+        }
+
+        let context = Context::before(text, start);
+        if context == Context::Hash {
+            return None;
+        }
+
+        let body = candidate.trim_end_matches('=');
+        let kind = if body.bytes().all(|b| b.is_ascii_hexdigit()) {
+            // Bare git SHAs and SHA-256 digests are everywhere in tool output
+            if matches!(body.len(), 40 | 64) && context != Context::Secret {
+                return None;
+            }
+            DetectionKind::Hex
+        } else {
+            if looks_like_path(body) || word_fraction(body) > MAX_WORD_FRACTION {
+                return None;
+            }
+            DetectionKind::Base64
+        };
+
+        let alphabet = match kind {
+            DetectionKind::Hex => 16,
+            _ => 64,
+        };
+        let entropy = normalized_entropy(body, alphabet);
+        let threshold = match context {
+            Context::Secret => self.threshold - KEYWORD_BONUS,
+            _ => self.threshold,
+        };
+        (entropy >= threshold).then(|| Detection {
+            kind,
+            start,
+            end: start + candidate.len(),
+            entropy,
+        })
+    }
+}
+
+impl Default for SecretDetector {
+    fn default() -> Self {
+        Self::from_config(&RedactionConfig::default())
+    }
+}
+
+/// Shannon entropy of `s` in bits per character.
+pub fn shannon_entropy(s: &str) -> f64 {
+    let mut counts = [0usize; 256];
+    for b in s.bytes() {
+        counts[b as usize] += 1;
+    }
+    let len = s.len() as f64;
+    counts
+        .iter()
+        .filter(|&&c| c > 0)
+        .map(|&c| {
+            let p = c as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Entropy of `s` relative to the most a string of its length over an
+/// alphabet of `alphabet` symbols can have.
+fn normalized_entropy(s: &str, alphabet: usize) -> f64 {
+    let max = (s.len().min(alphabet) as f64).log2();
+    if max <= 0.0 {
+        return 0.0;
+    }
+    shannon_entropy(s) / max
+}
+
+/// Share of `s` covered by runs of 4+ letters in one case (or a capitalised
+/// word), which random data rarely contains.
+fn word_fraction(s: &str) -> f64 {
+    let covered: usize = word_pattern().find_iter(s).map(|m| m.len()).sum();
+    covered as f64 / s.len() as f64
+}
+
+/// `src/components/Button2` is a path, `aGVs/bG8gd29ybGQ...` is base64:
+/// path segments are short, base64 has a `/` only every 64 characters or so.
+fn looks_like_path(s: &str) -> bool {
+    let segments = s.split('/').filter(|seg| !seg.is_empty()).count();
+    segments > 1 && s.len() / segments < 16
+}
+
+/// A JWT's header and payload must both decode to JSON objects, and the
+/// header must name an algorithm.
+fn is_jwt(token: &str) -> bool {
+    let mut parts = token.split('.');
+    let (Some(header), Some(payload)) = (parts.next(), parts.next()) else {
+        return false;
+    };
+    let decode = |part: &str| -> Option<serde_json::Value> {
+        let bytes = URL_SAFE_NO_PAD.decode(part).ok()?;
+        serde_json::from_slice(&bytes).ok()
+    };
+    match (decode(header), decode(payload)) {
+        (Some(header), Some(payload)) => header.get("alg").is_some() && payload.is_object(),
+        _ => false,
+    }
+}
+
+/// What the text just before a candidate says about it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Context {
+    /// A secret keyword such as `token` or `api_key`.
+    Secret,
+    /// A hash keyword such as `sha256`, `commit` or `checksum`.
+    Hash,
+    None,
+}
+
+impl Context {
+    fn before(text: &str, start: usize) -> Self {
+        let line_start = text[..start].rfind('\n').map_or(0, |i| i + 1);
+        let mut window_start = start.saturating_sub(CONTEXT_WINDOW).max(line_start);
+        while !text.is_char_boundary(window_start) {
+            window_start += 1;
+        }
+        let window = text[window_start..start].to_ascii_lowercase();
+
+        if hash_keyword_pattern().is_match(&window) {
+            Context::Hash
+        } else if secret_keyword_pattern().is_match(&window) {
+            Context::Secret
+        } else {
+            Context::None
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Patterns
+// ---------------------------------------------------------------------------
+
+fn jwt_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"eyJ[A-Za-z0-9_-]+\.eyJ[A-Za-z0-9_-]+\.[A-Za-z0-9_-]*")
+            .expect("invalid jwt regex")
+    })
+}
+
+fn candidate_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"[A-Za-z0-9+/_-]+={0,2}").expect("invalid candidate regex"))
+}
+
+fn uuid_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"^[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}$")
+            .expect("invalid uuid regex")
+    })
+}
+
+/// Subresource-integrity hashes as found in `package-lock.json` / `yarn.lock`.
+fn sri_hash_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"^sha(?:1|256|384|512)-").expect("invalid sri regex"))
+}
+
+fn word_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"[a-z]{4,}|[A-Z][a-z]{3,}|[A-Z]{4,}").expect("invalid word regex")
+    })
+}
+
+fn secret_keyword_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"key|token|secret|passw|auth|bearer|credential|session|signature")
+            .expect("invalid secret keyword regex")
+    })
+}
+
+fn hash_keyword_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"sha\d*|md5|commit|checksum|integrity|digest|hash|revision|\brev\b|resolved")
+            .expect("invalid hash keyword regex")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detector() -> SecretDetector {
+        SecretDetector::default()
+    }
+
+    fn kinds(text: &str) -> Vec<DetectionKind> {
+        detector().detect(text).iter().map(|d| d.kind).collect()
+    }
+
+    fn jwt() -> String {
+        let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"HS256","typ":"JWT"}"#);
+        let payload = URL_SAFE_NO_PAD.encode(r#"{"sub":"1234567890","name":"Jane"}"#);
+        format!("{header}.{payload}.SflKxwRJSMeKKF2QT4fwpMeJf36POk6yJV_adQssw5c")
+    }
+
+    #[test]
+    fn entropy_of_simple_strings() {
+        assert_eq!(shannon_entropy("aaaa"), 0.0);
+        assert!((shannon_entropy("abab") - 1.0).abs() < 1e-9);
+        assert!((shannon_entropy("abcd") - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn detects_unknown_vendor_keys() {
+        let text = "export ACME_KEY=aZ3kP9qL2xW7vB4nM8rT6yH1jD5fG0cS";
+        let found = detector().detect(text);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].kind, DetectionKind::Base64);
+        assert_eq!(
+            &text[found[0].start..found[0].end],
+            "aZ3kP9qL2xW7vB4nM8rT6yH1jD5fG0cS"
+        );
+    }
+
+    #[test]
+    fn detects_random_hex_tokens() {
+        assert_eq!(
+            kinds("x-api: 9f8e2d6c5b4a39a817c6f5e4d3c2b1a0"),
+            vec![DetectionKind::Hex]
+        );
+    }
+
+    #[test]
+    fn detects_base64_blobs() {
+        assert_eq!(
+            kinds("blob Q3VzdG9tZXIgc2VjcmV0IGhlcmU9MTIz/x7Kq+Lm2Pw== end"),
+            vec![DetectionKind::Base64]
+        );
+    }
+
+    #[test]
+    fn detects_jwts_by_structure() {
+        let token = jwt();
+        let text = format!("cookie {token};");
+        let found = detector().detect(&text);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].kind, DetectionKind::Jwt);
+        assert_eq!(&text[found[0].start..found[0].end], token);
+
+        // eyJ-prefixed but not decodable JSON is not a JWT
+        assert!(!is_jwt("eyJhbGciOi.eyJzdWIiOi.abc"));
+    }
+
+    #[test]
+    fn ignores_uuids_and_hashes() {
+        assert!(kinds("id 0b6f7c3e-8d2a-4f1b-9e5c-7a4d2b1c0f9e").is_empty());
+        // Bare git SHA-1 and SHA-256 digests
+        assert!(kinds("HEAD is now at 3f786850e387550fdab836ed7e6dc881de23001b").is_empty());
+        assert!(
+            kinds("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855").is_empty()
+        );
+        // Lockfile entries
+        assert!(kinds(r#"checksum = "a1b2c3d4e5f60718293a4b5c6d7e8f90a1b2c3d4""#).is_empty());
+        assert!(kinds(
+            r#""integrity": "sha512-z4PhNX7vuL3xVChQ1m2AB9Yg5AULVxXcg/SpIdNs6c5H0NE8XYXysP+DGNKHfuwvY7kxvUR6lgjbU""#
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn secret_context_overrides_hash_length_filter() {
+        assert_eq!(
+            kinds("api_token: 3f786850e387550fdab836ed7e6dc881de23001b"),
+            vec![DetectionKind::Hex]
+        );
+    }
+
+    #[test]
+    fn ignores_identifiers_paths_and_prose() {
+        for text in [
+            "getUserById2024Handler",
+            "test_encrypting_an_old_plaintext_database2",
+            "MAX_TOKENS_PER_SESSION_1024",
+            "aarch64-apple-darwin23 x86_64-unknown-linux-gnu",
+            "src/components/Button2/index/styles/v2",
+            "The quick brown fox jumps over the lazy dog 1234",
+            "12345678901234567890123",
+        ] {
+            assert!(kinds(text).is_empty(), "false positive in {text:?}");
+        }
+    }
+
+    #[test]
+    fn threshold_is_tunable() {
+        let text = "ref aZ3kP9qL2xW7vB4nM8rT6yH1jD5fG0cSaZ3kP9qL";
+        let entropy = detector().detect(text)[0].entropy;
+        assert!(entropy < 1.0);
+        assert!(SecretDetector::new(entropy + 0.01).detect(text).is_empty());
+        assert_eq!(SecretDetector::new(entropy).detect(text).len(), 1);
+    }
+}
@@ -15,7 +15,7 @@ use tracing::{debug, warn};
 
 /// Risk level assigned to a command by the security layer.
 ///
-/// Note: This is distinct from `d1_common::proto::RiskLevel` which describes
+/// Note: This is distinct from `d1_common::protocol::RiskTier` which describes
 /// plan-level risk. This enum describes *individual command* risk.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RiskLevel {
@@ -29,6 +29,17 @@ pub enum RiskLevel {
     Blocked,
 }
 
+impl RiskLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RiskLevel::Low => "low",
+            RiskLevel::Medium => "medium",
+            RiskLevel::High => "high",
+            RiskLevel::Blocked => "blocked",
+        }
+    }
+}
+
 /// Result of classifying a command string.
 #[derive(Debug, Clone)]
 pub struct RiskClassification {
@@ -0,0 +1,621 @@
+//! Persistent environment variables in shell startup files.
+//!
+//! [`SystemOps::env_set`](crate::system_ops::SystemOps::env_set) only
+//! affects the daemon process. This module writes `export` lines (or fish
+//! `set -gx`) into the user's rc file instead, inside a block delimited by
+//! [`BEGIN_MARKER`] / [`END_MARKER`]. Everything outside the block is left
+//! untouched, and the block is fully regenerated on each change so edits
+//! are idempotent.
+//!
+//! PATH entries are written with a runtime guard so re-sourcing the rc file
+//! never adds the same directory twice.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// First line of the managed block.
+pub const BEGIN_MARKER: &str = "# >>> d1-doctor managed env >>>";
+/// Last line of the managed block.
+pub const END_MARKER: &str = "# <<< d1-doctor managed env <<<";
+
+const NOTICE: &str = "# Managed by Day 1 Doctor. Changes inside this block are overwritten.";
+
+// ---------------------------------------------------------------------------
+// Shell detection
+// ---------------------------------------------------------------------------
+
+/// A shell whose rc file we know how to manage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "bash" | "sh" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            other => anyhow::bail!("unsupported shell: {other}"),
+        }
+    }
+
+    /// Detect the user's login shell from `$SHELL`, defaulting to bash.
+    pub fn detect() -> Self {
+        std::env::var("SHELL")
+            .ok()
+            .and_then(|s| {
+                Path::new(&s)
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .and_then(|n| Shell::parse(n).ok())
+            })
+            .unwrap_or(Shell::Bash)
+    }
+
+    /// The rc file this shell reads for interactive sessions.
+    pub fn rc_file(&self, home: &Path) -> PathBuf {
+        match self {
+            // macOS Terminal starts login shells, which skip ~/.bashrc.
+            Shell::Bash if cfg!(target_os = "macos") => home.join(".bash_profile"),
+            Shell::Bash => home.join(".bashrc"),
+            Shell::Zsh => std::env::var_os("ZDOTDIR")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| home.to_path_buf())
+                .join(".zshrc"),
+            Shell::Fish => std::env::var_os("XDG_CONFIG_HOME")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| home.join(".config"))
+                .join("fish")
+                .join("config.fish"),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Shell::Bash => "bash",
+            Shell::Zsh => "zsh",
+            Shell::Fish => "fish",
+        }
+    }
+}
+
+/// The shell and rc file an edit applies to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShellTarget {
+    pub shell: Shell,
+    pub rc_file: PathBuf,
+}
+
+impl ShellTarget {
+    /// Resolve a target from optional overrides, detecting anything missing.
+    pub fn resolve(shell: Option<&str>, rc_file: Option<&str>) -> Result<Self> {
+        let shell = match shell {
+            Some(s) => Shell::parse(s)?,
+            None => Shell::detect(),
+        };
+        let rc_file = match rc_file {
+            Some(p) => PathBuf::from(p),
+            None => {
+                let home = dirs::home_dir().context("cannot determine home directory")?;
+                shell.rc_file(&home)
+            }
+        };
+        Ok(ShellTarget { shell, rc_file })
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Managed block model
+// ---------------------------------------------------------------------------
+
+/// Where a directory is added to `PATH`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PathPosition {
+    Prepend,
+    Append,
+}
+
+impl PathPosition {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "prepend" => Ok(PathPosition::Prepend),
+            "append" => Ok(PathPosition::Append),
+            other => anyhow::bail!("unsupported PATH position: {other}"),
+        }
+    }
+}
+
+/// A change to the managed block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EnvEdit {
+    Set { key: String, value: String },
+    Unset { key: String },
+    PathAdd { dir: String, position: PathPosition },
+    PathRemove { dir: String },
+}
+
+/// Contents of the managed block.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManagedEnv {
+    /// Exported variables in file order.
+    pub vars: Vec<(String, String)>,
+    pub path_prepend: Vec<String>,
+    pub path_append: Vec<String>,
+}
+
+impl ManagedEnv {
+    /// Parse the managed block out of a whole rc file.
+    pub fn from_rc(content: &str, shell: Shell) -> Self {
+        let mut env = ManagedEnv::default();
+        let Some(body) = block_body(content) else {
+            return env;
+        };
+        for line in body {
+            let line = line.trim();
+            match shell {
+                Shell::Fish => env.parse_fish_line(line),
+                Shell::Bash | Shell::Zsh => env.parse_posix_line(line),
+            }
+        }
+        env
+    }
+
+    fn parse_posix_line(&mut self, line: &str) {
+        if let Some(rest) = line.strip_prefix("case \":$PATH:\" in *\":") {
+            let Some((dir, tail)) = rest.split_once(":\"*)") else {
+                return;
+            };
+            let dir = posix_unescape_double(dir);
+            if tail.contains("export PATH=\"$PATH:") {
+                self.path_append.push(dir);
+            } else {
+                // Rendered in reverse; see `render`.
+                self.path_prepend.insert(0, dir);
+            }
+        } else if let Some(rest) = line.strip_prefix("export ") {
+            if let Some((key, value)) = rest.split_once('=') {
+                self.vars
+                    .push((key.to_string(), posix_unquote_single(value)));
+            }
+        }
+    }
+
+    fn parse_fish_line(&mut self, line: &str) {
+        if let Some(rest) = line.strip_prefix("contains -- ") {
+            let Some((quoted, tail)) = rest.split_once(" $PATH;") else {
+                return;
+            };
+            let dir = fish_unquote(quoted);
+            if tail.trim_end().ends_with("$PATH") {
+                // Rendered in reverse; see `render`.
+                self.path_prepend.insert(0, dir);
+            } else {
+                self.path_append.push(dir);
+            }
+        } else if let Some(rest) = line.strip_prefix("set -gx ") {
+            if let Some((key, value)) = rest.split_once(' ') {
+                self.vars.push((key.to_string(), fish_unquote(value)));
+            }
+        }
+    }
+
+    /// Apply `edit`, returning `true` if anything changed.
+    pub fn apply(&mut self, edit: &EnvEdit) -> Result<bool> {
+        match edit {
+            EnvEdit::Set { key, value } => {
+                validate_var_name(key)?;
+                if value.contains('\0') || value.contains('\n') {
+                    anyhow::bail!("environment values must be a single line");
+                }
+                match self.vars.iter_mut().find(|(k, _)| k == key) {
+                    Some((_, v)) if v == value => Ok(false),
+                    Some((_, v)) => {
+                        *v = value.clone();
+                        Ok(true)
+                    }
+                    None => {
+                        self.vars.push((key.clone(), value.clone()));
+                        Ok(true)
+                    }
+                }
+            }
+            EnvEdit::Unset { key } => {
+                let before = self.vars.len();
+                self.vars.retain(|(k, _)| k != key);
+                Ok(self.vars.len() != before)
+            }
+            EnvEdit::PathAdd { dir, position } => {
+                validate_path_dir(dir)?;
+                let (same, other) = match position {
+                    PathPosition::Prepend => (&mut self.path_prepend, &mut self.path_append),
+                    PathPosition::Append => (&mut self.path_append, &mut self.path_prepend),
+                };
+                let moved = other.len();
+                other.retain(|d| d != dir);
+                if same.contains(dir) {
+                    return Ok(other.len() != moved);
+                }
+                same.push(dir.clone());
+                Ok(true)
+            }
+            EnvEdit::PathRemove { dir } => {
+                let before = self.path_prepend.len() + self.path_append.len();
+                self.path_prepend.retain(|d| d != dir);
+                self.path_append.retain(|d| d != dir);
+                Ok(self.path_prepend.len() + self.path_append.len() != before)
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vars.is_empty() && self.path_prepend.is_empty() && self.path_append.is_empty()
+    }
+
+    /// Render the block, including its markers.
+    pub fn render(&self, shell: Shell) -> String {
+        let mut lines = vec![BEGIN_MARKER.to_string(), NOTICE.to_string()];
+        for (key, value) in &self.vars {
+            lines.push(match shell {
+                Shell::Fish => format!("set -gx {key} {}", fish_quote(value)),
+                Shell::Bash | Shell::Zsh => format!("export {key}={}", posix_quote_single(value)),
+            });
+        }
+        // Prepends are emitted in reverse so the first entry ends up first.
+        for dir in self.path_prepend.iter().rev() {
+            lines.push(match shell {
+                Shell::Fish => {
+                    let q = fish_quote(dir);
+                    format!("contains -- {q} $PATH; or set -gx PATH {q} $PATH")
+                }
+                Shell::Bash | Shell::Zsh => {
+                    let d = posix_escape_double(dir);
+                    format!(
+                        "case \":$PATH:\" in *\":{d}:\"*) ;; *) export PATH=\"{d}:$PATH\" ;; esac"
+                    )
+                }
+            });
+        }
+        for dir in &self.path_append {
+            lines.push(match shell {
+                Shell::Fish => {
+                    let q = fish_quote(dir);
+                    format!("contains -- {q} $PATH; or set -gx PATH $PATH {q}")
+                }
+                Shell::Bash | Shell::Zsh => {
+                    let d = posix_escape_double(dir);
+                    format!(
+                        "case \":$PATH:\" in *\":{d}:\"*) ;; *) export PATH=\"$PATH:{d}\" ;; esac"
+                    )
+                }
+            });
+        }
+        lines.push(END_MARKER.to_string());
+        lines.join("\n") + "\n"
+    }
+}
+
+/// Lines strictly between the markers, if the block exists.
+fn block_body(content: &str) -> Option<Vec<&str>> {
+    let mut lines = content.lines();
+    lines.by_ref().find(|l| l.trim() == BEGIN_MARKER)?;
+    Some(lines.take_while(|l| l.trim() != END_MARKER).collect())
+}
+
+/// Replace (or insert, or with `None` remove) the managed block in `content`.
+pub fn replace_block(content: &str, block: Option<&str>) -> String {
+    let lines: Vec<&str> = content.lines().collect();
+    let begin = lines.iter().position(|l| l.trim() == BEGIN_MARKER);
+    let end = begin.and_then(|b| {
+        lines[b..]
+            .iter()
+            .position(|l| l.trim() == END_MARKER)
+            .map(|e| b + e)
+    });
+
+    let mut out = String::new();
+    match (begin, end) {
+        (Some(b), Some(e)) => {
+            for line in &lines[..b] {
+                out.push_str(line);
+                out.push('\n');
+            }
+            if let Some(block) = block {
+                out.push_str(block);
+            } else if out.ends_with("\n\n") {
+                // Drop the blank separator we added when inserting.
+                out.pop();
+            }
+            for line in &lines[e + 1..] {
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+        _ => {
+            out.push_str(content);
+            if let Some(block) = block {
+                if !out.is_empty() && !out.ends_with('\n') {
+                    out.push('\n');
+                }
+                if !out.is_empty() && !out.ends_with("\n\n") {
+                    out.push('\n');
+                }
+                out.push_str(block);
+            }
+        }
+    }
+    out
+}
+
+// ---------------------------------------------------------------------------
+// Validation and quoting
+// ---------------------------------------------------------------------------
+
+fn validate_var_name(key: &str) -> Result<()> {
+    let valid = !key.is_empty()
+        && !key.starts_with(|c: char| c.is_ascii_digit())
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        anyhow::bail!("invalid environment variable name: {key:?}");
+    }
+    if key == "PATH" {
+        anyhow::bail!("use path_add / path_remove to change PATH");
+    }
+    Ok(())
+}
+
+fn validate_path_dir(dir: &str) -> Result<()> {
+    if dir.is_empty() || dir.contains(':') || dir.contains('\n') || dir.contains('\0') {
+        anyhow::bail!("invalid PATH directory: {dir:?}");
+    }
+    Ok(())
+}
+
+/// Expand a leading `~/` so the directory works inside quotes.
+pub fn expand_home(dir: &str, home: &Path) -> String {
+    match dir.strip_prefix("~/") {
+        Some(rest) => home.join(rest).to_string_lossy().to_string(),
+        None if dir == "~" => home.to_string_lossy().to_string(),
+        None => dir.to_string(),
+    }
+}
+
+fn posix_quote_single(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+fn posix_unquote_single(raw: &str) -> String {
+    let raw = raw.trim();
+    match raw.strip_prefix('\'').and_then(|r| r.strip_suffix('\'')) {
+        Some(inner) => inner.replace("'\\''", "'"),
+        None => raw.to_string(),
+    }
+}
+
+fn posix_escape_double(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '\\' | '"' | '$' | '`') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn posix_unescape_double(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(next) = chars.next() {
+                out.push(next);
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn fish_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+fn fish_unquote(raw: &str) -> String {
+    let raw = raw.trim();
+    match raw.strip_prefix('\'').and_then(|r| r.strip_suffix('\'')) {
+        Some(inner) => posix_unescape_double(inner),
+        None => raw.to_string(),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> ManagedEnv {
+        let mut env = ManagedEnv::default();
+        for edit in [
+            EnvEdit::Set {
+                key: "EDITOR".into(),
+                value: "nvim".into(),
+            },
+            EnvEdit::Set {
+                key: "GREETING".into(),
+                value: "it's \"here\" $HOME".into(),
+            },
+            EnvEdit::PathAdd {
+                dir: "/opt/tools/bin".into(),
+                position: PathPosition::Prepend,
+            },
+            EnvEdit::PathAdd {
+                dir: "/home/dev/.cargo/bin".into(),
+                position: PathPosition::Prepend,
+            },
+            EnvEdit::PathAdd {
+                dir: "/usr/local/go/bin".into(),
+                position: PathPosition::Append,
+            },
+        ] {
+            assert!(env.apply(&edit).unwrap());
+        }
+        env
+    }
+
+    #[test]
+    fn test_render_posix() {
+        let block = sample().render(Shell::Bash);
+        assert!(block.starts_with(BEGIN_MARKER));
+        assert!(block.trim_end().ends_with(END_MARKER));
+        assert!(block.contains("export EDITOR='nvim'\n"));
+        assert!(block.contains("export GREETING='it'\\''s \"here\" $HOME'\n"));
+        assert!(block.contains(
+            "case \":$PATH:\" in *\":/usr/local/go/bin:\"*) ;; *) export PATH=\"$PATH:/usr/local/go/bin\" ;; esac"
+        ));
+        // The first prepended dir must be exported last so it wins.
+        let cargo = block.find(".cargo/bin").unwrap();
+        let tools = block.find("/opt/tools/bin").unwrap();
+        assert!(cargo < tools);
+    }
+
+    #[test]
+    fn test_roundtrip_all_shells() {
+        let env = sample();
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            let rc = format!("# mine\nalias ll='ls -l'\n\n{}", env.render(shell));
+            assert_eq!(ManagedEnv::from_rc(&rc, shell), env, "shell {shell:?}");
+        }
+    }
+
+    #[test]
+    fn test_render_fish() {
+        let block = sample().render(Shell::Fish);
+        assert!(block.contains("set -gx EDITOR 'nvim'\n"));
+        assert!(block.contains(
+            "contains -- '/usr/local/go/bin' $PATH; or set -gx PATH $PATH '/usr/local/go/bin'"
+        ));
+    }
+
+    #[test]
+    fn test_apply_is_idempotent_and_dedupes_path() {
+        let mut env = sample();
+        assert!(!env
+            .apply(&EnvEdit::Set {
+                key: "EDITOR".into(),
+                value: "nvim".into()
+            })
+            .unwrap());
+        assert!(!env
+            .apply(&EnvEdit::PathAdd {
+                dir: "/opt/tools/bin".into(),
+                position: PathPosition::Prepend
+            })
+            .unwrap());
+
+        // Moving a dir from append to prepend keeps a single entry.
+        assert!(env
+            .apply(&EnvEdit::PathAdd {
+                dir: "/usr/local/go/bin".into(),
+                position: PathPosition::Prepend
+            })
+            .unwrap());
+        assert!(env.path_append.is_empty());
+        assert_eq!(env.path_prepend.len(), 3);
+
+        assert!(env
+            .apply(&EnvEdit::PathRemove {
+                dir: "/usr/local/go/bin".into()
+            })
+            .unwrap());
+        assert!(env
+            .apply(&EnvEdit::Unset {
+                key: "EDITOR".into()
+            })
+            .unwrap());
+        assert!(!env
+            .apply(&EnvEdit::Unset {
+                key: "EDITOR".into()
+            })
+            .unwrap());
+    }
+
+    #[test]
+    fn test_apply_validation() {
+        let mut env = ManagedEnv::default();
+        for edit in [
+            EnvEdit::Set {
+                key: "PATH".into(),
+                value: "/x".into(),
+            },
+            EnvEdit::Set {
+                key: "1BAD".into(),
+                value: "x".into(),
+            },
+            EnvEdit::Set {
+                key: "OK".into(),
+                value: "a\nb".into(),
+            },
+            EnvEdit::PathAdd {
+                dir: "/a:/b".into(),
+                position: PathPosition::Append,
+            },
+        ] {
+            assert!(env.apply(&edit).is_err(), "{edit:?} should be rejected");
+        }
+    }
+
+    #[test]
+    fn test_replace_block_keeps_surroundings() {
+        let original = "# user stuff\nexport FOO=1\n";
+        let block = sample().render(Shell::Zsh);
+
+        let inserted = replace_block(original, Some(&block));
+        assert!(inserted.starts_with("# user stuff\nexport FOO=1\n\n# >>>"));
+
+        let tail = format!("{inserted}alias gs='git status'\n");
+        let mut env = ManagedEnv::from_rc(&tail, Shell::Zsh);
+        env.apply(&EnvEdit::Unset {
+            key: "EDITOR".into(),
+        })
+        .unwrap();
+        let replaced = replace_block(&tail, Some(&env.render(Shell::Zsh)));
+        assert!(!replaced.contains("EDITOR"));
+        assert!(replaced.ends_with("alias gs='git status'\n"));
+        assert_eq!(replaced.matches(BEGIN_MARKER).count(), 1);
+
+        let removed = replace_block(&replaced, None);
+        assert_eq!(
+            removed,
+            "# user stuff\nexport FOO=1\nalias gs='git status'\n"
+        );
+    }
+
+    #[test]
+    fn test_expand_home() {
+        let home = Path::new("/home/dev");
+        assert_eq!(expand_home("~/bin", home), "/home/dev/bin");
+        assert_eq!(expand_home("/opt/bin", home), "/opt/bin");
+    }
+
+    #[test]
+    fn test_rc_file_per_shell() {
+        let home = Path::new("/home/dev");
+        if !cfg!(target_os = "macos") {
+            assert_eq!(Shell::Bash.rc_file(home), home.join(".bashrc"));
+        }
+        assert!(Shell::Fish.rc_file(home).ends_with("fish/config.fish"));
+        assert_eq!(Shell::parse("zsh").unwrap(), Shell::Zsh);
+        assert!(Shell::parse("tcsh").is_err());
+    }
+}
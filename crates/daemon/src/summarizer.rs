@@ -0,0 +1,711 @@
+//! Summarizers used by memory compression.
+//!
+//! [`MemoryStore::run_compression`](crate::memory_store::MemoryStore::run_compression)
+//! archives old task and agent memories and replaces the live text with a
+//! summary produced by a [`Summarizer`].  The default [`StructuredSummarizer`]
+//! runs locally and keeps the parts worth recalling — commands, error
+//! signatures and fix steps — instead of cutting the text at a byte offset.
+//! [`CloudSummarizer`] asks the cloud orchestrator for an LLM summary and
+//! falls back to a local summarizer when the cloud is slow or unreachable.
+
+use std::collections::HashMap;
+use std::sync::{mpsc as std_mpsc, Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+use d1_common::protocol::{CloudMessage, Envelope};
+
+use crate::cloud_ws::OutboundFrame;
+use crate::redactor::Redactor;
+
+/// Text to summarize, tagged with where it came from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SummaryInput {
+    /// `task_memory.procedure_steps` (a JSON array), with the task's error
+    /// and fix patterns for context.
+    ProcedureSteps {
+        steps: String,
+        error_patterns: String,
+        fix_patterns: String,
+    },
+    /// `agent_memory.content`.
+    AgentMemory {
+        memory_type: String,
+        content: String,
+    },
+}
+
+impl SummaryInput {
+    /// The text that the summary replaces.
+    pub fn text(&self) -> &str {
+        match self {
+            SummaryInput::ProcedureSteps { steps, .. } => steps,
+            SummaryInput::AgentMemory { content, .. } => content,
+        }
+    }
+}
+
+/// Produces the compressed form of a memory.
+///
+/// A summary of procedure steps must itself be a JSON array of strings so
+/// the column keeps its shape.
+pub trait Summarizer: Send + Sync {
+    /// Short identifier, e.g. for logging which summarizer is active.
+    fn name(&self) -> &str;
+
+    fn summarize(&self, input: &SummaryInput) -> Result<String>;
+}
+
+/// Inputs at or below this many bytes are returned unchanged.
+pub const MIN_SUMMARY_INPUT: usize = 100;
+
+/// Longest single step or fragment kept in a summary.
+const MAX_FRAGMENT_BYTES: usize = 120;
+/// Most steps kept from a procedure.
+const MAX_STEPS: usize = 8;
+/// Most fragments kept from an agent memory.
+const MAX_FRAGMENTS: usize = 4;
+
+/// First words that mark a step as a shell command.
+const COMMAND_WORDS: &[&str] = &[
+    "apt",
+    "apt-get",
+    "brew",
+    "bundle",
+    "cargo",
+    "cd",
+    "chmod",
+    "chown",
+    "cp",
+    "curl",
+    "dnf",
+    "docker",
+    "export",
+    "gem",
+    "git",
+    "go",
+    "kubectl",
+    "launchctl",
+    "ln",
+    "make",
+    "mkdir",
+    "mv",
+    "node",
+    "npm",
+    "npx",
+    "nvm",
+    "pip",
+    "pip3",
+    "pnpm",
+    "pyenv",
+    "python",
+    "python3",
+    "rm",
+    "rustup",
+    "source",
+    "ssh",
+    "sudo",
+    "systemctl",
+    "tar",
+    "wget",
+    "yarn",
+    "yum",
+];
+
+/// Words that mark a fragment as describing a fix.
+const FIX_WORDS: &[&str] = &[
+    "fix",
+    "fixed",
+    "fixes",
+    "resolve",
+    "resolved",
+    "resolves",
+    "solution",
+    "workaround",
+    "instead",
+];
+
+/// Local summarizer that extracts structure rather than truncating.
+///
+/// Procedure steps keep shell commands (from backticks, `$ ` prompts or a
+/// leading command word), steps carrying an error signature, and steps that
+/// mention one of the task's fix patterns; a trailing note records how many
+/// steps were dropped.  Agent memories keep their first sentence plus any
+/// command, error or fix sentences.  JSON content is flattened to its string
+/// leaves first.
+pub struct StructuredSummarizer {
+    error_signature: Regex,
+    inline_code: Regex,
+}
+
+impl StructuredSummarizer {
+    pub fn new() -> Self {
+        Self {
+            error_signature: Regex::new(
+                r"\bE[A-Z]{3,}\b|(?i:\b[a-z]*(error|exception)\b|\bfail(ed|ure)?\b|\bdenied\b|\bnot found\b|\bexit (code|status) \d+|\bpanicked\b|\bsegmentation fault\b|\bHTTP [45]\d\d\b)",
+            )
+            .expect("static regex"),
+            inline_code: Regex::new(r"`([^`]+)`").expect("static regex"),
+        }
+    }
+
+    fn summarize_steps(&self, steps: &str, fix_patterns: &str) -> String {
+        let all = split_steps(steps);
+        let fixes: Vec<String> = json_strings(fix_patterns)
+            .into_iter()
+            .map(|f| f.to_lowercase())
+            .filter(|f| !f.is_empty())
+            .collect();
+
+        let mut kept: Vec<String> = Vec::new();
+        for step in &all {
+            let lower = step.to_lowercase();
+            let fragment = if let Some(command) = self.command(step) {
+                command
+            } else if self.error_signature.is_match(step)
+                || fixes.iter().any(|f| lower.contains(f.as_str()))
+            {
+                step.clone()
+            } else {
+                continue;
+            };
+            push_unique(&mut kept, truncate(&fragment));
+            if kept.len() == MAX_STEPS {
+                break;
+            }
+        }
+        if kept.is_empty() {
+            if let Some(first) = all.first() {
+                kept.push(truncate(first));
+            }
+        }
+
+        let omitted = all.len().saturating_sub(kept.len());
+        if omitted > 0 {
+            kept.push(format!("(+{omitted} more steps in archive)"));
+        }
+        Value::from(kept).to_string()
+    }
+
+    fn summarize_content(&self, content: &str) -> String {
+        let fragments = match serde_json::from_str::<Value>(content) {
+            Ok(value @ (Value::Array(_) | Value::Object(_))) => {
+                let mut leaves = Vec::new();
+                collect_strings(&value, &mut leaves);
+                leaves
+            }
+            _ => split_sentences(content),
+        };
+
+        let mut kept: Vec<String> = Vec::new();
+        for (i, fragment) in fragments.iter().enumerate() {
+            let keep = i == 0
+                || self.command(fragment).is_some()
+                || self.error_signature.is_match(fragment)
+                || has_fix_word(fragment);
+            if keep {
+                push_unique(&mut kept, truncate(fragment));
+            }
+            if kept.len() == MAX_FRAGMENTS {
+                break;
+            }
+        }
+        kept.join(" … ")
+    }
+
+    /// The shell command in `step`, if it has one.
+    fn command(&self, step: &str) -> Option<String> {
+        let commands: Vec<&str> = self
+            .inline_code
+            .captures_iter(step)
+            .filter_map(|c| c.get(1))
+            .map(|m| m.as_str().trim())
+            .filter(|c| !c.is_empty())
+            .collect();
+        if !commands.is_empty() {
+            return Some(commands.join("; "));
+        }
+        if let Some((_, rest)) = step.split_once("$ ") {
+            return Some(rest.trim().to_string());
+        }
+        let first = step.split_whitespace().next()?;
+        COMMAND_WORDS
+            .contains(&first)
+            .then(|| step.trim().trim_end_matches('.').to_string())
+    }
+}
+
+impl Default for StructuredSummarizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Summarizer for StructuredSummarizer {
+    fn name(&self) -> &str {
+        "structured"
+    }
+
+    fn summarize(&self, input: &SummaryInput) -> Result<String> {
+        if input.text().len() <= MIN_SUMMARY_INPUT {
+            return Ok(input.text().to_string());
+        }
+        Ok(match input {
+            SummaryInput::ProcedureSteps {
+                steps,
+                fix_patterns,
+                ..
+            } => self.summarize_steps(steps, fix_patterns),
+            SummaryInput::AgentMemory { content, .. } => self.summarize_content(content),
+        })
+    }
+}
+
+/// Steps of a procedure: the elements of a JSON array (objects contribute
+/// their `command`/`cmd`/`description`/`step` field), or non-empty lines.
+fn split_steps(steps: &str) -> Vec<String> {
+    match serde_json::from_str::<Value>(steps) {
+        Ok(Value::Array(items)) => items
+            .iter()
+            .filter_map(|item| match item {
+                Value::String(s) => Some(s.clone()),
+                Value::Object(map) => ["command", "cmd", "description", "step"]
+                    .iter()
+                    .find_map(|k| map.get(*k).and_then(Value::as_str))
+                    .map(str::to_string)
+                    .or_else(|| Some(item.to_string())),
+                Value::Null => None,
+                other => Some(other.to_string()),
+            })
+            .filter(|s| !s.trim().is_empty())
+            .collect(),
+        _ => steps
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(str::to_string)
+            .collect(),
+    }
+}
+
+/// String elements of a JSON array; empty for anything else.
+fn json_strings(json: &str) -> Vec<String> {
+    match serde_json::from_str::<Value>(json) {
+        Ok(Value::Array(items)) => items
+            .into_iter()
+            .filter_map(|v| v.as_str().map(str::to_string))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn collect_strings(value: &Value, out: &mut Vec<String>) {
+    match value {
+        Value::String(s) if !s.trim().is_empty() => out.push(s.trim().to_string()),
+        Value::Array(items) => items.iter().for_each(|v| collect_strings(v, out)),
+        Value::Object(map) => map.values().for_each(|v| collect_strings(v, out)),
+        _ => {}
+    }
+}
+
+/// Lines, further split after sentence-ending punctuation.
+fn split_sentences(text: &str) -> Vec<String> {
+    let mut sentences = Vec::new();
+    for line in text.lines() {
+        let mut start = 0;
+        for (i, _) in line.match_indices(['.', '!', '?']) {
+            let end = i + 1;
+            if line[end..].starts_with(' ') {
+                sentences.push(line[start..end].trim().to_string());
+                start = end;
+            }
+        }
+        sentences.push(line[start..].trim().to_string());
+    }
+    sentences.retain(|s| !s.is_empty());
+    sentences
+}
+
+fn has_fix_word(fragment: &str) -> bool {
+    fragment
+        .split(|c: char| !c.is_alphanumeric())
+        .any(|w| FIX_WORDS.contains(&w.to_lowercase().as_str()))
+}
+
+fn push_unique(kept: &mut Vec<String>, fragment: String) {
+    if !kept.contains(&fragment) {
+        kept.push(fragment);
+    }
+}
+
+/// Cut `s` to [`MAX_FRAGMENT_BYTES`] on a character boundary.
+fn truncate(s: &str) -> String {
+    if s.len() <= MAX_FRAGMENT_BYTES {
+        return s.to_string();
+    }
+    let mut end = MAX_FRAGMENT_BYTES;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}…", s[..end].trim_end())
+}
+
+// ---------------------------------------------------------------------------
+// CloudSummarizer
+// ---------------------------------------------------------------------------
+
+/// How long to wait for the cloud before falling back.
+pub const DEFAULT_CLOUD_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Waiters for cloud replies, keyed by request id.
+type ReplyMap = HashMap<String, std_mpsc::Sender<Result<String, String>>>;
+
+/// Summarizes through an LLM on the cloud side of the daemon's WebSocket.
+///
+/// Requests go out as redacted `memory_summarize` messages on the cloud
+/// outbound channel; the cloud reader hands the `memory_summary` replies to
+/// [`CloudSummarizer::handle_response`].
+/// Any failure — full queue, timeout, error reply — falls back to the local
+/// summarizer, so compression never stalls on the network.
+///
+/// [`Summarizer::summarize`] blocks, so call it from a blocking thread
+/// (compression already runs under `spawn_blocking`).
+pub struct CloudSummarizer {
+    outbound: mpsc::Sender<OutboundFrame>,
+    redactor: Arc<Redactor>,
+    fallback: Arc<dyn Summarizer>,
+    timeout: Duration,
+    pending: Mutex<ReplyMap>,
+}
+
+impl CloudSummarizer {
+    pub fn new(outbound: mpsc::Sender<OutboundFrame>, redactor: Arc<Redactor>) -> Self {
+        Self {
+            outbound,
+            redactor,
+            fallback: Arc::new(StructuredSummarizer::new()),
+            timeout: DEFAULT_CLOUD_TIMEOUT,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Override how long to wait for a reply.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Override the summarizer used when the cloud cannot answer.
+    pub fn with_fallback(mut self, fallback: Arc<dyn Summarizer>) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    /// Deliver a message from the cloud.  Returns `true` if it was a summary
+    /// reply (whether or not anyone was still waiting for it).
+    pub fn handle_response(&self, msg: &CloudMessage) -> bool {
+        let CloudMessage::MemorySummary(reply) = msg else {
+            return false;
+        };
+        let request_id = &reply.request_id;
+        let result = match (&reply.summary, &reply.error) {
+            (Some(summary), _) => Ok(summary.clone()),
+            (None, Some(error)) => Err(error.clone()),
+            (None, None) => Err("reply has neither summary nor error".to_string()),
+        };
+        let waiter = self.pending().remove(request_id);
+        match waiter {
+            Some(tx) => {
+                let _ = tx.send(result);
+            }
+            None => debug!(%request_id, "summarizer: late or unknown cloud reply"),
+        }
+        true
+    }
+
+    fn pending(&self) -> MutexGuard<'_, ReplyMap> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn ask_cloud(&self, input: &SummaryInput) -> Result<String> {
+        let payload = self.redactor.redact_json(&serde_json::to_value(input)?);
+        let request = Envelope::new(CloudMessage::MemorySummarize(payload));
+        let json = request.to_json();
+
+        let (tx, rx) = std_mpsc::channel();
+        self.pending().insert(request.id.clone(), tx);
+        let result = self
+            .outbound
+            .try_send(json.into())
+            .context("cloud outbound queue unavailable")
+            .and_then(|()| {
+                rx.recv_timeout(self.timeout)
+                    .context("timed out waiting for cloud summary")?
+                    .map_err(|e| anyhow::anyhow!("cloud summarizer error: {e}"))
+            });
+        self.pending().remove(&request.id);
+        result
+    }
+}
+
+impl Summarizer for CloudSummarizer {
+    fn name(&self) -> &str {
+        "cloud"
+    }
+
+    fn summarize(&self, input: &SummaryInput) -> Result<String> {
+        if input.text().len() <= MIN_SUMMARY_INPUT {
+            return Ok(input.text().to_string());
+        }
+        let summary = self.ask_cloud(input).and_then(|summary| {
+            // Keep the procedure_steps column a JSON array.
+            if matches!(input, SummaryInput::ProcedureSteps { .. }) {
+                serde_json::from_str::<Vec<String>>(&summary)
+                    .context("cloud summary of procedure steps is not a JSON string array")?;
+            }
+            Ok(summary)
+        });
+        match summary {
+            Ok(summary) => Ok(summary),
+            Err(e) => {
+                warn!(error = %format!("{e:#}"), fallback = self.fallback.name(), "summarizer: cloud summary failed");
+                self.fallback.summarize(input)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn steps(items: &[&str]) -> String {
+        serde_json::to_string(items).unwrap()
+    }
+
+    fn summarize_steps(items: &[&str], fixes: &[&str]) -> Vec<String> {
+        let input = SummaryInput::ProcedureSteps {
+            steps: steps(items),
+            error_patterns: "[]".into(),
+            fix_patterns: steps(fixes),
+        };
+        let summary = StructuredSummarizer::new().summarize(&input).unwrap();
+        serde_json::from_str(&summary).expect("summary must stay a JSON array")
+    }
+
+    #[test]
+    fn test_short_input_is_unchanged() {
+        let input = SummaryInput::AgentMemory {
+            memory_type: "fact".into(),
+            content: "user prefers zsh".into(),
+        };
+        assert_eq!(
+            StructuredSummarizer::new().summarize(&input).unwrap(),
+            "user prefers zsh"
+        );
+    }
+
+    #[test]
+    fn test_steps_keep_commands_errors_and_fixes() {
+        let summary = summarize_steps(
+            &[
+                "Checked the installed node version with `node -v` and it looked fine",
+                "npm install -g typescript",
+                "Got EACCES: permission denied, mkdir '/usr/local/lib/node_modules'",
+                "Spent a while reading the npm documentation about global installs",
+                "Changed the npm prefix to a directory in the home folder",
+                "$ export PATH=~/.npm-global/bin:$PATH",
+            ],
+            &["npm prefix"],
+        );
+        assert_eq!(
+            summary,
+            vec![
+                "node -v",
+                "npm install -g typescript",
+                "Got EACCES: permission denied, mkdir '/usr/local/lib/node_modules'",
+                "Changed the npm prefix to a directory in the home folder",
+                "export PATH=~/.npm-global/bin:$PATH",
+                "(+1 more steps in archive)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_steps_without_structure_keep_the_first_step() {
+        let summary = summarize_steps(
+            &[
+                "step one is to check the configuration",
+                "step two is to restart the daemon process",
+                "step three is to verify logs and confirm everything is running",
+            ],
+            &[],
+        );
+        assert_eq!(
+            summary,
+            vec![
+                "step one is to check the configuration",
+                "(+2 more steps in archive)"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_step_objects_and_line_lists() {
+        let summarizer = StructuredSummarizer::new();
+        let objects = SummaryInput::ProcedureSteps {
+            steps: r#"[{"command": "brew install postgresql@16", "output": "a long install log that nobody needs to read again"}, {"description": "waited for the service"}]"#.into(),
+            error_patterns: "[]".into(),
+            fix_patterns: "[]".into(),
+        };
+        let summary: Vec<String> =
+            serde_json::from_str(&summarizer.summarize(&objects).unwrap()).unwrap();
+        assert_eq!(
+            summary,
+            vec!["brew install postgresql@16", "(+1 more steps in archive)"]
+        );
+
+        let lines = SummaryInput::ProcedureSteps {
+            steps: "opened the settings panel and looked around for quite a while\ncargo build --release\nconfirmed that the build worked".into(),
+            error_patterns: "[]".into(),
+            fix_patterns: "[]".into(),
+        };
+        let summary: Vec<String> =
+            serde_json::from_str(&summarizer.summarize(&lines).unwrap()).unwrap();
+        assert_eq!(
+            summary,
+            vec!["cargo build --release", "(+2 more steps in archive)"]
+        );
+    }
+
+    #[test]
+    fn test_agent_memory_keeps_gist_and_key_sentences() {
+        let input = SummaryInput::AgentMemory {
+            memory_type: "learning".into(),
+            content: "Homebrew installs on Apple Silicon live under /opt/homebrew. \
+                      The user asked about it twice over several sessions. \
+                      brew doctor reported Error: unbrewed header files in /usr/local/include. \
+                      We chatted about editor themes for a bit. \
+                      The fix was to remove the stale headers and rerun brew doctor."
+                .into(),
+        };
+        let summary = StructuredSummarizer::new().summarize(&input).unwrap();
+        assert_eq!(
+            summary,
+            "Homebrew installs on Apple Silicon live under /opt/homebrew. … \
+             brew doctor reported Error: unbrewed header files in /usr/local/include. … \
+             The fix was to remove the stale headers and rerun brew doctor."
+        );
+    }
+
+    #[test]
+    fn test_agent_memory_json_is_flattened() {
+        let input = SummaryInput::AgentMemory {
+            memory_type: "pattern".into(),
+            content: r#"{"topic": "python venvs", "notes": ["the user likes verbose explanations", "pip failed with externally-managed-environment"], "commands": ["python3 -m venv .venv"]}"#.into(),
+        };
+        let summary = StructuredSummarizer::new().summarize(&input).unwrap();
+        assert_eq!(
+            summary,
+            "python venvs … pip failed with externally-managed-environment … python3 -m venv .venv"
+        );
+    }
+
+    #[test]
+    fn test_long_fragments_are_cut_on_char_boundary() {
+        let long = "é".repeat(200);
+        let cut = truncate(&long);
+        assert!(cut.ends_with('…'));
+        assert!(cut.len() <= MAX_FRAGMENT_BYTES + '…'.len_utf8());
+    }
+
+    fn agent_input() -> SummaryInput {
+        SummaryInput::AgentMemory {
+            memory_type: "fact".into(),
+            content: "The user keeps their dotfiles in a git repository. ".repeat(4),
+        }
+    }
+
+    fn summary_reply(request_id: &str, summary: &str) -> CloudMessage {
+        CloudMessage::MemorySummary(d1_common::protocol::MemorySummaryPayload {
+            request_id: request_id.to_string(),
+            summary: Some(summary.to_string()),
+            error: None,
+        })
+    }
+
+    #[test]
+    fn test_cloud_summarizer_uses_cloud_reply() {
+        let (tx, mut rx) = mpsc::channel(4);
+        let cloud = Arc::new(CloudSummarizer::new(tx, Arc::new(Redactor::new())));
+
+        let responder = {
+            let cloud = Arc::clone(&cloud);
+            std::thread::spawn(move || {
+                let sent = rx.blocking_recv().unwrap();
+                let request: Envelope<CloudMessage> = Envelope::parse(&sent.text).unwrap();
+                let CloudMessage::MemorySummarize(input) = &request.message else {
+                    panic!("expected memory_summarize, got {}", request.msg_type());
+                };
+                assert_eq!(input["kind"], "agent_memory");
+                assert!(cloud.handle_response(&summary_reply(&request.id, "dotfiles in git")));
+            })
+        };
+
+        assert_eq!(cloud.summarize(&agent_input()).unwrap(), "dotfiles in git");
+        responder.join().unwrap();
+        assert!(cloud.pending().is_empty());
+    }
+
+    #[test]
+    fn test_cloud_summarizer_falls_back_when_unanswered() {
+        let (tx, _rx) = mpsc::channel(4);
+        let cloud = CloudSummarizer::new(tx, Arc::new(Redactor::new()))
+            .with_timeout(Duration::from_millis(20));
+        let local = StructuredSummarizer::new()
+            .summarize(&agent_input())
+            .unwrap();
+        assert_eq!(cloud.summarize(&agent_input()).unwrap(), local);
+        assert!(cloud.pending().is_empty());
+    }
+
+    #[test]
+    fn test_cloud_summarizer_rejects_non_array_step_summary() {
+        let (tx, mut rx) = mpsc::channel(4);
+        let cloud = Arc::new(
+            CloudSummarizer::new(tx, Arc::new(Redactor::new()))
+                .with_timeout(Duration::from_secs(5)),
+        );
+        let responder = {
+            let cloud = Arc::clone(&cloud);
+            std::thread::spawn(move || {
+                let request: Envelope<CloudMessage> =
+                    Envelope::parse(&rx.blocking_recv().unwrap().text).unwrap();
+                cloud.handle_response(&summary_reply(&request.id, "free text"));
+            })
+        };
+
+        let input = SummaryInput::ProcedureSteps {
+            steps: steps(&["cargo build --release", &"x".repeat(120)]),
+            error_patterns: "[]".into(),
+            fix_patterns: "[]".into(),
+        };
+        let summary: Vec<String> = serde_json::from_str(&cloud.summarize(&input).unwrap()).unwrap();
+        assert_eq!(summary[0], "cargo build --release");
+        responder.join().unwrap();
+    }
+
+    #[test]
+    fn test_handle_response_ignores_other_messages() {
+        let (tx, _rx) = mpsc::channel(1);
+        let cloud = CloudSummarizer::new(tx, Arc::new(Redactor::new()));
+        assert!(!cloud.handle_response(&CloudMessage::Heartbeat(Default::default())));
+    }
+}
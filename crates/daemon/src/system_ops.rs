@@ -5,12 +5,23 @@
 //! network diagnostics. Uses `tokio::process::Command` for async shell
 //! execution and `cfg!(target_os)` for OS-specific dispatch.
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use tokio::process::Command;
 
+use crate::config_formats::{self, ConfigFormat, EditOp};
+use crate::disk_usage::{self, CleanupPlan, DiskUsageOptions, DiskUsageReport};
+use crate::filesystem::FilesystemOps;
+use crate::net_diag::{
+    self, DnsComparison, HttpProbeOptions, HttpProbeResult, ProxyReport, RecordType, TlsReport,
+    TraceResult,
+};
+use crate::security::SecurityLayer;
+use crate::shell_env::{self, EnvEdit, ManagedEnv, ShellTarget};
+
 // ---------------------------------------------------------------------------
 // Helper structs
 // ---------------------------------------------------------------------------
@@ -29,8 +40,118 @@ pub struct ServiceInfo {
     pub name: String,
     pub status: String,
     pub pid: Option<u32>,
+    pub manager: InitSystem,
+}
+
+/// Which service manager instance a service belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ServiceScope {
+    /// The system-wide manager (PID 1).
+    System,
+    /// The per-user manager (`systemctl --user`).
+    User,
+}
+
+impl ServiceScope {
+    /// Parse a scope name (`"system"` or `"user"`).
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "system" => Ok(ServiceScope::System),
+            "user" => Ok(ServiceScope::User),
+            other => anyhow::bail!("unsupported service scope: {other}"),
+        }
+    }
+}
+
+/// Init system / service manager running on the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InitSystem {
+    Systemd,
+    Launchd,
+    OpenRc,
+    Runit,
+    Unknown,
+}
+
+impl InitSystem {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InitSystem::Systemd => "systemd",
+            InitSystem::Launchd => "launchd",
+            InitSystem::OpenRc => "openrc",
+            InitSystem::Runit => "runit",
+            InitSystem::Unknown => "unknown",
+        }
+    }
 }
 
+/// Outcome of [`SystemOps::config_edit`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigEditResult {
+    /// Format the file was edited as.
+    pub format: String,
+    /// `false` when the edit was a no-op and the file was left untouched.
+    pub changed: bool,
+    /// Backup message from [`FilesystemOps::backup`], if one was taken.
+    pub backup: Option<String>,
+}
+
+/// Outcome of [`SystemOps::env_persist`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnvPersistResult {
+    pub shell: String,
+    pub rc_file: String,
+    /// `false` when the block already matched and the file was left untouched.
+    pub changed: bool,
+    /// Backup message from [`FilesystemOps::backup`], if one was taken.
+    pub backup: Option<String>,
+}
+
+/// Contents of the managed block in a shell rc file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnvPersistListing {
+    pub shell: String,
+    pub rc_file: String,
+    pub vars: BTreeMap<String, String>,
+    pub path_prepend: Vec<String>,
+    pub path_append: Vec<String>,
+}
+
+/// Log lines retrieved for a service.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceLogs {
+    pub name: String,
+    pub lines: Vec<String>,
+    /// `true` when more lines were available than the requested cap.
+    pub truncated: bool,
+}
+
+/// Template parameters for a systemd user unit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserUnitSpec {
+    pub name: String,
+    pub description: String,
+    pub exec_start: String,
+    #[serde(default)]
+    pub working_directory: Option<String>,
+    #[serde(default)]
+    pub environment: BTreeMap<String, String>,
+    #[serde(default = "default_restart_policy")]
+    pub restart: String,
+}
+
+fn default_restart_policy() -> String {
+    "on-failure".to_string()
+}
+
+/// Default number of log lines returned by [`SystemOps::service_logs`].
+pub const DEFAULT_LOG_LINES: usize = 100;
+
+/// Upper bound on log lines returned by [`SystemOps::service_logs`].
+pub const MAX_LOG_LINES: usize = 1000;
+
 /// Result of a network diagnostic check.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NetworkResult {
@@ -43,15 +164,53 @@ pub struct NetworkResult {
 // SystemOps
 // ---------------------------------------------------------------------------
 
-/// Stateless system operations.
+/// System operations.
 ///
 /// Every method spawns a child process (via `tokio::process::Command`) or
-/// uses standard library calls. No internal state is held.
-pub struct SystemOps;
+/// uses standard library calls. The only state held is how config files
+/// are backed up before editing them.
+pub struct SystemOps {
+    backups: Backups,
+}
+
+/// Where config files are backed up before an edit.
+enum Backups {
+    /// Edits go ahead without a backup.
+    Off,
+    /// Existing files are backed up through [`FilesystemOps::backup`].
+    To(FilesystemOps),
+    /// Backups were wanted but cannot be taken; writes are refused.
+    Unavailable(String),
+}
+
+impl Default for SystemOps {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl SystemOps {
+    /// Create a `SystemOps` that edits config files without backups.
     pub fn new() -> Self {
-        SystemOps
+        SystemOps {
+            backups: Backups::Off,
+        }
+    }
+
+    /// Create a `SystemOps` that backs up config files through `backups`
+    /// before every edit.
+    pub fn with_backups(backups: FilesystemOps) -> Self {
+        SystemOps {
+            backups: Backups::To(backups),
+        }
+    }
+
+    /// Create a `SystemOps` that refuses to write config files because no
+    /// backup location could be set up; `reason` says why.
+    pub fn backups_unavailable(reason: impl Into<String>) -> Self {
+        SystemOps {
+            backups: Backups::Unavailable(reason.into()),
+        }
     }
 
     // -- Package management -------------------------------------------------
@@ -119,64 +278,199 @@ impl SystemOps {
 
     // -- Service management -------------------------------------------------
 
-    /// Query the status of a system service.
-    pub async fn service_status(&self, name: &str) -> Result<ServiceInfo> {
-        if cfg!(target_os = "macos") {
-            self.launchctl_status(name).await
-        } else {
-            self.systemctl_status(name).await
+    /// Query the status of a service in the given scope.
+    ///
+    /// Dispatches to `launchctl`, `systemctl [--user]`, `rc-service` or `sv`
+    /// depending on the detected init system.
+    pub async fn service_status(&self, name: &str, scope: ServiceScope) -> Result<ServiceInfo> {
+        validate_service_name(name)?;
+        let init = Self::detect_init_system();
+
+        if init == InitSystem::Launchd {
+            return self.launchctl_status(name, scope).await;
         }
+
+        let (program, args) = status_command(init, scope, name)?;
+        let output = Command::new(program)
+            .args(&args)
+            .output()
+            .await
+            .with_context(|| format!("failed to run {program}"))?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        Ok(match init {
+            InitSystem::OpenRc => parse_openrc_status(name, &stdout),
+            InitSystem::Runit => parse_runit_status(name, &stdout),
+            _ => parse_systemctl_status(name, &stdout),
+        })
     }
 
-    /// Control a system service (start / stop / restart).
-    pub async fn service_control(&self, name: &str, action: &str) -> Result<String> {
-        match action {
-            "start" | "stop" | "restart" => {}
-            other => anyhow::bail!("unsupported service action: {other}"),
+    /// Control a service (start / stop / restart / enable / disable).
+    ///
+    /// System-scope actions run through `sudo`; user-scope actions talk to
+    /// the per-user manager (`systemctl --user`, or the `gui/<uid>` launchd
+    /// domain) directly.
+    pub async fn service_control(
+        &self,
+        name: &str,
+        action: &str,
+        scope: ServiceScope,
+    ) -> Result<String> {
+        validate_service_name(name)?;
+        let init = Self::detect_init_system();
+
+        let (program, args) = if init == InitSystem::Launchd {
+            let domain = launchd_domain(scope).await?;
+            launchctl_command(&domain, scope, name, action)?
+        } else {
+            control_command(init, scope, name, action)?
+        };
+        let output = Command::new(program)
+            .args(&args)
+            .output()
+            .await
+            .with_context(|| format!("failed to run {program}"))?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+
+        if output.status.success() {
+            Ok(format!("{stdout}\n{stderr}").trim().to_string())
+        } else {
+            anyhow::bail!("service {action} failed: {stderr}")
         }
+    }
 
-        let output = if cfg!(target_os = "macos") {
-            // launchctl uses kickstart -k for restart, bootstrap/bootout for start/stop
-            match action {
-                "start" => Command::new("launchctl")
-                    .args(["start", name])
+    /// Retrieve recent log lines for a service.
+    ///
+    /// On systemd hosts this queries `journalctl -u` with optional `since` /
+    /// `until` bounds (any format journalctl accepts, e.g. `"1 hour ago"` or
+    /// `"2024-01-01 10:00"`). On OpenRC and runit hosts the conventional log
+    /// file is tailed instead; time ranges are not supported there. At most
+    /// `lines` lines are returned, capped at [`MAX_LOG_LINES`].
+    pub async fn service_logs(
+        &self,
+        name: &str,
+        scope: ServiceScope,
+        since: Option<&str>,
+        until: Option<&str>,
+        lines: Option<usize>,
+    ) -> Result<ServiceLogs> {
+        validate_service_name(name)?;
+        let cap = lines.unwrap_or(DEFAULT_LOG_LINES).clamp(1, MAX_LOG_LINES);
+        let init = Self::detect_init_system();
+
+        let raw = match init {
+            InitSystem::Systemd | InitSystem::Unknown => {
+                let args = journalctl_args(name, scope, since, until, cap);
+                let output = Command::new("journalctl")
+                    .args(&args)
                     .output()
                     .await
-                    .context("failed to run launchctl start")?,
-                "stop" => Command::new("launchctl")
-                    .args(["stop", name])
+                    .context("failed to run journalctl")?;
+                if !output.status.success() {
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    anyhow::bail!("journalctl failed: {stderr}");
+                }
+                String::from_utf8_lossy(&output.stdout).to_string()
+            }
+            InitSystem::Launchd => {
+                let mut predicate = format!("process == \"{name}\"");
+                if scope == ServiceScope::User {
+                    predicate.push_str(&format!(" AND userIdentifier == {}", current_uid().await?));
+                }
+                let mut args = vec!["show", "--style", "syslog", "--predicate", &predicate];
+                match since {
+                    Some(start) => args.extend(["--start", start]),
+                    None => args.extend(["--last", "1h"]),
+                }
+                if let Some(end) = until {
+                    args.extend(["--end", end]);
+                }
+                let output = Command::new("log")
+                    .args(&args)
                     .output()
                     .await
-                    .context("failed to run launchctl stop")?,
-                "restart" => {
-                    // stop then start
-                    let _ = Command::new("launchctl")
-                        .args(["stop", name])
-                        .output()
-                        .await;
-                    Command::new("launchctl")
-                        .args(["start", name])
-                        .output()
-                        .await
-                        .context("failed to run launchctl start (restart)")?
+                    .context("failed to run log show")?;
+                String::from_utf8_lossy(&output.stdout).to_string()
+            }
+            InitSystem::OpenRc | InitSystem::Runit => {
+                if since.is_some() || until.is_some() {
+                    anyhow::bail!("time-range log filtering requires journald or unified logging");
                 }
-                _ => unreachable!(),
+                if scope == ServiceScope::User {
+                    anyhow::bail!("user-level services are not supported by {}", init.as_str());
+                }
+                let path = log_file_path(init, name);
+                tokio::fs::read_to_string(&path)
+                    .await
+                    .with_context(|| format!("failed to read service log: {}", path.display()))?
             }
-        } else {
-            Command::new("sudo")
-                .args(["systemctl", action, name])
-                .output()
-                .await
-                .context("failed to run systemctl")?
         };
 
-        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        let all: Vec<&str> = raw.lines().collect();
+        let truncated = all.len() > cap;
+        let lines = all[all.len().saturating_sub(cap)..]
+            .iter()
+            .map(|l| l.to_string())
+            .collect();
 
-        if output.status.success() {
-            Ok(format!("{stdout}\n{stderr}").trim().to_string())
+        Ok(ServiceLogs {
+            name: name.to_string(),
+            lines,
+            truncated,
+        })
+    }
+
+    /// Create a systemd user unit from a template and optionally enable it.
+    ///
+    /// Writes `~/.config/systemd/user/<name>.service`, reloads the user
+    /// manager and, if `enable` is set, runs `systemctl --user enable --now`.
+    /// Refuses to overwrite an existing unit file.
+    pub async fn create_user_unit(&self, spec: &UserUnitSpec, enable: bool) -> Result<String> {
+        if Self::detect_init_system() != InitSystem::Systemd {
+            anyhow::bail!("user unit templates require systemd");
+        }
+        let dir = dirs::config_dir()
+            .context("cannot determine config directory")?
+            .join("systemd")
+            .join("user");
+        let path = self.write_user_unit(&dir, spec).await?;
+
+        let reload = Command::new("systemctl")
+            .args(["--user", "daemon-reload"])
+            .output()
+            .await
+            .context("failed to run systemctl --user daemon-reload")?;
+        if !reload.status.success() {
+            let stderr = String::from_utf8_lossy(&reload.stderr);
+            anyhow::bail!("systemctl --user daemon-reload failed: {stderr}");
+        }
+
+        if enable {
+            self.service_control(&spec.name, "enable", ServiceScope::User)
+                .await?;
+        }
+
+        Ok(format!("created {}", path.display()))
+    }
+
+    /// Detect the init system / service manager of the running host.
+    pub fn detect_init_system() -> InitSystem {
+        if cfg!(target_os = "macos") {
+            return InitSystem::Launchd;
+        }
+        if Path::new("/run/systemd/system").is_dir() {
+            InitSystem::Systemd
+        } else if Path::new("/run/openrc").is_dir() {
+            InitSystem::OpenRc
+        } else if Path::new("/run/runit").is_dir() || Path::new("/etc/runit").is_dir() {
+            InitSystem::Runit
+        } else if binary_on_path("systemctl") {
+            // Containers often ship systemctl without systemd as PID 1.
+            InitSystem::Systemd
         } else {
-            anyhow::bail!("service {action} failed: {stderr}")
+            InitSystem::Unknown
         }
     }
 
@@ -184,41 +478,93 @@ impl SystemOps {
 
     /// Read a configuration file and return its contents as a JSON value.
     ///
-    /// Supported formats: `"toml"`, `"json"`, `"yaml"`.
+    /// Supported formats: `"toml"`, `"json"`, `"yaml"`, `"ini"`, `"dotenv"`,
+    /// `"ssh_config"`, or `"auto"` to detect from the path.
     pub async fn config_read(&self, path: &str, format: &str) -> Result<Value> {
         let content = tokio::fs::read_to_string(path)
             .await
             .with_context(|| format!("failed to read config file: {path}"))?;
 
-        match format.to_lowercase().as_str() {
-            "toml" => {
-                let table: toml::Value =
-                    toml::from_str(&content).context("failed to parse TOML")?;
-                let json_str =
-                    serde_json::to_string(&table).context("failed to convert TOML to JSON")?;
-                serde_json::from_str(&json_str).context("failed to parse converted JSON")
-            }
-            "json" => serde_json::from_str(&content).context("failed to parse JSON"),
-            "yaml" | "yml" => {
-                // Basic YAML support — we don't add a serde_yaml dep, so we
-                // only handle the subset that is also valid JSON (or error).
-                anyhow::bail!("YAML support requires the serde_yaml crate; currently only TOML and JSON are supported")
-            }
-            other => anyhow::bail!("unsupported config format: {other}"),
-        }
+        let format = match format {
+            "auto" => ConfigFormat::detect(path),
+            other => ConfigFormat::parse(other)?,
+        };
+        config_formats::read(format, &content)
     }
 
-    /// Set a key in a configuration file.
+    /// Set a key in a configuration file, detecting the format from the path.
     ///
-    /// For TOML files the `key` uses dotted notation (`section.key`).
-    /// For JSON files the `key` uses dotted notation mapped to nested objects.
+    /// The `key` uses dotted notation (`section.key`, `items[0]`).
     pub async fn config_set(&self, path: &str, key: &str, value: &str) -> Result<()> {
-        let format = Self::detect_format(path);
+        self.config_edit(path, key, &EditOp::Set(value.to_string()), None)
+            .await
+            .map(|_| ())
+    }
+
+    /// Apply a set / delete / append edit to a configuration file.
+    ///
+    /// Edits are format-preserving: comments, ordering and indentation of
+    /// untouched lines are kept. When a backup location is configured the
+    /// existing file is backed up via [`FilesystemOps::backup`] before it is
+    /// rewritten, and the edit is aborted if the backup fails.  A `SystemOps`
+    /// whose backups are unavailable refuses every write.
+    pub async fn config_edit(
+        &self,
+        path: &str,
+        key: &str,
+        op: &EditOp,
+        format: Option<&str>,
+    ) -> Result<ConfigEditResult> {
+        let format = match format {
+            Some(f) if f != "auto" => ConfigFormat::parse(f)?,
+            _ => ConfigFormat::detect(path),
+        };
+        let key_path = config_formats::parse_key_path(key)?;
 
-        match format.as_str() {
-            "toml" => self.config_set_toml(path, key, value).await,
-            "json" => self.config_set_json(path, key, value).await,
-            other => anyhow::bail!("unsupported config format for writing: {other}"),
+        let exists = Path::new(path).exists();
+        let content = if exists {
+            tokio::fs::read_to_string(path)
+                .await
+                .with_context(|| format!("failed to read config file: {path}"))?
+        } else {
+            String::new()
+        };
+
+        let updated = config_formats::edit(format, &content, &key_path, op)?;
+        if updated == content {
+            return Ok(ConfigEditResult {
+                format: format.as_str().to_string(),
+                changed: false,
+                backup: None,
+            });
+        }
+
+        let backup = self.backup_existing(path, exists)?;
+
+        tokio::fs::write(path, updated)
+            .await
+            .with_context(|| format!("failed to write config file: {path}"))?;
+
+        Ok(ConfigEditResult {
+            format: format.as_str().to_string(),
+            changed: true,
+            backup,
+        })
+    }
+
+    /// Back up `path` before it is rewritten, if backups are configured.
+    /// Fails without touching anything when backups are unavailable.
+    fn backup_existing(&self, path: &str, exists: bool) -> Result<Option<String>> {
+        match (&self.backups, exists) {
+            (Backups::Unavailable(reason), _) => {
+                bail!("refusing to write {path}: config backups are unavailable ({reason})")
+            }
+            (Backups::To(fs), true) => {
+                Ok(Some(fs.backup(path).with_context(|| {
+                    format!("failed to back up {path} before editing")
+                })?))
+            }
+            _ => Ok(None),
         }
     }
 
@@ -235,13 +581,93 @@ impl SystemOps {
 
     /// Set an environment variable for the current process.
     ///
-    /// Note: this only affects the running daemon process. It cannot persist
-    /// across processes without editing shell profile files.
+    /// Note: this only affects the running daemon process. Use
+    /// [`SystemOps::env_persist`] to persist a variable for new shells.
     pub fn env_set(&self, key: &str, value: &str) -> Result<()> {
         std::env::set_var(key, value);
         Ok(())
     }
 
+    /// Persist a variable or PATH change in the managed block of a shell rc file.
+    ///
+    /// The block is regenerated from its parsed contents, so repeating an
+    /// edit is a no-op. Text outside the block is never modified, and the rc
+    /// file is backed up first when backups are configured.
+    pub async fn env_persist(
+        &self,
+        target: &ShellTarget,
+        edit: &EnvEdit,
+    ) -> Result<EnvPersistResult> {
+        let path = target.rc_file.to_string_lossy().to_string();
+        let exists = target.rc_file.exists();
+        let content = if exists {
+            tokio::fs::read_to_string(&target.rc_file)
+                .await
+                .with_context(|| format!("failed to read shell rc file: {path}"))?
+        } else {
+            String::new()
+        };
+
+        let edit = match edit {
+            EnvEdit::PathAdd { dir, position } => match dirs::home_dir() {
+                Some(home) => EnvEdit::PathAdd {
+                    dir: shell_env::expand_home(dir, &home),
+                    position: *position,
+                },
+                None => edit.clone(),
+            },
+            other => other.clone(),
+        };
+
+        let mut env = ManagedEnv::from_rc(&content, target.shell);
+        let changed = env.apply(&edit)?;
+        let result = EnvPersistResult {
+            shell: target.shell.as_str().to_string(),
+            rc_file: path.clone(),
+            changed,
+            backup: None,
+        };
+        if !changed {
+            return Ok(result);
+        }
+
+        let block = (!env.is_empty()).then(|| env.render(target.shell));
+        let updated = shell_env::replace_block(&content, block.as_deref());
+        let backup = self.backup_existing(&path, exists)?;
+
+        if let Some(parent) = target.rc_file.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        tokio::fs::write(&target.rc_file, updated)
+            .await
+            .with_context(|| format!("failed to write shell rc file: {path}"))?;
+
+        Ok(EnvPersistResult { backup, ..result })
+    }
+
+    /// List the variables and PATH entries in the managed block of a rc file.
+    pub async fn env_persist_list(&self, target: &ShellTarget) -> Result<EnvPersistListing> {
+        let content = match tokio::fs::read_to_string(&target.rc_file).await {
+            Ok(c) => c,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => {
+                return Err(e).with_context(|| {
+                    format!("failed to read shell rc file: {}", target.rc_file.display())
+                })
+            }
+        };
+        let env = ManagedEnv::from_rc(&content, target.shell);
+        Ok(EnvPersistListing {
+            shell: target.shell.as_str().to_string(),
+            rc_file: target.rc_file.to_string_lossy().to_string(),
+            vars: env.vars.into_iter().collect(),
+            path_prepend: env.path_prepend,
+            path_append: env.path_append,
+        })
+    }
+
     // -- Network diagnostics ------------------------------------------------
 
     /// Run a network diagnostic check.
@@ -261,6 +687,66 @@ impl SystemOps {
         }
     }
 
+    /// Probe an HTTP(S) URL: status, per-phase timings and redirect chain.
+    pub async fn http_probe(&self, opts: &HttpProbeOptions) -> Result<HttpProbeResult> {
+        net_diag::http_probe(opts).await
+    }
+
+    /// Inspect the TLS certificate chain presented by `host:port`.
+    pub async fn tls_inspect(&self, host: &str, port: u16) -> Result<TlsReport> {
+        net_diag::tls_inspect(host, port).await
+    }
+
+    /// Trace the network path to `target`, with per-hop loss and latency.
+    pub async fn traceroute(&self, target: &str, max_hops: u32) -> Result<TraceResult> {
+        net_diag::traceroute(target, max_hops).await
+    }
+
+    /// Report proxy settings from the environment and the OS.
+    pub async fn proxy_detect(&self) -> ProxyReport {
+        net_diag::proxy_detect().await
+    }
+
+    /// Resolve `name` against several resolvers and compare the answers.
+    pub async fn dns_compare(
+        &self,
+        name: &str,
+        record_type: RecordType,
+        resolvers: &[String],
+    ) -> Result<DnsComparison> {
+        net_diag::dns_compare(name, record_type, resolvers).await
+    }
+
+    // -- Disk usage ---------------------------------------------------------
+
+    /// Report the largest directories and files under `path`.
+    ///
+    /// `path` must be inside the user's home directory. The walk runs on a
+    /// blocking thread pool so it does not stall the runtime.
+    pub async fn disk_usage(&self, path: &str, opts: &DiskUsageOptions) -> Result<DiskUsageReport> {
+        let path = path.to_string();
+        let opts = opts.clone();
+        tokio::task::spawn_blocking(move || {
+            disk_usage::analyze(&SecurityLayer::new(), &path, &opts)
+        })
+        .await
+        .context("disk usage task panicked")?
+    }
+
+    /// Build a dry-run plan for clearing well-known developer caches.
+    ///
+    /// Each step is classified by [`SecurityLayer`]; executing a step goes
+    /// through the command relay and its approval flow.
+    pub async fn disk_cleanup_plan(&self) -> Result<CleanupPlan> {
+        tokio::task::spawn_blocking(|| {
+            let security = SecurityLayer::new();
+            let home = security.sandbox_root.clone();
+            disk_usage::cleanup_plan(&security, &home)
+        })
+        .await
+        .context("cleanup plan task panicked")
+    }
+
     // =======================================================================
     // Private helpers
     // =======================================================================
@@ -343,211 +829,45 @@ impl SystemOps {
 
     // -- launchctl helpers --------------------------------------------------
 
-    async fn launchctl_status(&self, name: &str) -> Result<ServiceInfo> {
+    /// Query `name` in the launchd domain for `scope` with `launchctl print`.
+    async fn launchctl_status(&self, name: &str, scope: ServiceScope) -> Result<ServiceInfo> {
+        let target = format!("{}/{name}", launchd_domain(scope).await?);
         let output = Command::new("launchctl")
-            .args(["list"])
-            .output()
-            .await
-            .context("failed to run launchctl list")?;
-
-        let stdout = String::from_utf8_lossy(&output.stdout);
-
-        // launchctl list output columns: PID  Status  Label
-        for line in stdout.lines() {
-            let cols: Vec<&str> = line.split_whitespace().collect();
-            if cols.len() >= 3 {
-                let label = cols[2];
-                if label == name || label.contains(name) {
-                    let pid = cols[0].parse::<u32>().ok();
-                    let status = if pid.is_some() { "running" } else { "stopped" };
-                    return Ok(ServiceInfo {
-                        name: label.to_string(),
-                        status: status.to_string(),
-                        pid,
-                    });
-                }
-            }
-        }
-
-        Ok(ServiceInfo {
-            name: name.to_string(),
-            status: "not found".to_string(),
-            pid: None,
-        })
-    }
-
-    // -- systemctl helpers --------------------------------------------------
-
-    async fn systemctl_status(&self, name: &str) -> Result<ServiceInfo> {
-        let output = Command::new("systemctl")
-            .args(["status", name])
+            .args(["print", &target])
             .output()
             .await
-            .context("failed to run systemctl status")?;
-
-        let stdout = String::from_utf8_lossy(&output.stdout);
-
-        let mut status = "unknown".to_string();
-        let mut pid = None;
-
-        for line in stdout.lines() {
-            let trimmed = line.trim();
-            if trimmed.starts_with("Active:") {
-                // e.g. "Active: active (running) since ..."
-                if trimmed.contains("active (running)") {
-                    status = "running".to_string();
-                } else if trimmed.contains("inactive") {
-                    status = "stopped".to_string();
-                } else if trimmed.contains("failed") {
-                    status = "failed".to_string();
-                } else {
-                    status = trimmed.to_string();
-                }
-            }
-            if trimmed.starts_with("Main PID:") {
-                // e.g. "Main PID: 1234 (nginx)"
-                pid = trimmed
-                    .split_whitespace()
-                    .nth(2)
-                    .and_then(|s| s.parse::<u32>().ok());
-            }
+            .context("failed to run launchctl print")?;
+
+        if !output.status.success() {
+            return Ok(ServiceInfo {
+                name: name.to_string(),
+                status: "not found".to_string(),
+                pid: None,
+                manager: InitSystem::Launchd,
+            });
         }
-
-        Ok(ServiceInfo {
-            name: name.to_string(),
-            status,
-            pid,
-        })
+        Ok(parse_launchctl_print(
+            name,
+            &String::from_utf8_lossy(&output.stdout),
+        ))
     }
 
-    // -- config helpers -----------------------------------------------------
+    // -- user unit helpers --------------------------------------------------
 
-    fn detect_format(path: &str) -> String {
-        let ext = Path::new(path)
-            .extension()
-            .and_then(|e| e.to_str())
-            .unwrap_or("");
-        match ext {
-            "toml" => "toml".to_string(),
-            "json" => "json".to_string(),
-            "yaml" | "yml" => "yaml".to_string(),
-            _ => "toml".to_string(), // default
+    /// Render `spec` and write it into `dir` as `<name>.service`.
+    async fn write_user_unit(&self, dir: &Path, spec: &UserUnitSpec) -> Result<PathBuf> {
+        let unit = render_user_unit(spec)?;
+        let path = dir.join(format!("{}.service", spec.name));
+        if path.exists() {
+            anyhow::bail!("unit file already exists: {}", path.display());
         }
-    }
-
-    async fn config_set_toml(&self, path: &str, key: &str, value: &str) -> Result<()> {
-        let content = tokio::fs::read_to_string(path).await.unwrap_or_default();
-
-        let mut table: toml::map::Map<String, toml::Value> = if content.is_empty() {
-            toml::map::Map::new()
-        } else {
-            toml::from_str(&content).context("failed to parse existing TOML")?
-        };
-
-        // Parse the value: try integer, float, bool, then fall back to string
-        let toml_value = Self::parse_toml_value(value);
-
-        // Support dotted keys: "section.key" -> table["section"]["key"]
-        let parts: Vec<&str> = key.split('.').collect();
-        Self::set_nested_toml(&mut table, &parts, toml_value)?;
-
-        let output = toml::to_string_pretty(&table).context("failed to serialize TOML")?;
-        tokio::fs::write(path, output)
+        tokio::fs::create_dir_all(dir)
             .await
-            .context("failed to write TOML file")?;
-        Ok(())
-    }
-
-    fn parse_toml_value(s: &str) -> toml::Value {
-        if let Ok(i) = s.parse::<i64>() {
-            return toml::Value::Integer(i);
-        }
-        if let Ok(f) = s.parse::<f64>() {
-            return toml::Value::Float(f);
-        }
-        match s {
-            "true" => return toml::Value::Boolean(true),
-            "false" => return toml::Value::Boolean(false),
-            _ => {}
-        }
-        toml::Value::String(s.to_string())
-    }
-
-    fn set_nested_toml(
-        table: &mut toml::map::Map<String, toml::Value>,
-        parts: &[&str],
-        value: toml::Value,
-    ) -> Result<()> {
-        if parts.is_empty() {
-            anyhow::bail!("empty key");
-        }
-        if parts.len() == 1 {
-            table.insert(parts[0].to_string(), value);
-            return Ok(());
-        }
-
-        let entry = table
-            .entry(parts[0].to_string())
-            .or_insert_with(|| toml::Value::Table(toml::map::Map::new()));
-
-        match entry {
-            toml::Value::Table(ref mut inner) => Self::set_nested_toml(inner, &parts[1..], value),
-            _ => {
-                // Overwrite non-table with a new table
-                *entry = toml::Value::Table(toml::map::Map::new());
-                if let toml::Value::Table(ref mut inner) = entry {
-                    Self::set_nested_toml(inner, &parts[1..], value)
-                } else {
-                    unreachable!()
-                }
-            }
-        }
-    }
-
-    async fn config_set_json(&self, path: &str, key: &str, value: &str) -> Result<()> {
-        let content = tokio::fs::read_to_string(path).await.unwrap_or_default();
-
-        let mut root: Value = if content.is_empty() {
-            Value::Object(serde_json::Map::new())
-        } else {
-            serde_json::from_str(&content).context("failed to parse existing JSON")?
-        };
-
-        // Parse value: try JSON literal, then fall back to string
-        let json_value: Value =
-            serde_json::from_str(value).unwrap_or(Value::String(value.to_string()));
-
-        // Support dotted keys
-        let parts: Vec<&str> = key.split('.').collect();
-        Self::set_nested_json(&mut root, &parts, json_value)?;
-
-        let output = serde_json::to_string_pretty(&root).context("failed to serialize JSON")?;
-        tokio::fs::write(path, output)
+            .with_context(|| format!("failed to create unit directory: {}", dir.display()))?;
+        tokio::fs::write(&path, unit)
             .await
-            .context("failed to write JSON file")?;
-        Ok(())
-    }
-
-    fn set_nested_json(obj: &mut Value, parts: &[&str], value: Value) -> Result<()> {
-        if parts.is_empty() {
-            anyhow::bail!("empty key");
-        }
-        if parts.len() == 1 {
-            if let Value::Object(ref mut map) = obj {
-                map.insert(parts[0].to_string(), value);
-                return Ok(());
-            }
-            anyhow::bail!("cannot set key on non-object JSON value");
-        }
-
-        if let Value::Object(ref mut map) = obj {
-            let entry = map
-                .entry(parts[0].to_string())
-                .or_insert_with(|| Value::Object(serde_json::Map::new()));
-            Self::set_nested_json(entry, &parts[1..], value)
-        } else {
-            anyhow::bail!("cannot descend into non-object JSON value")
-        }
+            .with_context(|| format!("failed to write unit file: {}", path.display()))?;
+        Ok(path)
     }
 
     // -- network helpers ----------------------------------------------------
@@ -629,7 +949,7 @@ impl SystemOps {
             }),
             Err(_) => Ok(NetworkResult {
                 success: false,
-                output: format!("Connection timed out after 5s"),
+                output: "Connection timed out after 5s".to_string(),
                 latency_ms: None,
             }),
         }
@@ -667,6 +987,346 @@ impl SystemOps {
     }
 }
 
+// ---------------------------------------------------------------------------
+// Service helpers
+// ---------------------------------------------------------------------------
+
+/// Reject service names that could escape a unit directory or a launchd
+/// predicate. Allows the characters systemd permits in unit names.
+fn validate_service_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '@' | ':'));
+    if !valid {
+        anyhow::bail!("invalid service name: {name:?}");
+    }
+    Ok(())
+}
+
+/// Return `true` if `binary` is an executable file somewhere on `$PATH`.
+pub(crate) fn binary_on_path(binary: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|dir| dir.join(binary).is_file()))
+        .unwrap_or(false)
+}
+
+fn unsupported_user_scope(init: InitSystem) -> anyhow::Error {
+    anyhow::anyhow!("user-level services are not supported by {}", init.as_str())
+}
+
+/// Build the command that reports the status of `name`.
+fn status_command(
+    init: InitSystem,
+    scope: ServiceScope,
+    name: &str,
+) -> Result<(&'static str, Vec<String>)> {
+    let user = scope == ServiceScope::User;
+    match init {
+        InitSystem::OpenRc | InitSystem::Runit if user => Err(unsupported_user_scope(init)),
+        InitSystem::OpenRc => Ok(("rc-service", vec![name.into(), "status".into()])),
+        InitSystem::Runit => Ok(("sv", vec!["status".into(), name.into()])),
+        _ => {
+            let mut args = Vec::new();
+            if user {
+                args.push("--user".to_string());
+            }
+            args.extend(["status".to_string(), name.to_string()]);
+            Ok(("systemctl", args))
+        }
+    }
+}
+
+/// Build the command that performs `action` on `name`.
+///
+/// System-scope commands are wrapped in `sudo`.
+fn control_command(
+    init: InitSystem,
+    scope: ServiceScope,
+    name: &str,
+    action: &str,
+) -> Result<(&'static str, Vec<String>)> {
+    let user = scope == ServiceScope::User;
+    if user && matches!(init, InitSystem::OpenRc | InitSystem::Runit) {
+        return Err(unsupported_user_scope(init));
+    }
+    let args: Vec<&str> = match (init, action) {
+        (InitSystem::Launchd, _) => anyhow::bail!("launchd services are controlled via launchctl"),
+        (InitSystem::OpenRc, "start" | "stop" | "restart") => vec!["rc-service", name, action],
+        (InitSystem::OpenRc, "enable") => vec!["rc-update", "add", name, "default"],
+        (InitSystem::OpenRc, "disable") => vec!["rc-update", "del", name, "default"],
+        (InitSystem::Runit, "start") => vec!["sv", "up", name],
+        (InitSystem::Runit, "stop") => vec!["sv", "down", name],
+        (InitSystem::Runit, "restart") => vec!["sv", "restart", name],
+        (
+            InitSystem::Systemd | InitSystem::Unknown,
+            "start" | "stop" | "restart" | "enable" | "disable",
+        ) => {
+            if user {
+                let mut args = vec!["--user", action];
+                if action == "enable" {
+                    args.push("--now");
+                }
+                args.push(name);
+                return Ok(("systemctl", args.into_iter().map(String::from).collect()));
+            }
+            vec!["systemctl", action, name]
+        }
+        (_, other) => anyhow::bail!("unsupported service action for {}: {other}", init.as_str()),
+    };
+    Ok(("sudo", args.into_iter().map(String::from).collect()))
+}
+
+/// The launchd domain services in `scope` live in: `system` for system
+/// daemons, `gui/<uid>` for the current user's agents.
+async fn launchd_domain(scope: ServiceScope) -> Result<String> {
+    Ok(match scope {
+        ServiceScope::System => "system".to_string(),
+        ServiceScope::User => format!("gui/{}", current_uid().await?),
+    })
+}
+
+/// Numeric user id of the daemon process, from `id -u`.
+async fn current_uid() -> Result<u32> {
+    let output = Command::new("id")
+        .arg("-u")
+        .output()
+        .await
+        .context("failed to run id -u")?;
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .context("failed to parse the output of id -u")
+}
+
+/// Build the `launchctl` command that performs `action` on the job `name`
+/// in `domain`.
+///
+/// System-domain commands are wrapped in `sudo`.
+fn launchctl_command(
+    domain: &str,
+    scope: ServiceScope,
+    name: &str,
+    action: &str,
+) -> Result<(&'static str, Vec<String>)> {
+    let target = format!("{domain}/{name}");
+    let mut args: Vec<String> = match action {
+        "start" => vec!["kickstart".into(), target],
+        "stop" => vec!["kill".into(), "SIGTERM".into(), target],
+        "restart" => vec!["kickstart".into(), "-k".into(), target],
+        "enable" | "disable" => vec![action.into(), target],
+        other => anyhow::bail!("unsupported service action for launchd: {other}"),
+    };
+    if scope == ServiceScope::User {
+        return Ok(("launchctl", args));
+    }
+    args.insert(0, "launchctl".into());
+    Ok(("sudo", args))
+}
+
+/// Build `journalctl` arguments for a unit, newest lines last.
+fn journalctl_args(
+    name: &str,
+    scope: ServiceScope,
+    since: Option<&str>,
+    until: Option<&str>,
+    lines: usize,
+) -> Vec<String> {
+    let mut args = Vec::new();
+    if scope == ServiceScope::User {
+        args.push("--user".to_string());
+    }
+    args.extend([
+        "-u".to_string(),
+        name.to_string(),
+        "--no-pager".to_string(),
+        "-o".to_string(),
+        "short-iso".to_string(),
+        "-n".to_string(),
+        lines.to_string(),
+    ]);
+    if let Some(since) = since {
+        args.extend(["--since".to_string(), since.to_string()]);
+    }
+    if let Some(until) = until {
+        args.extend(["--until".to_string(), until.to_string()]);
+    }
+    args
+}
+
+/// Conventional log file location for services without a journal.
+fn log_file_path(init: InitSystem, name: &str) -> PathBuf {
+    match init {
+        // svlogd writes to <logdir>/current
+        InitSystem::Runit => PathBuf::from("/var/log").join(name).join("current"),
+        _ => PathBuf::from("/var/log").join(format!("{name}.log")),
+    }
+}
+
+/// Parse `systemctl status` output.
+fn parse_systemctl_status(name: &str, stdout: &str) -> ServiceInfo {
+    let mut status = "unknown".to_string();
+    let mut pid = None;
+
+    for line in stdout.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("Active:") {
+            // e.g. "Active: active (running) since ..."
+            if trimmed.contains("active (running)") {
+                status = "running".to_string();
+            } else if trimmed.contains("inactive") {
+                status = "stopped".to_string();
+            } else if trimmed.contains("failed") {
+                status = "failed".to_string();
+            } else {
+                status = trimmed.to_string();
+            }
+        }
+        if trimmed.starts_with("Main PID:") {
+            // e.g. "Main PID: 1234 (nginx)"
+            pid = trimmed
+                .split_whitespace()
+                .nth(2)
+                .and_then(|s| s.parse::<u32>().ok());
+        }
+    }
+
+    ServiceInfo {
+        name: name.to_string(),
+        status,
+        pid,
+        manager: InitSystem::Systemd,
+    }
+}
+
+/// Parse `launchctl print <domain>/<label>` output, whose job properties
+/// include `state = running` and `pid = 123`.
+fn parse_launchctl_print(name: &str, stdout: &str) -> ServiceInfo {
+    let mut status = "stopped".to_string();
+    let mut pid = None;
+    for line in stdout.lines() {
+        match line.trim().split_once(" = ") {
+            Some(("state", "running")) => status = "running".to_string(),
+            Some(("pid", value)) => pid = value.parse::<u32>().ok(),
+            _ => {}
+        }
+    }
+
+    ServiceInfo {
+        name: name.to_string(),
+        status,
+        pid,
+        manager: InitSystem::Launchd,
+    }
+}
+
+/// Parse `rc-service <name> status` output (e.g. ` * status: started`).
+fn parse_openrc_status(name: &str, stdout: &str) -> ServiceInfo {
+    let status = stdout
+        .lines()
+        .find_map(|line| line.split_once("status:").map(|(_, s)| s.trim()))
+        .map(|s| match s {
+            "started" => "running".to_string(),
+            "stopped" => "stopped".to_string(),
+            "crashed" => "failed".to_string(),
+            other => other.to_string(),
+        })
+        .unwrap_or_else(|| "unknown".to_string());
+
+    ServiceInfo {
+        name: name.to_string(),
+        status,
+        pid: None,
+        manager: InitSystem::OpenRc,
+    }
+}
+
+/// Parse `sv status <name>` output (e.g. `run: sshd: (pid 123) 45s`).
+fn parse_runit_status(name: &str, stdout: &str) -> ServiceInfo {
+    let line = stdout.lines().next().unwrap_or("").trim();
+    let status = match line.split(':').next().unwrap_or("") {
+        "run" => "running",
+        "down" => "stopped",
+        "finish" => "stopping",
+        "fail" => "failed",
+        _ => "unknown",
+    };
+    let pid = line
+        .split_once("(pid ")
+        .and_then(|(_, rest)| rest.split(')').next())
+        .and_then(|s| s.trim().parse::<u32>().ok());
+
+    ServiceInfo {
+        name: name.to_string(),
+        status: status.to_string(),
+        pid,
+        manager: InitSystem::Runit,
+    }
+}
+
+/// `Restart=` values systemd accepts.
+const RESTART_POLICIES: &[&str] = &[
+    "no",
+    "on-success",
+    "on-failure",
+    "on-abnormal",
+    "on-watchdog",
+    "on-abort",
+    "always",
+];
+
+/// Render a systemd user unit file from `spec`.
+///
+/// Every field must be a single line so a value cannot smuggle in extra
+/// directives, and `%` is escaped so values are not expanded as specifiers.
+fn render_user_unit(spec: &UserUnitSpec) -> Result<String> {
+    validate_service_name(&spec.name)?;
+    let single_line = |field: &str, value: &str| -> Result<()> {
+        if value.contains('\n') || value.contains('\r') {
+            anyhow::bail!("unit field {field} must not contain newlines");
+        }
+        Ok(())
+    };
+    single_line("Description", &spec.description)?;
+    single_line("ExecStart", &spec.exec_start)?;
+    if !RESTART_POLICIES.contains(&spec.restart.as_str()) {
+        anyhow::bail!(
+            "invalid restart policy {:?}; expected one of: {}",
+            spec.restart,
+            RESTART_POLICIES.join(", ")
+        );
+    }
+    if spec.exec_start.trim().is_empty() {
+        anyhow::bail!("unit ExecStart must not be empty");
+    }
+    let escape = |value: &str| value.replace('%', "%%");
+
+    let mut unit = String::new();
+    unit.push_str("[Unit]\n");
+    unit.push_str(&format!("Description={}\n\n", escape(&spec.description)));
+    unit.push_str("[Service]\nType=simple\n");
+    unit.push_str(&format!("ExecStart={}\n", escape(&spec.exec_start)));
+    if let Some(dir) = &spec.working_directory {
+        single_line("WorkingDirectory", dir)?;
+        unit.push_str(&format!("WorkingDirectory={}\n", escape(dir)));
+    }
+    for (key, value) in &spec.environment {
+        let valid_key = !key.is_empty()
+            && !key.starts_with(|c: char| c.is_ascii_digit())
+            && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_key {
+            anyhow::bail!("invalid environment variable name: {key:?}");
+        }
+        single_line("Environment", value)?;
+        let escaped = escape(value).replace('\\', "\\\\").replace('"', "\\\"");
+        unit.push_str(&format!("Environment=\"{key}={escaped}\"\n"));
+    }
+    unit.push_str(&format!("Restart={}\n\n", spec.restart));
+    unit.push_str("[Install]\nWantedBy=default.target\n");
+    Ok(unit)
+}
+
 // ---------------------------------------------------------------------------
 // Tests
 // ---------------------------------------------------------------------------
@@ -824,12 +1484,463 @@ port = 8080
         assert!(!result.output.is_empty());
     }
 
+    #[test]
+    fn test_service_scope_parse() {
+        assert_eq!(ServiceScope::parse("system").unwrap(), ServiceScope::System);
+        assert_eq!(ServiceScope::parse("user").unwrap(), ServiceScope::User);
+        assert!(ServiceScope::parse("global").is_err());
+    }
+
+    #[test]
+    fn test_validate_service_name() {
+        assert!(validate_service_name("postgresql").is_ok());
+        assert!(validate_service_name("getty@tty1.service").is_ok());
+        assert!(validate_service_name("").is_err());
+        assert!(validate_service_name("../../etc/passwd").is_err());
+        assert!(validate_service_name("foo bar").is_err());
+        assert!(validate_service_name("x\" || true").is_err());
+    }
+
+    #[test]
+    fn test_status_command_per_init_system() {
+        let (prog, args) =
+            status_command(InitSystem::Systemd, ServiceScope::User, "ollama").unwrap();
+        assert_eq!(prog, "systemctl");
+        assert_eq!(args, ["--user", "status", "ollama"]);
+
+        let (prog, args) =
+            status_command(InitSystem::OpenRc, ServiceScope::System, "sshd").unwrap();
+        assert_eq!(prog, "rc-service");
+        assert_eq!(args, ["sshd", "status"]);
+
+        let (prog, args) = status_command(InitSystem::Runit, ServiceScope::System, "sshd").unwrap();
+        assert_eq!(prog, "sv");
+        assert_eq!(args, ["status", "sshd"]);
+
+        assert!(status_command(InitSystem::Runit, ServiceScope::User, "sshd").is_err());
+    }
+
+    #[test]
+    fn test_control_command_scopes() {
+        let (prog, args) = control_command(
+            InitSystem::Systemd,
+            ServiceScope::System,
+            "nginx",
+            "restart",
+        )
+        .unwrap();
+        assert_eq!(prog, "sudo");
+        assert_eq!(args, ["systemctl", "restart", "nginx"]);
+
+        // User units never go through sudo.
+        let (prog, args) =
+            control_command(InitSystem::Systemd, ServiceScope::User, "ollama", "enable").unwrap();
+        assert_eq!(prog, "systemctl");
+        assert_eq!(args, ["--user", "enable", "--now", "ollama"]);
+
+        let (_, args) =
+            control_command(InitSystem::OpenRc, ServiceScope::System, "sshd", "enable").unwrap();
+        assert_eq!(args, ["rc-update", "add", "sshd", "default"]);
+
+        let (_, args) =
+            control_command(InitSystem::Runit, ServiceScope::System, "sshd", "stop").unwrap();
+        assert_eq!(args, ["sv", "down", "sshd"]);
+
+        assert!(
+            control_command(InitSystem::Runit, ServiceScope::System, "sshd", "enable").is_err()
+        );
+        assert!(control_command(InitSystem::Systemd, ServiceScope::System, "x", "mask").is_err());
+        assert!(control_command(InitSystem::OpenRc, ServiceScope::User, "x", "start").is_err());
+    }
+
+    #[test]
+    fn test_journalctl_args() {
+        let args = journalctl_args(
+            "postgresql",
+            ServiceScope::User,
+            Some("1 hour ago"),
+            None,
+            50,
+        );
+        assert_eq!(
+            args,
+            [
+                "--user",
+                "-u",
+                "postgresql",
+                "--no-pager",
+                "-o",
+                "short-iso",
+                "-n",
+                "50",
+                "--since",
+                "1 hour ago"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_systemctl_status() {
+        let out = "● ollama.service - Ollama\n     Loaded: loaded\n     Active: active (running) since Mon\n   Main PID: 4242 (ollama)\n";
+        let info = parse_systemctl_status("ollama", out);
+        assert_eq!(info.status, "running");
+        assert_eq!(info.pid, Some(4242));
+        assert_eq!(info.manager, InitSystem::Systemd);
+    }
+
+    #[test]
+    fn test_parse_openrc_status() {
+        assert_eq!(
+            parse_openrc_status("sshd", " * status: started\n").status,
+            "running"
+        );
+        assert_eq!(
+            parse_openrc_status("sshd", " * status: stopped\n").status,
+            "stopped"
+        );
+        assert_eq!(
+            parse_openrc_status("sshd", " * status: crashed\n").status,
+            "failed"
+        );
+        assert_eq!(parse_openrc_status("sshd", "").status, "unknown");
+    }
+
+    #[test]
+    fn test_parse_runit_status() {
+        let info = parse_runit_status("sshd", "run: sshd: (pid 123) 45s; run: log: (pid 7) 45s\n");
+        assert_eq!(info.status, "running");
+        assert_eq!(info.pid, Some(123));
+
+        let info = parse_runit_status("sshd", "down: sshd: 10s, normally up\n");
+        assert_eq!(info.status, "stopped");
+        assert_eq!(info.pid, None);
+    }
+
+    #[test]
+    fn test_render_user_unit() {
+        let mut environment = BTreeMap::new();
+        environment.insert("OLLAMA_HOST".to_string(), "127.0.0.1:11434".to_string());
+        let spec = UserUnitSpec {
+            name: "ollama".into(),
+            description: "Ollama server".into(),
+            exec_start: "/usr/local/bin/ollama serve".into(),
+            working_directory: Some("/home/dev".into()),
+            environment,
+            restart: default_restart_policy(),
+        };
+        let unit = render_user_unit(&spec).unwrap();
+        assert!(unit.contains("Description=Ollama server\n"));
+        assert!(unit.contains("ExecStart=/usr/local/bin/ollama serve\n"));
+        assert!(unit.contains("WorkingDirectory=/home/dev\n"));
+        assert!(unit.contains("Environment=\"OLLAMA_HOST=127.0.0.1:11434\"\n"));
+        assert!(unit.contains("Restart=on-failure\n"));
+        assert!(unit.contains("WantedBy=default.target"));
+    }
+
+    #[test]
+    fn test_render_user_unit_rejects_injection() {
+        let spec = UserUnitSpec {
+            name: "evil".into(),
+            description: "x\nExecStartPre=/bin/rm -rf ~".into(),
+            exec_start: "/bin/true".into(),
+            working_directory: None,
+            environment: BTreeMap::new(),
+            restart: default_restart_policy(),
+        };
+        assert!(render_user_unit(&spec).is_err());
+    }
+
+    #[test]
+    fn test_render_user_unit_escapes_specifiers_and_checks_restart() {
+        let mut environment = BTreeMap::new();
+        environment.insert("FORMAT".to_string(), "%Y-%m-%d".to_string());
+        let mut spec = UserUnitSpec {
+            name: "stamp".into(),
+            description: "100% up".into(),
+            exec_start: "/bin/date +%s".into(),
+            working_directory: None,
+            environment,
+            restart: "always".into(),
+        };
+        let unit = render_user_unit(&spec).unwrap();
+        assert!(unit.contains("Description=100%% up\n"));
+        assert!(unit.contains("ExecStart=/bin/date +%%s\n"));
+        assert!(unit.contains("Environment=\"FORMAT=%%Y-%%m-%%d\"\n"));
+        assert!(unit.contains("Restart=always\n"));
+
+        spec.restart = "sometimes".into();
+        assert!(render_user_unit(&spec).is_err());
+    }
+
+    #[test]
+    fn test_launchctl_commands_target_the_scope_domain() {
+        let (prog, args) =
+            launchctl_command("gui/501", ServiceScope::User, "com.ollama", "restart").unwrap();
+        assert_eq!(prog, "launchctl");
+        assert_eq!(args, ["kickstart", "-k", "gui/501/com.ollama"]);
+
+        let (prog, args) =
+            launchctl_command("system", ServiceScope::System, "com.ollama", "disable").unwrap();
+        assert_eq!(prog, "sudo");
+        assert_eq!(args, ["launchctl", "disable", "system/com.ollama"]);
+
+        let (_, args) =
+            launchctl_command("gui/501", ServiceScope::User, "com.ollama", "stop").unwrap();
+        assert_eq!(args, ["kill", "SIGTERM", "gui/501/com.ollama"]);
+        assert!(launchctl_command("system", ServiceScope::System, "x", "mask").is_err());
+    }
+
+    #[test]
+    fn test_parse_launchctl_print() {
+        let out =
+            "gui/501/com.ollama = {\n\tactive count = 1\n\tstate = running\n\tpid = 4242\n}\n";
+        let info = parse_launchctl_print("com.ollama", out);
+        assert_eq!(info.status, "running");
+        assert_eq!(info.pid, Some(4242));
+
+        let info = parse_launchctl_print("com.ollama", "x = {\n\tstate = not running\n}\n");
+        assert_eq!(info.status, "stopped");
+        assert_eq!(info.pid, None);
+    }
+
+    #[tokio::test]
+    async fn test_write_user_unit_refuses_overwrite() {
+        let dir = tempfile::tempdir().unwrap();
+        let spec = UserUnitSpec {
+            name: "devdb".into(),
+            description: "Local Postgres".into(),
+            exec_start: "/usr/bin/postgres -D /tmp/pg".into(),
+            working_directory: None,
+            environment: BTreeMap::new(),
+            restart: default_restart_policy(),
+        };
+
+        let ops = SystemOps::new();
+        let path = ops.write_user_unit(dir.path(), &spec).await.unwrap();
+        assert!(path.ends_with("devdb.service"));
+        assert!(ops.write_user_unit(dir.path(), &spec).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_config_edit_backs_up_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".gitconfig");
+        let original = "[user]\n\tname = Jane # me\n";
+        tokio::fs::write(&path, original).await.unwrap();
+
+        let fs = FilesystemOps::new(dir.path().to_path_buf(), dir.path().join("backups"));
+        let ops = SystemOps::with_backups(fs);
+        let result = ops
+            .config_edit(
+                path.to_str().unwrap(),
+                "user.email",
+                &EditOp::Set("jane@example.com".into()),
+                None,
+            )
+            .await
+            .unwrap();
+
+        assert!(result.changed);
+        assert_eq!(result.format, "ini");
+        assert!(result.backup.is_some());
+        let edited = tokio::fs::read_to_string(&path).await.unwrap();
+        assert_eq!(
+            edited,
+            "[user]\n\tname = Jane # me\n\temail = jane@example.com\n"
+        );
+
+        let backups: Vec<_> = std::fs::read_dir(dir.path().join("backups"))
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .collect();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(
+            std::fs::read_to_string(backups[0].path()).unwrap(),
+            original
+        );
+    }
+
+    #[tokio::test]
+    async fn test_config_edit_refuses_without_backup() {
+        let workspace = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        let path = outside.path().join("app.yaml");
+        tokio::fs::write(&path, "a: 1\n").await.unwrap();
+
+        let fs = FilesystemOps::new(workspace.path().to_path_buf(), workspace.path().join("b"));
+        let ops = SystemOps::with_backups(fs);
+        let result = ops
+            .config_edit(path.to_str().unwrap(), "a", &EditOp::Set("2".into()), None)
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(tokio::fs::read_to_string(&path).await.unwrap(), "a: 1\n");
+    }
+
+    #[tokio::test]
+    async fn test_writes_refused_when_backups_unavailable() {
+        use crate::shell_env::Shell;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.yaml");
+        tokio::fs::write(&path, "a: 1\n").await.unwrap();
+
+        let ops = SystemOps::backups_unavailable("no home directory");
+        let err = ops
+            .config_edit(path.to_str().unwrap(), "a", &EditOp::Set("2".into()), None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("backups are unavailable"));
+        assert_eq!(tokio::fs::read_to_string(&path).await.unwrap(), "a: 1\n");
+
+        let target = ShellTarget {
+            shell: Shell::Bash,
+            rc_file: dir.path().join(".bashrc"),
+        };
+        let edit = EnvEdit::Set {
+            key: "A".into(),
+            value: "1".into(),
+        };
+        assert!(ops.env_persist(&target, &edit).await.is_err());
+        assert!(!target.rc_file.exists());
+    }
+
+    #[tokio::test]
+    async fn test_config_edit_noop_delete() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env");
+        tokio::fs::write(&path, "A=1\n").await.unwrap();
+
+        let ops = SystemOps::new();
+        let result = ops
+            .config_edit(path.to_str().unwrap(), "MISSING", &EditOp::Delete, None)
+            .await
+            .unwrap();
+        assert!(!result.changed);
+    }
+
+    #[tokio::test]
+    async fn test_config_read_yaml() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("compose.yml");
+        tokio::fs::write(&path, "services:\n  web:\n    image: nginx\n")
+            .await
+            .unwrap();
+
+        let ops = SystemOps::new();
+        let val = ops
+            .config_read(path.to_str().unwrap(), "auto")
+            .await
+            .unwrap();
+        assert_eq!(val["services"]["web"]["image"], "nginx");
+    }
+
     #[test]
     fn test_detect_format() {
-        assert_eq!(SystemOps::detect_format("/etc/config.toml"), "toml");
-        assert_eq!(SystemOps::detect_format("/etc/config.json"), "json");
-        assert_eq!(SystemOps::detect_format("/etc/config.yaml"), "yaml");
-        assert_eq!(SystemOps::detect_format("/etc/config.yml"), "yaml");
-        assert_eq!(SystemOps::detect_format("/etc/config"), "toml"); // default
+        assert_eq!(ConfigFormat::detect("/etc/config.toml").as_str(), "toml");
+        assert_eq!(ConfigFormat::detect("/etc/config.json").as_str(), "json");
+        assert_eq!(ConfigFormat::detect("/etc/config.yaml").as_str(), "yaml");
+        assert_eq!(ConfigFormat::detect("/etc/config.yml").as_str(), "yaml");
+        assert_eq!(ConfigFormat::detect("/etc/config").as_str(), "toml"); // default
+    }
+
+    #[tokio::test]
+    async fn test_env_persist_is_idempotent_and_backs_up() {
+        use crate::shell_env::{PathPosition, Shell};
+
+        let dir = tempfile::tempdir().unwrap();
+        let rc = dir.path().join(".zshrc");
+        std::fs::write(
+            &rc,
+            "# my zshrc
+alias ll='ls -l'
+",
+        )
+        .unwrap();
+
+        let fs = FilesystemOps::new(dir.path().to_path_buf(), dir.path().join("backups"));
+        let ops = SystemOps::with_backups(fs);
+        let target = ShellTarget {
+            shell: Shell::Zsh,
+            rc_file: rc.clone(),
+        };
+
+        let set = EnvEdit::Set {
+            key: "EDITOR".into(),
+            value: "nvim".into(),
+        };
+        let first = ops.env_persist(&target, &set).await.unwrap();
+        assert!(first.changed);
+        assert!(first.backup.is_some());
+        let second = ops.env_persist(&target, &set).await.unwrap();
+        assert!(!second.changed);
+        assert!(second.backup.is_none());
+
+        let add = EnvEdit::PathAdd {
+            dir: "/opt/tools/bin".into(),
+            position: PathPosition::Append,
+        };
+        ops.env_persist(&target, &add).await.unwrap();
+        assert!(!ops.env_persist(&target, &add).await.unwrap().changed);
+
+        let listing = ops.env_persist_list(&target).await.unwrap();
+        assert_eq!(listing.vars.get("EDITOR").map(String::as_str), Some("nvim"));
+        assert_eq!(listing.path_append, vec!["/opt/tools/bin".to_string()]);
+
+        let content = std::fs::read_to_string(&rc).unwrap();
+        assert!(content.starts_with("# my zshrc\nalias ll='ls -l'\n"));
+        assert_eq!(content.matches(shell_env::BEGIN_MARKER).count(), 1);
+
+        // Removing everything drops the block and restores the original file.
+        ops.env_persist(
+            &target,
+            &EnvEdit::Unset {
+                key: "EDITOR".into(),
+            },
+        )
+        .await
+        .unwrap();
+        ops.env_persist(
+            &target,
+            &EnvEdit::PathRemove {
+                dir: "/opt/tools/bin".into(),
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&rc).unwrap(),
+            "# my zshrc\nalias ll='ls -l'\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_env_persist_creates_missing_rc_file() {
+        use crate::shell_env::Shell;
+
+        let dir = tempfile::tempdir().unwrap();
+        let rc = dir.path().join(".config/fish/config.fish");
+        let ops = SystemOps::new();
+        let target = ShellTarget {
+            shell: Shell::Fish,
+            rc_file: rc.clone(),
+        };
+        let listing = ops.env_persist_list(&target).await.unwrap();
+        assert!(listing.vars.is_empty());
+
+        let result = ops
+            .env_persist(
+                &target,
+                &EnvEdit::Set {
+                    key: "GOPATH".into(),
+                    value: "/home/dev/go".into(),
+                },
+            )
+            .await
+            .unwrap();
+        assert!(result.changed);
+        assert!(std::fs::read_to_string(&rc)
+            .unwrap()
+            .contains("set -gx GOPATH '/home/dev/go'"));
     }
 }
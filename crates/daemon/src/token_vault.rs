@@ -0,0 +1,251 @@
+//! Local vault for reversible redaction tokens.
+//!
+//! When reversible redaction is on, the [`Redactor`](crate::redactor::Redactor)
+//! replaces each sensitive value with a stable per-session token such as
+//! `«API_KEY_3»` instead of a fixed placeholder. The [`TokenVault`] remembers
+//! which original value each token stands for so that agent replies and
+//! command arguments coming back from the cloud can be re-hydrated locally.
+//!
+//! The vault lives only in daemon memory: it is never persisted, logged or
+//! serialized, and its `Debug` output shows counts only.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+
+use regex::Regex;
+
+/// Tokens minted per session before falling back to fixed placeholders.
+const MAX_TOKENS_PER_SESSION: usize = 1024;
+
+/// Sessions kept before the oldest one is forgotten.
+const MAX_SESSIONS: usize = 256;
+
+/// Matches a vault token such as `«API_KEY_3»`.
+fn token_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"«([A-Z_]+_\d+)»").expect("invalid token regex"))
+}
+
+#[derive(Default)]
+struct SessionVault {
+    by_value: HashMap<String, String>,
+    by_token: HashMap<String, String>,
+    counters: HashMap<&'static str, usize>,
+}
+
+#[derive(Default)]
+struct Inner {
+    sessions: HashMap<String, SessionVault>,
+    /// Session ids in creation order, for eviction.
+    order: VecDeque<String>,
+}
+
+/// In-memory mapping between redaction tokens and original values, scoped
+/// per chat session.
+#[derive(Default)]
+pub struct TokenVault {
+    inner: Mutex<Inner>,
+}
+
+impl TokenVault {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Token for `value` within `session_id`, minting `«LABEL_N»` on first
+    /// sight. The same value always maps to the same token in a session.
+    ///
+    /// Returns `None` once the session has reached its token limit; callers
+    /// should fall back to a fixed placeholder.
+    pub fn tokenize(&self, session_id: &str, label: &'static str, value: &str) -> Option<String> {
+        let mut inner = self.inner.lock().unwrap();
+        if !inner.sessions.contains_key(session_id) {
+            if inner.order.len() >= MAX_SESSIONS {
+                if let Some(oldest) = inner.order.pop_front() {
+                    inner.sessions.remove(&oldest);
+                }
+            }
+            inner.order.push_back(session_id.to_string());
+        }
+        let session = inner.sessions.entry(session_id.to_string()).or_default();
+
+        if let Some(token) = session.by_value.get(value) {
+            return Some(token.clone());
+        }
+        if session.by_token.len() >= MAX_TOKENS_PER_SESSION {
+            return None;
+        }
+
+        let counter = session.counters.entry(label).or_insert(0);
+        *counter += 1;
+        let token = format!("«{label}_{counter}»");
+        session.by_value.insert(value.to_string(), token.clone());
+        session.by_token.insert(token.clone(), value.to_string());
+        Some(token)
+    }
+
+    /// Replace every token known to `session_id` in `text` with its original
+    /// value. Unknown tokens are left untouched.
+    pub fn restore(&self, session_id: &str, text: &str) -> String {
+        if !text.contains('«') {
+            return text.to_string();
+        }
+        let inner = self.inner.lock().unwrap();
+        let Some(session) = inner.sessions.get(session_id) else {
+            return text.to_string();
+        };
+        token_pattern()
+            .replace_all(text, |caps: &regex::Captures| {
+                session
+                    .by_token
+                    .get(&caps[0])
+                    .cloned()
+                    .unwrap_or_else(|| caps[0].to_string())
+            })
+            .into_owned()
+    }
+
+    /// The inverse of [`TokenVault::restore`]: replace every original value
+    /// known to `session_id` in `text` with its token, longest first.
+    pub fn retokenize(&self, session_id: &str, text: &str) -> String {
+        let inner = self.inner.lock().unwrap();
+        let Some(session) = inner.sessions.get(session_id) else {
+            return text.to_string();
+        };
+        let mut values: Vec<_> = session.by_value.iter().collect();
+        values.sort_by_key(|(value, _)| std::cmp::Reverse(value.len()));
+
+        let mut result = text.to_string();
+        for (value, token) in values {
+            if !value.is_empty() && result.contains(value.as_str()) {
+                result = result.replace(value.as_str(), token);
+            }
+        }
+        result
+    }
+
+    /// Whether `text` contains at least one token known to `session_id`.
+    pub fn contains_tokens(&self, session_id: &str, text: &str) -> bool {
+        let inner = self.inner.lock().unwrap();
+        inner.sessions.get(session_id).is_some_and(|session| {
+            token_pattern()
+                .find_iter(text)
+                .any(|m| session.by_token.contains_key(m.as_str()))
+        })
+    }
+
+    /// Drop every token minted for `session_id`.
+    pub fn forget_session(&self, session_id: &str) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.sessions.remove(session_id);
+        inner.order.retain(|id| id != session_id);
+    }
+
+    /// Number of tokens held for `session_id`.
+    pub fn token_count(&self, session_id: &str) -> usize {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .sessions
+            .get(session_id)
+            .map_or(0, |session| session.by_token.len())
+    }
+}
+
+impl std::fmt::Debug for TokenVault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let inner = self.inner.lock().unwrap();
+        f.debug_struct("TokenVault")
+            .field("sessions", &inner.sessions.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_value_gets_the_same_token_per_session() {
+        let vault = TokenVault::new();
+        let a = vault.tokenize("s1", "API_KEY", "sk-one").unwrap();
+        let b = vault.tokenize("s1", "API_KEY", "sk-two").unwrap();
+        assert_eq!(a, "«API_KEY_1»");
+        assert_eq!(b, "«API_KEY_2»");
+        assert_eq!(vault.tokenize("s1", "API_KEY", "sk-one").unwrap(), a);
+
+        // Counters are per label and per session
+        assert_eq!(vault.tokenize("s1", "PATH", "/home/a").unwrap(), "«PATH_1»");
+        assert_eq!(
+            vault.tokenize("s2", "API_KEY", "sk-two").unwrap(),
+            "«API_KEY_1»"
+        );
+    }
+
+    #[test]
+    fn restore_only_uses_the_session_vault() {
+        let vault = TokenVault::new();
+        let token = vault
+            .tokenize("s1", "CREDENTIAL", "password=hunter2")
+            .unwrap();
+        let reply = format!("Use {token} and «CREDENTIAL_9» to log in");
+
+        assert_eq!(
+            vault.restore("s1", &reply),
+            "Use password=hunter2 and «CREDENTIAL_9» to log in"
+        );
+        assert_eq!(vault.restore("s2", &reply), reply);
+        assert!(vault.contains_tokens("s1", &reply));
+        assert!(!vault.contains_tokens("s2", &reply));
+    }
+
+    #[test]
+    fn retokenize_reverses_restore() {
+        let vault = TokenVault::new();
+        let short = vault.tokenize("s1", "TOKEN", "abc123").unwrap();
+        let long = vault.tokenize("s1", "CREDENTIAL", "secret=abc123").unwrap();
+        let text = format!("{long} then {short}");
+        let restored = vault.restore("s1", &text);
+        assert_eq!(restored, "secret=abc123 then abc123");
+        assert_eq!(vault.retokenize("s1", &restored), text);
+        assert_eq!(vault.retokenize("s2", &restored), restored);
+    }
+
+    #[test]
+    fn forget_session_drops_tokens() {
+        let vault = TokenVault::new();
+        let token = vault.tokenize("s1", "TOKEN", "abc").unwrap();
+        vault.forget_session("s1");
+        assert_eq!(vault.token_count("s1"), 0);
+        assert_eq!(vault.restore("s1", &token), token);
+    }
+
+    #[test]
+    fn session_token_limit_is_enforced() {
+        let vault = TokenVault::new();
+        for i in 0..MAX_TOKENS_PER_SESSION {
+            assert!(vault.tokenize("s1", "ENV", &format!("V{i}")).is_some());
+        }
+        assert!(vault.tokenize("s1", "ENV", "one too many").is_none());
+        // Known values still resolve
+        assert_eq!(vault.tokenize("s1", "ENV", "V0").unwrap(), "«ENV_1»");
+    }
+
+    #[test]
+    fn oldest_session_is_evicted() {
+        let vault = TokenVault::new();
+        for i in 0..=MAX_SESSIONS {
+            vault.tokenize(&format!("s{i}"), "PATH", "/tmp/x");
+        }
+        assert_eq!(vault.token_count("s0"), 0);
+        assert_eq!(vault.token_count(&format!("s{MAX_SESSIONS}")), 1);
+    }
+
+    #[test]
+    fn debug_does_not_show_values() {
+        let vault = TokenVault::new();
+        vault.tokenize("s1", "API_KEY", "sk-very-secret");
+        let debug = format!("{vault:?}");
+        assert!(!debug.contains("sk-very-secret"));
+        assert!(!debug.contains("s1"));
+    }
+}
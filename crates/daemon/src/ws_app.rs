@@ -1,137 +1,548 @@
 //! Mac App WebSocket handler — protocol translation layer.
 //!
-//! The Mac app connects to `/ws` and speaks the task-based protocol
-//! (task.submit, agent.message, task.completed, etc.). This handler
-//! translates between that protocol and ChatMessage v1 for the cloud bridge.
+//! The Mac app connects to `/ws` and speaks the task-based
+//! [`AppMessage`] protocol (task.submit, agent.message, task.completed,
+//! etc.). This handler translates between that protocol and ChatMessage v1
+//! for the cloud bridge.
+//!
+//! Besides chat, a connection carries the task's control flow: cloud plan
+//! proposals become `plan.proposed` and the app's `plan.approve` goes back
+//! as a plan decision; `task.cancel` stops the task in the cloud and its
+//! running commands; command approval prompts from the [`ApprovalBroker`]
+//! become `permission.requested`, answered by `permission.response`.
 
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
 use axum::extract::ws::{Message as AxumWsMessage, WebSocket};
 use d1_common::chat_message::{ChatMessage, ChatMessageType, ChatPayload};
+use d1_common::protocol::{
+    is_supported_version, ActionType, AgentMessagePayload, AgentMessageType, AgentName, AppMessage,
+    CloudMessage, DaemonStatusPayload, Envelope, ErrorCode, ErrorPayload, HeartbeatPayload,
+    PermissionRequestedPayload, PlanApproveAction, PlanApprovePayload, PlanProposedPayload,
+    RawEnvelope, RiskTier, TaskCancelPayload, TaskCompletedPayload, TaskError, TaskFailCode,
+    TaskFailedPayload, TaskSubmitPayload, WireMessage, PROTOCOL_VERSION,
+    SUPPORTED_PROTOCOL_VERSIONS,
+};
 use futures::{SinkExt, StreamExt};
 use serde_json::Value;
-use tokio::sync::mpsc;
-use tracing::{debug, warn};
+use tokio::sync::{broadcast, mpsc};
+use tracing::{debug, info, warn};
 use uuid::Uuid;
 
-use crate::chat_relay::ChatRelay;
+use crate::approvals::ApprovalBroker;
+use crate::chat_relay::{ChatRelay, LocalSubscriber};
+use crate::command_relay::{ApprovalKind, ApprovalPrompt, CommandRelay};
+use crate::connection_state::ConnectionStateMachine;
 use crate::redactor::Redactor;
 
-/// Build a Mac-app-protocol envelope.
-fn make_envelope(msg_type: &str, payload: Value) -> String {
-    serde_json::json!({
-        "v": 1,
-        "id": Uuid::new_v4().to_string(),
-        "ts": std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_millis() as u64,
-        "type": msg_type,
-        "payload": payload,
+/// What a `/ws` connection needs from the rest of the daemon.
+#[derive(Clone)]
+pub struct AppContext {
+    pub relay: Arc<ChatRelay>,
+    pub redactor: Arc<Redactor>,
+    pub connection: Arc<ConnectionStateMachine>,
+    /// Runs cloud commands; `task.cancel` stops a task's commands here.
+    pub commands: Arc<CommandRelay>,
+    /// Approval prompts shown to the app as `permission.requested`.
+    pub approvals: Arc<ApprovalBroker>,
+    /// Tasks in flight across all app connections.
+    pub active_tasks: Arc<AtomicUsize>,
+    pub device_id: String,
+    pub orchestrator_url: String,
+}
+
+// ---------------------------------------------------------------------------
+// Messages (daemon -> app)
+// ---------------------------------------------------------------------------
+
+/// `error` rejecting the app message `request_id`.
+fn protocol_error(request_id: &str, message: &str) -> AppMessage {
+    AppMessage::Error(ErrorPayload {
+        code: ErrorCode::ProtocolError,
+        message: message.to_string(),
+        request_id: (!request_id.is_empty()).then(|| request_id.to_string()),
+        daemon_version: None,
+        supported_protocol_versions: None,
+    })
+}
+
+/// `error` rejecting an app message written in protocol version `v`.
+fn version_mismatch(request_id: &str, v: u32) -> AppMessage {
+    AppMessage::Error(ErrorPayload {
+        code: ErrorCode::ProtocolVersionMismatch,
+        message: format!("protocol version {v} is not supported"),
+        request_id: (!request_id.is_empty()).then(|| request_id.to_string()),
+        daemon_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+        supported_protocol_versions: Some(SUPPORTED_PROTOCOL_VERSIONS.to_vec()),
+    })
+}
+
+/// `task.failed` for `task_id`.
+fn task_failed(task_id: &str, code: TaskFailCode, message: &str, steps_total: u32) -> AppMessage {
+    AppMessage::TaskFailed(TaskFailedPayload {
+        task_id: task_id.to_string(),
+        error: TaskError {
+            code,
+            message: message.to_string(),
+            failed_step: None,
+            diagnosis: None,
+            steps_completed: 0,
+            steps_total,
+        },
+        credits_used: 0,
+    })
+}
+
+/// `daemon.status` describing the daemon and its cloud connection.
+async fn daemon_status(ctx: &AppContext) -> AppMessage {
+    AppMessage::DaemonStatus(DaemonStatusPayload {
+        daemon_version: env!("CARGO_PKG_VERSION").to_string(),
+        protocol_version: PROTOCOL_VERSION,
+        orchestrator_connected: ctx.connection.is_connected().await,
+        orchestrator_url: ctx.orchestrator_url.clone(),
+        active_tasks: ctx.active_tasks.load(Ordering::Relaxed),
+        device_id: ctx.device_id.clone(),
+        connection_state: ctx.connection.state().await.to_string(),
+        queued_messages: ctx.connection.queue_len().await,
     })
-    .to_string()
 }
 
+/// `permission.requested` asking about `prompt` for `task_id`.
+fn permission_requested(prompt: &ApprovalPrompt, task_id: &str) -> AppMessage {
+    AppMessage::PermissionRequested(PermissionRequestedPayload {
+        task_id: task_id.to_string(),
+        step_id: String::new(),
+        permission_id: prompt.command_id.clone(),
+        risk_tier: RiskTier::High,
+        action_type: match prompt.kind {
+            ApprovalKind::Shell => ActionType::Shell,
+            ApprovalKind::FileRead => ActionType::FileRead,
+        },
+        description: prompt.reason.clone(),
+        command_preview: Some(prompt.command.clone()),
+        remember: prompt.session_id.is_some(),
+    })
+}
+
+/// The protocol version a `session_init_ack` chose, if this daemon can't
+/// speak it.
+fn unsupported_ack_version(msg: &ChatMessage) -> Option<u32> {
+    if msg.msg_type != ChatMessageType::SessionInitAck {
+        return None;
+    }
+    match Envelope::from_chat(msg).ok()?.message {
+        CloudMessage::SessionInitAck(ack)
+            if !is_supported_version(ack.metadata.protocol_version) =>
+        {
+            Some(ack.metadata.protocol_version)
+        }
+        _ => None,
+    }
+}
+
+/// Mac app protocol message for a cloud message about `task_id`.
+///
+/// Returns `None` for messages the app has no use for.
+fn translate_cloud_message(msg: &ChatMessage, task_id: &str) -> Option<AppMessage> {
+    let message = match msg.msg_type {
+        ChatMessageType::StreamEnd => AppMessage::TaskCompleted(TaskCompletedPayload {
+            task_id: task_id.to_string(),
+            summary: String::new(),
+            steps_completed: 1,
+            steps_total: 1,
+            duration_ms: 0,
+            credits_used: 0,
+            artifacts: None,
+        }),
+        ChatMessageType::Error => task_failed(
+            task_id,
+            TaskFailCode::InternalError,
+            &msg.payload.content,
+            1,
+        ),
+        ChatMessageType::PlanProposal => {
+            let mut plan = match &msg.payload.metadata {
+                Some(Value::Object(plan)) => plan.clone(),
+                _ => serde_json::Map::new(),
+            };
+            plan.insert("task_id".into(), task_id.into());
+            plan.entry("summary")
+                .or_insert_with(|| msg.payload.content.clone().into());
+            match serde_json::from_value::<PlanProposedPayload>(Value::Object(plan)) {
+                Ok(plan) => AppMessage::PlanProposed(plan),
+                Err(e) => {
+                    warn!(%e, task_id, "Malformed plan from cloud");
+                    return None;
+                }
+            }
+        }
+        ChatMessageType::SessionInitAck => {
+            let version = unsupported_ack_version(msg)?;
+            task_failed(
+                task_id,
+                TaskFailCode::InternalError,
+                &format!(
+                    "cloud chose protocol version {version}; this daemon speaks {SUPPORTED_PROTOCOL_VERSIONS:?}"
+                ),
+                0,
+            )
+        }
+        ChatMessageType::SessionInit
+        | ChatMessageType::PlanDecision
+        | ChatMessageType::TaskCancel
+        | ChatMessageType::Unknown => return None,
+        _ => AppMessage::AgentMessage(AgentMessagePayload {
+            task_id: task_id.to_string(),
+            step_id: "chat".to_string(),
+            agent: AgentName::Planner,
+            message: msg.payload.content.clone(),
+            message_type: AgentMessageType::Info,
+        }),
+    };
+    Some(message)
+}
+
+/// Whether `msg` ends its task.
+fn is_terminal(msg: &ChatMessage) -> bool {
+    matches!(
+        msg.msg_type,
+        ChatMessageType::StreamEnd | ChatMessageType::Error
+    ) || unsupported_ack_version(msg).is_some()
+}
+
+// ---------------------------------------------------------------------------
+// Task bookkeeping
+// ---------------------------------------------------------------------------
+
+/// A plan proposed to the app and not yet decided.
+#[derive(Debug, Clone)]
+struct ProposedPlan {
+    plan_id: String,
+    step_ids: HashSet<String>,
+}
+
+impl ProposedPlan {
+    /// The plan described by a `PlanProposal`'s metadata.
+    fn from_metadata(metadata: Option<&Value>) -> Option<Self> {
+        let plan = metadata?;
+        let plan_id = plan.get("plan_id")?.as_str()?.to_string();
+        let step_ids = plan
+            .get("steps")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|step| step.get("step_id")?.as_str().map(str::to_string))
+            .collect();
+        Some(Self { plan_id, step_ids })
+    }
+}
+
+/// In-flight tasks of one app connection: each task runs in its own cloud
+/// session so that responses map back to the right `task_id`.
+struct TaskSessions {
+    /// task_id by cloud session id.
+    tasks: HashMap<String, String>,
+    /// Plan awaiting a decision, by cloud session id.
+    plans: HashMap<String, ProposedPlan>,
+    /// Daemon-wide count of tasks in flight.
+    active: Arc<AtomicUsize>,
+}
+
+impl TaskSessions {
+    fn new(active: Arc<AtomicUsize>) -> Self {
+        Self {
+            tasks: HashMap::new(),
+            plans: HashMap::new(),
+            active,
+        }
+    }
+
+    fn start(&mut self, session_id: String, task_id: String) {
+        if self.tasks.insert(session_id, task_id).is_none() {
+            self.active.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Forget the task running in `session_id`, returning its task_id.
+    fn finish(&mut self, session_id: &str) -> Option<String> {
+        self.plans.remove(session_id);
+        let task_id = self.tasks.remove(session_id)?;
+        self.active.fetch_sub(1, Ordering::Relaxed);
+        Some(task_id)
+    }
+
+    fn task_id(&self, session_id: &str) -> Option<String> {
+        self.tasks.get(session_id).cloned()
+    }
+
+    fn session_of(&self, task_id: &str) -> Option<String> {
+        self.tasks
+            .iter()
+            .find(|(_, task)| *task == task_id)
+            .map(|(session, _)| session.clone())
+    }
+
+    /// Every `(session_id, task_id)` pair in flight.
+    fn all(&self) -> Vec<(String, String)> {
+        self.tasks
+            .iter()
+            .map(|(session, task)| (session.clone(), task.clone()))
+            .collect()
+    }
+}
+
+impl Drop for TaskSessions {
+    fn drop(&mut self) {
+        self.active.fetch_sub(self.tasks.len(), Ordering::Relaxed);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Control messages
+// ---------------------------------------------------------------------------
+
+/// Forward the app's decision on a proposed plan to the cloud. A rejected
+/// plan ends the task; the returned message tells the app so.
+async fn decide_plan(
+    ctx: &AppContext,
+    tasks: &Mutex<TaskSessions>,
+    subscriber: &LocalSubscriber,
+    decision: PlanApprovePayload,
+) -> Result<Option<AppMessage>, String> {
+    let session_id = {
+        let mut tasks = tasks.lock().unwrap();
+        let session_id = tasks
+            .session_of(&decision.task_id)
+            .ok_or_else(|| format!("unknown task {}", decision.task_id))?;
+        let plan = tasks
+            .plans
+            .get(&session_id)
+            .ok_or_else(|| format!("task {} has no plan awaiting approval", decision.task_id))?;
+        if plan.plan_id != decision.plan_id {
+            return Err(format!(
+                "plan {} is not the pending plan of task {}",
+                decision.plan_id, decision.task_id
+            ));
+        }
+        if let Some(unknown) = decision
+            .modifications
+            .iter()
+            .flatten()
+            .find(|m| !plan.step_ids.contains(&m.step_id))
+        {
+            return Err(format!(
+                "plan {} has no step {}",
+                decision.plan_id, unknown.step_id
+            ));
+        }
+        tasks.plans.remove(&session_id);
+        if decision.action == PlanApproveAction::Reject {
+            tasks.finish(&session_id);
+            subscriber.leave(&session_id);
+        }
+        session_id
+    };
+
+    let mut modifications = decision.modifications.unwrap_or_default();
+    for modification in &mut modifications {
+        if let Some(description) = &modification.description {
+            modification.description =
+                Some(ctx.redactor.redact_for_session(description, &session_id));
+        }
+    }
+    let action = decision.action.as_str();
+    let msg = ChatMessage::new(
+        ChatMessageType::PlanDecision,
+        ChatPayload {
+            session_id,
+            content: action.to_string(),
+            metadata: Some(serde_json::json!({
+                "plan_id": decision.plan_id,
+                "action": action,
+                "modifications": modifications,
+            })),
+        },
+    );
+    if let Err(e) = ctx.relay.send_to_cloud(msg).await {
+        warn!(%e, "Failed to forward plan decision");
+    }
+    info!(task_id = %decision.task_id, action = ?decision.action, "Plan decided");
+
+    Ok((decision.action == PlanApproveAction::Reject).then(|| {
+        task_failed(
+            &decision.task_id,
+            TaskFailCode::UserDenied,
+            "Plan rejected",
+            0,
+        )
+    }))
+}
+
+/// Stop a task: tell the cloud, stop its running commands and deny its
+/// pending approvals. Returns the message telling the app it ended.
+async fn cancel_task(
+    ctx: &AppContext,
+    tasks: &Mutex<TaskSessions>,
+    subscriber: &LocalSubscriber,
+    cancel: TaskCancelPayload,
+) -> Result<AppMessage, String> {
+    let session_id = {
+        let mut tasks = tasks.lock().unwrap();
+        let session_id = tasks
+            .session_of(&cancel.task_id)
+            .ok_or_else(|| format!("unknown task {}", cancel.task_id))?;
+        tasks.finish(&session_id);
+        session_id
+    };
+    subscriber.leave(&session_id);
+
+    let commands = ctx.commands.cancel_session(&session_id);
+    let approvals = ctx.approvals.cancel_session(&session_id);
+    let msg = ChatMessage::new(
+        ChatMessageType::TaskCancel,
+        ChatPayload {
+            session_id,
+            content: String::new(),
+            metadata: None,
+        },
+    );
+    if let Err(e) = ctx.relay.send_to_cloud(msg).await {
+        warn!(%e, "Failed to forward task cancellation");
+    }
+    info!(task_id = %cancel.task_id, commands, approvals, "Task cancelled");
+
+    Ok(task_failed(
+        &cancel.task_id,
+        TaskFailCode::Cancelled,
+        "Task cancelled",
+        0,
+    ))
+}
+
+// ---------------------------------------------------------------------------
+// Connection handler
+// ---------------------------------------------------------------------------
+
 /// Handle a Mac app WebSocket connection on `/ws`.
-pub async fn handle_app_ws(ws: WebSocket, relay: Arc<ChatRelay>, redactor: Arc<Redactor>) {
+///
+/// A `daemon.status` is sent on connect and again whenever the cloud
+/// connection changes state.
+pub async fn handle_app_ws(ws: WebSocket, ctx: AppContext) {
     let (mut ws_tx, mut ws_rx) = ws.split();
 
     // Channel for sending messages to the Mac app (both cloud responses and heartbeats)
-    let (out_tx, mut out_rx) = mpsc::channel::<String>(256);
+    let (out_tx, mut out_rx) = mpsc::channel::<AppMessage>(256);
 
     // Writer task: drain the channel → WebSocket
     let writer_task = tokio::spawn(async move {
-        while let Some(text) = out_rx.recv().await {
-            if ws_tx.send(AxumWsMessage::Text(text.into())).await.is_err() {
+        while let Some(msg) = out_rx.recv().await {
+            let text = Envelope::new(msg).to_json();
+            if ws_tx.send(AxumWsMessage::Text(text)).await.is_err() {
                 break;
             }
         }
     });
 
-    // Send initial daemon.status
-    let status = make_envelope(
-        "daemon.status",
-        serde_json::json!({
-            "daemon_version": env!("CARGO_PKG_VERSION"),
-            "protocol_version": 1,
-            "orchestrator_connected": true,
-            "orchestrator_url": "",
-            "active_tasks": 0,
-            "device_id": ""
-        }),
-    );
-    let _ = out_tx.send(status).await;
+    // Send daemon.status now and on every connection state change
+    let mut state_events = ctx.connection.subscribe();
+    let _ = out_tx.send(daemon_status(&ctx).await).await;
+    let ctx_for_status = ctx.clone();
+    let out_tx_status = out_tx.clone();
+    let status_task = tokio::spawn(async move {
+        loop {
+            match state_events.recv().await {
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => return,
+            }
+            if out_tx_status
+                .send(daemon_status(&ctx_for_status).await)
+                .await
+                .is_err()
+            {
+                return;
+            }
+        }
+    });
+
+    let tasks = Arc::new(Mutex::new(TaskSessions::new(Arc::clone(&ctx.active_tasks))));
+    let (subscriber, mut session_rx) = ctx.relay.subscribe_sessions();
+    let subscriber = Arc::new(subscriber);
 
-    // Track the current task_id for mapping cloud responses
-    let current_task_id = Arc::new(tokio::sync::Mutex::new(String::new()));
-    let session_id = Uuid::new_v4().to_string();
+    // Approval prompts for this connection's tasks → permission.requested
+    let mut prompts = ctx.approvals.subscribe();
+    let tasks_for_prompts = Arc::clone(&tasks);
+    let out_tx_prompts = out_tx.clone();
+    let prompt_task = tokio::spawn(async move {
+        loop {
+            let prompt = match prompts.recv().await {
+                Ok(prompt) => prompt,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return,
+            };
+            // A prompt outside any session is shown to every app
+            let task_id = match &prompt.session_id {
+                Some(session_id) => match tasks_for_prompts.lock().unwrap().task_id(session_id) {
+                    Some(task_id) => task_id,
+                    None => continue,
+                },
+                None => String::new(),
+            };
+            let envelope = permission_requested(&prompt, &task_id);
+            if out_tx_prompts.send(envelope).await.is_err() {
+                return;
+            }
+        }
+    });
 
-    // Cloud response translator: ChatRelay broadcasts → Mac app protocol
-    let mut broadcast_rx = relay.subscribe_local();
-    let task_id_for_cloud = Arc::clone(&current_task_id);
+    // Cloud response translator: the connection's sessions → Mac app protocol
+    let tasks_for_cloud = Arc::clone(&tasks);
+    let subscriber_for_cloud = Arc::clone(&subscriber);
     let out_tx_cloud = out_tx.clone();
     let cloud_task = tokio::spawn(async move {
-        while let Ok(msg) = broadcast_rx.recv().await {
-            let task_id = task_id_for_cloud.lock().await.clone();
-
-            let envelope = match msg.msg_type {
-                ChatMessageType::StreamChunk => make_envelope(
-                    "agent.message",
-                    serde_json::json!({
-                        "task_id": task_id,
-                        "step_id": "chat",
-                        "agent": "planner",
-                        "message": msg.payload.content,
-                        "message_type": "INFO"
-                    }),
-                ),
-                ChatMessageType::StreamEnd => make_envelope(
-                    "task.completed",
-                    serde_json::json!({
-                        "task_id": task_id,
-                        "summary": "",
-                        "steps_completed": 1,
-                        "steps_total": 1,
-                        "duration_ms": 0,
-                        "credits_used": 0
-                    }),
-                ),
-                ChatMessageType::Error => make_envelope(
-                    "task.failed",
-                    serde_json::json!({
-                        "task_id": task_id,
-                        "error": {
-                            "code": "INTERNAL_ERROR",
-                            "message": msg.payload.content,
-                            "steps_completed": 0,
-                            "steps_total": 1
-                        },
-                        "credits_used": 0
-                    }),
-                ),
-                ChatMessageType::SessionInit
-                | ChatMessageType::SessionInitAck
-                | ChatMessageType::Unknown => continue,
-                _ => make_envelope(
-                    "agent.message",
-                    serde_json::json!({
-                        "task_id": task_id,
-                        "step_id": "chat",
-                        "agent": "planner",
-                        "message": msg.payload.content,
-                        "message_type": "INFO"
-                    }),
-                ),
+        while let Some(msg) = session_rx.recv().await {
+            let session_id = msg.payload.session_id.clone();
+            let targets = {
+                let mut tasks = tasks_for_cloud.lock().unwrap();
+                // A notice without a session concerns every task in flight
+                let targets = if session_id.is_empty() {
+                    tasks.all()
+                } else {
+                    match tasks.task_id(&session_id) {
+                        Some(task_id) => vec![(session_id.clone(), task_id)],
+                        None => Vec::new(),
+                    }
+                };
+                if msg.msg_type == ChatMessageType::PlanProposal {
+                    if let Some(plan) = ProposedPlan::from_metadata(msg.payload.metadata.as_ref()) {
+                        for (session, _) in &targets {
+                            tasks.plans.insert(session.clone(), plan.clone());
+                        }
+                    }
+                }
+                if is_terminal(&msg) {
+                    for (session, _) in &targets {
+                        tasks.finish(session);
+                        subscriber_for_cloud.leave(session);
+                    }
+                }
+                targets
             };
 
-            if out_tx_cloud.send(envelope).await.is_err() {
-                break;
+            for (_, task_id) in targets {
+                let Some(envelope) = translate_cloud_message(&msg, &task_id) else {
+                    continue;
+                };
+                if out_tx_cloud.send(envelope).await.is_err() {
+                    return;
+                }
             }
         }
     });
 
     // Main loop: Mac app messages → ChatRelay
-    let mut session_initialized = false;
+    let mut sessions_used = Vec::new();
     while let Some(Ok(msg)) = ws_rx.next().await {
         let text = match msg {
             AxumWsMessage::Text(t) => t,
@@ -139,66 +550,649 @@ pub async fn handle_app_ws(ws: WebSocket, relay: Arc<ChatRelay>, redactor: Arc<R
             _ => continue,
         };
 
-        let parsed: Value = match serde_json::from_str(&text) {
-            Ok(v) => v,
+        let raw: RawEnvelope = match serde_json::from_str(&text) {
+            Ok(raw) => raw,
             Err(e) => {
                 warn!("Invalid JSON from Mac app: {}", e);
                 continue;
             }
         };
+        if !is_supported_version(raw.v) {
+            warn!(v = raw.v, "Unsupported protocol version from Mac app");
+            let _ = out_tx.send(version_mismatch(&raw.id, raw.v)).await;
+            continue;
+        }
+        let request_id = raw.id.clone();
+        let msg_type = raw.msg_type.clone();
 
-        let msg_type = parsed.get("type").and_then(|t| t.as_str()).unwrap_or("");
-
-        match msg_type {
-            "task.submit" => {
-                let payload = parsed.get("payload").cloned().unwrap_or(Value::Null);
-                let task_id = payload
-                    .get("task_id")
-                    .and_then(|t| t.as_str())
-                    .unwrap_or("")
-                    .to_string();
-                let input = payload
-                    .get("input")
-                    .and_then(|i| i.as_str())
-                    .unwrap_or("")
-                    .to_string();
-
-                *current_task_id.lock().await = task_id;
-
-                // Send session_init on first task
-                if !session_initialized {
-                    let locale = std::env::var("LANG").unwrap_or_else(|_| "en".to_string());
-                    let init_msg = ChatMessage::session_init(session_id.clone(), locale);
-                    let _ = relay.send_to_cloud(init_msg).await;
-                    session_initialized = true;
-                }
+        let reply = match raw.decode::<AppMessage>().map(|envelope| envelope.message) {
+            Err(e) => Err(format!("invalid payload: {e}")),
+            Ok(AppMessage::TaskSubmit(submit)) => {
+                let TaskSubmitPayload { task_id, input, .. } = submit;
+
+                // Each task gets its own cloud session
+                let session_id = Uuid::new_v4().to_string();
+                tasks.lock().unwrap().start(session_id.clone(), task_id);
+                subscriber.join(&session_id);
+                sessions_used.push(session_id.clone());
+
+                let locale = std::env::var("LANG").unwrap_or_else(|_| "en".to_string());
+                let init_msg = ChatMessage::session_init(session_id.clone(), locale);
+                let _ = ctx.relay.send_to_cloud(init_msg).await;
 
                 // Translate task.submit → user_message
-                let redacted_input = redactor.redact(&input);
+                let redacted_input = ctx.redactor.redact_for_session(&input, &session_id);
                 let chat_msg = ChatMessage::new(
                     ChatMessageType::UserMessage,
                     ChatPayload {
-                        session_id: session_id.clone(),
+                        session_id,
                         content: redacted_input,
                         metadata: None,
                     },
                 );
-                let _ = relay.send_to_cloud(chat_msg).await;
+                let _ = ctx.relay.send_to_cloud(chat_msg).await;
                 debug!("task.submit translated to user_message");
+                Ok(None)
             }
-            "heartbeat" => {
-                let pong = make_envelope("heartbeat", serde_json::json!({"pong": true}));
-                let _ = out_tx.send(pong).await;
+            Ok(AppMessage::Heartbeat(_)) => Ok(Some(AppMessage::Heartbeat(HeartbeatPayload {
+                ping: false,
+                pong: true,
+            }))),
+            Ok(AppMessage::PlanApprove(decision)) => {
+                decide_plan(&ctx, &tasks, &subscriber, decision).await
             }
-            "plan.approve" | "task.cancel" | "permission.response" => {
-                debug!("Received {} — forwarding not yet implemented", msg_type);
+            Ok(AppMessage::TaskCancel(cancel)) => cancel_task(&ctx, &tasks, &subscriber, cancel)
+                .await
+                .map(Some),
+            Ok(AppMessage::PermissionResponse(response)) => {
+                let granted = response.action.is_granted();
+                if ctx
+                    .approvals
+                    .respond(&response.permission_id, granted, response.remember)
+                {
+                    Ok(None)
+                } else {
+                    Err(format!("no pending permission {}", response.permission_id))
+                }
             }
-            _ => {
+            Ok(AppMessage::Unknown { .. }) => {
                 warn!("Unknown message type from Mac app: {}", msg_type);
+                Ok(None)
             }
+            Ok(other) => Err(format!("{} is sent by the daemon", other.msg_type())),
+        };
+
+        let reply = match reply {
+            Ok(reply) => reply,
+            Err(e) => {
+                warn!(msg_type, "Rejected app message: {}", e);
+                Some(protocol_error(&request_id, &e))
+            }
+        };
+        if let Some(reply) = reply {
+            let _ = out_tx.send(reply).await;
         }
     }
 
+    status_task.abort();
+    prompt_task.abort();
     cloud_task.abort();
     writer_task.abort();
+    if let Some(vault) = ctx.redactor.vault() {
+        for session_id in &sessions_used {
+            vault.forget_session(session_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection_state::ConnectionState;
+    use axum::extract::WebSocketUpgrade;
+    use tokio_tungstenite::tungstenite::{Error as WsError, Message};
+
+    fn cloud_msg(msg_type: ChatMessageType, session: &str, content: &str) -> ChatMessage {
+        ChatMessage::new(
+            msg_type,
+            ChatPayload {
+                session_id: session.to_string(),
+                content: content.to_string(),
+                metadata: None,
+            },
+        )
+    }
+
+    fn parse(envelope: &str) -> Value {
+        serde_json::from_str(envelope).unwrap()
+    }
+
+    /// The app envelope `msg` becomes for `task_id`, as sent on the wire.
+    fn translated(msg: &ChatMessage, task_id: &str) -> Value {
+        let message = translate_cloud_message(msg, task_id).unwrap();
+        parse(&Envelope::new(message).to_json())
+    }
+
+    #[test]
+    fn cloud_messages_map_to_the_given_task() {
+        let chunk = cloud_msg(ChatMessageType::StreamChunk, "s1", "Hello");
+        let env = translated(&chunk, "task-1");
+        assert_eq!(env["type"], "agent.message");
+        assert_eq!(env["payload"]["task_id"], "task-1");
+        assert_eq!(env["payload"]["message"], "Hello");
+
+        let end = cloud_msg(ChatMessageType::StreamEnd, "s1", "");
+        let env = translated(&end, "task-2");
+        assert_eq!(env["type"], "task.completed");
+        assert_eq!(env["payload"]["task_id"], "task-2");
+        assert!(is_terminal(&end));
+
+        let err = cloud_msg(ChatMessageType::Error, "s1", "boom");
+        let env = translated(&err, "task-3");
+        assert_eq!(env["type"], "task.failed");
+        assert_eq!(env["payload"]["error"]["message"], "boom");
+
+        let ack = cloud_msg(ChatMessageType::SessionInitAck, "s1", "");
+        assert!(translate_cloud_message(&ack, "task-1").is_none());
+        assert!(!is_terminal(&chunk));
+        assert!(!is_terminal(&ack));
+    }
+
+    #[test]
+    fn unsupported_session_versions_fail_the_task() {
+        let mut ack = cloud_msg(ChatMessageType::SessionInitAck, "s1", "");
+        ack.payload.metadata = Some(serde_json::json!({ "protocol_version": 99 }));
+        let env = translated(&ack, "task-1");
+        assert_eq!(env["type"], "task.failed");
+        assert_eq!(env["payload"]["task_id"], "task-1");
+        assert_eq!(env["payload"]["error"]["code"], "INTERNAL_ERROR");
+        assert!(env["payload"]["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("version 99"));
+        assert!(is_terminal(&ack));
+    }
+
+    fn plan_proposal(session: &str) -> ChatMessage {
+        let mut msg = cloud_msg(ChatMessageType::PlanProposal, session, "Install Node");
+        msg.payload.metadata = Some(serde_json::json!({
+            "plan_id": "plan-1",
+            "risk_tier": "MEDIUM",
+            "steps": [
+                { "step_id": "st-1", "order": 1, "description": "brew install node",
+                  "agent": "executor", "risk_tier": "MEDIUM" },
+                { "step_id": "st-2", "order": 2, "description": "node --version",
+                  "agent": "executor", "risk_tier": "LOW" }
+            ]
+        }));
+        msg
+    }
+
+    #[test]
+    fn plan_proposals_become_plan_proposed() {
+        let msg = plan_proposal("s1");
+        let env = translated(&msg, "task-1");
+        assert_eq!(env["type"], "plan.proposed");
+        assert_eq!(env["payload"]["task_id"], "task-1");
+        assert_eq!(env["payload"]["plan_id"], "plan-1");
+        assert_eq!(env["payload"]["summary"], "Install Node");
+        assert_eq!(env["payload"]["requires_approval"], true);
+        assert_eq!(env["payload"]["steps"][1]["step_id"], "st-2");
+        assert!(!is_terminal(&msg));
+
+        let plan = ProposedPlan::from_metadata(msg.payload.metadata.as_ref()).unwrap();
+        assert_eq!(plan.plan_id, "plan-1");
+        assert!(plan.step_ids.contains("st-1") && plan.step_ids.contains("st-2"));
+    }
+
+    #[test]
+    fn task_sessions_look_up_by_session() {
+        let active = Arc::new(AtomicUsize::new(0));
+        let mut sessions = TaskSessions::new(Arc::clone(&active));
+        sessions.start("s1".into(), "task-1".into());
+        sessions.start("s2".into(), "task-2".into());
+        assert_eq!(sessions.task_id("s2").as_deref(), Some("task-2"));
+        assert_eq!(sessions.session_of("task-1").as_deref(), Some("s1"));
+        assert!(sessions.task_id("s3").is_none());
+        assert_eq!(sessions.all().len(), 2);
+        assert_eq!(active.load(Ordering::Relaxed), 2);
+
+        assert_eq!(sessions.finish("s1").as_deref(), Some("task-1"));
+        assert!(sessions.finish("s1").is_none());
+        assert_eq!(active.load(Ordering::Relaxed), 1);
+        drop(sessions);
+        assert_eq!(active.load(Ordering::Relaxed), 0);
+    }
+
+    /// Next app envelope other than `daemon.status`.
+    async fn next_envelope<S>(client: &mut S) -> Value
+    where
+        S: futures::Stream<Item = Result<Message, WsError>> + Unpin,
+    {
+        loop {
+            if let Some(Ok(Message::Text(text))) = client.next().await {
+                let env = parse(&text);
+                if env["type"] != "daemon.status" {
+                    return env;
+                }
+            }
+        }
+    }
+
+    fn context(relay: ChatRelay) -> AppContext {
+        let (connection, _) = ConnectionStateMachine::new(Default::default());
+        AppContext {
+            relay: Arc::new(relay),
+            redactor: Arc::new(Redactor::new()),
+            connection,
+            commands: Arc::new(CommandRelay::new()),
+            approvals: Arc::new(ApprovalBroker::default()),
+            active_tasks: Arc::default(),
+            device_id: "device-1".into(),
+            orchestrator_url: "wss://example.test/ws".into(),
+        }
+    }
+
+    type AppClient = tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+    >;
+
+    /// Serve `/ws` on an ephemeral port and connect an app client to it.
+    async fn connect_app(ctx: AppContext) -> AppClient {
+        let app = axum::Router::new().route(
+            "/ws",
+            axum::routing::get(move |ws: WebSocketUpgrade| {
+                let ctx = ctx.clone();
+                async move { ws.on_upgrade(move |socket| handle_app_ws(socket, ctx)) }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let (client, _) = tokio_tungstenite::connect_async(format!("ws://{addr}/ws"))
+            .await
+            .unwrap();
+        client
+    }
+
+    async fn send(client: &mut AppClient, msg_type: &str, payload: Value) {
+        let envelope = serde_json::json!({
+            "v": 1,
+            "id": format!("req-{msg_type}"),
+            "ts": 0,
+            "type": msg_type,
+            "payload": payload
+        });
+        client
+            .send(Message::Text(envelope.to_string()))
+            .await
+            .unwrap();
+    }
+
+    /// Submit `task_id` and return the cloud session it was given.
+    async fn submit(
+        client: &mut AppClient,
+        cloud_rx: &mut mpsc::Receiver<ChatMessage>,
+        task_id: &str,
+    ) -> String {
+        send(
+            client,
+            "task.submit",
+            serde_json::json!({ "task_id": task_id, "input": format!("run {task_id}") }),
+        )
+        .await;
+        let init = cloud_rx.recv().await.unwrap();
+        assert_eq!(init.msg_type, ChatMessageType::SessionInit);
+        let user = cloud_rx.recv().await.unwrap();
+        assert_eq!(user.payload.content, format!("run {task_id}"));
+        user.payload.session_id
+    }
+
+    #[tokio::test]
+    async fn daemon_status_follows_connection_state() {
+        let (relay, _cloud_rx) = ChatRelay::new();
+        let ctx = context(relay);
+        let connection = Arc::clone(&ctx.connection);
+        let mut client = connect_app(ctx).await;
+
+        let Some(Ok(Message::Text(text))) = client.next().await else {
+            panic!("expected daemon.status");
+        };
+        let status = parse(&text);
+        assert_eq!(status["type"], "daemon.status");
+        assert_eq!(status["payload"]["orchestrator_connected"], false);
+        assert_eq!(status["payload"]["connection_state"], "offline");
+        assert_eq!(status["payload"]["device_id"], "device-1");
+        assert_eq!(
+            status["payload"]["orchestrator_url"],
+            "wss://example.test/ws"
+        );
+        assert_eq!(status["payload"]["active_tasks"], 0);
+
+        connection
+            .transition(ConnectionState::Online, "authenticated")
+            .await
+            .unwrap();
+        let Some(Ok(Message::Text(text))) = client.next().await else {
+            panic!("expected daemon.status");
+        };
+        let status = parse(&text);
+        assert_eq!(status["type"], "daemon.status");
+        assert_eq!(status["payload"]["orchestrator_connected"], true);
+        assert_eq!(status["payload"]["connection_state"], "online");
+    }
+
+    #[tokio::test]
+    async fn plans_are_approved_step_by_step() {
+        let (relay, mut cloud_rx) = ChatRelay::new();
+        let ctx = context(relay);
+        let relay = Arc::clone(&ctx.relay);
+        relay.set_connected().await.unwrap();
+        let mut client = connect_app(ctx).await;
+        let session = submit(&mut client, &mut cloud_rx, "task-a").await;
+
+        // Nothing to approve before a plan is proposed
+        let approve = serde_json::json!({
+            "task_id": "task-a", "plan_id": "plan-1", "action": "APPROVE"
+        });
+        send(&mut client, "plan.approve", approve.clone()).await;
+        let env = next_envelope(&mut client).await;
+        assert_eq!(env["type"], "error");
+        assert_eq!(env["payload"]["request_id"], "req-plan.approve");
+
+        relay.send_to_local(plan_proposal(&session)).unwrap();
+        let env = next_envelope(&mut client).await;
+        assert_eq!(env["type"], "plan.proposed");
+        assert_eq!(env["payload"]["task_id"], "task-a");
+
+        // Modifications must name steps of the plan
+        send(
+            &mut client,
+            "plan.approve",
+            serde_json::json!({
+                "task_id": "task-a", "plan_id": "plan-1", "action": "MODIFY",
+                "modifications": [{ "step_id": "st-9", "skip": true }]
+            }),
+        )
+        .await;
+        let env = next_envelope(&mut client).await;
+        assert_eq!(env["type"], "error");
+        assert!(env["payload"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("no step st-9"));
+
+        send(
+            &mut client,
+            "plan.approve",
+            serde_json::json!({
+                "task_id": "task-a", "plan_id": "plan-1", "action": "MODIFY",
+                "modifications": [
+                    { "step_id": "st-1", "description": "brew install node@20" },
+                    { "step_id": "st-2", "skip": true }
+                ]
+            }),
+        )
+        .await;
+        let decision = cloud_rx.recv().await.unwrap();
+        assert_eq!(decision.msg_type, ChatMessageType::PlanDecision);
+        assert_eq!(decision.payload.session_id, session);
+        assert_eq!(decision.payload.content, "MODIFY");
+        let metadata = decision.payload.metadata.unwrap();
+        assert_eq!(metadata["plan_id"], "plan-1");
+        assert_eq!(
+            metadata["modifications"][0]["description"],
+            "brew install node@20"
+        );
+        assert_eq!(metadata["modifications"][1]["skip"], true);
+
+        // A decided plan can't be decided again
+        send(&mut client, "plan.approve", approve).await;
+        assert_eq!(next_envelope(&mut client).await["type"], "error");
+    }
+
+    #[tokio::test]
+    async fn rejecting_a_plan_ends_the_task() {
+        let (relay, mut cloud_rx) = ChatRelay::new();
+        let ctx = context(relay);
+        let relay = Arc::clone(&ctx.relay);
+        let active = Arc::clone(&ctx.active_tasks);
+        relay.set_connected().await.unwrap();
+        let mut client = connect_app(ctx).await;
+        let session = submit(&mut client, &mut cloud_rx, "task-a").await;
+        assert_eq!(active.load(Ordering::Relaxed), 1);
+
+        relay.send_to_local(plan_proposal(&session)).unwrap();
+        next_envelope(&mut client).await;
+        send(
+            &mut client,
+            "plan.approve",
+            serde_json::json!({ "task_id": "task-a", "plan_id": "plan-1", "action": "REJECT" }),
+        )
+        .await;
+        let decision = cloud_rx.recv().await.unwrap();
+        assert_eq!(decision.payload.content, "REJECT");
+        let env = next_envelope(&mut client).await;
+        assert_eq!(env["type"], "task.failed");
+        assert_eq!(env["payload"]["error"]["code"], "USER_DENIED");
+        assert_eq!(active.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn cancelling_a_task_stops_it_everywhere() {
+        let (relay, mut cloud_rx) = ChatRelay::new();
+        let ctx = context(relay);
+        let relay = Arc::clone(&ctx.relay);
+        let commands = Arc::clone(&ctx.commands);
+        let active = Arc::clone(&ctx.active_tasks);
+        relay.set_connected().await.unwrap();
+        let mut client = connect_app(ctx).await;
+        let session = submit(&mut client, &mut cloud_rx, "task-a").await;
+
+        // A command of the task is running
+        let request = crate::command_relay::CommandRequest {
+            id: "cmd-1".into(),
+            command_type: "shell_exec".into(),
+            payload: serde_json::json!("sleep 10"),
+            cwd: None,
+            timeout_ms: None,
+            session_id: Some(session.clone()),
+        };
+        let running = Arc::clone(&commands);
+        let command = tokio::spawn(async move {
+            let mut rx = running.execute(request).await;
+            let mut last = None;
+            while let Some(response) = rx.recv().await {
+                last = Some(response);
+            }
+            last
+        });
+        while commands.running_count() == 0 {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        send(
+            &mut client,
+            "task.cancel",
+            serde_json::json!({ "task_id": "task-a" }),
+        )
+        .await;
+        let cancel = cloud_rx.recv().await.unwrap();
+        assert_eq!(cancel.msg_type, ChatMessageType::TaskCancel);
+        assert_eq!(cancel.payload.session_id, session);
+        let env = next_envelope(&mut client).await;
+        assert_eq!(env["type"], "task.failed");
+        assert_eq!(env["payload"]["task_id"], "task-a");
+        assert_eq!(env["payload"]["error"]["code"], "CANCELLED");
+        assert_eq!(active.load(Ordering::Relaxed), 0);
+
+        let last = tokio::time::timeout(std::time::Duration::from_secs(5), command)
+            .await
+            .expect("command should stop")
+            .unwrap();
+        assert!(matches!(
+            last,
+            Some(crate::command_relay::CommandResponse::Completed { success: false, .. })
+        ));
+
+        // The task is gone
+        send(
+            &mut client,
+            "task.cancel",
+            serde_json::json!({ "task_id": "task-a" }),
+        )
+        .await;
+        assert_eq!(next_envelope(&mut client).await["type"], "error");
+    }
+
+    #[tokio::test]
+    async fn permission_responses_answer_approval_prompts() {
+        use crate::command_relay::{ApprovalHandler, ApprovalKind};
+
+        let (relay, mut cloud_rx) = ChatRelay::new();
+        let ctx = context(relay);
+        let approvals = Arc::clone(&ctx.approvals);
+        ctx.relay.set_connected().await.unwrap();
+        let mut client = connect_app(ctx).await;
+        let session = submit(&mut client, &mut cloud_rx, "task-a").await;
+
+        let prompt = ApprovalPrompt {
+            command_id: "cmd-1".into(),
+            session_id: Some(session),
+            kind: ApprovalKind::Shell,
+            command: "sudo ls".into(),
+            reason: "High-risk command".into(),
+        };
+        let asking = Arc::clone(&approvals);
+        let answer = tokio::spawn(async move { asking.approve(&prompt).await });
+
+        let env = next_envelope(&mut client).await;
+        assert_eq!(env["type"], "permission.requested");
+        assert_eq!(env["payload"]["task_id"], "task-a");
+        assert_eq!(env["payload"]["permission_id"], "cmd-1");
+        assert_eq!(env["payload"]["action_type"], "SHELL");
+        assert_eq!(env["payload"]["command_preview"], "sudo ls");
+
+        send(
+            &mut client,
+            "permission.response",
+            serde_json::json!({
+                "task_id": "task-a", "permission_id": "cmd-1", "action": "GRANT"
+            }),
+        )
+        .await;
+        assert!(answer.await.unwrap());
+
+        // Answering again is an error
+        send(
+            &mut client,
+            "permission.response",
+            serde_json::json!({
+                "task_id": "task-a", "permission_id": "cmd-1", "action": "DENY"
+            }),
+        )
+        .await;
+        assert_eq!(next_envelope(&mut client).await["type"], "error");
+    }
+
+    #[tokio::test]
+    async fn unsupported_versions_and_payloads_are_rejected() {
+        let (relay, mut cloud_rx) = ChatRelay::new();
+        let ctx = context(relay);
+        ctx.relay.set_connected().await.unwrap();
+        let mut client = connect_app(ctx).await;
+
+        let future = serde_json::json!({
+            "v": 99, "id": "req-1", "ts": 0, "type": "task.submit",
+            "payload": { "task_id": "task-a", "input": "hi" }
+        });
+        client
+            .send(Message::Text(future.to_string()))
+            .await
+            .unwrap();
+        let env = next_envelope(&mut client).await;
+        assert_eq!(env["type"], "error");
+        assert_eq!(env["payload"]["code"], "PROTOCOL_VERSION_MISMATCH");
+        assert_eq!(env["payload"]["request_id"], "req-1");
+        assert_eq!(
+            env["payload"]["supported_protocol_versions"],
+            serde_json::json!(SUPPORTED_PROTOCOL_VERSIONS)
+        );
+
+        send(
+            &mut client,
+            "task.submit",
+            serde_json::json!({ "input": "no task id" }),
+        )
+        .await;
+        let env = next_envelope(&mut client).await;
+        assert_eq!(env["type"], "error");
+        assert_eq!(env["payload"]["request_id"], "req-task.submit");
+        assert!(cloud_rx.try_recv().is_err());
+
+        // Messages only the daemon sends are refused
+        send(
+            &mut client,
+            "credits.updated",
+            serde_json::json!({
+                "daily_balance": 1, "bonus_balance": 0, "used_this_task": 0, "reset_at": ""
+            }),
+        )
+        .await;
+        assert_eq!(next_envelope(&mut client).await["type"], "error");
+    }
+
+    #[tokio::test]
+    async fn concurrent_tasks_get_their_own_responses() {
+        let (relay, mut cloud_rx) = ChatRelay::new();
+        let ctx = context(relay);
+        let relay = Arc::clone(&ctx.relay);
+        relay.set_connected().await.unwrap();
+        let mut client = connect_app(ctx).await;
+
+        // Submit two tasks and learn their cloud sessions
+        let mut sessions = HashMap::new();
+        for task in ["task-a", "task-b"] {
+            let submit = serde_json::json!({
+                "type": "task.submit",
+                "payload": { "task_id": task, "input": format!("run {task}") }
+            });
+            client
+                .send(Message::Text(submit.to_string()))
+                .await
+                .unwrap();
+            let init = cloud_rx.recv().await.unwrap();
+            assert_eq!(init.msg_type, ChatMessageType::SessionInit);
+            let user = cloud_rx.recv().await.unwrap();
+            assert_eq!(user.payload.content, format!("run {task}"));
+            sessions.insert(task, user.payload.session_id);
+        }
+        assert_ne!(sessions["task-a"], sessions["task-b"]);
+
+        // Interleaved responses map back to their own task
+        let send = |ty, task: &str, content: &str| {
+            relay
+                .send_to_local(cloud_msg(ty, &sessions[task], content))
+                .unwrap();
+        };
+        send(ChatMessageType::StreamChunk, "task-b", "from b");
+        send(ChatMessageType::StreamChunk, "task-a", "from a");
+        send(ChatMessageType::StreamEnd, "task-a", "");
+
+        let env = next_envelope(&mut client).await;
+        assert_eq!(env["payload"]["task_id"], "task-b");
+        assert_eq!(env["payload"]["message"], "from b");
+        let env = next_envelope(&mut client).await;
+        assert_eq!(env["payload"]["task_id"], "task-a");
+        assert_eq!(env["payload"]["message"], "from a");
+        let env = next_envelope(&mut client).await;
+        assert_eq!(env["type"], "task.completed");
+        assert_eq!(env["payload"]["task_id"], "task-a");
+
+        // Other sessions never reach this connection
+        assert!(relay
+            .send_to_local(cloud_msg(ChatMessageType::StreamChunk, "other", "x"))
+            .is_err());
+    }
 }
@@ -3,6 +3,8 @@
 //! Database: ~/.d1doctor/d1doctor.db  (created and owned by the daemon)
 //! Schema:   tasks(id, session_id, request, status, created_at)
 //! Access:   read-only, WAL mode compatible concurrent reads
+//! Key:      when `[database.encryption]` is enabled in config.toml, the
+//!           SQLCipher key is resolved the same way the daemon does it
 
 use rusqlite::{Connection, OpenFlags};
 use serde::Serialize;
@@ -22,6 +24,47 @@ fn db_path() -> Result<std::path::PathBuf, String> {
     Ok(home.join(".d1doctor").join("d1doctor.db"))
 }
 
+/// SQLCipher `PRAGMA key` value for the daemon database, or `None` when
+/// encryption is off: the passphrase from `passphrase_env` if set, else the
+/// hex key in `key_file` in raw-key form.
+fn db_key(config: &toml::Value) -> Result<Option<String>, String> {
+    let Some(encryption) = config.get("database").and_then(|d| d.get("encryption")) else {
+        return Ok(None);
+    };
+    if !encryption.get("enabled").and_then(|v| v.as_bool()).unwrap_or(false) {
+        return Ok(None);
+    }
+
+    let passphrase_env = encryption
+        .get("passphrase_env")
+        .and_then(|v| v.as_str())
+        .unwrap_or("D1_DB_PASSPHRASE");
+    if let Ok(passphrase) = std::env::var(passphrase_env) {
+        if !passphrase.is_empty() {
+            return Ok(Some(passphrase));
+        }
+    }
+
+    let key_file = match encryption.get("key_file").and_then(|v| v.as_str()) {
+        Some(path) => std::path::PathBuf::from(path),
+        None => dirs::home_dir()
+            .ok_or_else(|| "Cannot determine home directory".to_string())?
+            .join(".d1doctor")
+            .join("db.key"),
+    };
+    let key = std::fs::read_to_string(&key_file)
+        .map_err(|e| format!("Cannot read key file {}: {e}", key_file.display()))?;
+    Ok(Some(format!("x'{}'", key.trim())))
+}
+
+fn read_config() -> toml::Value {
+    dirs::home_dir()
+        .map(|h| h.join(".d1doctor").join("config.toml"))
+        .and_then(|p| std::fs::read_to_string(p).ok())
+        .and_then(|s| s.parse().ok())
+        .unwrap_or_else(|| toml::Value::Table(Default::default()))
+}
+
 /// Return up to 20 most recent tasks from the daemon's local SQLite database.
 ///
 /// Returns an empty array when:
@@ -54,6 +97,21 @@ pub async fn list_recent_tasks() -> Vec<TaskSummary> {
         }
     };
 
+    // Encrypted databases need the key before the first read
+    match db_key(&read_config()) {
+        Ok(None) => {}
+        Ok(Some(key)) => {
+            if let Err(e) = conn.pragma_update(None, "key", &key) {
+                eprintln!("[tasks] Failed to set database key: {e}");
+                return vec![];
+            }
+        }
+        Err(e) => {
+            eprintln!("[tasks] Failed to load database key: {e}");
+            return vec![];
+        }
+    }
+
     // 500 ms busy timeout so we don't block the UI if daemon is mid-write
     if let Err(e) = conn.busy_timeout(std::time::Duration::from_millis(500)) {
         eprintln!("[tasks] Failed to set busy timeout: {e}");
@@ -102,6 +160,25 @@ mod tests {
         assert_eq!(path.file_name().unwrap(), "d1doctor.db");
     }
 
+    #[test]
+    fn db_key_follows_daemon_config() {
+        let off: toml::Value = "[database]\npath = \"x\"".parse().unwrap();
+        assert_eq!(db_key(&off).unwrap(), None);
+
+        let dir = std::env::temp_dir().join(format!("d1-desktop-key-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let key_file = dir.join("db.key");
+        std::fs::write(&key_file, "ab01\n").unwrap();
+        let on: toml::Value = format!(
+            "[database.encryption]\nenabled = true\nkey_file = {:?}\npassphrase_env = \"D1_DESKTOP_TEST_UNSET\"",
+            key_file.to_string_lossy()
+        )
+        .parse()
+        .unwrap();
+        assert_eq!(db_key(&on).unwrap().as_deref(), Some("x'ab01'"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
     #[tokio::test]
     async fn list_recent_tasks_never_panics() {
         // The function must not panic even if DB doesn't exist or has wrong schema.
@@ -1,6 +1,6 @@
 // Day1 Doctor — Daemon WebSocket protocol types (local JSON channel, v1)
 // Source of truth: LocalStack_v2.4.1_Spec.md §1.4
-// Rust types: generated by prost from d1-doctor-proto (separate repo)
+// Rust types: d1_common::protocol::app (golden files in crates/common/src/protocol/golden/app)
 // IMPORTANT: this file must be updated whenever PROTOCOL_VERSION bumps. See §1.0.
 
 export const PROTOCOL_VERSION = 1 as const;
@@ -44,6 +44,7 @@ export type TaskFailCode =
   | 'CONNECTION_LOST'
   | 'USER_DENIED'
   | 'PERMISSION_TIMEOUT'
+  | 'CANCELLED'
   | 'INTERNAL_ERROR';
 
 export type ErrorCode =
@@ -229,6 +230,8 @@ export interface DaemonStatusPayload {
   orchestrator_url: string;
   active_tasks: number;
   device_id: string;
+  connection_state?: string;
+  queued_messages?: number;
 }
 
 export interface ErrorPayload {